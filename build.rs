@@ -1,7 +1,13 @@
+use std::env;
 use substrate_build_script_utils::{generate_cargo_keys, rerun_if_git_head_changed};
 
 fn main() {
 	generate_cargo_keys();
 
+	println!(
+		"cargo:rustc-env=KULUPU_BUILD_PROFILE={}",
+		env::var("PROFILE").unwrap_or_default()
+	);
+
 	rerun_if_git_head_changed();
 }
@@ -22,17 +22,30 @@
 
 use codec::{Decode, Encode};
 use frame_support::{
-	decl_module, decl_storage,
+	decl_error, decl_module, decl_storage, ensure,
 	traits::{Get, OnTimestampSet},
 };
+use frame_system::ensure_root;
 use kulupu_primitives::{
-	Difficulty, CLAMP_FACTOR, DIFFICULTY_ADJUST_WINDOW, DIFFICULTY_DAMP_FACTOR, MAX_DIFFICULTY,
-	MIN_DIFFICULTY,
+	Difficulty, CLAMP_FACTOR, DIFFICULTY_ADJUST_WINDOW, DIFFICULTY_DAMP_FACTOR,
+	LWMA_SOLVE_TIME_LIMIT, MAX_DIFFICULTY, MIN_DIFFICULTY,
 };
 use scale_info::TypeInfo;
 use sp_core::U256;
-use sp_runtime::traits::UniqueSaturatedInto;
-use sp_std::cmp::{max, min};
+use sp_runtime::traits::{Saturating, UniqueSaturatedInto};
+use sp_std::{
+	cmp::{max, min},
+	prelude::*,
+};
+
+/// Maximum number of blocks whose timestamps a median time past is taken of.
+pub const MAX_MEDIAN_TIME_PAST_BLOCKS: u32 = 11;
+
+/// Number of blocks of each difficulty epoch summary.
+pub const EPOCH_LENGTH: u32 = 1000;
+
+/// Number of completed epochs whose summaries are kept.
+pub const MAX_EPOCH_SUMMARIES: u32 = 1000;
 
 #[derive(Encode, Decode, TypeInfo, Clone, Copy, Eq, PartialEq, Debug)]
 pub struct DifficultyAndTimestamp<M> {
@@ -40,6 +53,58 @@ pub struct DifficultyAndTimestamp<M> {
 	pub timestamp: M,
 }
 
+/// Difficulties of the blocks of an epoch.
+#[derive(Encode, Decode, TypeInfo, Clone, Copy, Eq, PartialEq, Debug, Default)]
+pub struct EpochSummary {
+	/// Number of blocks recorded.
+	pub blocks: u32,
+	/// Lowest difficulty.
+	pub min: Difficulty,
+	/// Highest difficulty.
+	pub max: Difficulty,
+	/// Sum of the difficulties.
+	pub total: Difficulty,
+}
+
+impl EpochSummary {
+	/// Record a block mined at `difficulty`.
+	pub fn record(&mut self, difficulty: Difficulty) {
+		if self.blocks == 0 {
+			self.min = difficulty;
+			self.max = difficulty;
+		} else {
+			self.min = min(self.min, difficulty);
+			self.max = max(self.max, difficulty);
+		}
+		self.blocks = self.blocks.saturating_add(1);
+		self.total = self.total.saturating_add(difficulty);
+	}
+
+	/// Average difficulty of the recorded blocks.
+	pub fn average(&self) -> Difficulty {
+		match self.blocks {
+			0 => Difficulty::zero(),
+			blocks => self.total / blocks,
+		}
+	}
+}
+
+/// Difficulty adjustment algorithm.
+#[derive(Encode, Decode, TypeInfo, Clone, Copy, Eq, PartialEq, Debug)]
+pub enum DifficultyAlgorithm {
+	/// Total solve time of the window, damped and clamped toward the target.
+	Damped,
+	/// Linearly weighted moving average of solve times, weighing recent blocks
+	/// the most so that hashrate changes are followed within a few blocks.
+	Lwma,
+}
+
+impl Default for DifficultyAlgorithm {
+	fn default() -> Self {
+		DifficultyAlgorithm::Damped
+	}
+}
+
 /// Move value linearly toward a goal
 pub fn damp(actual: u128, goal: u128, damp_factor: u128) -> u128 {
 	(actual + (damp_factor - 1) * goal) / damp_factor
@@ -58,7 +123,7 @@ pub trait Config: pallet_timestamp::Config {
 decl_storage! {
 	trait Store for Module<T: Config> as Difficulty {
 		/// Past difficulties and timestamps, from earliest to latest.
-		PastDifficultiesAndTimestamps:
+		pub PastDifficultiesAndTimestamps get(fn past_difficulties_and_timestamps):
 		[Option<DifficultyAndTimestamp<T::Moment>>; 60]
 			= [None; DIFFICULTY_ADJUST_WINDOW as usize];
 		/// Current difficulty.
@@ -67,32 +132,97 @@ decl_storage! {
 		}): Difficulty;
 		/// Initial difficulty.
 		pub InitialDifficulty config(initial_difficulty): Difficulty;
+		/// Difficulty adjustment algorithm in use.
+		pub Algorithm get(fn algorithm): DifficultyAlgorithm;
+		/// Number of blocks whose median timestamp stands in for the timestamp
+		/// of each block in the adjustment. Zero or one uses the timestamps as
+		/// they are.
+		pub MedianTimePastBlocks get(fn median_time_past_blocks): u32;
+		/// Whether the timestamps seeded at genesis are yet to be moved up to
+		/// the first block.
+		RebaseTimestamps: bool;
+		/// Index and summary of the epoch in progress.
+		pub CurrentEpoch get(fn current_epoch): Option<(u32, EpochSummary)>;
+		/// Summaries of the last `MAX_EPOCH_SUMMARIES` completed epochs, by
+		/// index. Epoch `i` covers blocks `i * EPOCH_LENGTH` to
+		/// `(i + 1) * EPOCH_LENGTH - 1`.
+		pub EpochSummaries get(fn epoch_summary):
+			map hasher(twox_64_concat) u32 => Option<EpochSummary>;
+	}
+	add_extra_genesis {
+		/// Difficulties and timestamps of the final blocks of the previous era,
+		/// from earliest to latest, to seed the difficulty adjustment with.
+		config(past_difficulties_and_timestamps): Vec<(Difficulty, u64)>;
+		build(|config: &GenesisConfig| {
+			let past = &config.past_difficulties_and_timestamps;
+			if past.is_empty() {
+				return;
+			}
+
+			let mut data = [None; DIFFICULTY_ADJUST_WINDOW as usize];
+			let len = min(past.len(), data.len());
+			let start = data.len() - len;
+			for (entry, (difficulty, timestamp)) in
+				data[start..].iter_mut().zip(&past[past.len() - len..])
+			{
+				*entry = Some(DifficultyAndTimestamp {
+					difficulty: *difficulty,
+					timestamp: UniqueSaturatedInto::<T::Moment>::unique_saturated_into(*timestamp),
+				});
+			}
+
+			PastDifficultiesAndTimestamps::<T>::put(data);
+			RebaseTimestamps::put(true);
+		});
+	}
+}
+
+decl_error! {
+	pub enum Error for Module<T: Config> {
+		/// The median time past is taken of too many blocks.
+		MedianTimePastTooLong,
 	}
 }
 
 decl_module! {
 	pub struct Module<T: Config> for enum Call where origin: T::Origin {
+		type Error = Error<T>;
+
 		/// Target block time in milliseconds.
 		const TargetBlockTime: T::Moment = T::TargetBlockTime::get();
-	}
-}
 
-impl<T: Config> OnTimestampSet<T::Moment> for Module<T> {
-	fn on_timestamp_set(now: T::Moment) {
-		let block_time =
-			UniqueSaturatedInto::<u128>::unique_saturated_into(T::TargetBlockTime::get());
-		let block_time_window = DIFFICULTY_ADJUST_WINDOW as u128 * block_time;
+		/// Number of blocks of each difficulty epoch summary.
+		const EpochLength: u32 = EPOCH_LENGTH;
 
-		let mut data = PastDifficultiesAndTimestamps::<T>::get();
+		/// Switch the difficulty adjustment algorithm, from the next block on.
+		#[weight = T::DbWeight::get().writes(1)]
+		fn set_algorithm(origin, algorithm: DifficultyAlgorithm) {
+			ensure_root(origin)?;
 
-		for i in 1..data.len() {
-			data[i - 1] = data[i];
+			Algorithm::put(algorithm);
 		}
 
-		data[data.len() - 1] = Some(DifficultyAndTimestamp {
-			timestamp: now,
-			difficulty: Self::difficulty(),
-		});
+		/// Adjust difficulty with the median timestamp of the last `blocks`
+		/// blocks in place of the timestamp of each block, from the next block
+		/// on. This limits how far the clock of a single miner moves difficulty.
+		/// Zero or one switches back to plain timestamps.
+		#[weight = T::DbWeight::get().writes(1)]
+		fn set_median_time_past_blocks(origin, blocks: u32) {
+			ensure_root(origin)?;
+			ensure!(blocks <= MAX_MEDIAN_TIME_PAST_BLOCKS, Error::<T>::MedianTimePastTooLong);
+
+			MedianTimePastBlocks::put(blocks);
+		}
+	}
+}
+
+impl<T: Config> Module<T> {
+	/// Difficulty of the damped algorithm for the window `data`.
+	fn damped_difficulty(
+		data: &[Option<DifficultyAndTimestamp<T::Moment>>],
+		block_time: u128,
+	) -> U256 {
+		let block_time_window = DIFFICULTY_ADJUST_WINDOW as u128 * block_time;
 
 		let mut ts_delta = 0;
 		for i in 1..(DIFFICULTY_ADJUST_WINDOW as usize) {
@@ -130,13 +260,137 @@ impl<T: Config> OnTimestampSet<T::Moment> for Module<T> {
 			CLAMP_FACTOR,
 		);
 
+		diff_sum * U256::from(block_time) / U256::from(adj_ts)
+	}
+
+	/// Difficulty of the LWMA algorithm for the window `data`. The solve time
+	/// of the `i`th block of the window is weighed by `i`.
+	fn lwma_difficulty(
+		data: &[Option<DifficultyAndTimestamp<T::Moment>>],
+		block_time: u128,
+	) -> U256 {
+		let mut weighted_solve_times = 0u128;
+		let mut diff_sum = U256::zero();
+		for i in 1..(DIFFICULTY_ADJUST_WINDOW as usize) {
+			let prev: Option<u128> = data[i - 1].map(|d| d.timestamp.unique_saturated_into());
+			let cur: Option<u128> = data[i].map(|d| d.timestamp.unique_saturated_into());
+
+			let solve_time = match (prev, cur) {
+				(Some(prev), Some(cur)) => cur.saturating_sub(prev),
+				_ => block_time,
+			};
+			let solve_time = min(solve_time, LWMA_SOLVE_TIME_LIMIT * block_time);
+			weighted_solve_times += i as u128 * solve_time;
+
+			diff_sum += data[i]
+				.map(|d| d.difficulty)
+				.unwrap_or_else(InitialDifficulty::get);
+		}
+
+		let blocks = DIFFICULTY_ADJUST_WINDOW as u128 - 1;
+		// Weighted solve times if all blocks were on target.
+		let target = blocks * (blocks + 1) / 2 * block_time;
+		// Limit the increase when timestamps bunch up.
+		let weighted_solve_times = max(weighted_solve_times, target / 10);
+
+		diff_sum * U256::from(target) / U256::from(blocks * weighted_solve_times)
+	}
+
+	/// Replace the timestamp of each entry of `data` by the median timestamp of
+	/// the entry and up to `blocks - 1` entries before it. Of an even number of
+	/// timestamps, the lower median is taken.
+	fn median_time_past(data: &mut [Option<DifficultyAndTimestamp<T::Moment>>], blocks: u32) {
+		let blocks = blocks as usize;
+		if blocks <= 1 {
+			return;
+		}
+
+		let timestamps = data
+			.iter()
+			.map(|entry| entry.map(|entry| entry.timestamp))
+			.collect::<Vec<_>>();
+		for (i, entry) in data.iter_mut().enumerate() {
+			if let Some(entry) = entry {
+				let start = (i + 1).saturating_sub(blocks);
+				let mut window = timestamps[start..=i].iter().flatten().collect::<Vec<_>>();
+				window.sort_unstable();
+				entry.timestamp = *window[(window.len() - 1) / 2];
+			}
+		}
+	}
+
+	/// Record the current difficulty into the summary of the epoch of the
+	/// current block, completing the previous epoch if it has ended.
+	fn record_epoch(difficulty: Difficulty) {
+		let number: u32 = frame_system::Pallet::<T>::block_number().unique_saturated_into();
+		let index = number / EPOCH_LENGTH;
+
+		let mut summary = match CurrentEpoch::get() {
+			Some((current, summary)) if current == index => summary,
+			Some((current, summary)) => {
+				EpochSummaries::insert(current, summary);
+				if let Some(expired) = current.checked_sub(MAX_EPOCH_SUMMARIES) {
+					EpochSummaries::remove(expired);
+				}
+				EpochSummary::default()
+			}
+			None => EpochSummary::default(),
+		};
+		summary.record(difficulty);
+
+		CurrentEpoch::put((index, summary));
+	}
+
+	/// Move the timestamps of `data` up so that the latest is one target block
+	/// time before `now`. The history seeded from the previous era otherwise
+	/// ends with the gap between the eras, which would read as a very slow
+	/// block.
+	fn rebase_timestamps(data: &mut [Option<DifficultyAndTimestamp<T::Moment>>], now: T::Moment) {
+		let latest = match data.iter().rev().find_map(|entry| *entry) {
+			Some(entry) => entry.timestamp,
+			None => return,
+		};
+		let offset = now
+			.saturating_sub(T::TargetBlockTime::get())
+			.saturating_sub(latest);
+
+		for entry in data.iter_mut().flatten() {
+			entry.timestamp = entry.timestamp.saturating_add(offset);
+		}
+	}
+}
+
+impl<T: Config> OnTimestampSet<T::Moment> for Module<T> {
+	fn on_timestamp_set(now: T::Moment) {
+		let block_time =
+			UniqueSaturatedInto::<u128>::unique_saturated_into(T::TargetBlockTime::get());
+
+		let mut data = PastDifficultiesAndTimestamps::<T>::get();
+		if RebaseTimestamps::take() {
+			Self::rebase_timestamps(&mut data, now);
+		}
+
+		for i in 1..data.len() {
+			data[i - 1] = data[i];
+		}
+
+		data[data.len() - 1] = Some(DifficultyAndTimestamp {
+			timestamp: now,
+			difficulty: Self::difficulty(),
+		});
+		Self::record_epoch(Self::difficulty());
+
+		let mut window = data;
+		Self::median_time_past(&mut window, MedianTimePastBlocks::get());
+		let difficulty = match Algorithm::get() {
+			DifficultyAlgorithm::Damped => Self::damped_difficulty(&window, block_time),
+			DifficultyAlgorithm::Lwma => Self::lwma_difficulty(&window, block_time),
+		};
+
 		// minimum difficulty avoids getting stuck due to dampening
 		let difficulty = min(
 			U256::from(MAX_DIFFICULTY),
-			max(
-				U256::from(MIN_DIFFICULTY),
-				diff_sum * U256::from(block_time) / U256::from(adj_ts),
-			),
+			max(U256::from(MIN_DIFFICULTY), difficulty),
 		);
 
 		<PastDifficultiesAndTimestamps<T>>::put(data);
@@ -0,0 +1,203 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Kulupu.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Kulupu is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Kulupu is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Kulupu. If not, see <http://www.gnu.org/licenses/>.
+
+//! Balance claims of past eras.
+//!
+//! Each past era recorded by the `eras` module has a final state root. An
+//! account can prove its balance in that state with a storage proof, instead
+//! of relying on a balance list bundled with the node. Proven balances are
+//! recorded once per era and account, and handed to `OnClaim`.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use frame_support::{
+	decl_error, decl_event, decl_module, decl_storage, ensure,
+	traits::{Currency, Get},
+	weights::Weight,
+};
+use frame_system::ensure_signed;
+use sp_core::H256;
+use sp_runtime::traits::BlakeTwo256;
+use sp_std::prelude::*;
+use sp_trie::{read_trie_value, Layout, StorageProof};
+
+/// Weight of checking one trie node of a proof.
+const PROOF_NODE_WEIGHT: Weight = 10_000_000;
+
+/// Storage layout of balances in past era states.
+pub trait EraBalances<AccountId, Balance> {
+	/// Storage key of the balance of `who` in era `era`, or `None` if claims
+	/// of the era are not supported.
+	fn storage_key(era: u32, who: &AccountId) -> Option<Vec<u8>>;
+	/// Decode a balance stored in era `era`.
+	fn decode_balance(era: u32, value: &[u8]) -> Option<Balance>;
+}
+
+/// Handler of proven past era balances.
+pub trait OnClaim<AccountId, Balance> {
+	/// Called once `who` proved holding `balance` at the end of era `era`.
+	fn on_claim(era: u32, who: &AccountId, balance: Balance);
+}
+
+impl<AccountId, Balance> OnClaim<AccountId, Balance> for () {
+	fn on_claim(_era: u32, _who: &AccountId, _balance: Balance) {}
+}
+
+/// Config for era claims.
+pub trait Config: pallet_eras::Config + frame_system::Config<Hash = H256> {
+	/// The overarching event type.
+	type Event: From<Event<Self>> + Into<<Self as frame_system::Config>::Event>;
+	/// An implementation of on-chain currency.
+	type Currency: Currency<Self::AccountId>;
+	/// Storage layout of balances in past era states.
+	type EraBalances: EraBalances<Self::AccountId, BalanceOf<Self>>;
+	/// Handler of proven balances.
+	type OnClaim: OnClaim<Self::AccountId, BalanceOf<Self>>;
+}
+
+/// Type alias for currency balance.
+pub type BalanceOf<T> =
+	<<T as Config>::Currency as Currency<<T as frame_system::Config>::AccountId>>::Balance;
+
+decl_error! {
+	pub enum Error for Module<T: Config> {
+		/// Era is unknown, or does not support claims.
+		UnknownEra,
+		/// Proof does not match the final state root of the era.
+		InvalidProof,
+		/// Account has no balance in the era.
+		NoBalance,
+		/// Balance of the era is already claimed.
+		AlreadyClaimed,
+	}
+}
+
+decl_storage! {
+	trait Store for Module<T: Config> as EraClaims {
+		/// Claimed balances, by era and account.
+		pub Claims get(fn claims): double_map hasher(twox_64_concat) u32, hasher(blake2_128_concat) T::AccountId => Option<BalanceOf<T>>;
+	}
+}
+
+decl_event! {
+	pub enum Event<T> where
+		AccountId = <T as frame_system::Config>::AccountId,
+		Balance = BalanceOf<T>,
+	{
+		/// Balance of an era claimed.
+		Claimed(u32, AccountId, Balance),
+	}
+}
+
+decl_module! {
+	pub struct Module<T: Config> for enum Call where origin: T::Origin {
+		type Error = Error<T>;
+
+		fn deposit_event() = default;
+
+		/// Claim the balance of the sender at the end of era `era`, proven by
+		/// `proof`, the trie nodes of a storage read proof of its balance.
+		#[weight = T::DbWeight::get().reads_writes(2, 1)
+			.saturating_add((proof.len() as Weight).saturating_mul(PROOF_NODE_WEIGHT))]
+		fn claim(origin, era: u32, proof: Vec<Vec<u8>>) {
+			let who = ensure_signed(origin)?;
+			ensure!(!Claims::<T>::contains_key(era, &who), Error::<T>::AlreadyClaimed);
+
+			let balance = Self::verify_balance(era, &who, proof)?;
+			Claims::<T>::insert(era, &who, balance);
+			T::OnClaim::on_claim(era, &who, balance);
+			Self::deposit_event(Event::<T>::Claimed(era, who, balance));
+		}
+	}
+}
+
+impl<T: Config> Module<T> {
+	/// Balance of `who` at the end of era `era`, proven by `proof`.
+	pub fn verify_balance(
+		era: u32,
+		who: &T::AccountId,
+		proof: Vec<Vec<u8>>,
+	) -> Result<BalanceOf<T>, Error<T>> {
+		let root = pallet_eras::Pallet::<T>::past_eras()
+			.get(era as usize)
+			.map(|era| era.final_state_root)
+			.ok_or(Error::<T>::UnknownEra)?;
+		let key = T::EraBalances::storage_key(era, who).ok_or(Error::<T>::UnknownEra)?;
+
+		let value = read_proof_value(root, proof, &key)
+			.map_err(|()| Error::<T>::InvalidProof)?
+			.ok_or(Error::<T>::NoBalance)?;
+		T::EraBalances::decode_balance(era, &value).ok_or(Error::<T>::InvalidProof)
+	}
+}
+
+/// Read `key` from the state with `root`, using the trie nodes of `proof`.
+pub fn read_proof_value(
+	root: H256,
+	proof: Vec<Vec<u8>>,
+	key: &[u8],
+) -> Result<Option<Vec<u8>>, ()> {
+	let db = StorageProof::new(proof).into_memory_db::<BlakeTwo256>();
+	read_trie_value::<Layout<BlakeTwo256>, _>(&db, &root, key).map_err(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sp_trie::{MemoryDB, TrieDBMut, TrieMut};
+
+	fn state(entries: &[(&str, &str)]) -> (H256, Vec<Vec<u8>>) {
+		let mut db = MemoryDB::<BlakeTwo256>::default();
+		let mut root = H256::default();
+		{
+			let mut trie = TrieDBMut::<Layout<BlakeTwo256>>::new(&mut db, &mut root);
+			for (key, value) in entries {
+				trie.insert(key.as_bytes(), value.as_bytes()).unwrap();
+			}
+		}
+
+		let proof = db.drain().into_iter().map(|(_, (node, _))| node).collect();
+		(root, proof)
+	}
+
+	#[test]
+	fn reads_proven_values() {
+		let (root, proof) = state(&[("alice", "100"), ("bob", "200")]);
+
+		assert_eq!(
+			read_proof_value(root, proof.clone(), b"bob"),
+			Ok(Some(b"200".to_vec()))
+		);
+		assert_eq!(read_proof_value(root, proof, b"carol"), Ok(None));
+	}
+
+	#[test]
+	fn rejects_proofs_of_other_roots() {
+		let (_, proof) = state(&[("alice", "100")]);
+		let (other_root, _) = state(&[("alice", "999")]);
+
+		assert_eq!(read_proof_value(other_root, proof, b"alice"), Err(()));
+	}
+
+	#[test]
+	fn rejects_incomplete_proofs() {
+		let (root, _) = state(&[("alice", "100"), ("bob", "200")]);
+
+		assert_eq!(read_proof_value(root, Vec::new(), b"alice"), Err(()));
+	}
+}
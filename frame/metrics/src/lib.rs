@@ -0,0 +1,95 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Kulupu.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Kulupu is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Kulupu is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Kulupu. If not, see <http://www.gnu.org/licenses/>.
+
+//! Block weight utilization module.
+//!
+//! Records how much of the normal and operational weight limits each recent
+//! block used, so that changes to the block weight limits can be based on
+//! on-chain data.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use codec::{Decode, Encode};
+use frame_support::{
+	decl_module, decl_storage,
+	traits::Get,
+	weights::{DispatchClass, Weight},
+};
+use scale_info::TypeInfo;
+use sp_runtime::Perbill;
+use sp_std::prelude::*;
+
+#[derive(Encode, Decode, TypeInfo, Clone, Copy, Eq, PartialEq, Debug)]
+pub struct BlockUtilization<N> {
+	/// Block number.
+	pub number: N,
+	/// Part of the normal weight limit used.
+	pub normal: Perbill,
+	/// Part of the operational weight limit used.
+	pub operational: Perbill,
+}
+
+pub trait Config: frame_system::Config {
+	/// Number of blocks to keep utilization of.
+	type HistoryLength: Get<u32>;
+}
+
+decl_storage! {
+	trait Store for Module<T: Config> as Metrics {
+		/// Weight utilization of past blocks, from earliest to latest.
+		pub PastUtilization get(fn past_utilization): Vec<BlockUtilization<T::BlockNumber>>;
+	}
+}
+
+decl_module! {
+	pub struct Module<T: Config> for enum Call where origin: T::Origin {
+		/// Number of blocks to keep utilization of.
+		const HistoryLength: u32 = T::HistoryLength::get();
+
+		fn on_initialize() -> Weight {
+			// Weight of `on_finalize`.
+			T::DbWeight::get().reads_writes(2, 1)
+		}
+
+		fn on_finalize(now: T::BlockNumber) {
+			let utilization = BlockUtilization {
+				number: now,
+				normal: Self::utilization(DispatchClass::Normal),
+				operational: Self::utilization(DispatchClass::Operational),
+			};
+
+			PastUtilization::<T>::mutate(|past| {
+				past.push(utilization);
+
+				let excess = past.len().saturating_sub(T::HistoryLength::get() as usize);
+				past.drain(..excess);
+			});
+		}
+	}
+}
+
+impl<T: Config> Module<T> {
+	/// Part of the weight limit of `class` used in the current block.
+	fn utilization(class: DispatchClass) -> Perbill {
+		let limits = T::BlockWeights::get();
+		let max = limits.get(class).max_total.unwrap_or(limits.max_block);
+		let used = *frame_system::Pallet::<T>::block_weight().get(class);
+
+		Perbill::from_rational(used, max)
+	}
+}
@@ -0,0 +1,95 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Kulupu.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Kulupu is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Kulupu is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Kulupu. If not, see <http://www.gnu.org/licenses/>.
+
+//! Benchmarking for Pool pallet.
+
+use super::*;
+use frame_benchmarking::{account, benchmarks, whitelisted_caller};
+use frame_system::RawOrigin;
+use sp_runtime::traits::Bounded;
+
+fn members<T: Config>(m: u32) -> Vec<(T::AccountId, u32)> {
+	(0..m).map(|i| (account("member", i, 0), 1)).collect()
+}
+
+// This function registers `operator` as a pool with `m` members.
+fn create_pool<T: Config>(operator: &T::AccountId, m: u32) -> Result<(), &'static str> {
+	T::Currency::make_free_balance_be(operator, BalanceOf::<T>::max_value());
+	Module::<T>::register(
+		RawOrigin::Signed(operator.clone()).into(),
+		Perbill::from_percent(1),
+	)?;
+	Module::<T>::set_members(RawOrigin::Signed(operator.clone()).into(), members::<T>(m))?;
+	Ok(())
+}
+
+benchmarks! {
+	register {
+		let caller: T::AccountId = whitelisted_caller();
+		T::Currency::make_free_balance_be(&caller, BalanceOf::<T>::max_value());
+	}: _(RawOrigin::Signed(caller.clone()), Perbill::from_percent(1))
+	verify {
+		assert!(Pools::<T>::contains_key(&caller));
+	}
+
+	// Worst case: the pool has the maximum number of members.
+	set_fee {
+		let caller: T::AccountId = whitelisted_caller();
+		create_pool::<T>(&caller, T::MaxMembers::get())?;
+	}: _(RawOrigin::Signed(caller.clone()), Perbill::from_percent(10))
+	verify {
+		assert_eq!(Pools::<T>::get(&caller).unwrap().fee, Perbill::from_percent(10));
+	}
+
+	// Worst case: the pool has the maximum number of members, which are all replaced.
+	set_members {
+		let m in 0 .. T::MaxMembers::get();
+		let caller: T::AccountId = whitelisted_caller();
+		create_pool::<T>(&caller, T::MaxMembers::get())?;
+		let members = (0..m).map(|i| (account("new member", i, 0), 1)).collect::<Vec<_>>();
+	}: _(RawOrigin::Signed(caller.clone()), members)
+	verify {
+		assert_eq!(Pools::<T>::get(&caller).unwrap().members.len() as u32, m);
+	}
+
+	// Worst case: the pool has the maximum number of members.
+	unregister {
+		let caller: T::AccountId = whitelisted_caller();
+		create_pool::<T>(&caller, T::MaxMembers::get())?;
+	}: _(RawOrigin::Signed(caller.clone()))
+	verify {
+		assert!(!Pools::<T>::contains_key(&caller));
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::mock::{new_test_ext, Test};
+	use frame_support::assert_ok;
+
+	#[test]
+	fn test_benchmarks() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(test_benchmark_register::<Test>());
+			assert_ok!(test_benchmark_set_fee::<Test>());
+			assert_ok!(test_benchmark_set_members::<Test>());
+			assert_ok!(test_benchmark_unregister::<Test>());
+		});
+	}
+}
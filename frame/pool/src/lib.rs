@@ -0,0 +1,228 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Kulupu.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Kulupu is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Kulupu is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Kulupu. If not, see <http://www.gnu.org/licenses/>.
+
+//! Mining pool payout module for Kulupu.
+//!
+//! A pool operator registers the account it mines with as a pool, and keeps
+//! the pool's members up to date, with each member's shares tallied off-chain
+//! from the share proofs miners submit to the pool. Block rewards of the pool
+//! are split into the operator fee and payouts to members proportional to
+//! their shares, and reward locks apply to each payout proportionally.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(feature = "runtime-benchmarks")]
+mod benchmarking;
+mod default_weights;
+#[cfg(test)]
+mod mock;
+#[cfg(test)]
+mod tests;
+
+use codec::{Decode, Encode};
+use frame_support::{
+	decl_error, decl_event, decl_module, decl_storage, ensure,
+	traits::{Currency, Get, ReservableCurrency},
+	weights::Weight,
+};
+use frame_system::ensure_signed;
+use scale_info::TypeInfo;
+use sp_runtime::{traits::Saturating, Perbill};
+use sp_std::prelude::*;
+
+/// A registered mining pool.
+#[derive(Encode, Decode, TypeInfo, Clone, PartialEq, Eq, Debug)]
+pub struct PoolInfo<AccountId, Balance> {
+	/// Part of each block reward kept by the operator.
+	pub fee: Perbill,
+	/// Deposit reserved from the operator.
+	pub deposit: Balance,
+	/// Pool members and their shares.
+	pub members: Vec<(AccountId, u32)>,
+}
+
+pub trait WeightInfo {
+	fn register() -> Weight;
+	fn set_fee() -> Weight;
+	fn set_members(m: u32) -> Weight;
+	fn unregister() -> Weight;
+}
+
+/// Config for pool.
+pub trait Config: frame_system::Config {
+	/// The overarching event type.
+	type Event: From<Event<Self>> + Into<<Self as frame_system::Config>::Event>;
+	/// An implementation of on-chain currency.
+	type Currency: ReservableCurrency<Self::AccountId>;
+	/// Deposit reserved for registering a pool.
+	type PoolDeposit: Get<BalanceOf<Self>>;
+	/// Maximum number of members of a pool.
+	type MaxMembers: Get<u32>;
+	/// Weight of paying a block reward to a pool member. Payouts of a block are
+	/// weighed at its start, so `set_members` includes it for each member.
+	type MemberPayoutWeight: Get<Weight>;
+	/// Weights for this pallet.
+	type WeightInfo: WeightInfo;
+}
+
+/// Type alias for currency balance.
+pub type BalanceOf<T> =
+	<<T as Config>::Currency as Currency<<T as frame_system::Config>::AccountId>>::Balance;
+
+decl_error! {
+	pub enum Error for Module<T: Config> {
+		/// Account is already registered as a pool.
+		AlreadyRegistered,
+		/// Account is not registered as a pool.
+		NotRegistered,
+		/// Too many pool members.
+		TooManyMembers,
+		/// Pool member has no shares.
+		NoShares,
+	}
+}
+
+decl_storage! {
+	trait Store for Module<T: Config> as Pool {
+		/// Registered pools, keyed by the account they mine with.
+		Pools get(fn pools): map hasher(twox_64_concat) T::AccountId => Option<PoolInfo<T::AccountId, BalanceOf<T>>>;
+	}
+}
+
+decl_event! {
+	pub enum Event<T> where AccountId = <T as frame_system::Config>::AccountId {
+		/// A pool has been registered.
+		PoolRegistered(AccountId, Perbill),
+		/// A pool has been unregistered.
+		PoolUnregistered(AccountId),
+		/// Pool fee has been changed.
+		PoolFeeChanged(AccountId, Perbill),
+		/// Pool members have been set.
+		MembersSet(AccountId, u32),
+	}
+}
+
+decl_module! {
+	pub struct Module<T: Config> for enum Call where origin: T::Origin {
+		type Error = Error<T>;
+
+		/// Deposit reserved for registering a pool.
+		const PoolDeposit: BalanceOf<T> = T::PoolDeposit::get();
+		/// Maximum number of members of a pool.
+		const MaxMembers: u32 = T::MaxMembers::get();
+
+		fn deposit_event() = default;
+
+		/// Register the sender as a pool keeping `fee` of each block reward.
+		#[weight = T::WeightInfo::register()]
+		fn register(origin, fee: Perbill) {
+			let operator = ensure_signed(origin)?;
+			ensure!(!Pools::<T>::contains_key(&operator), Error::<T>::AlreadyRegistered);
+
+			let deposit = T::PoolDeposit::get();
+			T::Currency::reserve(&operator, deposit)?;
+
+			Pools::<T>::insert(&operator, PoolInfo {
+				fee,
+				deposit,
+				members: Vec::new(),
+			});
+			Self::deposit_event(RawEvent::PoolRegistered(operator, fee));
+		}
+
+		/// Change the fee of the sender's pool.
+		#[weight = T::WeightInfo::set_fee()]
+		fn set_fee(origin, fee: Perbill) {
+			let operator = ensure_signed(origin)?;
+
+			Pools::<T>::try_mutate(&operator, |pool| -> Result<(), Error<T>> {
+				let pool = pool.as_mut().ok_or(Error::<T>::NotRegistered)?;
+				pool.fee = fee;
+				Ok(())
+			})?;
+			Self::deposit_event(RawEvent::PoolFeeChanged(operator, fee));
+		}
+
+		/// Set the members of the sender's pool and their shares, replacing the
+		/// existing ones.
+		#[weight = T::WeightInfo::set_members(members.len() as u32)
+			.saturating_add((members.len() as Weight).saturating_mul(T::MemberPayoutWeight::get()))]
+		fn set_members(origin, members: Vec<(T::AccountId, u32)>) {
+			let operator = ensure_signed(origin)?;
+			ensure!(members.len() <= T::MaxMembers::get() as usize, Error::<T>::TooManyMembers);
+			ensure!(members.iter().all(|(_, shares)| *shares > 0), Error::<T>::NoShares);
+
+			let count = members.len() as u32;
+			Pools::<T>::try_mutate(&operator, |pool| -> Result<(), Error<T>> {
+				let pool = pool.as_mut().ok_or(Error::<T>::NotRegistered)?;
+				pool.members = members;
+				Ok(())
+			})?;
+			Self::deposit_event(RawEvent::MembersSet(operator, count));
+		}
+
+		/// Unregister the sender's pool and return its deposit.
+		#[weight = T::WeightInfo::unregister()]
+		fn unregister(origin) {
+			let operator = ensure_signed(origin)?;
+			let pool = Pools::<T>::take(&operator).ok_or(Error::<T>::NotRegistered)?;
+
+			T::Currency::unreserve(&operator, pool.deposit);
+			Self::deposit_event(RawEvent::PoolUnregistered(operator));
+		}
+	}
+}
+
+impl<T: Config> pallet_rewards::SplitReward<T::AccountId, BalanceOf<T>> for Module<T> {
+	fn split_reward(
+		author: &T::AccountId,
+		reward: BalanceOf<T>,
+	) -> Vec<(T::AccountId, BalanceOf<T>)> {
+		let pool = match Self::pools(author) {
+			Some(pool) if !pool.members.is_empty() => pool,
+			_ => return vec![(author.clone(), reward)],
+		};
+
+		let total_shares = pool.members.iter().fold(0u64, |acc, (_, shares)| {
+			acc.saturating_add(u64::from(*shares))
+		});
+		let members_reward = reward.saturating_sub(pool.fee * reward);
+
+		let mut payouts = Vec::with_capacity(pool.members.len() + 1);
+		let mut operator_reward = reward;
+		for (member, shares) in pool.members {
+			let payout = Perbill::from_rational(u64::from(shares), total_shares) * members_reward;
+			operator_reward = operator_reward.saturating_sub(payout);
+			payouts.push((member, payout));
+		}
+		// The operator keeps the fee and any rounding remainder.
+		payouts.push((author.clone(), operator_reward));
+
+		payouts
+	}
+
+	fn payees(author: &T::AccountId) -> Vec<T::AccountId> {
+		let mut payees = match Self::pools(author) {
+			Some(pool) => pool.members.into_iter().map(|(member, _)| member).collect(),
+			None => Vec::new(),
+		};
+		payees.push(author.clone());
+
+		payees
+	}
+}
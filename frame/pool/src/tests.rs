@@ -0,0 +1,137 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Kulupu.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Kulupu is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Kulupu is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Kulupu. If not, see <http://www.gnu.org/licenses/>.
+
+//! Tests for Pool Pallet
+
+use crate::mock::*;
+use crate::*;
+use frame_support::{assert_noop, assert_ok};
+use pallet_balances::Error as BalancesError;
+use pallet_rewards::SplitReward;
+
+// Get the last event from System
+fn last_event() -> mock::Event {
+	System::events().pop().expect("Event expected").event
+}
+
+#[test]
+fn register_works() {
+	new_test_ext().execute_with(|| {
+		assert_ok!(Pool::register(Origin::signed(1), Perbill::from_percent(10)));
+		assert_eq!(Balances::reserved_balance(1), 10);
+		assert_eq!(
+			Pool::pools(1),
+			Some(PoolInfo {
+				fee: Perbill::from_percent(10),
+				deposit: 10,
+				members: Vec::new(),
+			})
+		);
+		assert_eq!(
+			last_event(),
+			RawEvent::PoolRegistered(1, Perbill::from_percent(10)).into()
+		);
+
+		// Cannot register twice
+		assert_noop!(
+			Pool::register(Origin::signed(1), Perbill::zero()),
+			Error::<Test>::AlreadyRegistered
+		);
+		// Cannot register without the deposit
+		assert_noop!(
+			Pool::register(Origin::signed(2), Perbill::zero()),
+			BalancesError::<Test, _>::InsufficientBalance
+		);
+	});
+}
+
+#[test]
+fn set_members_works() {
+	new_test_ext().execute_with(|| {
+		assert_noop!(
+			Pool::set_members(Origin::signed(1), vec![(11, 1)]),
+			Error::<Test>::NotRegistered
+		);
+
+		assert_ok!(Pool::register(Origin::signed(1), Perbill::zero()));
+		assert_noop!(
+			Pool::set_members(Origin::signed(1), vec![(11, 1), (12, 1), (13, 1), (14, 1)]),
+			Error::<Test>::TooManyMembers
+		);
+		assert_noop!(
+			Pool::set_members(Origin::signed(1), vec![(11, 1), (12, 0)]),
+			Error::<Test>::NoShares
+		);
+
+		assert_ok!(Pool::set_members(Origin::signed(1), vec![(11, 1), (12, 3)]));
+		assert_eq!(Pool::pools(1).unwrap().members, vec![(11, 1), (12, 3)]);
+		assert_eq!(last_event(), RawEvent::MembersSet(1, 2).into());
+	});
+}
+
+#[test]
+fn unregister_works() {
+	new_test_ext().execute_with(|| {
+		assert_noop!(
+			Pool::unregister(Origin::signed(1)),
+			Error::<Test>::NotRegistered
+		);
+
+		assert_ok!(Pool::register(Origin::signed(1), Perbill::zero()));
+		assert_ok!(Pool::unregister(Origin::signed(1)));
+		assert_eq!(Pool::pools(1), None);
+		assert_eq!(Balances::reserved_balance(1), 0);
+		assert_eq!(last_event(), RawEvent::PoolUnregistered(1).into());
+	});
+}
+
+#[test]
+fn split_reward_works() {
+	new_test_ext().execute_with(|| {
+		// Non-pool authors keep the whole reward
+		assert_eq!(Pool::split_reward(&1, 100), vec![(1, 100)]);
+
+		// Pools without members keep the whole reward
+		assert_ok!(Pool::register(Origin::signed(1), Perbill::from_percent(10)));
+		assert_eq!(Pool::split_reward(&1, 100), vec![(1, 100)]);
+
+		// Members split the reward after the fee by shares
+		assert_ok!(Pool::set_members(Origin::signed(1), vec![(11, 1), (12, 2)]));
+		assert_eq!(
+			Pool::split_reward(&1, 100),
+			vec![(11, 30), (12, 60), (1, 10)]
+		);
+
+		// The operator keeps the rounding remainder
+		assert_ok!(Pool::set_fee(Origin::signed(1), Perbill::zero()));
+		assert_eq!(Pool::split_reward(&1, 10), vec![(11, 3), (12, 7), (1, 0)]);
+	});
+}
+
+#[test]
+fn payees_include_members() {
+	new_test_ext().execute_with(|| {
+		assert_eq!(Pool::payees(&1), vec![1]);
+
+		assert_ok!(Pool::register(Origin::signed(1), Perbill::from_percent(10)));
+		assert_eq!(Pool::payees(&1), vec![1]);
+
+		assert_ok!(Pool::set_members(Origin::signed(1), vec![(11, 1), (12, 2)]));
+		assert_eq!(Pool::payees(&1), vec![11, 12, 1]);
+	});
+}
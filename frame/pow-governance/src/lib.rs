@@ -0,0 +1,146 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Kulupu.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Kulupu is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Kulupu is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Kulupu. If not, see <http://www.gnu.org/licenses/>.
+
+//! PoW governance track module for Kulupu.
+//!
+//! PoW parameters sometimes need to change faster than a full referendum
+//! allows, for example after a large swing of hashrate. This module is a fast
+//! lane for them: the council proposes a call, the technical committee
+//! approves it, and it is then dispatched as root. Only calls passing the
+//! `AllowedCalls` filter can be proposed, so that the lane cannot be used for
+//! anything but the PoW parameters. Everything else still goes through
+//! democracy.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(test)]
+mod mock;
+#[cfg(test)]
+mod tests;
+
+use frame_support::{
+	decl_error, decl_event, decl_module, decl_storage,
+	dispatch::{DispatchResult, Dispatchable, GetDispatchInfo, Parameter, PostDispatchInfo},
+	ensure,
+	traits::{Contains, EnsureOrigin, Get},
+	weights::Weight,
+};
+use sp_runtime::traits::Hash as HashT;
+use sp_std::prelude::*;
+
+/// Config for the PoW governance track.
+pub trait Config: frame_system::Config {
+	/// The overarching event type.
+	type Event: From<Event<Self>> + Into<<Self as frame_system::Config>::Event>;
+	/// The overarching call type.
+	type Call: Parameter
+		+ Dispatchable<Origin = Self::Origin, PostInfo = PostDispatchInfo>
+		+ GetDispatchInfo;
+	/// Calls that can be proposed on the track.
+	type AllowedCalls: Contains<<Self as Config>::Call>;
+	/// Origin allowed to propose calls.
+	type ProposeOrigin: EnsureOrigin<Self::Origin>;
+	/// Origin allowed to approve proposed calls, dispatching them.
+	type ApproveOrigin: EnsureOrigin<Self::Origin>;
+	/// Origin allowed to cancel proposed calls.
+	type CancelOrigin: EnsureOrigin<Self::Origin>;
+}
+
+decl_error! {
+	pub enum Error for Module<T: Config> {
+		/// Call is not allowed on the PoW governance track.
+		CallNotAllowed,
+		/// Call is already proposed.
+		DuplicateProposal,
+		/// No call is proposed with the hash.
+		NoProposal,
+		/// Weight of the call is above the given bound.
+		WeightBoundTooLow,
+	}
+}
+
+decl_storage! {
+	trait Store for Module<T: Config> as PowGovernance {
+		/// Proposed calls awaiting approval, by call hash.
+		pub Proposals get(fn proposal): map hasher(identity) T::Hash => Option<<T as Config>::Call>;
+	}
+}
+
+decl_event! {
+	pub enum Event<T> where
+		Hash = <T as frame_system::Config>::Hash,
+	{
+		/// A call has been proposed, with its hash.
+		Proposed(Hash),
+		/// A proposed call has been approved and dispatched, with its result.
+		Enacted(Hash, DispatchResult),
+		/// A proposed call has been cancelled.
+		Cancelled(Hash),
+	}
+}
+
+decl_module! {
+	pub struct Module<T: Config> for enum Call where origin: T::Origin {
+		type Error = Error<T>;
+
+		fn deposit_event() = default;
+
+		/// Propose `call`, to be dispatched as root once approved.
+		#[weight = 20_000_000 + T::DbWeight::get().reads_writes(1, 1)]
+		fn propose(origin, call: Box<<T as Config>::Call>) {
+			T::ProposeOrigin::ensure_origin(origin)?;
+			ensure!(T::AllowedCalls::contains(&call), Error::<T>::CallNotAllowed);
+
+			let hash = T::Hashing::hash_of(&call);
+			ensure!(!Proposals::<T>::contains_key(hash), Error::<T>::DuplicateProposal);
+
+			Proposals::<T>::insert(hash, *call);
+			Self::deposit_event(RawEvent::Proposed(hash));
+		}
+
+		/// Approve the call proposed with `hash`, dispatching it as root.
+		/// `weight_bound` must be at least the weight of the call.
+		#[weight = weight_bound
+			.saturating_add(30_000_000)
+			.saturating_add(T::DbWeight::get().reads_writes(1, 1))]
+		fn approve(origin, hash: T::Hash, weight_bound: Weight) {
+			T::ApproveOrigin::ensure_origin(origin)?;
+			let call = Self::proposal(hash).ok_or(Error::<T>::NoProposal)?;
+			ensure!(
+				call.get_dispatch_info().weight <= weight_bound,
+				Error::<T>::WeightBoundTooLow,
+			);
+
+			Proposals::<T>::remove(hash);
+			let result = call
+				.dispatch(frame_system::RawOrigin::Root.into())
+				.map(|_| ())
+				.map_err(|e| e.error);
+			Self::deposit_event(RawEvent::Enacted(hash, result));
+		}
+
+		/// Cancel the call proposed with `hash`.
+		#[weight = 20_000_000 + T::DbWeight::get().reads_writes(1, 1)]
+		fn cancel(origin, hash: T::Hash) {
+			T::CancelOrigin::ensure_origin(origin)?;
+			Proposals::<T>::take(hash).ok_or(Error::<T>::NoProposal)?;
+
+			Self::deposit_event(RawEvent::Cancelled(hash));
+		}
+	}
+}
@@ -0,0 +1,110 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Kulupu.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Kulupu is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Kulupu is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Kulupu. If not, see <http://www.gnu.org/licenses/>.
+
+//! Mock runtime for tests
+
+use super::*;
+use crate as pallet_pow_governance;
+
+use frame_support::{ord_parameter_types, parameter_types, traits::Everything};
+use frame_system::{self as system, EnsureRoot, EnsureSignedBy};
+use sp_core::H256;
+use sp_runtime::{
+	testing::Header,
+	traits::{BlakeTwo256, IdentityLookup},
+};
+
+type UncheckedExtrinsic = frame_system::mocking::MockUncheckedExtrinsic<Test>;
+type Block = frame_system::mocking::MockBlock<Test>;
+
+frame_support::construct_runtime! {
+	pub enum Test where
+		Block = Block,
+		NodeBlock = Block,
+		UncheckedExtrinsic = UncheckedExtrinsic,
+	{
+		System: frame_system::{Pallet, Call, Config, Storage, Event<T>},
+		PowGovernance: pallet_pow_governance::{Pallet, Call, Storage, Event<T>},
+	}
+}
+
+parameter_types! {
+	pub const BlockHashCount: u64 = 250;
+}
+
+ord_parameter_types! {
+	pub const Proposer: u64 = 1;
+	pub const Approver: u64 = 2;
+}
+
+impl system::Config for Test {
+	type BaseCallFilter = Everything;
+	type Origin = Origin;
+	type Call = Call;
+	type Index = u64;
+	type BlockNumber = u64;
+	type Hash = H256;
+	type Hashing = BlakeTwo256;
+	type AccountId = u64;
+	type Lookup = IdentityLookup<Self::AccountId>;
+	type Header = Header;
+	type Event = Event;
+	type BlockHashCount = BlockHashCount;
+	type DbWeight = ();
+	type Version = ();
+	type PalletInfo = PalletInfo;
+	type BlockWeights = ();
+	type BlockLength = ();
+	type AccountData = ();
+	type OnNewAccount = ();
+	type OnKilledAccount = ();
+	type SystemWeightInfo = ();
+	type SS58Prefix = ();
+	type OnSetCode = ();
+}
+
+/// Only setting heap pages stands in for the PoW parameter calls.
+pub struct HeapPagesOnly;
+impl Contains<Call> for HeapPagesOnly {
+	fn contains(call: &Call) -> bool {
+		matches!(
+			call,
+			Call::System(frame_system::Call::set_heap_pages { .. })
+		)
+	}
+}
+
+impl pallet_pow_governance::Config for Test {
+	type Event = Event;
+	type Call = Call;
+	type AllowedCalls = HeapPagesOnly;
+	type ProposeOrigin = EnsureSignedBy<Proposer, u64>;
+	type ApproveOrigin = EnsureSignedBy<Approver, u64>;
+	type CancelOrigin = EnsureRoot<u64>;
+}
+
+// Build genesis storage according to the mock runtime.
+pub fn new_test_ext() -> sp_io::TestExternalities {
+	let t = system::GenesisConfig::default()
+		.build_storage::<Test>()
+		.unwrap();
+
+	let mut ext = sp_io::TestExternalities::new(t);
+	ext.execute_with(|| System::set_block_number(1));
+	ext
+}
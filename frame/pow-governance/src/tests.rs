@@ -0,0 +1,121 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Kulupu.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Kulupu is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Kulupu is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Kulupu. If not, see <http://www.gnu.org/licenses/>.
+
+//! Tests for PoW Governance Pallet
+
+use crate::mock::*;
+use crate::*;
+use codec::Encode;
+use frame_support::{assert_noop, assert_ok};
+use sp_core::storage::well_known_keys;
+use sp_runtime::traits::{BadOrigin, BlakeTwo256, Hash};
+
+// Get the last event from System
+fn last_event() -> mock::Event {
+	System::events().pop().expect("Event expected").event
+}
+
+fn heap_pages_call(pages: u64) -> Box<mock::Call> {
+	Box::new(mock::Call::System(frame_system::Call::set_heap_pages {
+		pages,
+	}))
+}
+
+#[test]
+fn approved_proposals_are_dispatched_as_root() {
+	new_test_ext().execute_with(|| {
+		let call = heap_pages_call(10);
+		let hash = BlakeTwo256::hash_of(&call);
+		let weight = call.get_dispatch_info().weight;
+
+		assert_ok!(PowGovernance::propose(Origin::signed(1), call.clone()));
+		assert_eq!(PowGovernance::proposal(hash), Some(*call));
+		assert_eq!(last_event(), RawEvent::Proposed(hash).into());
+
+		// Only the approve origin can approve
+		assert_noop!(
+			PowGovernance::approve(Origin::signed(1), hash, weight),
+			BadOrigin
+		);
+		// The weight bound must cover the call
+		assert_noop!(
+			PowGovernance::approve(Origin::signed(2), hash, weight - 1),
+			Error::<Test>::WeightBoundTooLow
+		);
+
+		assert_ok!(PowGovernance::approve(Origin::signed(2), hash, weight));
+		assert_eq!(PowGovernance::proposal(hash), None);
+		assert_eq!(last_event(), RawEvent::Enacted(hash, Ok(())).into());
+		assert_eq!(
+			sp_io::storage::get(well_known_keys::HEAP_PAGES),
+			Some(10u64.encode())
+		);
+
+		assert_noop!(
+			PowGovernance::approve(Origin::signed(2), hash, weight),
+			Error::<Test>::NoProposal
+		);
+	});
+}
+
+#[test]
+fn only_allowed_calls_can_be_proposed() {
+	new_test_ext().execute_with(|| {
+		let remark = Box::new(mock::Call::System(frame_system::Call::remark {
+			remark: vec![1],
+		}));
+		assert_noop!(
+			PowGovernance::propose(Origin::signed(1), remark),
+			Error::<Test>::CallNotAllowed
+		);
+
+		// Only the propose origin can propose
+		assert_noop!(
+			PowGovernance::propose(Origin::signed(2), heap_pages_call(10)),
+			BadOrigin
+		);
+
+		assert_ok!(PowGovernance::propose(
+			Origin::signed(1),
+			heap_pages_call(10)
+		));
+		assert_noop!(
+			PowGovernance::propose(Origin::signed(1), heap_pages_call(10)),
+			Error::<Test>::DuplicateProposal
+		);
+	});
+}
+
+#[test]
+fn cancel_works() {
+	new_test_ext().execute_with(|| {
+		let call = heap_pages_call(10);
+		let hash = BlakeTwo256::hash_of(&call);
+		assert_ok!(PowGovernance::propose(Origin::signed(1), call));
+
+		assert_noop!(PowGovernance::cancel(Origin::signed(1), hash), BadOrigin);
+		assert_ok!(PowGovernance::cancel(Origin::root(), hash));
+		assert_eq!(PowGovernance::proposal(hash), None);
+		assert_eq!(last_event(), RawEvent::Cancelled(hash).into());
+
+		assert_noop!(
+			PowGovernance::cancel(Origin::root(), hash),
+			Error::<Test>::NoProposal
+		);
+	});
+}
@@ -45,10 +45,23 @@ fn create_locks<T: Config>(who: &T::AccountId, num_of_locks: u32) {
 	RewardLocks::<T>::insert(who, locks);
 }
 
+// Number of reward locks of accounts in the worst case of unlocking them: the most locks a miner
+// can get, or more than can be kept, so that they are compacted.
+fn unlock_locks<T: Config>() -> u32 {
+	let max_locks = T::GenerateRewardLocks::max_locks(T::LockParametersBounds::get());
+	max_locks.max(T::MaxRewardLocks::get().saturating_add(2).saturating_mul(2))
+}
+
 benchmarks! {
-	// Worst case: Author info is in digest.
+	// Worst case: Author info is in digest, and the author of the block leaving the recency window is
+	// forgotten.
 	on_initialize {
 		let author: T::AccountId = account("author", 0, 0);
+		let expired_author: T::AccountId = account("author", 1, 0);
+		let expired = T::BlockNumber::one();
+		RecentAuthors::<T>::insert(expired, &expired_author);
+		LastAuthored::<T>::insert(&expired_author, expired);
+		frame_system::Module::<T>::set_block_number(expired + T::RecentAuthorBlocks::get());
 		let author_digest = DigestItemOf::<T>::PreRuntime(sp_consensus_pow::POW_ENGINE_ID, author.encode());
 		frame_system::Module::<T>::deposit_log(author_digest);
 
@@ -61,17 +74,26 @@ benchmarks! {
 	}: { crate::Module::<T>::on_initialize(block_number); }
 	verify {
 		assert_eq!(Author::<T>::get(), Some(author));
+		assert!(LastAuthored::<T>::get(&expired_author).is_none());
 	}
 
 	// Worst case: This author already has `max_locks` locked up, produces a new block, and we unlock
-	// everything in addition to creating brand new locks for the new reward.
+	// everything in addition to creating brand new locks for the new reward. Part of the reward is
+	// donated, and the rest is paid out to `p` accounts, the author and the destinations of its
+	// payout split.
 	on_finalize {
+		let p in 1 .. T::MaxPayoutDestinations::get() + 1;
 		let author: T::AccountId = account("author", 0, 0);
 		let reward = BalanceOf::<T>::max_value();
 
 		// Setup pallet variables
 		Author::<T>::put(&author);
 		Reward::<T>::put(reward);
+		let split = (1..p)
+			.map(|i| (account("destination", i, 0), Perbill::from_rational(1, p)))
+			.collect::<Vec<(T::AccountId, _)>>();
+		PayoutSplits::<T>::insert(&author, split);
+		StandingDonations::<T>::insert(&author, Perbill::from_percent(10));
 
 		// Create existing locks on author.
 		let max_locks = T::GenerateRewardLocks::max_locks(T::LockParametersBounds::get());
@@ -91,33 +113,148 @@ benchmarks! {
 		assert!(RewardLocks::<T>::get(&author).iter().count() > 0);
 	}
 
-	// Worst case: Target user has `max_locks` which are all unlocked during this call.
+	// Worst case: Target user has `max_locks`, or more than `MaxRewardLocks`, half of which are
+	// unlocked during this call. The rest is compacted, and the entry of the target in the unlock
+	// schedule is moved to its next unlock.
 	unlock {
 		let miner = account("miner", 0, 0);
-		let max_locks = T::GenerateRewardLocks::max_locks(T::LockParametersBounds::get());
-		create_locks::<T>(&miner, max_locks);
+		let locks = unlock_locks::<T>();
+		create_locks::<T>(&miner, locks);
+		UnlockSchedule::<T>::insert(T::BlockNumber::from(0u32), &miner, ());
 		let caller = whitelisted_caller();
-		frame_system::Module::<T>::set_block_number(max_locks.into());
-		assert_eq!(RewardLocks::<T>::get(&miner).iter().count() as u32, max_locks);
+		frame_system::Module::<T>::set_block_number((locks / 2).into());
 	}: _(RawOrigin::Signed(caller), miner.clone())
 	verify {
-		assert_eq!(RewardLocks::<T>::get(&miner).iter().count(), 0);
+		assert!(!UnlockSchedule::<T>::contains_key(T::BlockNumber::from(0u32), &miner));
+		let next_unlock = RewardLocks::<T>::get(&miner).keys().next().cloned().unwrap();
+		assert!(next_unlock > (locks / 2).into());
+		assert!(UnlockSchedule::<T>::contains_key(next_unlock, &miner));
+	}
+
+	// Worst case: `b` blocks of the unlock schedule are scanned, and `n` accounts are unlocked as
+	// in the worst case of `unlock`.
+	auto_unlock {
+		let b in 1 .. T::MaxAutoUnlocks::get();
+		let n in 0 .. T::MaxAutoUnlocks::get();
+		let locks = unlock_locks::<T>();
+		let miners = (0..n).map(|i| account("miner", i, 0)).collect::<Vec<T::AccountId>>();
+		for miner in &miners {
+			create_locks::<T>(miner, locks);
+			UnlockSchedule::<T>::insert(T::BlockNumber::from(0u32), miner, ());
+		}
+		frame_system::Module::<T>::set_block_number((locks / 2).max(b).into());
+	}: { crate::Module::<T>::do_auto_unlocks(frame_system::Module::<T>::block_number()); }
+	verify {
+		for miner in &miners {
+			assert!(!UnlockSchedule::<T>::contains_key(T::BlockNumber::from(0u32), miner));
+		}
 	}
 
+	// Worst case: `m` mints, `r` reward changes and `c` mint changes of one
+	// mint each.
 	set_schedule {
+		let m in 0 .. T::MaxMints::get();
+		let r in 0 .. T::MaxRewardChanges::get();
+		let c in 0 .. T::MaxMintChanges::get();
+		let minimum = T::Currency::minimum_balance();
+		let mints = (0..m)
+			.map(|i| (account("mint", i, 0), minimum))
+			.collect::<Vec<(T::AccountId, _)>>();
+		let reward_changes = (0..r)
+			.map(|i| (T::BlockNumber::from(i + 1), minimum))
+			.collect::<Vec<_>>();
+		let mint_changes = (0..c)
+			.map(|i| (T::BlockNumber::from(i + 1), vec![(account("mint", i, 0), minimum)]))
+			.collect::<Vec<_>>();
+	}: _(RawOrigin::Root, minimum, mints, reward_changes, mint_changes)
+	verify {
+		assert_eq!(RewardChanges::<T>::get().len() as u32, r);
+		assert_eq!(MintChanges::<T>::get().len() as u32, c);
+	}
 
-	}: _(RawOrigin::Root, T::Currency::minimum_balance(), BTreeMap::new(), BTreeMap::new(), BTreeMap::new())
+	// Worst case: the maximum number of reward changes, one replaced.
+	add_reward_change {
+		let minimum = T::Currency::minimum_balance();
+		let reward_changes = (0..T::MaxRewardChanges::get())
+			.map(|i| (T::BlockNumber::from(i + 1), minimum))
+			.collect::<BTreeMap<_, _>>();
+		RewardChanges::<T>::put(BoundedBTreeMap::try_from(reward_changes).unwrap());
+	}: _(RawOrigin::Root, T::BlockNumber::from(1u32), minimum)
+
+	// Worst case: the maximum number of reward changes.
+	remove_reward_change {
+		let minimum = T::Currency::minimum_balance();
+		let reward_changes = (0..T::MaxRewardChanges::get())
+			.map(|i| (T::BlockNumber::from(i + 1), minimum))
+			.collect::<BTreeMap<_, _>>();
+		RewardChanges::<T>::put(BoundedBTreeMap::try_from(reward_changes).unwrap());
+	}: _(RawOrigin::Root, T::BlockNumber::from(1u32))
+
+	// Worst case: the change has `m` mints and replaces another one.
+	add_mint_change {
+		let m in 0 .. T::MaxMints::get();
+		let minimum = T::Currency::minimum_balance();
+		let mints = (0..m)
+			.map(|i| (account("mint", i, 0), minimum))
+			.collect::<Vec<(T::AccountId, _)>>();
+		let mint_changes = vec![(T::BlockNumber::from(1u32), MintsOf::<T>::default())]
+			.into_iter()
+			.collect::<BTreeMap<_, _>>();
+		MintChanges::<T>::put(BoundedBTreeMap::try_from(mint_changes).unwrap());
+	}: _(RawOrigin::Root, T::BlockNumber::from(1u32), mints)
+	verify {
+		assert_eq!(MintChanges::<T>::get()[&T::BlockNumber::from(1u32)].len() as u32, m);
+	}
+
+	// Worst case: the maximum number of mint changes.
+	remove_mint_change {
+		let mint_changes = (0..T::MaxMintChanges::get())
+			.map(|i| (T::BlockNumber::from(i + 1), MintsOf::<T>::default()))
+			.collect::<BTreeMap<_, _>>();
+		MintChanges::<T>::put(BoundedBTreeMap::try_from(mint_changes).unwrap());
+	}: _(RawOrigin::Root, T::BlockNumber::from(1u32))
+
+	// Worst case: a standing donation is set.
+	set_standing_donation {
+		let caller: T::AccountId = whitelisted_caller();
+	}: _(RawOrigin::Signed(caller.clone()), Perbill::from_percent(10))
+	verify {
+		assert_eq!(StandingDonations::<T>::get(&caller), Perbill::from_percent(10));
+	}
 
 	// Worst case: a new lock params is set.
 	set_lock_params {
 
 	}: _(RawOrigin::Root, LockParameters {period: 150, divide: 25} )
+
+	// Worst case: the split has the maximum number of destinations.
+	set_payout_split {
+		let d in 0 .. T::MaxPayoutDestinations::get();
+		let caller: T::AccountId = whitelisted_caller();
+		let split = (0..d)
+			.map(|i| (account("destination", i, 0), Perbill::from_percent(1)))
+			.collect::<Vec<_>>();
+	}: _(RawOrigin::Signed(caller.clone()), split)
+	verify {
+		assert_eq!(PayoutSplits::<T>::get(&caller).len() as u32, d);
+	}
+
+	// Worst case: the split has the maximum number of destinations.
+	set_tip_split {
+		let d in 0 .. T::MaxPayoutDestinations::get();
+		let split = (0..d)
+			.map(|i| (account("destination", i, 0), Perbill::from_percent(1)))
+			.collect::<Vec<_>>();
+	}: _(RawOrigin::Root, split)
+	verify {
+		assert_eq!(TipSplit::<T>::get().len() as u32, d);
+	}
 }
 
 #[cfg(test)]
 mod tests {
 	use super::*;
-	use crate::mock::{new_test_ext, Test};
+	use crate::mock::{new_test_ext, MaxAutoUnlocks, Test};
 	use frame_support::assert_ok;
 
 	#[test]
@@ -126,8 +263,17 @@ mod tests {
 			assert_ok!(test_benchmark_on_finalize::<Test>());
 			assert_ok!(test_benchmark_on_initialize::<Test>());
 			assert_ok!(test_benchmark_unlock::<Test>());
+			MaxAutoUnlocks::set(2);
+			assert_ok!(test_benchmark_auto_unlock::<Test>());
 			assert_ok!(test_benchmark_set_schedule::<Test>());
+			assert_ok!(test_benchmark_add_reward_change::<Test>());
+			assert_ok!(test_benchmark_remove_reward_change::<Test>());
+			assert_ok!(test_benchmark_add_mint_change::<Test>());
+			assert_ok!(test_benchmark_remove_mint_change::<Test>());
 			assert_ok!(test_benchmark_set_lock_params::<Test>());
+			assert_ok!(test_benchmark_set_payout_split::<Test>());
+			assert_ok!(test_benchmark_set_tip_split::<Test>());
+			assert_ok!(test_benchmark_set_standing_donation::<Test>());
 		});
 	}
 }
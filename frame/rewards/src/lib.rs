@@ -31,16 +31,29 @@ mod tests;
 
 use codec::{Decode, Encode};
 use frame_support::{
-	decl_error, decl_event, decl_module, decl_storage, ensure,
-	traits::{Currency, Get, LockIdentifier, LockableCurrency, WithdrawReasons},
-	weights::Weight,
+	decl_error, decl_event, decl_module, decl_storage,
+	dispatch::{DispatchResult, DispatchResultWithPostInfo},
+	ensure,
+	storage::bounded_btree_map::BoundedBTreeMap,
+	traits::{
+		Currency, Get, Imbalance, LockIdentifier, LockableCurrency, OnRuntimeUpgrade, OnUnbalanced,
+		WithdrawReasons,
+	},
+	weights::{Pays, Weight},
 };
 use frame_system::{ensure_root, ensure_signed};
 use scale_info::TypeInfo;
 use sp_consensus_pow::POW_ENGINE_ID;
-use sp_runtime::traits::{Saturating, Zero};
+use sp_runtime::{
+	traits::{AtLeast32BitUnsigned, Bounded, CheckedDiv, CheckedSub, One, Saturating, Zero},
+	Perbill,
+};
 use sp_std::{
-	collections::btree_map::BTreeMap, iter::FromIterator, ops::Bound::Included, prelude::*,
+	collections::btree_map::BTreeMap,
+	convert::TryFrom,
+	iter::FromIterator,
+	ops::Bound::{Excluded, Included, Unbounded},
+	prelude::*,
 };
 
 pub struct LockBounds {
@@ -82,12 +95,39 @@ impl<T: Config> GenerateRewardLocks<T> for () {
 	}
 }
 
+/// Trait for splitting a block reward into payouts.
+pub trait SplitReward<AccountId, Balance> {
+	/// Split the `reward` of block `author` into payouts. Payouts should add up to
+	/// at most `reward`.
+	fn split_reward(author: &AccountId, reward: Balance) -> Vec<(AccountId, Balance)>;
+	/// Accounts a reward of block `author` is split among.
+	fn payees(author: &AccountId) -> Vec<AccountId>;
+}
+
+impl<AccountId: Clone, Balance> SplitReward<AccountId, Balance> for () {
+	fn split_reward(author: &AccountId, reward: Balance) -> Vec<(AccountId, Balance)> {
+		vec![(author.clone(), reward)]
+	}
+
+	fn payees(author: &AccountId) -> Vec<AccountId> {
+		vec![author.clone()]
+	}
+}
+
 pub trait WeightInfo {
 	fn on_initialize() -> Weight;
-	fn on_finalize() -> Weight;
+	fn on_finalize(p: u32) -> Weight;
 	fn unlock() -> Weight;
-	fn set_schedule() -> Weight;
+	fn auto_unlock(b: u32, n: u32) -> Weight;
+	fn set_schedule(m: u32, r: u32, c: u32) -> Weight;
+	fn add_reward_change() -> Weight;
+	fn remove_reward_change() -> Weight;
+	fn add_mint_change(m: u32) -> Weight;
+	fn remove_mint_change() -> Weight;
 	fn set_lock_params() -> Weight;
+	fn set_payout_split(d: u32) -> Weight;
+	fn set_tip_split(d: u32) -> Weight;
+	fn set_standing_donation() -> Weight;
 }
 
 /// Config for rewards.
@@ -104,12 +144,41 @@ pub trait Config: frame_system::Config {
 	type WeightInfo: WeightInfo;
 	/// Lock Parameters Bounds.
 	type LockParametersBounds: Get<LockBounds>;
+	/// Withdraw reasons that reward locks do not apply to.
+	type LockExemptReasons: Get<WithdrawReasons>;
+	/// Split block rewards into payouts, for example among pool members.
+	type SplitReward: SplitReward<Self::AccountId, BalanceOf<Self>>;
+	/// Maximum number of reward locks kept per account. Adjacent locks are merged
+	/// beyond this.
+	type MaxRewardLocks: Get<u32>;
+	/// Maximum number of destinations in a payout split.
+	type MaxPayoutDestinations: Get<u32>;
+	/// Maximum number of mint destinations.
+	type MaxMints: Get<u32>;
+	/// Maximum number of planned reward changes.
+	type MaxRewardChanges: Get<u32>;
+	/// Maximum number of planned mint changes.
+	type MaxMintChanges: Get<u32>;
+	/// Maximum number of accounts whose expired reward locks are unlocked
+	/// automatically each block. Zero disables automatic unlocks.
+	type MaxAutoUnlocks: Get<u32>;
+	/// Blocks since an account last authored for it to count as a recent author.
+	type RecentAuthorBlocks: Get<Self::BlockNumber>;
 }
 
 /// Type alias for currency balance.
 pub type BalanceOf<T> =
 	<<T as Config>::Currency as Currency<<T as frame_system::Config>::AccountId>>::Balance;
 
+/// Type alias for negative imbalances of the currency.
+pub type NegativeImbalanceOf<T> = <<T as Config>::Currency as Currency<
+	<T as frame_system::Config>::AccountId,
+>>::NegativeImbalance;
+
+/// Type alias for mints of a block.
+pub type MintsOf<T> =
+	BoundedBTreeMap<<T as frame_system::Config>::AccountId, BalanceOf<T>, <T as Config>::MaxMints>;
+
 decl_error! {
 	pub enum Error for Module<T: Config> {
 		/// Reward set is too low.
@@ -122,35 +191,80 @@ decl_error! {
 		LockParamsOutOfBounds,
 		/// Lock period is not a mutiple of the divide.
 		LockPeriodNotDivisible,
+		/// Payout split has too many destinations.
+		TooManyPayoutDestinations,
+		/// Payout split adds up to more than the whole reward.
+		PayoutSplitOverflow,
+		/// Schedule has too many mint destinations.
+		TooManyMints,
+		/// Schedule has too many reward changes.
+		TooManyRewardChanges,
+		/// Schedule has too many mint changes.
+		TooManyMintChanges,
+		/// No change is planned at the block.
+		NoPlannedChange,
 	}
 }
 
 decl_storage! {
 	trait Store for Module<T: Config> as Rewards {
-		/// Current block author.
+		/// Current block author, or its payout account if the pre-runtime digest
+		/// carries one.
 		Author get(fn author): Option<T::AccountId>;
 
+		/// Tips paid to the current block author so far.
+		Tips get(fn tips): BalanceOf<T>;
+		/// Last block authored by each author, or their payout account.
+		LastAuthored get(fn last_authored): map hasher(twox_64_concat) T::AccountId => Option<T::BlockNumber>;
+		/// Authors of the last `RecentAuthorBlocks` blocks, by block, to forget them
+		/// in `LastAuthored` once they are no longer recent.
+		RecentAuthors get(fn recent_authors): map hasher(twox_64_concat) T::BlockNumber => Option<T::AccountId>;
+
 		/// Current block reward for miner.
 		Reward get(fn reward) config(): BalanceOf<T>;
 		/// Pending reward locks.
 		RewardLocks get(fn reward_locks): map hasher(twox_64_concat) T::AccountId => BTreeMap<T::BlockNumber, BalanceOf<T>>;
 		/// Reward changes planned in the future.
-		RewardChanges get(fn reward_changes): BTreeMap<T::BlockNumber, BalanceOf<T>>;
+		RewardChanges get(fn reward_changes): BoundedBTreeMap<T::BlockNumber, BalanceOf<T>, T::MaxRewardChanges>;
 
 		/// Current block mints.
-		Mints get(fn mints) config(): BTreeMap<T::AccountId, BalanceOf<T>>;
+		Mints get(fn mints): MintsOf<T>;
 		/// Mint changes planned in the future.
-		MintChanges get(fn mint_changes): BTreeMap<T::BlockNumber, BTreeMap<T::AccountId, BalanceOf<T>>>;
+		MintChanges get(fn mint_changes): BoundedBTreeMap<T::BlockNumber, MintsOf<T>, T::MaxMintChanges>;
 
 		/// Lock parameters (period and divide).
 		LockParams get(fn lock_params): Option<LockParameters>;
 
-		StorageVersion build(|_| migrations::StorageVersion::V1): migrations::StorageVersion;
+		/// Payout splits of miners, as destinations and their part of each reward.
+		PayoutSplits get(fn payout_splits): map hasher(twox_64_concat) T::AccountId => Vec<(T::AccountId, Perbill)>;
+		/// Parts of their block rewards miners donate to the donation destination.
+		StandingDonations get(fn standing_donation): map hasher(twox_64_concat) T::AccountId => Perbill;
+		/// Parts of transaction tips paid to other accounts than the block author.
+		TipSplit get(fn tip_split): Vec<(T::AccountId, Perbill)>;
+
+		/// Accounts with reward locks, by the block of their next unlock.
+		UnlockSchedule: double_map hasher(twox_64_concat) T::BlockNumber, hasher(twox_64_concat) T::AccountId => ();
+		/// Earliest block of the unlock schedule with accounts possibly left to unlock.
+		UnlockCursor get(fn unlock_cursor): T::BlockNumber;
+
+		StorageVersion build(|_| migrations::StorageVersion::V2): migrations::StorageVersion;
+	}
+	add_extra_genesis {
+		config(mints): BTreeMap<T::AccountId, BalanceOf<T>>;
+		build(|config: &GenesisConfig<T>| {
+			let mints = MintsOf::<T>::try_from(config.mints.clone())
+				.expect("Genesis mints must not exceed MaxMints");
+			Mints::<T>::put(mints);
+		});
 	}
 }
 
 decl_event! {
-	pub enum Event<T> where AccountId = <T as frame_system::Config>::AccountId, Balance = BalanceOf<T> {
+	pub enum Event<T> where
+		AccountId = <T as frame_system::Config>::AccountId,
+		Balance = BalanceOf<T>,
+		BlockNumber = <T as frame_system::Config>::BlockNumber,
+	{
 		/// A new schedule has been set.
 		ScheduleSet,
 		/// Reward has been sent.
@@ -163,6 +277,28 @@ decl_event! {
 		MintsChanged(BTreeMap<AccountId, Balance>),
 		/// Lock Parameters have been changed.
 		LockParamsChanged(LockParameters),
+		/// Payout split of a miner has been changed.
+		PayoutSplitChanged(AccountId),
+		/// Reward locks have been unlocked, with the released and still locked
+		/// balances, and the block of the next unlock.
+		Unlocked(AccountId, Balance, Balance, Option<BlockNumber>),
+		/// Tips of the block have been paid to its author.
+		TipsPaid(AccountId, Balance),
+		/// Split of transaction tips has been changed.
+		TipSplitChanged(Vec<(AccountId, Perbill)>),
+		/// Standing donation of a miner has been changed.
+		StandingDonationChanged(AccountId, Perbill),
+		/// Part of the block reward of a miner has been donated to the donation
+		/// destination by their standing donation.
+		StandingDonated(AccountId, Balance),
+		/// A reward change has been planned at a block, replacing any previous one.
+		RewardChangeAdded(BlockNumber, Balance),
+		/// The reward change planned at a block has been removed.
+		RewardChangeRemoved(BlockNumber),
+		/// A mint change has been planned at a block, replacing any previous one.
+		MintChangeAdded(BlockNumber, BTreeMap<AccountId, Balance>),
+		/// The mint change planned at a block has been removed.
+		MintChangeRemoved(BlockNumber),
 	}
 }
 
@@ -173,18 +309,23 @@ decl_module! {
 		fn deposit_event() = default;
 
 		fn on_initialize(now: T::BlockNumber) -> Weight {
-			let author = frame_system::Pallet::<T>::digest()
-				.logs
-				.iter()
-				.filter_map(|s| s.as_pre_runtime())
-				.filter_map(|(id, mut data)| if id == POW_ENGINE_ID {
-					T::AccountId::decode(&mut data).ok()
-				} else {
-					None
-				})
-				.next();
+			let author = Self::author_from_digest(&frame_system::Pallet::<T>::digest());
 
+			if let Some(expired) = now.checked_sub(&T::RecentAuthorBlocks::get()) {
+				if let Some(expired_author) = RecentAuthors::<T>::take(expired) {
+					if LastAuthored::<T>::get(&expired_author) == Some(expired) {
+						LastAuthored::<T>::remove(&expired_author);
+					}
+				}
+			}
+
+			// Payouts of the block reward are made in `on_finalize`, so they are
+			// weighed here.
+			let mut payouts = 0;
 			if let Some(author) = author {
+				payouts = Self::payout_count(&author);
+				LastAuthored::<T>::insert(&author, now);
+				RecentAuthors::<T>::insert(now, &author);
 				<Self as Store>::Author::put(author);
 			}
 
@@ -210,7 +351,7 @@ decl_module! {
 					Mints::<T>::set(mints.clone());
 					removing.push(*block_number);
 
-					Self::deposit_event(Event::<T>::MintsChanged(mints.clone()));
+					Self::deposit_event(Event::<T>::MintsChanged((**mints).clone()));
 				}
 
 				for block_number in removing {
@@ -218,13 +359,23 @@ decl_module! {
 				}
 			});
 
-			T::WeightInfo::on_initialize().saturating_add(T::WeightInfo::on_finalize())
+			let (scanned, unlocked) = Self::do_auto_unlocks(now);
+
+			T::WeightInfo::on_initialize()
+				.saturating_add(T::DbWeight::get().reads(1 + payouts as Weight))
+				.saturating_add(T::WeightInfo::on_finalize(payouts))
+				.saturating_add(T::WeightInfo::auto_unlock(scanned, unlocked))
 		}
 
 		fn on_finalize(now: T::BlockNumber) {
+			let tips = Tips::<T>::take();
 			if let Some(author) = <Self as Store>::Author::get() {
-				let reward = Reward::<T>::get();
-				Self::do_reward(&author, reward, now);
+				let reward = Self::do_reward(&author, Reward::<T>::get(), now);
+				Self::deposit_event(RawEvent::Rewarded(author.clone(), reward));
+
+				if !tips.is_zero() {
+					Self::deposit_event(RawEvent::TipsPaid(author, tips));
+				}
 			}
 
 			let mints = Mints::<T>::get();
@@ -233,15 +384,14 @@ decl_module! {
 			<Self as Store>::Author::kill();
 		}
 
-		fn on_runtime_upgrade() -> frame_support::weights::Weight {
-			let version = StorageVersion::get();
-			let new_version = version.migrate::<T>();
-			StorageVersion::put(new_version);
-
-			0
-		}
-
-		#[weight = T::WeightInfo::set_schedule()]
+		/// Set the reward and mints, with their planned changes. The weight
+		/// scales with the number of mints, reward changes and mints of all
+		/// mint changes, so oversized schedules do not fit in a block.
+		#[weight = T::WeightInfo::set_schedule(
+			mints.len() as u32,
+			reward_changes.len() as u32,
+			mint_changes_len(mint_changes),
+		)]
 		fn set_schedule(
 			origin,
 			reward: BalanceOf<T>,
@@ -251,21 +401,27 @@ decl_module! {
 		) {
 			ensure_root(origin)?;
 
-			let mints = BTreeMap::from_iter(mints.into_iter());
-			let reward_changes = BTreeMap::from_iter(reward_changes.into_iter());
-			let mint_changes = BTreeMap::from_iter(
-				mint_changes.into_iter().map(|(k, v)| (k, BTreeMap::from_iter(v.into_iter())))
-			);
+			let mints = MintsOf::<T>::try_from(BTreeMap::from_iter(mints.into_iter()))
+				.map_err(|_| Error::<T>::TooManyMints)?;
+			let reward_changes = BoundedBTreeMap::<_, _, T::MaxRewardChanges>::try_from(
+				BTreeMap::from_iter(reward_changes.into_iter())
+			).map_err(|_| Error::<T>::TooManyRewardChanges)?;
+			let mint_changes = BoundedBTreeMap::<_, _, T::MaxMintChanges>::try_from(
+				mint_changes.into_iter()
+					.map(|(k, v)| Ok((k, MintsOf::<T>::try_from(BTreeMap::from_iter(v.into_iter()))?)))
+					.collect::<Result<BTreeMap<_, _>, ()>>()
+					.map_err(|_| Error::<T>::TooManyMints)?
+			).map_err(|_| Error::<T>::TooManyMintChanges)?;
 
 			ensure!(reward >= T::Currency::minimum_balance(), Error::<T>::RewardTooLow);
-			for (_, mint) in &mints {
+			for (_, mint) in mints.iter() {
 				ensure!(*mint >= T::Currency::minimum_balance(), Error::<T>::MintTooLow);
 			}
-			for (_, reward_change) in &reward_changes {
+			for (_, reward_change) in reward_changes.iter() {
 				ensure!(*reward_change >= T::Currency::minimum_balance(), Error::<T>::RewardTooLow);
 			}
-			for (_, mint_change) in &mint_changes {
-				for (_, mint) in mint_change {
+			for (_, mint_change) in mint_changes.iter() {
+				for (_, mint) in mint_change.iter() {
 					ensure!(*mint >= T::Currency::minimum_balance(), Error::<T>::MintTooLow);
 				}
 			}
@@ -273,8 +429,8 @@ decl_module! {
 			Reward::<T>::put(reward);
 			Self::deposit_event(RawEvent::RewardChanged(reward));
 
-			Mints::<T>::put(mints.clone());
-			Self::deposit_event(RawEvent::MintsChanged(mints));
+			Self::deposit_event(RawEvent::MintsChanged((*mints).clone()));
+			Mints::<T>::put(mints);
 
 			RewardChanges::<T>::put(reward_changes);
 			MintChanges::<T>::put(mint_changes);
@@ -294,55 +450,476 @@ decl_module! {
 			Self::deposit_event(RawEvent::LockParamsChanged(lock_params));
 		}
 
-		/// Unlock any vested rewards for `target` account.
+		/// Unlock any vested rewards for `target` account. Free of fees when it
+		/// releases any rewards, which happens at most once per expiring lock.
 		#[weight = T::WeightInfo::unlock()]
-		fn unlock(origin, target: T::AccountId) {
+		fn unlock(origin, target: T::AccountId) -> DispatchResultWithPostInfo {
 			ensure_signed(origin)?;
 
-			let locks = Self::reward_locks(&target);
-			let current_number = frame_system::Pallet::<T>::block_number();
-			Self::do_update_reward_locks(&target, locks, current_number);
+			let released = Self::do_unlock(target, frame_system::Pallet::<T>::block_number());
+			if released.is_zero() {
+				Ok(Pays::Yes.into())
+			} else {
+				Ok(Pays::No.into())
+			}
+		}
+
+		/// Split the sender's mining rewards between payout destinations, each receiving
+		/// its part of every reward along with the matching reward locks. Whatever is
+		/// not split off stays with the sender. An empty split removes it.
+		///
+		/// Payouts of a block are weighed at its start, so the weight includes
+		/// payouts to the destinations of the split in the current block.
+		#[weight = T::WeightInfo::set_payout_split(split.len() as u32)
+			.saturating_add(T::WeightInfo::on_finalize(split.len() as u32))
+			.saturating_sub(T::WeightInfo::on_finalize(0))]
+		fn set_payout_split(origin, split: Vec<(T::AccountId, Perbill)>) {
+			let miner = ensure_signed(origin)?;
+
+			Self::ensure_valid_split(&split)?;
+
+			if split.is_empty() {
+				PayoutSplits::<T>::remove(&miner);
+			} else {
+				PayoutSplits::<T>::insert(&miner, split);
+			}
+			Self::deposit_event(RawEvent::PayoutSplitChanged(miner));
+		}
+
+		/// Split transaction tips between the block author and other accounts,
+		/// such as an infrastructure fund. Whatever is not split off goes to the
+		/// author.
+		#[weight = T::WeightInfo::set_tip_split(split.len() as u32)]
+		fn set_tip_split(origin, split: Vec<(T::AccountId, Perbill)>) {
+			ensure_root(origin)?;
+
+			Self::ensure_valid_split(&split)?;
+
+			TipSplit::<T>::put(split.clone());
+			Self::deposit_event(RawEvent::TipSplitChanged(split));
+		}
+
+		/// Donate part of every block reward of the sender to the donation
+		/// destination, as rewards are paid. Zero stops donating.
+		#[weight = T::WeightInfo::set_standing_donation()]
+		fn set_standing_donation(origin, donation: Perbill) {
+			let miner = ensure_signed(origin)?;
+
+			if donation.is_zero() {
+				StandingDonations::<T>::remove(&miner);
+			} else {
+				StandingDonations::<T>::insert(&miner, donation);
+			}
+			Self::deposit_event(RawEvent::StandingDonationChanged(miner, donation));
+		}
+
+		/// Plan the reward to change to `reward` at block `at`, replacing the
+		/// reward change already planned there, if any.
+		#[weight = T::WeightInfo::add_reward_change()]
+		fn add_reward_change(origin, at: T::BlockNumber, reward: BalanceOf<T>) {
+			ensure_root(origin)?;
+			ensure!(reward >= T::Currency::minimum_balance(), Error::<T>::RewardTooLow);
+
+			let mut reward_changes = (*RewardChanges::<T>::get()).clone();
+			reward_changes.insert(at, reward);
+			let reward_changes =
+				BoundedBTreeMap::<_, _, T::MaxRewardChanges>::try_from(reward_changes)
+					.map_err(|_| Error::<T>::TooManyRewardChanges)?;
+
+			RewardChanges::<T>::put(reward_changes);
+			Self::deposit_event(RawEvent::RewardChangeAdded(at, reward));
+		}
+
+		/// Remove the reward change planned at block `at`.
+		#[weight = T::WeightInfo::remove_reward_change()]
+		fn remove_reward_change(origin, at: T::BlockNumber) {
+			ensure_root(origin)?;
+
+			RewardChanges::<T>::try_mutate(|reward_changes| {
+				reward_changes.remove(&at).map(|_| ()).ok_or(Error::<T>::NoPlannedChange)
+			})?;
+			Self::deposit_event(RawEvent::RewardChangeRemoved(at));
+		}
+
+		/// Plan the mints to change to `mints` at block `at`, replacing the mint
+		/// change already planned there, if any.
+		#[weight = T::WeightInfo::add_mint_change(mints.len() as u32)]
+		fn add_mint_change(origin, at: T::BlockNumber, mints: Vec<(T::AccountId, BalanceOf<T>)>) {
+			ensure_root(origin)?;
+
+			let mints = MintsOf::<T>::try_from(BTreeMap::from_iter(mints.into_iter()))
+				.map_err(|_| Error::<T>::TooManyMints)?;
+			for (_, mint) in mints.iter() {
+				ensure!(*mint >= T::Currency::minimum_balance(), Error::<T>::MintTooLow);
+			}
+
+			let mut mint_changes = (*MintChanges::<T>::get()).clone();
+			mint_changes.insert(at, mints.clone());
+			let mint_changes =
+				BoundedBTreeMap::<_, _, T::MaxMintChanges>::try_from(mint_changes)
+					.map_err(|_| Error::<T>::TooManyMintChanges)?;
+
+			MintChanges::<T>::put(mint_changes);
+			Self::deposit_event(RawEvent::MintChangeAdded(at, (*mints).clone()));
+		}
+
+		/// Remove the mint change planned at block `at`.
+		#[weight = T::WeightInfo::remove_mint_change()]
+		fn remove_mint_change(origin, at: T::BlockNumber) {
+			ensure_root(origin)?;
+
+			MintChanges::<T>::try_mutate(|mint_changes| {
+				mint_changes.remove(&at).map(|_| ()).ok_or(Error::<T>::NoPlannedChange)
+			})?;
+			Self::deposit_event(RawEvent::MintChangeRemoved(at));
+		}
+	}
+}
+
+/// Balance lock identifier of the reward locks.
+pub const REWARDS_ID: LockIdentifier = *b"rewards ";
+
+/// Pays imbalances, such as transaction tips, to the current block author.
+impl<T: Config> OnUnbalanced<NegativeImbalanceOf<T>> for Module<T> {
+	fn on_nonzero_unbalanced(amount: NegativeImbalanceOf<T>) {
+		if let Some(author) = Self::author() {
+			let total = amount.peek();
+			let mut remaining = amount;
+			for (destination, part) in Self::tip_split() {
+				let (paid, rest) = remaining.split(part * total);
+				T::Currency::resolve_creating(&destination, paid);
+				remaining = rest;
+			}
+
+			Tips::<T>::mutate(|tips| *tips = tips.saturating_add(remaining.peek()));
+			T::Currency::resolve_creating(&author, remaining);
+		} else {
+			drop(amount);
 		}
 	}
 }
 
-const REWARDS_ID: LockIdentifier = *b"rewards ";
+/// Merge reward locks into buckets of doubling width until at most `max` remain.
+///
+/// Unlock heights are rounded up to the end of their bucket, so locked balances
+/// are never released earlier than scheduled.
+fn compact_reward_locks<N, B>(mut locks: BTreeMap<N, B>, max: usize) -> BTreeMap<N, B>
+where
+	N: AtLeast32BitUnsigned + Copy,
+	B: Saturating + Zero + Copy,
+{
+	let mut width = N::one();
+
+	while locks.len() > max.max(1) && width < N::max_value() {
+		width = width.saturating_add(width);
+
+		let mut compacted = BTreeMap::new();
+		for (number, balance) in locks {
+			let bucket = if (number % width).is_zero() {
+				number
+			} else {
+				(number / width)
+					.saturating_add(One::one())
+					.saturating_mul(width)
+			};
+			let merged: &mut B = compacted.entry(bucket).or_insert_with(Zero::zero);
+			*merged = merged.saturating_add(balance);
+		}
+		locks = compacted;
+	}
+
+	locks
+}
+
+/// Size of `mint_changes` for weights: the number of mints of each change, with
+/// empty changes counting as one.
+fn mint_changes_len<BlockNumber, AccountId, Balance>(
+	mint_changes: &[(BlockNumber, Vec<(AccountId, Balance)>)],
+) -> u32 {
+	mint_changes.iter().fold(0u32, |len, (_, mints)| {
+		len.saturating_add((mints.len() as u32).max(1))
+	})
+}
+
+/// Migrate rewards storage to the latest version, run by runtime upgrades.
+/// Weighs nothing when storage is already at the latest version.
+pub struct MigrateStorage<T>(sp_std::marker::PhantomData<T>);
+
+impl<T: Config> OnRuntimeUpgrade for MigrateStorage<T> {
+	fn on_runtime_upgrade() -> Weight {
+		let version = StorageVersion::get();
+		let (new_version, weight) = version.migrate::<T>();
+		if version == new_version {
+			return 0;
+		}
+
+		StorageVersion::put(new_version);
+		log::info!(
+			target: "runtime::rewards",
+			"Migrated rewards storage from {:?} to {:?}, weight {}",
+			version,
+			new_version,
+			weight,
+		);
+
+		weight.saturating_add(T::DbWeight::get().reads_writes(1, 1))
+	}
+}
 
 impl<T: Config> Module<T> {
-	fn do_reward(author: &T::AccountId, reward: BalanceOf<T>, when: T::BlockNumber) {
-		let miner_total = reward;
+	/// Account rewarded for a block with `digest`: the payout account of its
+	/// PoW pre-runtime digest if any, otherwise its author.
+	pub fn author_from_digest(digest: &frame_system::DigestOf<T>) -> Option<T::AccountId> {
+		digest
+			.logs
+			.iter()
+			.filter_map(|s| s.as_pre_runtime())
+			.filter_map(|(id, mut data)| {
+				if id == POW_ENGINE_ID {
+					// The author may be followed by a separate payout account.
+					let author = T::AccountId::decode(&mut data).ok()?;
+					Some(T::AccountId::decode(&mut data).unwrap_or(author))
+				} else {
+					None
+				}
+			})
+			.next()
+	}
+
+	/// Whether `who` authored one of the last `RecentAuthorBlocks` blocks,
+	/// including the current one.
+	pub fn is_recent_author(who: &T::AccountId) -> bool {
+		let now = frame_system::Pallet::<T>::block_number();
+		LastAuthored::<T>::get(who).map_or(false, |last| {
+			now.saturating_sub(last) < T::RecentAuthorBlocks::get()
+		})
+	}
+
+	/// Balance of the reward locks of `who` still locked at block `n`, once
+	/// the expired ones are unlocked.
+	pub fn locked_at(who: &T::AccountId, n: T::BlockNumber) -> BalanceOf<T> {
+		Self::reward_locks(who)
+			.range((Excluded(n), Unbounded))
+			.fold(Zero::zero(), |locked, (_, balance)| {
+				locked.saturating_add(*balance)
+			})
+	}
+
+	/// Reward and mints in effect at the current block, followed by those taking
+	/// effect at each planned change. Changes due at or before the current block
+	/// take effect at the next one.
+	pub fn emission_schedule() -> Vec<(
+		T::BlockNumber,
+		BalanceOf<T>,
+		BTreeMap<T::AccountId, BalanceOf<T>>,
+	)> {
+		let now = frame_system::Pallet::<T>::block_number();
+		let reward_changes = RewardChanges::<T>::get();
+		let mint_changes = MintChanges::<T>::get();
+
+		let mut reward = Reward::<T>::get();
+		let mut mints = (*Mints::<T>::get()).clone();
+		let mut schedule = vec![(now, reward, mints.clone())];
+
+		let mut changes = reward_changes
+			.keys()
+			.chain(mint_changes.keys())
+			.collect::<Vec<_>>();
+		changes.sort();
+		changes.dedup();
+
+		for number in changes {
+			if let Some(change) = reward_changes.get(number) {
+				reward = *change;
+			}
+			if let Some(change) = mint_changes.get(number) {
+				mints = (**change).clone();
+			}
 
-		let miner_reward_locks =
-			T::GenerateRewardLocks::generate_reward_locks(when, miner_total, LockParams::get());
+			let start = (*number).max(now.saturating_add(One::one()));
+			match schedule.last_mut() {
+				Some(last) if last.0 == start => *last = (start, reward, mints.clone()),
+				_ => schedule.push((start, reward, mints.clone())),
+			}
+		}
 
-		drop(T::Currency::deposit_creating(&author, miner_total));
+		schedule
+	}
 
-		if miner_reward_locks.len() > 0 {
-			let mut locks = Self::reward_locks(&author);
+	/// Unlock accounts in the unlock schedule up to `now`, scanning at most
+	/// `MaxAutoUnlocks` blocks of the schedule and unlocking at most as many
+	/// accounts. Returns the number of blocks scanned and accounts unlocked.
+	fn do_auto_unlocks(now: T::BlockNumber) -> (u32, u32) {
+		let max = T::MaxAutoUnlocks::get();
+		let mut cursor = UnlockCursor::<T>::get();
+		let mut scanned = 0;
+		let mut unlocked = 0;
+
+		while cursor <= now && scanned < max && unlocked < max {
+			scanned += 1;
+
+			let remaining = (max - unlocked) as usize;
+			let accounts = UnlockSchedule::<T>::iter_prefix(cursor)
+				.map(|(account, ())| account)
+				.take(remaining)
+				.collect::<Vec<_>>();
+			let exhausted = accounts.len() < remaining;
+
+			for account in accounts {
+				Self::do_unlock(account, now);
+				unlocked += 1;
+			}
 
-			for (new_lock_number, new_lock_balance) in miner_reward_locks {
-				let old_balance = *locks
-					.get(&new_lock_number)
-					.unwrap_or(&BalanceOf::<T>::default());
-				let new_balance = old_balance.saturating_add(new_lock_balance);
-				locks.insert(new_lock_number, new_balance);
+			if exhausted {
+				cursor = cursor.saturating_add(One::one());
 			}
+		}
 
-			Self::do_update_reward_locks(&author, locks, when);
+		if max > 0 {
+			UnlockCursor::<T>::put(cursor);
 		}
+
+		(scanned, unlocked)
 	}
 
+	/// Unlock the expired reward locks of `target`, returning the released
+	/// balance.
+	fn do_unlock(target: T::AccountId, now: T::BlockNumber) -> BalanceOf<T> {
+		let locks = Self::reward_locks(&target);
+		let (released, locked) = Self::do_update_reward_locks(&target, locks, now);
+		let next_unlock = Self::reward_locks(&target).keys().next().cloned();
+
+		Self::deposit_event(RawEvent::Unlocked(target, released, locked, next_unlock));
+		released
+	}
+
+	/// Pay `reward` to `author`, less their standing donation, and return the
+	/// reward paid.
+	fn do_reward(
+		author: &T::AccountId,
+		reward: BalanceOf<T>,
+		when: T::BlockNumber,
+	) -> BalanceOf<T> {
+		let donated = Self::standing_donation(author) * reward;
+		let reward = reward.saturating_sub(donated);
+		if !donated.is_zero() {
+			drop(T::Currency::deposit_creating(
+				&T::DonationDestination::get(),
+				donated,
+			));
+			Self::deposit_event(RawEvent::StandingDonated(author.clone(), donated));
+		}
+
+		let reward_locks =
+			T::GenerateRewardLocks::generate_reward_locks(when, reward, LockParams::get());
+
+		let payouts = T::SplitReward::split_reward(author, reward)
+			.into_iter()
+			.flat_map(|(account, payout)| Self::split_payout(account, payout))
+			.collect::<Vec<_>>();
+
+		for (account, payout) in payouts {
+			drop(T::Currency::deposit_creating(&account, payout));
+
+			// Reward locks apply to each payout proportionally.
+			let payout_locks = reward_locks
+				.iter()
+				.filter_map(|(number, lock)| {
+					let lock = if payout == reward {
+						*lock
+					} else {
+						lock.saturating_mul(payout)
+							.checked_div(&reward)
+							.unwrap_or_else(Zero::zero)
+					};
+
+					if lock.is_zero() {
+						None
+					} else {
+						Some((*number, lock))
+					}
+				})
+				.collect::<Vec<_>>();
+
+			if payout_locks.len() > 0 {
+				let mut locks = Self::reward_locks(&account);
+
+				for (new_lock_number, new_lock_balance) in payout_locks {
+					let old_balance = *locks
+						.get(&new_lock_number)
+						.unwrap_or(&BalanceOf::<T>::default());
+					let new_balance = old_balance.saturating_add(new_lock_balance);
+					locks.insert(new_lock_number, new_balance);
+				}
+
+				Self::do_update_reward_locks(&account, locks, when);
+			}
+		}
+
+		reward
+	}
+
+	/// Ensure `split` has at most `MaxPayoutDestinations` destinations, and
+	/// their parts add up to at most the whole.
+	fn ensure_valid_split(split: &[(T::AccountId, Perbill)]) -> DispatchResult {
+		ensure!(
+			split.len() <= T::MaxPayoutDestinations::get() as usize,
+			Error::<T>::TooManyPayoutDestinations
+		);
+		let total = split
+			.iter()
+			.fold(0u64, |acc, (_, part)| acc + u64::from(part.deconstruct()));
+		ensure!(
+			total <= u64::from(Perbill::one().deconstruct()),
+			Error::<T>::PayoutSplitOverflow
+		);
+
+		Ok(())
+	}
+
+	/// Split a payout to `account` according to its payout split.
+	fn split_payout(
+		account: T::AccountId,
+		payout: BalanceOf<T>,
+	) -> Vec<(T::AccountId, BalanceOf<T>)> {
+		let mut remaining = payout;
+		let mut payouts = Self::payout_splits(&account)
+			.into_iter()
+			.map(|(destination, part)| {
+				let amount = part * payout;
+				remaining = remaining.saturating_sub(amount);
+				(destination, amount)
+			})
+			.collect::<Vec<_>>();
+		payouts.push((account, remaining));
+
+		payouts
+	}
+
+	/// Number of payouts of a reward of block `author`, to each payee and the
+	/// destinations of their payout splits.
+	fn payout_count(author: &T::AccountId) -> u32 {
+		T::SplitReward::payees(author)
+			.iter()
+			.map(|payee| 1 + PayoutSplits::<T>::decode_len(payee).unwrap_or(0) as u32)
+			.fold(0, u32::saturating_add)
+	}
+
+	/// Remove expired reward locks, and update the balance lock and the unlock
+	/// schedule. Returns the released and the still locked balances.
 	fn do_update_reward_locks(
 		author: &T::AccountId,
 		mut locks: BTreeMap<T::BlockNumber, BalanceOf<T>>,
 		current_number: T::BlockNumber,
-	) {
+	) -> (BalanceOf<T>, BalanceOf<T>) {
 		let mut expired = Vec::new();
+		let mut released: BalanceOf<T> = Zero::zero();
 		let mut total_locked: BalanceOf<T> = Zero::zero();
 
 		for (block_number, locked_balance) in &locks {
 			if block_number <= &current_number {
 				expired.push(*block_number);
+				released = released.saturating_add(*locked_balance);
 			} else {
 				total_locked = total_locked.saturating_add(*locked_balance);
 			}
@@ -352,14 +929,31 @@ impl<T: Config> Module<T> {
 			locks.remove(&block_number);
 		}
 
-		T::Currency::set_lock(
-			REWARDS_ID,
-			&author,
-			total_locked,
-			WithdrawReasons::except(WithdrawReasons::TRANSACTION_PAYMENT),
-		);
+		let locks = compact_reward_locks(locks, T::MaxRewardLocks::get() as usize);
+
+		let previous_unlock = Self::reward_locks(author).keys().next().cloned();
+		let next_unlock = locks.keys().next().cloned();
+		if next_unlock != previous_unlock {
+			if let Some(previous_unlock) = previous_unlock {
+				UnlockSchedule::<T>::remove(previous_unlock, author);
+			}
+			if let Some(next_unlock) = next_unlock {
+				UnlockSchedule::<T>::insert(next_unlock, author, ());
+			}
+		}
+
+		// `set_lock` leaves the previous lock in place for a zero amount or no
+		// reasons, so the lock is removed explicitly.
+		let reasons = WithdrawReasons::except(T::LockExemptReasons::get());
+		if total_locked.is_zero() || reasons.is_empty() {
+			T::Currency::remove_lock(REWARDS_ID, &author);
+		} else {
+			T::Currency::set_lock(REWARDS_ID, &author, total_locked, reasons);
+		}
 
 		<Self as Store>::RewardLocks::insert(author, locks);
+
+		(released, total_locked)
 	}
 
 	fn do_mints(mints: &BTreeMap<T::AccountId, BalanceOf<T>>) {
@@ -16,14 +16,22 @@
 // You should have received a copy of the GNU General Public License
 // along with Kulupu. If not, see <http://www.gnu.org/licenses/>.
 
-use crate::{BalanceOf, Config, Mints, RewardChanges};
+use crate::{
+	BalanceOf, Config, Mints, MintsOf, RewardChanges, RewardLocks, UnlockCursor, UnlockSchedule,
+};
 use codec::{Decode, Encode};
-use frame_support::storage::StorageValue;
+use frame_support::{
+	storage::{
+		bounded_btree_map::BoundedBTreeMap, IterableStorageMap, StorageDoubleMap, StorageValue,
+	},
+	traits::Get,
+	weights::Weight,
+};
 use scale_info::TypeInfo;
 #[cfg(feature = "std")]
 use serde::{Deserialize, Serialize};
 use sp_runtime::{Perbill, RuntimeDebug};
-use sp_std::{collections::btree_map::BTreeMap, prelude::*};
+use sp_std::{collections::btree_map::BTreeMap, convert::TryFrom, prelude::*};
 
 /// A value placed in storage that represents the current version of the Scheduler storage.
 /// This value is used by the `on_runtime_upgrade` logic to determine whether we run
@@ -33,6 +41,7 @@ use sp_std::{collections::btree_map::BTreeMap, prelude::*};
 pub enum StorageVersion {
 	V0 = 0,
 	V1 = 1,
+	V2 = 2,
 }
 
 impl Default for StorageVersion {
@@ -42,13 +51,16 @@ impl Default for StorageVersion {
 }
 
 impl StorageVersion {
-	pub fn migrate<T: Config>(self) -> StorageVersion {
-		match self {
-			StorageVersion::V0 => migrate_v0_to_v1::<T>(),
-			StorageVersion::V1 => (),
-		}
+	/// Migrate storage from this version to the latest, returning the latest
+	/// version with the weight of the migration.
+	pub fn migrate<T: Config>(self) -> (StorageVersion, Weight) {
+		let weight = match self {
+			StorageVersion::V0 => migrate_v0_to_v1::<T>().saturating_add(migrate_v1_to_v2::<T>()),
+			StorageVersion::V1 => migrate_v1_to_v2::<T>(),
+			StorageVersion::V2 => 0,
+		};
 
-		StorageVersion::V1
+		(StorageVersion::V2, weight)
 	}
 }
 
@@ -88,7 +100,7 @@ type AdditionalRewardsV0<T: Config> = frame_support::storage::types::StorageValu
 	Vec<(T::AccountId, BalanceOf<T>)>,
 >;
 
-fn migrate_v0_to_v1<T: Config>() {
+fn migrate_v0_to_v1<T: Config>() -> Weight {
 	let curve = CurveV0::<T>::take().unwrap_or_default();
 	let additional_rewards = AdditionalRewardsV0::<T>::take().unwrap_or_default();
 
@@ -96,11 +108,40 @@ fn migrate_v0_to_v1<T: Config>() {
 	for point in curve {
 		reward_changes.insert(point.start, point.reward);
 	}
-	RewardChanges::<T>::put(reward_changes);
+	// A schedule beyond the bounds can't be kept, and is dropped.
+	if let Ok(reward_changes) =
+		BoundedBTreeMap::<_, _, T::MaxRewardChanges>::try_from(reward_changes)
+	{
+		RewardChanges::<T>::put(reward_changes);
+	}
 
 	let mut mints = BTreeMap::new();
 	for (destination, additional_reward) in additional_rewards {
 		mints.insert(destination, additional_reward);
 	}
-	Mints::<T>::put(mints);
+	if let Ok(mints) = MintsOf::<T>::try_from(mints) {
+		Mints::<T>::put(mints);
+	}
+
+	T::DbWeight::get().reads_writes(2, 4)
+}
+
+/// Schedule the next unlock of each account with reward locks. The cursor
+/// starts at the current block rather than the earliest unlock, which could be
+/// far enough in the past for automatic unlocks to take a long time catching
+/// up. Accounts with unlocks already due are released lazily instead, when
+/// they are unlocked or paid a reward, which also moves their schedule entry.
+fn migrate_v1_to_v2<T: Config>() -> Weight {
+	let mut accounts = 0u64;
+
+	for (account, locks) in RewardLocks::<T>::iter() {
+		accounts += 1;
+		if let Some(next_unlock) = locks.keys().next().cloned() {
+			UnlockSchedule::<T>::insert(next_unlock, account, ());
+		}
+	}
+
+	UnlockCursor::<T>::put(frame_system::Pallet::<T>::block_number());
+
+	T::DbWeight::get().reads_writes(accounts, accounts.saturating_add(1))
 }
@@ -25,7 +25,7 @@ use crate as pallet_rewards;
 use codec::Encode;
 use frame_support::{
 	parameter_types,
-	traits::{Everything, OnInitialize},
+	traits::{Everything, OnInitialize, WithdrawReasons},
 };
 use frame_system::{self as system, InitKind};
 use sp_core::H256;
@@ -147,10 +147,45 @@ impl crate::GenerateRewardLocks<Test> for GenerateRewardLocks {
 	}
 }
 
+/// Block author that splits its rewards with accounts 11 and 12.
+pub const POOL: u64 = 10;
+
+pub struct SplitReward;
+impl crate::SplitReward<u64, Balance> for SplitReward {
+	fn split_reward(author: &u64, reward: Balance) -> Vec<(u64, Balance)> {
+		if *author == POOL {
+			let member_reward = reward / 2;
+			vec![
+				(POOL, reward - 2 * member_reward),
+				(11, member_reward),
+				(12, member_reward),
+			]
+		} else {
+			vec![(*author, reward)]
+		}
+	}
+
+	fn payees(author: &u64) -> Vec<u64> {
+		if *author == POOL {
+			vec![POOL, 11, 12]
+		} else {
+			vec![*author]
+		}
+	}
+}
+
 parameter_types! {
 	pub DonationDestination: u64 = 255;
 	pub const LockBounds: pallet_rewards::LockBounds = pallet_rewards::LockBounds {period_max: 500, period_min: 20,
 																					divide_max: 50, divide_min: 2};
+	pub static LockExemptReasons: WithdrawReasons = WithdrawReasons::TRANSACTION_PAYMENT;
+	pub static MaxRewardLocks: u32 = 1000;
+	pub const MaxPayoutDestinations: u32 = 2;
+	pub const MaxMints: u32 = 2;
+	pub static MaxRewardChanges: u32 = 4;
+	pub static MaxMintChanges: u32 = 4;
+	pub static MaxAutoUnlocks: u32 = 0;
+	pub static RecentAuthorBlocks: u64 = 2;
 }
 
 impl pallet_rewards::Config for Test {
@@ -160,6 +195,15 @@ impl pallet_rewards::Config for Test {
 	type GenerateRewardLocks = GenerateRewardLocks;
 	type WeightInfo = ();
 	type LockParametersBounds = LockBounds;
+	type LockExemptReasons = LockExemptReasons;
+	type SplitReward = SplitReward;
+	type MaxRewardLocks = MaxRewardLocks;
+	type MaxPayoutDestinations = MaxPayoutDestinations;
+	type MaxMints = MaxMints;
+	type MaxRewardChanges = MaxRewardChanges;
+	type MaxMintChanges = MaxMintChanges;
+	type MaxAutoUnlocks = MaxAutoUnlocks;
+	type RecentAuthorBlocks = RecentAuthorBlocks;
 }
 
 // Build genesis storage according to the mock runtime.
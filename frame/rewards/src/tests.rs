@@ -106,6 +106,190 @@ fn set_reward_works() {
 	});
 }
 
+#[test]
+fn set_schedule_rejects_oversized_schedules() {
+	new_test_ext(1).execute_with(|| {
+		MaxRewardChanges::set(2);
+		MaxMintChanges::set(2);
+
+		assert_noop!(
+			Rewards::set_schedule(
+				Origin::root(),
+				42,
+				vec![(1, 10), (2, 10), (3, 10)],
+				Default::default(),
+				Default::default()
+			),
+			Error::<Test>::TooManyMints
+		);
+		assert_noop!(
+			Rewards::set_schedule(
+				Origin::root(),
+				42,
+				Default::default(),
+				vec![(10, 42), (20, 42), (30, 42)],
+				Default::default()
+			),
+			Error::<Test>::TooManyRewardChanges
+		);
+		assert_noop!(
+			Rewards::set_schedule(
+				Origin::root(),
+				42,
+				Default::default(),
+				Default::default(),
+				vec![(10, vec![(1, 10), (2, 10), (3, 10)])]
+			),
+			Error::<Test>::TooManyMints
+		);
+		assert_noop!(
+			Rewards::set_schedule(
+				Origin::root(),
+				42,
+				Default::default(),
+				Default::default(),
+				vec![(10, vec![]), (20, vec![]), (30, vec![])]
+			),
+			Error::<Test>::TooManyMintChanges
+		);
+
+		// Schedules within the bounds are accepted.
+		assert_ok!(Rewards::set_schedule(
+			Origin::root(),
+			42,
+			vec![(1, 10), (2, 10)],
+			vec![(10, 42), (20, 42)],
+			vec![(10, vec![(1, 10), (2, 10)]), (20, vec![])]
+		));
+		assert_eq!(Rewards::mint_changes().len(), 2);
+	});
+}
+
+#[test]
+fn set_schedule_weight_scales_with_schedule_size() {
+	use frame_support::weights::GetDispatchInfo;
+
+	let weight = |mints: Vec<(u64, u128)>,
+	              reward_changes: Vec<(u64, u128)>,
+	              mint_changes: Vec<(u64, Vec<(u64, u128)>)>| {
+		crate::Call::<Test>::set_schedule {
+			reward: 42,
+			mints,
+			reward_changes,
+			mint_changes,
+		}
+		.get_dispatch_info()
+		.weight
+	};
+
+	let empty = weight(vec![], vec![], vec![]);
+	assert!(weight(vec![(1, 10)], vec![], vec![]) > empty);
+	assert!(weight(vec![], vec![(10, 42)], vec![]) > empty);
+	assert!(weight(vec![], vec![], vec![(10, vec![])]) > empty);
+	assert!(
+		weight(vec![], vec![], vec![(10, vec![(1, 10), (2, 10)])])
+			> weight(vec![], vec![], vec![(10, vec![(1, 10)])])
+	);
+}
+
+#[test]
+fn single_schedule_changes_can_be_added_and_removed() {
+	new_test_ext(1).execute_with(|| {
+		MaxRewardChanges::set(2);
+		MaxMintChanges::set(2);
+
+		assert_ok!(Rewards::set_schedule(
+			Origin::root(),
+			60,
+			vec![(1, 10)],
+			vec![(10, 50)],
+			vec![(10, vec![(2, 5)])]
+		));
+
+		assert_noop!(
+			Rewards::add_reward_change(Origin::signed(1), 20, 40),
+			BadOrigin
+		);
+		assert_ok!(Rewards::add_reward_change(Origin::root(), 20, 40));
+		assert_eq!(last_event(), RawEvent::RewardChangeAdded(20, 40).into());
+		// Changes at an already planned block replace it.
+		assert_ok!(Rewards::add_reward_change(Origin::root(), 10, 45));
+		assert_eq!(
+			*Rewards::reward_changes(),
+			vec![(10, 45), (20, 40)]
+				.into_iter()
+				.collect::<BTreeMap<_, _>>()
+		);
+		assert_noop!(
+			Rewards::add_reward_change(Origin::root(), 30, 40),
+			Error::<Test>::TooManyRewardChanges
+		);
+
+		assert_ok!(Rewards::remove_reward_change(Origin::root(), 10));
+		assert_eq!(
+			*Rewards::reward_changes(),
+			vec![(20, 40)].into_iter().collect::<BTreeMap<_, _>>()
+		);
+		assert_noop!(
+			Rewards::remove_reward_change(Origin::root(), 10),
+			Error::<Test>::NoPlannedChange
+		);
+
+		assert_ok!(Rewards::add_mint_change(Origin::root(), 20, vec![]));
+		assert_ok!(Rewards::add_mint_change(
+			Origin::root(),
+			10,
+			vec![(1, 10), (2, 10)]
+		));
+		assert_eq!(
+			*Rewards::mint_changes()[&10],
+			vec![(1, 10), (2, 10)]
+				.into_iter()
+				.collect::<BTreeMap<_, _>>()
+		);
+		assert_noop!(
+			Rewards::add_mint_change(Origin::root(), 30, vec![]),
+			Error::<Test>::TooManyMintChanges
+		);
+		assert_noop!(
+			Rewards::add_mint_change(Origin::root(), 10, vec![(1, 10), (2, 10), (3, 10)]),
+			Error::<Test>::TooManyMints
+		);
+
+		assert_ok!(Rewards::remove_mint_change(Origin::root(), 20));
+		assert_eq!(last_event(), RawEvent::MintChangeRemoved(20).into());
+		assert_noop!(
+			Rewards::remove_mint_change(Origin::root(), 20),
+			Error::<Test>::NoPlannedChange
+		);
+		assert_eq!(Rewards::mint_changes().len(), 1);
+	});
+}
+
+#[test]
+fn emission_schedule_merges_changes() {
+	new_test_ext(1).execute_with(|| {
+		assert_ok!(Rewards::set_schedule(
+			Origin::root(),
+			60,
+			vec![(1, 10)],
+			vec![(10, 50), (20, 40)],
+			vec![(0, vec![(2, 5)]), (20, vec![])]
+		));
+
+		let mints = |entries: &[(u64, u64)]| entries.iter().cloned().collect::<BTreeMap<_, _>>();
+		assert_eq!(
+			Rewards::emission_schedule(),
+			vec![
+				(1, 60, mints(&[(1, 10)])),
+				(2, 60, mints(&[(2, 5)])),
+				(10, 50, mints(&[(2, 5)])),
+				(20, 40, mints(&[])),
+			]
+		);
+	});
+}
+
 #[test]
 fn set_author_works() {
 	new_test_ext(1).execute_with(|| {
@@ -113,6 +297,26 @@ fn set_author_works() {
 	});
 }
 
+#[test]
+fn recent_authors_are_tracked() {
+	new_test_ext(1).execute_with(|| {
+		run_to_block(3, 2);
+		assert_eq!(Rewards::last_authored(2), Some(3));
+		assert!(Rewards::is_recent_author(&2));
+		assert!(!Rewards::is_recent_author(&3));
+
+		// Authors are forgotten once their last block is no longer recent.
+		run_to_block(4, 3);
+		assert!(Rewards::is_recent_author(&2));
+		assert_eq!(Rewards::recent_authors(2), None);
+		run_to_block(5, 3);
+		assert!(!Rewards::is_recent_author(&2));
+		assert_eq!(Rewards::last_authored(2), None);
+		assert_eq!(Rewards::recent_authors(3), None);
+		assert!(Rewards::is_recent_author(&3));
+	});
+}
+
 #[test]
 fn reward_payment_works() {
 	new_test_ext(1).execute_with(|| {
@@ -172,6 +376,7 @@ fn reward_locks_work() {
 		// Locks updated
 		expected_locks.remove(&11);
 		assert_eq!(Rewards::reward_locks(1), expected_locks);
+		assert_eq!(last_event(), RawEvent::Unlocked(1, 10, 90, Some(21)).into());
 		// Transfer works
 		assert_ok!(Balances::transfer(Origin::signed(1), 2, 10));
 		// Cannot transfer more
@@ -282,6 +487,381 @@ fn reward_locks_work() {
 	});
 }
 
+#[test]
+fn reward_locks_respect_exempt_reasons() {
+	new_test_ext(1).execute_with(|| {
+		assert_ok!(Rewards::set_schedule(
+			Origin::root(),
+			101,
+			Default::default(),
+			Default::default(),
+			Default::default()
+		));
+		run_to_block(2, 1);
+
+		// By default, only transaction payment is exempted from the lock.
+		assert_eq!(
+			Balances::locks(1)[0].reasons,
+			pallet_balances::Reasons::Misc
+		);
+		assert_noop!(
+			Balances::transfer(Origin::signed(1), 2, 2),
+			BalancesError::<Test, _>::LiquidityRestrictions
+		);
+
+		// Exempting all reasons removes the lock on the next update.
+		LockExemptReasons::set(&WithdrawReasons::all());
+		assert_ok!(Rewards::unlock(Origin::signed(1), 1));
+		assert!(Balances::locks(1).is_empty());
+		assert_ok!(Balances::transfer(Origin::signed(1), 2, 101));
+	});
+}
+
+#[test]
+fn split_rewards_lock_proportionally() {
+	new_test_ext(POOL).execute_with(|| {
+		assert_ok!(Rewards::set_schedule(
+			Origin::root(),
+			101,
+			Default::default(),
+			Default::default(),
+			Default::default()
+		));
+		run_to_block(2, POOL);
+
+		// Reward is split 1/50/50 between the pool and its members.
+		assert_eq!(Balances::free_balance(POOL), 1);
+		assert_eq!(Balances::free_balance(11), 50);
+		assert_eq!(Balances::free_balance(12), 50);
+
+		// The 10 locks of 10 are scaled to each payout, rounding down.
+		assert!(Rewards::reward_locks(POOL).is_empty());
+		let expected_locks = (1..=10)
+			.map(|x| (x * 10 + 1, 4))
+			.collect::<BTreeMap<_, _>>();
+		assert_eq!(Rewards::reward_locks(11), expected_locks);
+		assert_eq!(Rewards::reward_locks(12), expected_locks);
+
+		assert_ok!(Balances::transfer(Origin::signed(11), 2, 10));
+		assert_noop!(
+			Balances::transfer(Origin::signed(11), 2, 1),
+			BalancesError::<Test, _>::LiquidityRestrictions
+		);
+	});
+}
+
+#[test]
+fn compact_reward_locks_works() {
+	let locks = (1..=10)
+		.map(|x| (x * 10 + 1, 10))
+		.collect::<BTreeMap<u64, u128>>();
+
+	// Nothing to do within bounds.
+	assert_eq!(compact_reward_locks(locks.clone(), 10), locks);
+
+	// Heights are rounded up to buckets of 16 blocks.
+	let expected = vec![
+		(16, 10),
+		(32, 20),
+		(48, 10),
+		(64, 20),
+		(80, 10),
+		(96, 20),
+		(112, 10),
+	]
+	.into_iter()
+	.collect::<BTreeMap<_, _>>();
+	assert_eq!(compact_reward_locks(locks.clone(), 7), expected);
+
+	// Buckets of 32 blocks.
+	let expected = vec![(32, 30), (64, 30), (96, 30), (128, 10)]
+		.into_iter()
+		.collect::<BTreeMap<_, _>>();
+	assert_eq!(compact_reward_locks(locks.clone(), 5), expected);
+
+	// Everything ends up in a single lock, keeping the total.
+	let compacted = compact_reward_locks(locks, 1);
+	assert_eq!(compacted.len(), 1);
+	assert_eq!(compacted.values().sum::<u128>(), 100);
+}
+
+#[test]
+fn expired_reward_locks_are_unlocked_automatically() {
+	new_test_ext(1).execute_with(|| {
+		assert_ok!(Rewards::set_schedule(
+			Origin::root(),
+			101,
+			Default::default(),
+			Default::default(),
+			Default::default()
+		));
+		MaxAutoUnlocks::set(2);
+
+		// Blocks 1 and 2 are mined by 1, the rest by 2.
+		run_to_block(2, 1);
+		run_to_block(10, 2);
+		assert!(UnlockSchedule::<Test>::contains_key(11, 1));
+
+		// The lock of block 1 expires without 1 calling `unlock`.
+		run_to_block(11, 2);
+		assert!(!Rewards::reward_locks(1).contains_key(&11));
+		assert!(!UnlockSchedule::<Test>::contains_key(11, 1));
+		assert!(UnlockSchedule::<Test>::contains_key(12, 1));
+		assert!(System::events()
+			.iter()
+			.any(|record| record.event == RawEvent::Unlocked(1, 10, 190, Some(12)).into()));
+		assert!(Rewards::unlock_cursor() <= 12);
+	});
+}
+
+#[test]
+fn unlock_schedule_migration_starts_at_current_block() {
+	new_test_ext(1).execute_with(|| {
+		MaxAutoUnlocks::set(2);
+		RewardLocks::<Test>::insert(5, vec![(3, 10)].into_iter().collect::<BTreeMap<_, _>>());
+		RewardLocks::<Test>::insert(6, vec![(20, 10)].into_iter().collect::<BTreeMap<_, _>>());
+		System::set_block_number(10);
+
+		assert_eq!(
+			migrations::StorageVersion::V1.migrate::<Test>().0,
+			migrations::StorageVersion::V2
+		);
+		assert_eq!(Rewards::unlock_cursor(), 10);
+		assert!(UnlockSchedule::<Test>::contains_key(3, 5));
+		assert!(UnlockSchedule::<Test>::contains_key(20, 6));
+
+		// Unlocks due after the migration are made automatically.
+		run_to_block(20, 1);
+		assert!(Rewards::reward_locks(6).is_empty());
+		assert!(!UnlockSchedule::<Test>::contains_key(20, 6));
+
+		// Unlocks already due are left to `unlock`.
+		assert!(!Rewards::reward_locks(5).is_empty());
+		assert_ok!(Rewards::unlock(Origin::signed(2), 5));
+		assert!(Rewards::reward_locks(5).is_empty());
+		assert!(!UnlockSchedule::<Test>::contains_key(3, 5));
+	});
+}
+
+#[test]
+fn unlock_is_feeless_when_releasing_rewards() {
+	new_test_ext(1).execute_with(|| {
+		assert_ok!(Rewards::set_schedule(
+			Origin::root(),
+			101,
+			Default::default(),
+			Default::default(),
+			Default::default()
+		));
+		run_to_block(2, 1);
+
+		let pays = |result: DispatchResultWithPostInfo| result.unwrap().pays_fee;
+		assert_eq!(pays(Rewards::unlock(Origin::signed(2), 1)), Pays::Yes);
+
+		System::set_block_number(11);
+		assert_eq!(pays(Rewards::unlock(Origin::signed(2), 1)), Pays::No);
+		assert_eq!(pays(Rewards::unlock(Origin::signed(2), 1)), Pays::Yes);
+	});
+}
+
+#[test]
+fn tips_are_paid_to_author() {
+	new_test_ext(1).execute_with(|| {
+		Rewards::on_unbalanced(Balances::issue(5));
+		assert_eq!(Balances::free_balance(1), 5);
+		assert_eq!(Rewards::tips(), 5);
+
+		Rewards::on_finalize(1);
+		assert!(System::events()
+			.iter()
+			.any(|record| record.event == RawEvent::Rewarded(1, 60).into()));
+		assert_eq!(last_event(), RawEvent::TipsPaid(1, 5).into());
+		assert_eq!(Rewards::tips(), 0);
+	});
+}
+
+#[test]
+fn tips_are_split() {
+	new_test_ext(1).execute_with(|| {
+		assert_noop!(
+			Rewards::set_tip_split(Origin::signed(1), vec![(2, Perbill::from_percent(20))]),
+			BadOrigin
+		);
+		assert_noop!(
+			Rewards::set_tip_split(
+				Origin::root(),
+				vec![
+					(2, Perbill::from_percent(60)),
+					(3, Perbill::from_percent(50))
+				]
+			),
+			Error::<Test>::PayoutSplitOverflow
+		);
+
+		let split = vec![(2, Perbill::from_percent(20))];
+		assert_ok!(Rewards::set_tip_split(Origin::root(), split.clone()));
+		assert_eq!(last_event(), RawEvent::TipSplitChanged(split).into());
+
+		Rewards::on_unbalanced(Balances::issue(10));
+		assert_eq!(Balances::free_balance(1), 8);
+		assert_eq!(Balances::free_balance(2), 2);
+		assert_eq!(Rewards::tips(), 8);
+	});
+}
+
+#[test]
+fn standing_donation_is_paid_to_destination() {
+	new_test_ext(1).execute_with(|| {
+		assert_ok!(Rewards::set_standing_donation(
+			Origin::signed(1),
+			Perbill::from_percent(25)
+		));
+		assert_eq!(
+			last_event(),
+			RawEvent::StandingDonationChanged(1, Perbill::from_percent(25)).into()
+		);
+
+		Rewards::on_finalize(1);
+		assert_eq!(Balances::free_balance(DonationDestination::get()), 15);
+		assert_eq!(Balances::free_balance(1), 45);
+		assert!(System::events()
+			.iter()
+			.any(|record| record.event == RawEvent::StandingDonated(1, 15).into()));
+		assert!(System::events()
+			.iter()
+			.any(|record| record.event == RawEvent::Rewarded(1, 45).into()));
+
+		// Zero stops donating.
+		assert_ok!(Rewards::set_standing_donation(
+			Origin::signed(1),
+			Perbill::zero()
+		));
+		assert!(!StandingDonations::<Test>::contains_key(1));
+	});
+}
+
+#[test]
+fn reward_locks_are_compacted() {
+	new_test_ext(1).execute_with(|| {
+		assert_ok!(Rewards::set_schedule(
+			Origin::root(),
+			101,
+			Default::default(),
+			Default::default(),
+			Default::default()
+		));
+		MaxRewardLocks::set(5);
+		run_to_block(2, 1);
+
+		let expected_locks = vec![(32, 30), (64, 30), (96, 30), (128, 10)]
+			.into_iter()
+			.collect::<BTreeMap<_, _>>();
+		assert_eq!(Rewards::reward_locks(1), expected_locks);
+
+		// Total locked balance is unchanged.
+		assert_ok!(Balances::transfer(Origin::signed(1), 2, 1));
+		assert_noop!(
+			Balances::transfer(Origin::signed(1), 2, 1),
+			BalancesError::<Test, _>::LiquidityRestrictions
+		);
+	});
+}
+
+#[test]
+fn set_payout_split_works() {
+	new_test_ext(1).execute_with(|| {
+		assert_noop!(
+			Rewards::set_payout_split(
+				Origin::signed(1),
+				vec![
+					(2, Perbill::from_percent(10)),
+					(3, Perbill::from_percent(10)),
+					(4, Perbill::from_percent(10))
+				]
+			),
+			Error::<Test>::TooManyPayoutDestinations
+		);
+		assert_noop!(
+			Rewards::set_payout_split(
+				Origin::signed(1),
+				vec![
+					(2, Perbill::from_percent(60)),
+					(3, Perbill::from_percent(50))
+				]
+			),
+			Error::<Test>::PayoutSplitOverflow
+		);
+
+		assert_ok!(Rewards::set_payout_split(
+			Origin::signed(1),
+			vec![(3, Perbill::from_percent(80))]
+		));
+		assert_eq!(
+			Rewards::payout_splits(1),
+			vec![(3, Perbill::from_percent(80))]
+		);
+		assert_eq!(last_event(), RawEvent::PayoutSplitChanged(1).into());
+
+		// Empty split removes it
+		assert_ok!(Rewards::set_payout_split(Origin::signed(1), vec![]));
+		assert!(Rewards::payout_splits(1).is_empty());
+	});
+}
+
+#[test]
+fn payout_split_rewards_and_locks_destinations() {
+	new_test_ext(1).execute_with(|| {
+		assert_ok!(Rewards::set_schedule(
+			Origin::root(),
+			101,
+			Default::default(),
+			Default::default(),
+			Default::default()
+		));
+		assert_ok!(Rewards::set_payout_split(
+			Origin::signed(1),
+			vec![(3, Perbill::from_percent(80))]
+		));
+		run_to_block(2, 1);
+
+		// 80% goes to the cold wallet, the rest stays with the miner
+		assert_eq!(Balances::free_balance(3), 81);
+		assert_eq!(Balances::free_balance(1), 20);
+
+		// The 10 locks of 10 are scaled to each payout, rounding down
+		let expected_locks = |amount| {
+			(1..=10)
+				.map(|x| (x * 10 + 1, amount))
+				.collect::<BTreeMap<_, _>>()
+		};
+		assert_eq!(Rewards::reward_locks(3), expected_locks(8));
+		assert_eq!(Rewards::reward_locks(1), expected_locks(1));
+	});
+}
+
+#[test]
+fn payouts_are_weighed_on_initialize() {
+	new_test_ext(POOL).execute_with(|| {
+		// The pool and its two members are paid.
+		assert_eq!(Rewards::payout_count(&POOL), 3);
+		assert_eq!(Rewards::payout_count(&1), 1);
+		let weight = Rewards::on_initialize(1);
+
+		assert_ok!(Rewards::set_payout_split(
+			Origin::signed(11),
+			vec![
+				(3, Perbill::from_percent(40)),
+				(4, Perbill::from_percent(40))
+			]
+		));
+		assert_eq!(Rewards::payout_count(&POOL), 5);
+		assert!(
+			Rewards::on_initialize(1)
+				>= weight + <() as WeightInfo>::on_finalize(2) - <() as WeightInfo>::on_finalize(0)
+		);
+	});
+}
+
 fn test_curve() -> Vec<(u64, u128)> {
 	vec![(50, 20), (40, 25), (20, 50), (10, 100)]
 }
@@ -433,3 +1013,31 @@ fn set_lock_params_works() {
 		);
 	});
 }
+
+#[test]
+fn payout_account_in_pre_digest_is_rewarded() {
+	new_test_ext(1).execute_with(|| {
+		Rewards::on_finalize(1);
+		Balances::on_finalize(1);
+
+		let parent_hash = System::parent_hash();
+		let pre_digest =
+			DigestItem::PreRuntime(sp_consensus_pow::POW_ENGINE_ID, (1u64, 2u64).encode());
+		System::initialize(
+			&2,
+			&parent_hash,
+			&Digest {
+				logs: vec![pre_digest],
+			},
+			InitKind::Full,
+		);
+		System::set_block_number(2);
+		Balances::on_initialize(2);
+		Rewards::on_initialize(2);
+		assert_eq!(Author::<Test>::get(), Some(2));
+
+		run_to_block(3, 1);
+		assert_eq!(Balances::free_balance(1), 60);
+		assert_eq!(Balances::free_balance(2), 60);
+	});
+}
@@ -0,0 +1,146 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Kulupu.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Kulupu is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Kulupu is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Kulupu. If not, see <http://www.gnu.org/licenses/>.
+
+//! Treasury sponsored contract deployment module.
+//!
+//! Governance approves contract code hashes along with a budget. Anyone can
+//! then deploy an approved code, with the endowment, and thus the contract
+//! deposit, paid from the funding account instead of by the deployer. This
+//! lets public-good contracts be deployed without ordinary users bearing the
+//! deposit.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use frame_support::{
+	decl_error, decl_event, decl_module, decl_storage, ensure,
+	traits::{Currency, EnsureOrigin, Get},
+	weights::Weight,
+};
+use frame_system::ensure_signed;
+use pallet_contracts_primitives::Code;
+use sp_runtime::traits::Saturating;
+use sp_std::prelude::*;
+
+/// Config for sponsored contracts.
+pub trait Config: pallet_contracts::Config {
+	/// The overarching event type.
+	type Event: From<Event<Self>> + Into<<Self as frame_system::Config>::Event>;
+	/// Origin allowed to approve and revoke contract code.
+	type ApproveOrigin: EnsureOrigin<Self::Origin>;
+	/// Account paying the endowment of sponsored contracts.
+	type FundingAccount: Get<Self::AccountId>;
+}
+
+/// Type alias for currency balance.
+pub type BalanceOf<T> = <<T as pallet_contracts::Config>::Currency as Currency<
+	<T as frame_system::Config>::AccountId,
+>>::Balance;
+
+decl_error! {
+	pub enum Error for Module<T: Config> {
+		/// Contract code is not approved for sponsoring.
+		NotApproved,
+		/// Endowment exceeds the remaining budget of the code.
+		BudgetExceeded,
+		/// Contract instantiation reverted.
+		Reverted,
+	}
+}
+
+decl_storage! {
+	trait Store for Module<T: Config> as SponsoredContracts {
+		/// Approved contract code hashes, and their remaining budget.
+		pub ApprovedCode get(fn approved_code): map hasher(identity) T::Hash => Option<BalanceOf<T>>;
+		/// Deployed sponsored contracts, and their code hash.
+		pub SponsoredContracts get(fn sponsored_contracts): map hasher(twox_64_concat) T::AccountId => Option<T::Hash>;
+	}
+}
+
+decl_event! {
+	pub enum Event<T> where
+		AccountId = <T as frame_system::Config>::AccountId,
+		Hash = <T as frame_system::Config>::Hash,
+		Balance = BalanceOf<T>,
+	{
+		/// Contract code approved with the given budget.
+		CodeApproved(Hash, Balance),
+		/// Contract code approval revoked.
+		CodeRevoked(Hash),
+		/// Contract deployed from approved code, with the given endowment.
+		ContractSponsored(Hash, AccountId, Balance),
+	}
+}
+
+decl_module! {
+	pub struct Module<T: Config> for enum Call where origin: T::Origin {
+		type Error = Error<T>;
+
+		fn deposit_event() = default;
+
+		/// Approve contract code for sponsoring, with the total endowment that
+		/// may be paid for its contracts. Replaces any previous budget.
+		#[weight = T::DbWeight::get().writes(1)]
+		fn approve_code(origin, code_hash: T::Hash, budget: BalanceOf<T>) {
+			T::ApproveOrigin::ensure_origin(origin)?;
+
+			ApprovedCode::<T>::insert(code_hash, budget);
+			Self::deposit_event(Event::<T>::CodeApproved(code_hash, budget));
+		}
+
+		/// Revoke an approval. Already deployed contracts are unaffected.
+		#[weight = T::DbWeight::get().reads_writes(1, 1)]
+		fn revoke_code(origin, code_hash: T::Hash) {
+			T::ApproveOrigin::ensure_origin(origin)?;
+			ensure!(ApprovedCode::<T>::contains_key(code_hash), Error::<T>::NotApproved);
+
+			ApprovedCode::<T>::remove(code_hash);
+			Self::deposit_event(Event::<T>::CodeRevoked(code_hash));
+		}
+
+		/// Instantiate a contract from approved, already uploaded code, with
+		/// the endowment paid by the funding account.
+		#[weight = gas_limit.saturating_add(T::DbWeight::get().reads_writes(2, 2))]
+		fn instantiate(
+			origin,
+			code_hash: T::Hash,
+			#[compact] endowment: BalanceOf<T>,
+			#[compact] gas_limit: Weight,
+			data: Vec<u8>,
+			salt: Vec<u8>,
+		) {
+			ensure_signed(origin)?;
+			let budget = Self::approved_code(code_hash).ok_or(Error::<T>::NotApproved)?;
+			ensure!(endowment <= budget, Error::<T>::BudgetExceeded);
+
+			let ret = pallet_contracts::Pallet::<T>::bare_instantiate(
+				T::FundingAccount::get(),
+				endowment,
+				gas_limit,
+				Code::Existing(code_hash),
+				data,
+				salt,
+				false,
+			).result?;
+			ensure!(!ret.result.did_revert(), Error::<T>::Reverted);
+
+			ApprovedCode::<T>::insert(code_hash, budget.saturating_sub(endowment));
+			SponsoredContracts::<T>::insert(&ret.account_id, code_hash);
+			Self::deposit_event(Event::<T>::ContractSponsored(code_hash, ret.account_id, endowment));
+		}
+	}
+}
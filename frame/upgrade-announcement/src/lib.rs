@@ -0,0 +1,166 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Kulupu.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Kulupu is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Kulupu is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Kulupu. If not, see <http://www.gnu.org/licenses/>.
+
+//! Runtime upgrade announcement module for Kulupu.
+//!
+//! The council announces an upcoming runtime upgrade ahead of time, with the
+//! hash of its code, its spec version and the block it is expected to be in
+//! effect from. Nodes watch the announcement to warn operators whose node
+//! does not support the new runtime, and stop mining from the activation
+//! block on if it still does not. The announcement is removed once the
+//! runtime is upgraded to the announced spec version, or cancelled.
+//!
+//! The storage migrations run by the last runtime upgrade are recorded along
+//! with their weights, so that operators can confirm the upgrade completed
+//! them.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(test)]
+mod mock;
+#[cfg(test)]
+mod tests;
+
+use codec::{Decode, Encode};
+use frame_support::{
+	decl_error, decl_event, decl_module, decl_storage, ensure,
+	traits::{EnsureOrigin, Get},
+	weights::Weight,
+};
+use scale_info::TypeInfo;
+use sp_std::prelude::*;
+
+/// An announced runtime upgrade.
+#[derive(Encode, Decode, TypeInfo, Clone, PartialEq, Eq, Debug)]
+pub struct Announcement<Hash, BlockNumber> {
+	/// Hash of the runtime code of the upgrade.
+	pub code_hash: Hash,
+	/// Spec version of the upgraded runtime.
+	pub spec_version: u32,
+	/// Block the upgrade is expected to be in effect from.
+	pub activation: BlockNumber,
+}
+
+/// Config for upgrade announcement.
+pub trait Config: frame_system::Config {
+	/// The overarching event type.
+	type Event: From<Event<Self>> + Into<<Self as frame_system::Config>::Event>;
+	/// Origin allowed to announce and cancel upgrades.
+	type AnnounceOrigin: EnsureOrigin<Self::Origin>;
+}
+
+decl_error! {
+	pub enum Error for Module<T: Config> {
+		/// Activation block is not in the future.
+		ActivationInPast,
+		/// Spec version is not above the current runtime's.
+		SpecVersionNotNewer,
+		/// No upgrade is announced.
+		NoAnnouncement,
+	}
+}
+
+decl_storage! {
+	trait Store for Module<T: Config> as UpgradeAnnouncement {
+		/// Announced runtime upgrade, if any.
+		pub Upcoming get(fn upcoming): Option<Announcement<T::Hash, T::BlockNumber>>;
+		/// Storage migrations run by the last runtime upgrade, by name, with
+		/// their weights.
+		pub Migrations get(fn migrations): Vec<(Vec<u8>, Weight)>;
+		/// Spec version of the runtime upgrade that ran `Migrations`.
+		pub MigrationsSpecVersion get(fn migrations_spec_version): u32;
+	}
+}
+
+decl_event! {
+	pub enum Event<T> where
+		Hash = <T as frame_system::Config>::Hash,
+		BlockNumber = <T as frame_system::Config>::BlockNumber,
+	{
+		/// A runtime upgrade has been announced, with code hash, spec version
+		/// and activation block.
+		Announced(Hash, u32, BlockNumber),
+		/// The announced runtime upgrade has been cancelled.
+		Cancelled,
+		/// The runtime has been upgraded to the announced spec version.
+		Enacted(u32),
+	}
+}
+
+decl_module! {
+	pub struct Module<T: Config> for enum Call where origin: T::Origin {
+		type Error = Error<T>;
+
+		fn deposit_event() = default;
+
+		fn on_initialize(_now: T::BlockNumber) -> Weight {
+			match Self::upcoming() {
+				Some(announcement) if Self::spec_version() >= announcement.spec_version => {
+					Upcoming::<T>::kill();
+					Self::deposit_event(RawEvent::Enacted(announcement.spec_version));
+					T::DbWeight::get().reads_writes(1, 1)
+				}
+				_ => T::DbWeight::get().reads(1),
+			}
+		}
+
+		/// Announce a runtime upgrade with the hash of its code and its spec
+		/// version, expected to be in effect from block `activation`. Replaces
+		/// any earlier announcement.
+		#[weight = 20_000_000 + T::DbWeight::get().writes(1)]
+		fn announce(origin, code_hash: T::Hash, spec_version: u32, activation: T::BlockNumber) {
+			T::AnnounceOrigin::ensure_origin(origin)?;
+			ensure!(
+				activation > frame_system::Pallet::<T>::block_number(),
+				Error::<T>::ActivationInPast,
+			);
+			ensure!(spec_version > Self::spec_version(), Error::<T>::SpecVersionNotNewer);
+
+			Upcoming::<T>::put(Announcement { code_hash, spec_version, activation });
+			Self::deposit_event(RawEvent::Announced(code_hash, spec_version, activation));
+		}
+
+		/// Cancel the announced runtime upgrade.
+		#[weight = 20_000_000 + T::DbWeight::get().reads_writes(1, 1)]
+		fn cancel(origin) {
+			T::AnnounceOrigin::ensure_origin(origin)?;
+			Upcoming::<T>::take().ok_or(Error::<T>::NoAnnouncement)?;
+
+			Self::deposit_event(RawEvent::Cancelled);
+		}
+	}
+}
+
+impl<T: Config> Module<T> {
+	/// Record migration `name` as run by the current runtime upgrade, with its
+	/// weight. Migrations recorded by earlier upgrades are cleared.
+	pub fn record_migration(name: &[u8], weight: Weight) {
+		let spec_version = Self::spec_version();
+		if Self::migrations_spec_version() != spec_version {
+			MigrationsSpecVersion::put(spec_version);
+			Migrations::kill();
+		}
+
+		Migrations::mutate(|migrations| migrations.push((name.to_vec(), weight)));
+	}
+
+	/// Spec version of the current runtime.
+	fn spec_version() -> u32 {
+		<T as frame_system::Config>::Version::get().spec_version
+	}
+}
@@ -0,0 +1,132 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Kulupu.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Kulupu is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Kulupu is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Kulupu. If not, see <http://www.gnu.org/licenses/>.
+
+//! Tests for Upgrade Announcement Pallet
+
+use crate::mock::*;
+use crate::*;
+use frame_support::{assert_noop, assert_ok, traits::OnInitialize};
+use sp_core::H256;
+use sp_runtime::traits::BadOrigin;
+
+// Get the last event from System
+fn last_event() -> mock::Event {
+	System::events().pop().expect("Event expected").event
+}
+
+#[test]
+fn announce_works() {
+	new_test_ext().execute_with(|| {
+		let code_hash = H256::repeat_byte(1);
+		assert_ok!(UpgradeAnnouncement::announce(
+			Origin::root(),
+			code_hash,
+			2,
+			10
+		));
+		assert_eq!(
+			UpgradeAnnouncement::upcoming(),
+			Some(Announcement {
+				code_hash,
+				spec_version: 2,
+				activation: 10,
+			})
+		);
+		assert_eq!(last_event(), RawEvent::Announced(code_hash, 2, 10).into());
+
+		// Only the announce origin can announce
+		assert_noop!(
+			UpgradeAnnouncement::announce(Origin::signed(1), code_hash, 2, 10),
+			BadOrigin
+		);
+		// Activation must be in the future
+		assert_noop!(
+			UpgradeAnnouncement::announce(Origin::root(), code_hash, 2, 1),
+			Error::<Test>::ActivationInPast
+		);
+		// Spec version must be newer than the current runtime
+		assert_noop!(
+			UpgradeAnnouncement::announce(Origin::root(), code_hash, 1, 10),
+			Error::<Test>::SpecVersionNotNewer
+		);
+	});
+}
+
+#[test]
+fn cancel_works() {
+	new_test_ext().execute_with(|| {
+		assert_noop!(
+			UpgradeAnnouncement::cancel(Origin::root()),
+			Error::<Test>::NoAnnouncement
+		);
+
+		assert_ok!(UpgradeAnnouncement::announce(
+			Origin::root(),
+			H256::repeat_byte(1),
+			2,
+			10
+		));
+		assert_noop!(UpgradeAnnouncement::cancel(Origin::signed(1)), BadOrigin);
+		assert_ok!(UpgradeAnnouncement::cancel(Origin::root()));
+		assert_eq!(UpgradeAnnouncement::upcoming(), None);
+		assert_eq!(last_event(), RawEvent::Cancelled.into());
+	});
+}
+
+#[test]
+fn enacted_on_upgrade() {
+	new_test_ext().execute_with(|| {
+		assert_ok!(UpgradeAnnouncement::announce(
+			Origin::root(),
+			H256::repeat_byte(1),
+			2,
+			10
+		));
+
+		// Not yet upgraded
+		UpgradeAnnouncement::on_initialize(2);
+		assert!(UpgradeAnnouncement::upcoming().is_some());
+
+		VERSION.with(|v| v.borrow_mut().spec_version = 2);
+		UpgradeAnnouncement::on_initialize(3);
+		assert_eq!(UpgradeAnnouncement::upcoming(), None);
+		assert_eq!(last_event(), RawEvent::Enacted(2).into());
+	});
+}
+
+#[test]
+fn migrations_are_recorded_per_upgrade() {
+	new_test_ext().execute_with(|| {
+		VERSION.with(|v| v.borrow_mut().spec_version = 2);
+		UpgradeAnnouncement::record_migration(b"first", 10);
+		UpgradeAnnouncement::record_migration(b"second", 20);
+		assert_eq!(UpgradeAnnouncement::migrations_spec_version(), 2);
+		assert_eq!(
+			UpgradeAnnouncement::migrations(),
+			vec![(b"first".to_vec(), 10), (b"second".to_vec(), 20)]
+		);
+
+		VERSION.with(|v| v.borrow_mut().spec_version = 3);
+		UpgradeAnnouncement::record_migration(b"third", 30);
+		assert_eq!(UpgradeAnnouncement::migrations_spec_version(), 3);
+		assert_eq!(
+			UpgradeAnnouncement::migrations(),
+			vec![(b"third".to_vec(), 30)]
+		);
+	});
+}
@@ -131,7 +131,7 @@ impl<B: BlockT> std::convert::From<Error<B>> for ConsensusError {
 pub const POW_AUX_PREFIX: [u8; 4] = *b"PoW:";
 
 /// Get the auxiliary storage key used by engine to store total difficulty.
-fn aux_key<T: AsRef<[u8]>>(hash: &T) -> Vec<u8> {
+pub fn aux_key<T: AsRef<[u8]>>(hash: &T) -> Vec<u8> {
 	POW_AUX_PREFIX
 		.iter()
 		.chain(hash.as_ref())
@@ -19,11 +19,46 @@
 use std::marker::PhantomData;
 use std::sync::Arc;
 
+pub use sys::RANDOMX_COMMIT;
+
 pub const HASH_SIZE: usize = sys::RANDOMX_HASH_SIZE as usize;
+/// Bytes of memory used by a cache.
+pub const CACHE_SIZE: u64 = 256 * 1024 * 1024;
+/// Bytes of memory used by the scratchpad of each VM.
+pub const SCRATCHPAD_SIZE: u64 = 2 * 1024 * 1024;
+
+/// Bytes of memory used by a dataset, which full caches have on top of the
+/// cache.
+pub fn dataset_size() -> u64 {
+	// Dataset items are 64 bytes each.
+	unsafe { sys::randomx_dataset_item_count() as u64 * 64 }
+}
+
+/// Caches to compute hashes with.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CacheMode {
+	/// Full caches for mining, and for verification when one is already
+	/// available. Light caches otherwise.
+	Auto,
+	/// Full caches only.
+	Full,
+	/// Light caches only.
+	Light,
+	/// Light caches only, always on the same fixed key, for development
+	/// chains. Hashes do not match those of the other modes.
+	#[cfg(feature = "light-deterministic")]
+	LightDeterministic,
+}
 
 pub struct Config {
 	pub large_pages: bool,
 	pub secure: bool,
+	pub cache_mode: CacheMode,
+	/// Maximum number of full VMs, one per mining thread, at once.
+	pub max_full_vms: Option<usize>,
+	/// Number of VMs of each cache mode shared by all threads. Each thread has
+	/// VMs of its own if unset.
+	pub vm_count: Option<usize>,
 }
 
 impl Config {
@@ -31,6 +66,9 @@ impl Config {
 		Config {
 			large_pages: false,
 			secure: false,
+			cache_mode: CacheMode::Auto,
+			max_full_vms: None,
+			vm_count: None,
 		}
 	}
 }
@@ -62,11 +100,6 @@ impl From<Error> for String {
 	}
 }
 
-pub enum CacheMode {
-	Full,
-	Light,
-}
-
 pub unsafe trait WithCacheMode {
 	fn has_dataset() -> bool;
 	fn has_large_pages(config: &Config) -> bool;
@@ -211,6 +244,10 @@ pub struct VM<M: WithCacheMode> {
 	ptr: *mut sys::randomx_vm,
 }
 
+// A VM may move between threads, as pooled VMs do, but is never used by two
+// threads at once.
+unsafe impl<M: WithCacheMode> Send for VM<M> {}
+
 pub type FullVM = VM<WithFullCacheMode>;
 pub type LightVM = VM<WithLightCacheMode>;
 
@@ -1,9 +1,20 @@
 use std::env;
 use std::path::PathBuf;
+use std::process::Command;
 
 fn main() {
 	let target = env::var("TARGET").unwrap();
 
+	let commit = Command::new("git")
+		.args(&["rev-parse", "HEAD"])
+		.current_dir("randomx")
+		.output()
+		.ok()
+		.filter(|output| output.status.success())
+		.map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+		.unwrap_or_else(|| "unknown".to_string());
+	println!("cargo:rustc-env=RANDOMX_COMMIT={}", commit);
+
 	println!("cargo:rerun-if-env-changed=RANDOMX_ARCH");
 	let mut config = cmake::Config::new("randomx");
 	config.define(
@@ -21,3 +21,6 @@
 #![allow(non_snake_case)]
 
 include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
+
+/// Commit of the RandomX library the bindings are built against.
+pub const RANDOMX_COMMIT: &str = env!("RANDOMX_COMMIT");
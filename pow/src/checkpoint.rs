@@ -0,0 +1,336 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Kulupu.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Kulupu is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Kulupu is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Kulupu. If not, see <http://www.gnu.org/licenses/>.
+
+//! Checkpoints of socially final blocks.
+//!
+//! Once a block is buried deep enough below the best block, it can be
+//! recorded as a checkpoint in the aux store. A checkpointing node refuses to
+//! import any block whose chain does not contain the latest checkpoint at or
+//! below its height, so deposits confirmed past a checkpoint cannot be
+//! reverted by a reorg, however much work it has.
+//!
+//! A trusted checkpoint may also be given up front, for example from the
+//! command line or the chain spec, in which case it is enforced whether or not
+//! checkpoints are recorded.
+
+use codec::{Decode, Encode};
+use log::*;
+use sc_client_api::AuxStore;
+use sc_consensus::{BlockCheckParams, BlockImport, BlockImportParams, ImportResult};
+use sp_blockchain::{well_known_cache_keys::Id as CacheKeyId, HeaderMetadata};
+use sp_consensus::Error as ConsensusError;
+use sp_runtime::traits::{Block as BlockT, Header as HeaderT, NumberFor};
+use std::{collections::HashMap, fmt::Debug, marker::PhantomData, str::FromStr, sync::Arc};
+
+/// Aux store key of the checkpoints.
+const CHECKPOINTS_KEY: &[u8] = b"kulupu_pow_checkpoints";
+
+/// Maximum number of checkpoints kept. Older checkpoints are dropped, as the
+/// later ones already build on them.
+pub const MAX_CHECKPOINTS: usize = 64;
+
+/// Block recorded as socially final.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Encode, Decode)]
+pub struct Checkpoint<Number, Hash> {
+	/// Block number.
+	pub number: Number,
+	/// Block hash.
+	pub hash: Hash,
+}
+
+impl<Number: FromStr, Hash: FromStr> FromStr for Checkpoint<Number, Hash> {
+	type Err = String;
+
+	/// Parse a checkpoint given as `<number>:<hash>`.
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let (number, hash) = s
+			.split_once(':')
+			.ok_or_else(|| "Checkpoint must be given as <number>:<hash>".to_string())?;
+		let number = number
+			.parse()
+			.map_err(|_| format!("Invalid checkpoint number: {}", number))?;
+		let hash = hash
+			.trim_start_matches("0x")
+			.parse()
+			.map_err(|_| format!("Invalid checkpoint hash: {}", hash))?;
+
+		Ok(Self { number, hash })
+	}
+}
+
+/// Checkpoint of a block type.
+pub type CheckpointFor<B> = Checkpoint<NumberFor<B>, <B as BlockT>::Hash>;
+
+/// Checkpoints in the aux store, from earliest to latest.
+pub fn checkpoints<B: BlockT, C: AuxStore>(
+	client: &C,
+) -> Result<Vec<CheckpointFor<B>>, sp_blockchain::Error> {
+	match client.get_aux(CHECKPOINTS_KEY)? {
+		Some(bytes) => Vec::decode(&mut &bytes[..]).map_err(|e| {
+			sp_blockchain::Error::Backend(format!("Decoding checkpoints failed: {:?}", e))
+		}),
+		None => Ok(Vec::new()),
+	}
+}
+
+/// Record a checkpoint, replacing any checkpoints at or above its height.
+pub fn add_checkpoint<B: BlockT, C: AuxStore>(
+	client: &C,
+	checkpoint: CheckpointFor<B>,
+) -> Result<(), sp_blockchain::Error> {
+	let mut checkpoints = checkpoints::<B, _>(client)?;
+	checkpoints.retain(|c| c.number < checkpoint.number);
+	checkpoints.push(checkpoint);
+	if checkpoints.len() > MAX_CHECKPOINTS {
+		checkpoints.drain(..checkpoints.len() - MAX_CHECKPOINTS);
+	}
+
+	client.insert_aux(&[(CHECKPOINTS_KEY, &checkpoints.encode()[..])], &[])
+}
+
+/// Remove all checkpoints.
+pub fn clear_checkpoints<C: AuxStore>(client: &C) -> Result<(), sp_blockchain::Error> {
+	client.insert_aux(&[], &[CHECKPOINTS_KEY])
+}
+
+/// Block import refusing blocks that conflict with the checkpoints. It must be
+/// combined with a PoW block import.
+pub struct CheckpointBlockImport<B: BlockT, I, C> {
+	inner: I,
+	client: Arc<C>,
+	enabled: bool,
+	trusted: Option<CheckpointFor<B>>,
+	_marker: PhantomData<B>,
+}
+
+impl<B: BlockT, I: Clone, C> Clone for CheckpointBlockImport<B, I, C> {
+	fn clone(&self) -> Self {
+		Self {
+			inner: self.inner.clone(),
+			client: self.client.clone(),
+			enabled: self.enabled,
+			trusted: self.trusted,
+			_marker: PhantomData,
+		}
+	}
+}
+
+impl<B: BlockT, I, C> CheckpointBlockImport<B, I, C> {
+	/// Create a new block import for checkpoints.
+	pub fn new(inner: I, client: Arc<C>, enabled: bool) -> Self {
+		Self {
+			inner,
+			client,
+			enabled,
+			trusted: None,
+			_marker: PhantomData,
+		}
+	}
+
+	/// Additionally enforce the trusted `checkpoint`, even if recorded
+	/// checkpoints are disabled.
+	pub fn with_trusted(mut self, checkpoint: CheckpointFor<B>) -> Self {
+		self.trusted = Some(checkpoint);
+		self
+	}
+}
+
+impl<B, I, C> CheckpointBlockImport<B, I, C>
+where
+	B: BlockT,
+	C: HeaderMetadata<B> + AuxStore,
+	C::Error: Debug,
+{
+	/// Check that the chain of a block with the given hash, number and parent
+	/// contains the latest checkpoint at or below its height.
+	fn check_checkpoints(
+		&self,
+		hash: B::Hash,
+		number: NumberFor<B>,
+		parent_hash: B::Hash,
+	) -> Result<(), ConsensusError> {
+		let mut checkpoints = if self.enabled {
+			checkpoints::<B, _>(self.client.as_ref())
+				.map_err(|e| ConsensusError::ClientImport(e.to_string()))?
+		} else {
+			Vec::new()
+		};
+		checkpoints.extend(self.trusted);
+		checkpoints.sort_by_key(|c| c.number);
+
+		let checkpoint = match checkpoints.iter().rev().find(|c| c.number <= number) {
+			Some(checkpoint) => checkpoint,
+			None => return Ok(()),
+		};
+
+		let conflicts = if checkpoint.number == number {
+			checkpoint.hash != hash
+		} else {
+			let route =
+				sp_blockchain::tree_route(self.client.as_ref(), checkpoint.hash, parent_hash)
+					.map_err(|e| format!("Find route from checkpoint failed: {:?}", e))?;
+			!route.retracted().is_empty()
+		};
+
+		if conflicts {
+			warn!(
+				target: "kulupu-pow",
+				"Refusing block #{} ({}) conflicting with checkpoint #{} ({})",
+				number,
+				hash,
+				checkpoint.number,
+				checkpoint.hash,
+			);
+			return Err(ConsensusError::ClientImport(format!(
+				"Block conflicts with checkpoint #{} ({})",
+				checkpoint.number, checkpoint.hash,
+			)));
+		}
+
+		Ok(())
+	}
+}
+
+#[async_trait::async_trait]
+impl<B, I, C> BlockImport<B> for CheckpointBlockImport<B, I, C>
+where
+	B: BlockT,
+	I: BlockImport<B> + Send + Sync,
+	I::Error: Into<ConsensusError>,
+	I::Transaction: Send + 'static,
+	C: HeaderMetadata<B> + AuxStore + Send + Sync,
+	C::Error: Debug,
+{
+	type Error = ConsensusError;
+	type Transaction = I::Transaction;
+
+	async fn check_block(
+		&mut self,
+		block: BlockCheckParams<B>,
+	) -> Result<ImportResult, Self::Error> {
+		self.inner.check_block(block).await.map_err(Into::into)
+	}
+
+	async fn import_block(
+		&mut self,
+		block: BlockImportParams<B, Self::Transaction>,
+		new_cache: HashMap<CacheKeyId, Vec<u8>>,
+	) -> Result<ImportResult, Self::Error> {
+		if self.enabled || self.trusted.is_some() {
+			self.check_checkpoints(
+				block.post_hash(),
+				*block.header.number(),
+				*block.header.parent_hash(),
+			)?;
+		}
+
+		self.inner
+			.import_block(block, new_cache)
+			.await
+			.map_err(Into::into)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use parking_lot::Mutex;
+	use sp_core::H256;
+	use sp_runtime::testing::{Block as TestBlock, ExtrinsicWrapper};
+
+	type Block = TestBlock<ExtrinsicWrapper<u64>>;
+
+	#[derive(Default)]
+	struct Aux(Mutex<HashMap<Vec<u8>, Vec<u8>>>);
+
+	impl AuxStore for Aux {
+		fn insert_aux<
+			'a,
+			'b: 'a,
+			'c: 'a,
+			I: IntoIterator<Item = &'a (&'c [u8], &'c [u8])>,
+			D: IntoIterator<Item = &'a &'b [u8]>,
+		>(
+			&self,
+			insert: I,
+			delete: D,
+		) -> sp_blockchain::Result<()> {
+			let mut aux = self.0.lock();
+			for (key, value) in insert {
+				aux.insert(key.to_vec(), value.to_vec());
+			}
+			for key in delete {
+				aux.remove(*key);
+			}
+			Ok(())
+		}
+
+		fn get_aux(&self, key: &[u8]) -> sp_blockchain::Result<Option<Vec<u8>>> {
+			Ok(self.0.lock().get(key).cloned())
+		}
+	}
+
+	fn checkpoint(number: u64) -> CheckpointFor<Block> {
+		Checkpoint {
+			number,
+			hash: H256::from_low_u64_be(number),
+		}
+	}
+
+	#[test]
+	fn checkpoints_replace_later_ones_and_are_pruned() {
+		let aux = Aux::default();
+		assert_eq!(checkpoints::<Block, _>(&aux).unwrap(), vec![]);
+
+		add_checkpoint::<Block, _>(&aux, checkpoint(10)).unwrap();
+		add_checkpoint::<Block, _>(&aux, checkpoint(30)).unwrap();
+		add_checkpoint::<Block, _>(&aux, checkpoint(20)).unwrap();
+		assert_eq!(
+			checkpoints::<Block, _>(&aux).unwrap(),
+			vec![checkpoint(10), checkpoint(20)],
+		);
+
+		for number in 0..MAX_CHECKPOINTS as u64 {
+			add_checkpoint::<Block, _>(&aux, checkpoint(100 + number)).unwrap();
+		}
+		let kept = checkpoints::<Block, _>(&aux).unwrap();
+		assert_eq!(kept.len(), MAX_CHECKPOINTS);
+		assert_eq!(kept[0], checkpoint(100));
+
+		clear_checkpoints(&aux).unwrap();
+		assert_eq!(checkpoints::<Block, _>(&aux).unwrap(), vec![]);
+	}
+
+	#[test]
+	fn checkpoints_parse_from_number_and_hash() {
+		let hash = H256::repeat_byte(0xab);
+		assert_eq!(
+			format!("42:{:?}", hash).parse::<CheckpointFor<Block>>(),
+			Ok(Checkpoint { number: 42, hash }),
+		);
+		assert_eq!(
+			format!("42:{}", "ab".repeat(32)).parse::<CheckpointFor<Block>>(),
+			Ok(Checkpoint { number: 42, hash }),
+		);
+		assert!("42".parse::<CheckpointFor<Block>>().is_err());
+		assert!("x:00".parse::<CheckpointFor<Block>>().is_err());
+		assert!(format!("-1:{:?}", hash)
+			.parse::<CheckpointFor<Block>>()
+			.is_err());
+	}
+}
@@ -0,0 +1,72 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Kulupu.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Kulupu is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Kulupu is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Kulupu. If not, see <http://www.gnu.org/licenses/>.
+
+use super::{Error, Loop};
+use once_cell::sync::OnceCell;
+use sp_core::H256;
+
+/// Alternative backend computing RandomX hashes for mining, for example on a
+/// GPU. Verification always uses the built-in CPU implementation.
+pub trait RandomXBackend: Send + Sync {
+	/// Name of the backend, for logging.
+	fn name(&self) -> &'static str;
+
+	/// Hash each of `inputs` with the RandomX key `key_hash`, returning the
+	/// hashes in the same order.
+	fn hash_batch(&self, key_hash: &H256, inputs: &[Vec<u8>]) -> Result<Vec<H256>, Error>;
+}
+
+static BACKEND: OnceCell<Box<dyn RandomXBackend>> = OnceCell::new();
+
+/// Set the backend used for mining. Can only be set once, before mining starts.
+pub fn set_backend(backend: Box<dyn RandomXBackend>) -> Result<(), Box<dyn RandomXBackend>> {
+	BACKEND.set(backend)
+}
+
+/// Backend used for mining, if one is set.
+pub fn backend() -> Option<&'static dyn RandomXBackend> {
+	BACKEND.get().map(|backend| &**backend)
+}
+
+/// Mining loop of `super::loop_raw`, hashing all `round` inputs in one batch.
+pub(super) fn loop_raw_with_backend<FPre, I, FValidate, R>(
+	backend: &dyn RandomXBackend,
+	key_hash: &H256,
+	mut f_pre: FPre,
+	f_validate: FValidate,
+	round: usize,
+) -> Result<Option<R>, Error>
+where
+	FPre: FnMut() -> (Vec<u8>, I),
+	FValidate: Fn(H256, I) -> Loop<Option<R>>,
+{
+	let (inputs, ints): (Vec<_>, Vec<_>) = (0..round).map(|_| f_pre()).unzip();
+	let hashes = backend.hash_batch(key_hash, &inputs)?;
+
+	if hashes.len() != inputs.len() {
+		return Err(Error::Backend(backend.name()));
+	}
+
+	for (hash, int) in hashes.into_iter().zip(ints) {
+		if let Loop::Break(ret) = f_validate(hash, int) {
+			return Ok(ret);
+		}
+	}
+
+	Ok(None)
+}
@@ -16,46 +16,82 @@
 // You should have received a copy of the GNU General Public License
 // along with Kulupu. If not, see <http://www.gnu.org/licenses/>.
 
+mod backend;
+mod pool;
 mod v1;
 mod v2;
 
+pub use self::backend::{backend, set_backend, RandomXBackend};
+use self::pool::{loop_raw_pooled, VmPool};
 pub use self::v1::{ComputeV1, SealV1};
 pub use self::v2::{ComputeV2, SealV2};
-pub use randomx::Config;
 pub use randomx::Error as RandomxError;
+pub use randomx::{CacheMode, Config, RANDOMX_COMMIT};
 
 use codec::{Decode, Encode};
 use kulupu_primitives::Difficulty;
 use kulupu_randomx as randomx;
 use lazy_static::lazy_static;
-use log::info;
+use log::{info, warn};
 use lru_cache::LruCache;
 use once_cell::sync::OnceCell;
-use parking_lot::Mutex;
+use parking_lot::{Condvar, Mutex, MutexGuard};
 use randomx::WithCacheMode;
-use sp_core::H256;
+use sp_core::{blake2_256, H256};
 use std::cell::RefCell;
-use std::sync::Arc;
+use std::sync::{
+	atomic::{AtomicUsize, Ordering},
+	Arc,
+};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Number of full caches kept around, for the current and next key hash.
+const FULL_CACHES: usize = 2;
+/// Number of light caches kept around.
+const LIGHT_CACHES: usize = 3;
+/// Number of hashes computed ahead of verification kept around.
+const PRECOMPUTED_HASHES: usize = 1024;
+/// Longest time to wait for machines of other threads to release a large page
+/// cache.
+const CACHE_WAIT_TIMEOUT: Duration = Duration::from_secs(30);
+/// Key hash of the only cache used in light deterministic mode.
+#[cfg(feature = "light-deterministic")]
+pub const DETERMINISTIC_KEY_HASH: H256 = H256::zero();
 
 lazy_static! {
-	static ref FULL_SHARED_CACHES: Arc<Mutex<LruCache<H256, Arc<randomx::FullCache>>>> =
-		Arc::new(Mutex::new(LruCache::new(2)));
-	static ref LIGHT_SHARED_CACHES: Arc<Mutex<LruCache<H256, Arc<randomx::LightCache>>>> =
-		Arc::new(Mutex::new(LruCache::new(3)));
+	static ref FULL_SHARED_CACHES: SharedCaches<randomx::WithFullCacheMode> =
+		SharedCaches::new(FULL_CACHES);
+	static ref LIGHT_SHARED_CACHES: SharedCaches<randomx::WithLightCacheMode> =
+		SharedCaches::new(LIGHT_CACHES);
+	static ref FULL_VM_POOL: VmPool<randomx::WithFullCacheMode> = VmPool::new();
+	static ref LIGHT_VM_POOL: VmPool<randomx::WithLightCacheMode> = VmPool::new();
+	static ref WARMING_UP: Mutex<Option<H256>> = Mutex::new(None);
+	/// Hashes computed ahead of verification, by key hash and hash of the input.
+	static ref PRECOMPUTED: Mutex<LruCache<(H256, H256), H256>> =
+		Mutex::new(LruCache::new(PRECOMPUTED_HASHES));
 }
 
 thread_local! {
 	static FULL_MACHINE: RefCell<Option<(H256, randomx::FullVM)>> = RefCell::new(None);
 	static LIGHT_MACHINE: RefCell<Option<(H256, randomx::LightVM)>> = RefCell::new(None);
+	static FULL_VM_PERMIT: RefCell<Option<FullVmPermit>> = RefCell::new(None);
 }
 
+static ACTIVE_FULL_VMS: AtomicUsize = AtomicUsize::new(0);
+
 static GLOBAL_CONFIG: OnceCell<Config> = OnceCell::new();
 static DEFAULT_CONFIG: Config = Config::new();
 
 #[derive(Debug)]
 pub enum Error {
+	/// No large page cache was released by other machines in time.
 	CacheNotAvailable,
 	Randomx(RandomxError),
+	/// The named mining backend failed.
+	Backend(&'static str),
+	/// All full VMs allowed by the global config are in use by other threads.
+	FullVmLimitReached,
 }
 
 impl Error {
@@ -63,6 +99,8 @@ impl Error {
 		match self {
 			Error::Randomx(e) => e.description(),
 			Error::CacheNotAvailable => "Randomx cache not available",
+			Error::Backend(_) => "Mining backend failed",
+			Error::FullVmLimitReached => "Randomx full VM limit reached",
 		}
 	}
 }
@@ -81,6 +119,74 @@ pub fn set_global_config(config: Config) -> Result<(), Config> {
 	GLOBAL_CONFIG.set(config)
 }
 
+/// Estimated bytes of memory used with `full_vms` full and `light_vms` light
+/// VMs, once the cache LRUs are filled.
+pub fn memory_usage(full_vms: usize, light_vms: usize) -> u64 {
+	let full_caches = if full_vms > 0 {
+		FULL_CACHES as u64 * (randomx::dataset_size() + randomx::CACHE_SIZE)
+	} else {
+		0
+	};
+
+	full_caches
+		+ LIGHT_CACHES as u64 * randomx::CACHE_SIZE
+		+ (full_vms + light_vms) as u64 * randomx::SCRATCHPAD_SIZE
+}
+
+/// Number of full VMs that fit in `limit` bytes of memory, next to a light VM
+/// for block verification. Zero if the full caches do not fit at all.
+pub fn max_full_vms(limit: u64) -> usize {
+	let base = memory_usage(1, 1);
+	if limit < base {
+		return 0;
+	}
+
+	1 + ((limit - base) / randomx::SCRATCHPAD_SIZE) as usize
+}
+
+/// Permit of a thread to keep a full VM, counted against the maximum number of
+/// full VMs in the global config.
+struct FullVmPermit;
+
+impl FullVmPermit {
+	fn acquire() -> Option<Self> {
+		let max = global_config().max_full_vms.unwrap_or(usize::MAX);
+		ACTIVE_FULL_VMS
+			.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |active| {
+				if active < max {
+					Some(active + 1)
+				} else {
+					None
+				}
+			})
+			.ok()
+			.map(|_| FullVmPermit)
+	}
+}
+
+impl Drop for FullVmPermit {
+	fn drop(&mut self) {
+		ACTIVE_FULL_VMS.fetch_sub(1, Ordering::SeqCst);
+	}
+}
+
+/// Whether the current thread may use a full VM. The permit is kept for the
+/// lifetime of the thread once acquired. Pooled full VMs are bounded by the
+/// size of the pool instead.
+fn has_full_vm_permit() -> bool {
+	if global_config().vm_count.is_some() {
+		return true;
+	}
+
+	FULL_VM_PERMIT.with(|permit| {
+		let mut permit = permit.borrow_mut();
+		if permit.is_none() {
+			*permit = FullVmPermit::acquire();
+		}
+		permit.is_some()
+	})
+}
+
 #[derive(Clone, Copy, PartialEq, Eq, Encode, Decode, Debug)]
 pub enum ComputeMode {
 	Sync,
@@ -106,6 +212,91 @@ impl From<RandomxError> for Error {
 	}
 }
 
+type CacheLru<M> = LruCache<H256, Arc<randomx::Cache<M>>>;
+
+/// Caches shared by the machines of all threads. A cache is in use while a
+/// machine holds a reference to it.
+struct SharedCaches<M: randomx::WithCacheMode> {
+	caches: Mutex<CacheLru<M>>,
+	/// Notified when a machine releases its cache, or a cache is added.
+	changed: Condvar,
+}
+
+impl<M: randomx::WithCacheMode> SharedCaches<M> {
+	fn new(capacity: usize) -> Self {
+		Self {
+			caches: Mutex::new(LruCache::new(capacity)),
+			changed: Condvar::new(),
+		}
+	}
+
+	/// Replace the machine of the current thread, waking up threads waiting for
+	/// the cache of the old machine to be released.
+	fn replace_machine(
+		&self,
+		machine: &RefCell<Option<(H256, randomx::VM<M>)>>,
+		new: Option<(H256, randomx::VM<M>)>,
+	) {
+		let old = machine.replace(new);
+		if old.is_some() {
+			drop(old);
+			self.changed.notify_all();
+		}
+	}
+
+	/// Release the machine of the current thread outside of cache generation.
+	fn release_machine(&self, machine: &RefCell<Option<(H256, randomx::VM<M>)>>) {
+		// Waiting threads check for released caches under the lock, so the
+		// notification cannot be missed in between.
+		let _caches = self.caches.lock();
+		self.replace_machine(machine, None);
+	}
+
+	/// Release machines no thread is using.
+	fn release_vms(&self, vms: Vec<(H256, randomx::VM<M>)>) {
+		let _caches = self.caches.lock();
+		drop(vms);
+		self.changed.notify_all();
+	}
+
+	/// Insert a newly generated cache, waking up threads waiting for it.
+	fn insert(&self, caches: &mut CacheLru<M>, key_hash: H256, cache: Arc<randomx::Cache<M>>) {
+		caches.insert(key_hash, cache);
+		self.changed.notify_all();
+	}
+
+	/// Wait until the cache for `key_hash` is generated by another thread, or a
+	/// cache no machine is using can be reinitialized for it, in which case its
+	/// key is returned. Large page caches are only allocated up to the LRU
+	/// capacity, so the caller is otherwise left without one. Fails after
+	/// `CACHE_WAIT_TIMEOUT`.
+	fn wait_for_cache(
+		&self,
+		caches: &mut MutexGuard<CacheLru<M>>,
+		key_hash: &H256,
+	) -> Result<Option<H256>, Error> {
+		let deadline = Instant::now() + CACHE_WAIT_TIMEOUT;
+
+		loop {
+			if caches.contains_key(key_hash) {
+				return Ok(None);
+			}
+
+			let unused = caches
+				.iter()
+				.find(|&(_, cache)| Arc::strong_count(cache) == 1)
+				.map(|(key, _)| *key);
+			if unused.is_some() {
+				return Ok(unused);
+			}
+
+			if self.changed.wait_until(caches, deadline).timed_out() {
+				return Err(Error::CacheNotAvailable);
+			}
+		}
+	}
+}
+
 fn need_new_vm<M: randomx::WithCacheMode>(
 	key_hash: &H256,
 	machine: &RefCell<Option<(H256, randomx::VM<M>)>>,
@@ -123,17 +314,15 @@ fn need_new_vm<M: randomx::WithCacheMode>(
 fn do_new_vm<M: randomx::WithCacheMode>(
 	key_hash: &H256,
 	machine: &RefCell<Option<(H256, randomx::VM<M>)>>,
-	shared_caches: &Mutex<LruCache<H256, Arc<randomx::Cache<M>>>>,
+	shared_caches: &SharedCaches<M>,
 	f_has_large_pages: fn(&Config) -> bool,
 ) -> Result<(), Error> {
-	let mut shared_caches = shared_caches.lock();
+	let mut caches = shared_caches.caches.lock();
 
 	if !f_has_large_pages(global_config()) {
-		if let Some(cache) = shared_caches.get_mut(key_hash) {
-			machine.replace(Some((
-				*key_hash,
-				randomx::VM::new(cache.clone(), global_config()),
-			)));
+		if let Some(cache) = caches.get_mut(key_hash) {
+			let vm = randomx::VM::new(cache.clone(), global_config());
+			shared_caches.replace_machine(machine, Some((*key_hash, vm)));
 
 			Ok(())
 		} else {
@@ -146,63 +335,182 @@ fn do_new_vm<M: randomx::WithCacheMode>(
 
 			let cache = Arc::new(randomx::Cache::new(&key_hash[..], global_config())?);
 
-			shared_caches.insert(*key_hash, cache.clone());
-			machine.replace(Some((*key_hash, randomx::VM::new(cache, global_config()))));
+			shared_caches.insert(&mut caches, *key_hash, cache.clone());
+			let vm = randomx::VM::new(cache, global_config());
+			shared_caches.replace_machine(machine, Some((*key_hash, vm)));
 
 			Ok(())
 		}
 	} else {
-		if let Some(cache) = shared_caches.get_mut(key_hash) {
-			machine.replace(Some((
-				*key_hash,
-				randomx::VM::new(cache.clone(), global_config()),
-			)));
-
-			Ok(())
+		// Release the old cache first, as machines of other threads may be
+		// waiting for it.
+		shared_caches.replace_machine(machine, None);
+		let key_to_replace = if caches.is_empty() {
+			None
 		} else {
-			let info = format!(
-				"At block boundary, generating new RandomX {} cache with key hash {} ...",
-				M::description(),
-				key_hash,
-			);
+			shared_caches.wait_for_cache(&mut caches, key_hash)?
+		};
 
-			if shared_caches.is_empty() {
-				info!(target: "kulupu-randomx", "{}", info);
-				let cache = Arc::new(randomx::Cache::new(&key_hash[..], global_config())?);
+		if let Some(cache) = caches.get_mut(key_hash) {
+			let vm = randomx::VM::new(cache.clone(), global_config());
+			shared_caches.replace_machine(machine, Some((*key_hash, vm)));
 
-				shared_caches.insert(*key_hash, cache.clone());
-				machine.replace(Some((*key_hash, randomx::VM::new(cache, global_config()))));
+			return Ok(());
+		}
 
-				Ok(())
-			} else {
-				machine.replace(None);
-				let key_to_replace = (*shared_caches)
-					.iter()
-					.find(|&(_, cache)| Arc::strong_count(cache) == 1)
-					.and_then(|(key, _)| Some(*key))
-					.ok_or(Error::CacheNotAvailable)?;
-
-				info!(target: "kulupu-randomx", "{}", info);
-				let mut cache = shared_caches
+		info!(
+			target: "kulupu-randomx",
+			"At block boundary, generating new RandomX {} cache with key hash {} ...",
+			M::description(),
+			key_hash,
+		);
+
+		let cache = match key_to_replace {
+			Some(key_to_replace) => {
+				let mut cache = caches
 					.remove(&key_to_replace)
 					.expect("That key should still be in the lru cache.");
 
 				Arc::get_mut(&mut cache)
 					.expect("The mutable reference should be available as strong_count is 1.")
 					.reinit(&key_hash[..]);
-				shared_caches.insert(*key_hash, cache.clone());
-				machine.replace(Some((*key_hash, randomx::VM::new(cache, global_config()))));
+				cache
+			}
+			None => Arc::new(randomx::Cache::new(&key_hash[..], global_config())?),
+		};
+
+		shared_caches.insert(&mut caches, *key_hash, cache.clone());
+		let vm = randomx::VM::new(cache, global_config());
+		shared_caches.replace_machine(machine, Some((*key_hash, vm)));
+
+		Ok(())
+	}
+}
+
+fn warm_up_cache<M: randomx::WithCacheMode>(
+	key_hash: &H256,
+	shared_caches: &SharedCaches<M>,
+	f_has_large_pages: fn(&Config) -> bool,
+) -> Result<(), Error> {
+	let reused = {
+		let mut caches = shared_caches.caches.lock();
 
-				Ok(())
+		if caches.contains_key(key_hash) {
+			return Ok(());
+		}
+
+		// Large page caches are only allocated up to the LRU capacity, after
+		// which a cache no machine is using is reinitialized instead.
+		if f_has_large_pages(global_config()) && caches.len() >= caches.capacity() {
+			match shared_caches.wait_for_cache(&mut caches, key_hash)? {
+				Some(key_to_replace) => Some(
+					caches
+						.remove(&key_to_replace)
+						.expect("That key should still be in the lru cache."),
+				),
+				// Generated by a machine in the meantime.
+				None => return Ok(()),
 			}
+		} else {
+			None
 		}
+	};
+
+	info!(
+		target: "kulupu-randomx",
+		"Ahead of block boundary, generating new RandomX {} cache with key hash {} ...",
+		M::description(),
+		key_hash,
+	);
+
+	// The cache is built without holding the lock, so machines on the current
+	// key hash are not held up.
+	let cache = match reused {
+		Some(mut cache) => {
+			Arc::get_mut(&mut cache)
+				.expect("The mutable reference should be available as strong_count is 1.")
+				.reinit(&key_hash[..]);
+			cache
+		}
+		None => Arc::new(randomx::Cache::new(&key_hash[..], global_config())?),
+	};
+
+	let mut caches = shared_caches.caches.lock();
+	shared_caches.insert(&mut caches, *key_hash, cache);
+
+	Ok(())
+}
+
+/// Generate the caches for `key_hash` in a background thread, so that machines
+/// switch to it at the next block boundary without pausing mining or import.
+/// Which caches are generated follows the cache mode of the global config, as
+/// `loop_raw` does. Each key hash is only warmed
+/// up once.
+pub fn spawn_warm_up(key_hash: H256, mode: ComputeMode) {
+	{
+		let mut warming_up = WARMING_UP.lock();
+		if *warming_up == Some(key_hash) {
+			return;
+		}
+		*warming_up = Some(key_hash);
 	}
+
+	let (full, light) = match global_config().cache_mode {
+		CacheMode::Auto => (mode == ComputeMode::Mining, true),
+		// Verification-only nodes never generate full caches.
+		CacheMode::Full => (mode == ComputeMode::Mining, mode == ComputeMode::Sync),
+		CacheMode::Light => (false, true),
+		// The only cache is generated at first use.
+		#[cfg(feature = "light-deterministic")]
+		CacheMode::LightDeterministic => return,
+	};
+	let full = full && global_config().max_full_vms != Some(0);
+
+	thread::spawn(move || {
+		if full {
+			if let Err(err) = warm_up_cache::<randomx::WithFullCacheMode>(
+				&key_hash,
+				&FULL_SHARED_CACHES,
+				randomx::WithFullCacheMode::has_large_pages,
+			) {
+				warn!(
+					target: "kulupu-randomx",
+					"Generating RandomX full cache ahead of block boundary failed: {}",
+					err.description(),
+				);
+			}
+		}
+
+		if light {
+			if let Err(err) = warm_up_cache::<randomx::WithLightCacheMode>(
+				&key_hash,
+				&LIGHT_SHARED_CACHES,
+				randomx::WithLightCacheMode::has_large_pages,
+			) {
+				warn!(
+					target: "kulupu-randomx",
+					"Generating RandomX light cache ahead of block boundary failed: {}",
+					err.description(),
+				);
+			}
+		}
+	});
+}
+
+/// Generate the full cache for `key_hash` right away, so that a forced full
+/// cache mode fails at startup rather than at the first hash.
+pub fn prepare_full_cache(key_hash: &H256) -> Result<(), Error> {
+	warm_up_cache::<randomx::WithFullCacheMode>(
+		key_hash,
+		&FULL_SHARED_CACHES,
+		randomx::WithFullCacheMode::has_large_pages,
+	)
 }
 
 fn loop_raw_with_cache<M: randomx::WithCacheMode, FPre, I, FValidate, R>(
 	key_hash: &H256,
 	machine: &RefCell<Option<(H256, randomx::VM<M>)>>,
-	shared_caches: &Arc<Mutex<LruCache<H256, Arc<randomx::Cache<M>>>>>,
+	shared_caches: &SharedCaches<M>,
 	mut f_pre: FPre,
 	f_validate: FValidate,
 	f_has_large_pages: fn(&Config) -> bool,
@@ -281,9 +589,8 @@ where
 	Ok(ret)
 }
 
-pub fn loop_raw<FPre, I, FValidate, R>(
+fn loop_raw_full<FPre, I, FValidate, R>(
 	key_hash: &H256,
-	mode: ComputeMode,
 	f_pre: FPre,
 	f_validate: FValidate,
 	round: usize,
@@ -292,8 +599,18 @@ where
 	FPre: FnMut() -> (Vec<u8>, I),
 	FValidate: Fn(H256, I) -> Loop<Option<R>>,
 {
-	match mode {
-		ComputeMode::Mining => FULL_MACHINE.with(|machine| {
+	if global_config().vm_count.is_some() {
+		loop_raw_pooled(
+			key_hash,
+			&FULL_VM_POOL,
+			&FULL_SHARED_CACHES,
+			f_pre,
+			f_validate,
+			randomx::WithFullCacheMode::has_large_pages,
+			round,
+		)
+	} else {
+		FULL_MACHINE.with(|machine| {
 			loop_raw_with_cache::<randomx::WithFullCacheMode, _, _, _, _>(
 				key_hash,
 				machine,
@@ -303,55 +620,138 @@ where
 				randomx::WithFullCacheMode::has_large_pages,
 				round,
 			)
-		}),
-		ComputeMode::Sync => {
-			let full_ret = FULL_MACHINE.with(|machine| {
-				if !need_new_vm::<randomx::WithFullCacheMode>(key_hash, machine) {
-					Ok(
-						loop_raw_with_cache::<randomx::WithFullCacheMode, _, _, _, _>(
-							key_hash,
-							machine,
-							&FULL_SHARED_CACHES,
-							f_pre,
-							f_validate,
-							randomx::WithFullCacheMode::has_large_pages,
-							round,
-						),
-					)
-				} else {
-					Err((f_pre, f_validate))
-				}
-			});
-
-			match full_ret {
-				Ok(ret) => ret,
-				Err((f_pre, f_validate)) => LIGHT_MACHINE.with(|machine| {
-					loop_raw_with_cache::<randomx::WithLightCacheMode, _, _, _, _>(
-						key_hash,
-						machine,
-						&LIGHT_SHARED_CACHES,
-						f_pre,
-						f_validate,
-						randomx::WithLightCacheMode::has_large_pages,
-						round,
-					)
-				}),
+		})
+	}
+}
+
+fn loop_raw_light<FPre, I, FValidate, R>(
+	key_hash: &H256,
+	f_pre: FPre,
+	f_validate: FValidate,
+	round: usize,
+) -> Result<Option<R>, Error>
+where
+	FPre: FnMut() -> (Vec<u8>, I),
+	FValidate: Fn(H256, I) -> Loop<Option<R>>,
+{
+	if global_config().vm_count.is_some() {
+		loop_raw_pooled(
+			key_hash,
+			&LIGHT_VM_POOL,
+			&LIGHT_SHARED_CACHES,
+			f_pre,
+			f_validate,
+			randomx::WithLightCacheMode::has_large_pages,
+			round,
+		)
+	} else {
+		LIGHT_MACHINE.with(|machine| {
+			loop_raw_with_cache::<randomx::WithLightCacheMode, _, _, _, _>(
+				key_hash,
+				machine,
+				&LIGHT_SHARED_CACHES,
+				f_pre,
+				f_validate,
+				randomx::WithLightCacheMode::has_large_pages,
+				round,
+			)
+		})
+	}
+}
+
+pub fn loop_raw<FPre, I, FValidate, R>(
+	key_hash: &H256,
+	mode: ComputeMode,
+	f_pre: FPre,
+	f_validate: FValidate,
+	round: usize,
+) -> Result<Option<R>, Error>
+where
+	FPre: FnMut() -> (Vec<u8>, I),
+	FValidate: Fn(H256, I) -> Loop<Option<R>>,
+{
+	match (mode, global_config().cache_mode) {
+		(_, CacheMode::Light) => loop_raw_light(key_hash, f_pre, f_validate, round),
+		#[cfg(feature = "light-deterministic")]
+		(_, CacheMode::LightDeterministic) => {
+			loop_raw_light(&DETERMINISTIC_KEY_HASH, f_pre, f_validate, round)
+		}
+		(ComputeMode::Mining, _) => match backend() {
+			Some(backend) => {
+				backend::loop_raw_with_backend(backend, key_hash, f_pre, f_validate, round)
+			}
+			None if has_full_vm_permit() => loop_raw_full(key_hash, f_pre, f_validate, round),
+			None => Err(Error::FullVmLimitReached),
+		},
+		(ComputeMode::Sync, cache_mode) => {
+			// Only use the full machine if it is already on this key hash, as
+			// generating a full cache for verification alone is too costly. A
+			// full machine on another key hash is released instead, so that
+			// mining threads can reuse its cache. Pooled full machines are left
+			// to mining, rather than hold up verification behind mining rounds.
+			let has_full_vm = global_config().vm_count.is_none()
+				&& FULL_MACHINE.with(|machine| {
+					let has_full_vm = !need_new_vm::<randomx::WithFullCacheMode>(key_hash, machine);
+					if !has_full_vm && machine.borrow().is_some() {
+						FULL_SHARED_CACHES.release_machine(machine);
+					}
+					has_full_vm
+				});
+			// In full mode, a full cache mining generated for this key hash is
+			// reused too. Verification falls back to light machines rather
+			// than fail on the full VM limit.
+			let has_full_cache = cache_mode == CacheMode::Full
+				&& FULL_SHARED_CACHES.caches.lock().contains_key(key_hash)
+				&& has_full_vm_permit();
+
+			if has_full_vm || has_full_cache {
+				loop_raw_full(key_hash, f_pre, f_validate, round)
+			} else {
+				loop_raw_light(key_hash, f_pre, f_validate, round)
 			}
 		}
 	}
 }
 
-pub fn compute<T: Encode>(key_hash: &H256, input: &T, mode: ComputeMode) -> Result<H256, Error> {
+fn compute_raw(key_hash: &H256, input: &[u8], mode: ComputeMode) -> Result<H256, Error> {
 	Ok(loop_raw(
 		key_hash,
 		mode,
-		|| (input.encode(), ()),
+		|| (input.to_vec(), ()),
 		|hash, ()| Loop::Break(Some(hash)),
 		1,
 	)?
 	.expect("Loop break always returns Some; qed"))
 }
 
+pub fn compute<T: Encode>(key_hash: &H256, input: &T, mode: ComputeMode) -> Result<H256, Error> {
+	let input = input.encode();
+	if mode == ComputeMode::Sync {
+		let input_hash = H256(blake2_256(&input));
+		if let Some(hash) = PRECOMPUTED.lock().remove(&(*key_hash, input_hash)) {
+			return Ok(hash);
+		}
+	}
+
+	compute_raw(key_hash, &input, mode)
+}
+
+/// Compute the hash of `input` ahead of its verification, for example while
+/// earlier blocks are still being imported. Verification then takes the hash
+/// from the precomputed ones.
+pub fn precompute<T: Encode>(key_hash: &H256, input: &T) -> Result<(), Error> {
+	let input = input.encode();
+	let key = (*key_hash, H256(blake2_256(&input)));
+	if PRECOMPUTED.lock().contains_key(&key) {
+		return Ok(());
+	}
+
+	let hash = compute_raw(key_hash, &input, ComputeMode::Sync)?;
+	PRECOMPUTED.lock().insert(key, hash);
+
+	Ok(())
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
@@ -362,6 +762,18 @@ mod tests {
 		assert_eq!(randomx::HASH_SIZE, 32);
 	}
 
+	#[test]
+	fn max_full_vms_fits_memory_usage() {
+		assert_eq!(max_full_vms(0), 0);
+		assert_eq!(max_full_vms(memory_usage(1, 1) - 1), 0);
+		assert_eq!(max_full_vms(memory_usage(1, 1)), 1);
+		assert_eq!(max_full_vms(memory_usage(16, 1)), 16);
+		assert_eq!(
+			max_full_vms(memory_usage(16, 1) + randomx::SCRATCHPAD_SIZE - 1),
+			16
+		);
+	}
+
 	#[test]
 	fn randomx_collision() -> Result<(), String> {
 		let mut compute = ComputeV1 {
@@ -398,4 +810,43 @@ mod tests {
 
 		Ok(())
 	}
+
+	struct FirstByteBackend;
+
+	impl RandomXBackend for FirstByteBackend {
+		fn name(&self) -> &'static str {
+			"first-byte"
+		}
+
+		fn hash_batch(&self, _key_hash: &H256, inputs: &[Vec<u8>]) -> Result<Vec<H256>, Error> {
+			Ok(inputs
+				.iter()
+				.map(|input| H256::repeat_byte(input[0]))
+				.collect())
+		}
+	}
+
+	#[test]
+	fn backend_loop_validates_in_order() {
+		let mut next = 0u8;
+		let ret = backend::loop_raw_with_backend(
+			&FirstByteBackend,
+			&H256::default(),
+			|| {
+				next += 1;
+				(vec![next], next)
+			},
+			|hash, int| {
+				if hash == H256::repeat_byte(3) {
+					Loop::Break(Some(int))
+				} else {
+					Loop::Continue
+				}
+			},
+			5,
+		);
+
+		assert_eq!(ret.unwrap(), Some(3));
+		assert_eq!(next, 5);
+	}
 }
@@ -0,0 +1,130 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Kulupu.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Kulupu is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Kulupu is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Kulupu. If not, see <http://www.gnu.org/licenses/>.
+
+use super::{global_config, loop_raw_with_cache, Error, Loop, SharedCaches};
+use kulupu_randomx as randomx;
+use parking_lot::{Condvar, Mutex};
+use sp_core::H256;
+use std::cell::RefCell;
+
+/// Machines shared by all threads, when the number of VMs is limited by the
+/// `vm_count` of the global config. A thread takes a machine out of the pool
+/// for one mining round or verification, and returns it afterwards.
+pub(super) struct VmPool<M: randomx::WithCacheMode> {
+	state: Mutex<VmPoolState<M>>,
+	/// Notified when a machine is returned to the pool.
+	returned: Condvar,
+}
+
+struct VmPoolState<M: randomx::WithCacheMode> {
+	/// Machines not taken by any thread.
+	idle: Vec<(H256, randomx::VM<M>)>,
+	/// Number of machines taken, including those yet to be created.
+	taken: usize,
+}
+
+impl<M: randomx::WithCacheMode> VmPool<M> {
+	pub(super) fn new() -> Self {
+		Self {
+			state: Mutex::new(VmPoolState {
+				idle: Vec::new(),
+				taken: 0,
+			}),
+			returned: Condvar::new(),
+		}
+	}
+
+	/// Take a machine for `key_hash` out of the pool, waiting for one to be
+	/// returned if all machines are taken. `None` if a machine is to be
+	/// created. Idle machines on other key hashes are released, so that their
+	/// caches can be reused.
+	fn take(
+		&self,
+		key_hash: &H256,
+		shared_caches: &SharedCaches<M>,
+	) -> Option<(H256, randomx::VM<M>)> {
+		let size = global_config().vm_count.unwrap_or(1);
+		let mut state = self.state.lock();
+
+		if state
+			.idle
+			.iter()
+			.any(|(mkey_hash, _)| mkey_hash != key_hash)
+		{
+			let (idle, stale) = state
+				.idle
+				.drain(..)
+				.partition(|(mkey_hash, _)| mkey_hash == key_hash);
+			state.idle = idle;
+			shared_caches.release_vms(stale);
+		}
+
+		loop {
+			if let Some(vm) = state.idle.pop() {
+				state.taken += 1;
+				return Some(vm);
+			}
+
+			if state.taken < size {
+				state.taken += 1;
+				return None;
+			}
+
+			self.returned.wait(&mut state);
+		}
+	}
+
+	/// Return a machine taken out of the pool, or `None` if none was created.
+	fn put(&self, machine: Option<(H256, randomx::VM<M>)>) {
+		let mut state = self.state.lock();
+		state.taken -= 1;
+		if let Some(machine) = machine {
+			state.idle.push(machine);
+		}
+		self.returned.notify_one();
+	}
+}
+
+/// `super::loop_raw_with_cache` on a machine taken out of `pool`.
+pub(super) fn loop_raw_pooled<M: randomx::WithCacheMode, FPre, I, FValidate, R>(
+	key_hash: &H256,
+	pool: &VmPool<M>,
+	shared_caches: &SharedCaches<M>,
+	f_pre: FPre,
+	f_validate: FValidate,
+	f_has_large_pages: fn(&randomx::Config) -> bool,
+	round: usize,
+) -> Result<Option<R>, Error>
+where
+	FPre: FnMut() -> (Vec<u8>, I),
+	FValidate: Fn(H256, I) -> Loop<Option<R>>,
+{
+	let machine = RefCell::new(pool.take(key_hash, shared_caches));
+	let ret = loop_raw_with_cache(
+		key_hash,
+		&machine,
+		shared_caches,
+		f_pre,
+		f_validate,
+		f_has_large_pages,
+		round,
+	);
+	pool.put(machine.into_inner());
+
+	ret
+}
@@ -17,16 +17,10 @@
 // along with Kulupu. If not, see <http://www.gnu.org/licenses/>.
 
 use super::Calculation;
-use codec::{Decode, Encode};
 use kulupu_primitives::Difficulty;
+pub use kulupu_primitives::SealV1;
 use sp_core::H256;
 
-#[derive(Clone, PartialEq, Eq, Encode, Decode, Debug)]
-pub struct SealV1 {
-	pub difficulty: Difficulty,
-	pub nonce: H256,
-}
-
 #[derive(Clone, PartialEq, Eq)]
 pub struct ComputeV1 {
 	pub key_hash: H256,
@@ -18,16 +18,11 @@
 
 use super::Calculation;
 use crate::app;
-use codec::{Decode, Encode};
+use codec::Encode;
 use kulupu_primitives::Difficulty;
 use sp_core::{crypto::Pair, hashing::blake2_256, H256};
 
-#[derive(Clone, PartialEq, Eq, Encode, Decode, Debug)]
-pub struct SealV2 {
-	pub difficulty: Difficulty,
-	pub nonce: H256,
-	pub signature: app::Signature,
-}
+pub type SealV2 = kulupu_primitives::SealV2<app::Signature>;
 
 #[derive(Clone, PartialEq, Eq)]
 pub struct ComputeV2 {
@@ -75,7 +70,7 @@ impl ComputeV2 {
 		}
 	}
 
-	fn signing_message(&self) -> [u8; 32] {
+	pub fn signing_message(&self) -> [u8; 32] {
 		let calculation = Calculation {
 			difficulty: self.difficulty,
 			pre_hash: self.pre_hash,
@@ -90,6 +85,11 @@ impl ComputeV2 {
 		pair.sign(&hash[..])
 	}
 
+	pub fn sign_joint(&self, pairs: &[app::Pair]) -> app::Signature {
+		let hash = self.signing_message();
+		crate::joint::sign(pairs, &hash[..])
+	}
+
 	pub fn verify(&self, signature: &app::Signature, public: &app::Public) -> bool {
 		let hash = self.signing_message();
 		app::Pair::verify(signature, &hash[..], public)
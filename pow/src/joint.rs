@@ -0,0 +1,132 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Kulupu.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Kulupu is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Kulupu is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Kulupu. If not, see <http://www.gnu.org/licenses/>.
+
+//! Joint mining keys.
+//!
+//! Several sr25519 keys can be aggregated with MuSig into a single public key,
+//! used as the author account. Signatures by all of the keys together are
+//! ordinary sr25519 signatures of the aggregated key, so seals signed jointly
+//! verify like any other.
+
+use crate::app;
+use schnorrkel::{
+	musig::{aggregate_public_key_from_slice, AggregatePublicKey},
+	signing_context, Keypair, PublicKey,
+};
+use sp_core::sr25519;
+
+/// Signing context of sr25519 signatures in Substrate.
+const SIGNING_CTX: &[u8] = b"substrate";
+
+/// Author public key of `publics` signing jointly. `None` if any of the keys is
+/// invalid.
+pub fn joint_public(publics: &[app::Public]) -> Option<app::Public> {
+	let mut keys = publics
+		.iter()
+		.map(|public| PublicKey::from_bytes(public.as_ref()).ok())
+		.collect::<Option<Vec<_>>>()?;
+	let aggregate = aggregate_public_key_from_slice(&mut keys)?;
+
+	Some(sr25519::Public::from_raw(aggregate.public_key().to_bytes()).into())
+}
+
+fn keypair(pair: &app::Pair) -> &Keypair {
+	AsRef::<sr25519::Pair>::as_ref(pair).as_ref()
+}
+
+/// Sign `message` with all of `pairs`, for their joint public key.
+///
+/// All rounds of MuSig run locally, so none of them can fail.
+pub fn sign(pairs: &[app::Pair], message: &[u8]) -> app::Signature {
+	let transcript = signing_context(SIGNING_CTX).bytes(message);
+	let publics = pairs
+		.iter()
+		.map(|pair| keypair(pair).public)
+		.collect::<Vec<_>>();
+
+	let mut commits = pairs
+		.iter()
+		.map(|pair| keypair(pair).musig(transcript.clone()))
+		.collect::<Vec<_>>();
+	let commitments = commits
+		.iter()
+		.map(|commit| commit.our_commitment())
+		.collect::<Vec<_>>();
+	for (i, commit) in commits.iter_mut().enumerate() {
+		for (j, commitment) in commitments.iter().enumerate().filter(|(j, _)| *j != i) {
+			commit
+				.add_their_commitment(publics[j], *commitment)
+				.expect("commitments are of distinct local keys; qed");
+		}
+	}
+
+	let mut reveals = commits
+		.into_iter()
+		.map(|commit| commit.reveal_stage())
+		.collect::<Vec<_>>();
+	let revealed = reveals
+		.iter()
+		.map(|reveal| reveal.our_reveal().clone())
+		.collect::<Vec<_>>();
+	for (i, reveal) in reveals.iter_mut().enumerate() {
+		for (j, revealed) in revealed.iter().enumerate().filter(|(j, _)| *j != i) {
+			reveal
+				.add_their_reveal(publics[j], revealed.clone())
+				.expect("reveals match the commitments added above; qed");
+		}
+	}
+
+	let cosigns = reveals
+		.into_iter()
+		.map(|reveal| reveal.cosign_stage())
+		.collect::<Vec<_>>();
+	let mut cosigns = cosigns.into_iter().enumerate();
+	let (_, mut cosign) = cosigns.next().expect("joint keys are not empty; qed");
+	for (j, other) in cosigns {
+		cosign
+			.add_their_cosignature(publics[j], other.our_cosignature())
+			.expect("cosignatures match the reveals added above; qed");
+	}
+
+	let signature = cosign.sign().expect("all keys have cosigned above; qed");
+	sr25519::Signature::from_raw(signature.to_bytes()).into()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sp_core::crypto::Pair;
+
+	#[test]
+	fn joint_signatures_verify_against_joint_public() {
+		let pairs = vec![
+			app::Pair::from_string("//Alice", None).unwrap(),
+			app::Pair::from_string("//Bob", None).unwrap(),
+		];
+		let publics = pairs.iter().map(|pair| pair.public()).collect::<Vec<_>>();
+		let public = joint_public(&publics).unwrap();
+
+		let signature = sign(&pairs, b"seal");
+		assert!(app::Pair::verify(&signature, b"seal", &public));
+		assert!(!app::Pair::verify(&signature, b"other seal", &public));
+		assert!(!app::Pair::verify(&signature, b"seal", &publics[0]));
+
+		let reversed = publics.iter().rev().cloned().collect::<Vec<_>>();
+		assert_eq!(joint_public(&reversed), Some(public));
+	}
+}
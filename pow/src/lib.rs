@@ -16,15 +16,18 @@
 // You should have received a copy of the GNU General Public License
 // along with Kulupu. If not, see <http://www.gnu.org/licenses/>.
 
+pub mod checkpoint;
 pub mod compute;
+pub mod joint;
+pub mod preverify;
 pub mod weak_sub;
 
-use codec::{Decode, Encode};
+use codec::{Decode, DecodeAll, Encode};
 use kulupu_pow_consensus::PowAlgorithm;
-use kulupu_primitives::{AlgorithmApi, Difficulty};
+use kulupu_primitives::{decode_seal, AlgorithmApi, Difficulty, Seal, SealError};
 use log::*;
 use parking_lot::Mutex;
-use rand::{rngs::SmallRng, thread_rng, SeedableRng};
+use rand::thread_rng;
 use sc_client_api::{backend::AuxStore, blockchain::HeaderBackend};
 use sc_keystore::LocalKeystore;
 use sp_api::ProvideRuntimeApi;
@@ -33,11 +36,12 @@ use sp_core::{blake2_256, H256, U256};
 use sp_runtime::generic::BlockId;
 use sp_runtime::traits::{Block as BlockT, Header as HeaderT, UniqueSaturatedInto};
 use std::{
+	collections::{BTreeMap, VecDeque},
 	sync::Arc,
-	time::{Duration, Instant},
+	time::{Duration, Instant, SystemTime},
 };
 
-use crate::compute::{ComputeMode, ComputeV1, ComputeV2, SealV1, SealV2};
+use crate::compute::{ComputeMode, ComputeV1, ComputeV2};
 
 pub mod app {
 	use sp_application_crypto::{app_crypto, sr25519};
@@ -55,34 +59,105 @@ pub fn is_valid_hash(hash: &H256, difficulty: Difficulty) -> bool {
 	!overflowed
 }
 
-pub fn key_hash<B, C>(
+/// Number of blocks between key hash rotations, ~2.8 days.
+const KEY_PERIOD: u64 = 4096;
+/// Number of blocks after a key block before its hash is used, 2 hours.
+const KEY_OFFSET: u64 = 128;
+
+fn parent_header<B, C>(
 	client: &C,
 	parent: &BlockId<B>,
-) -> Result<H256, kulupu_pow_consensus::Error<B>>
+) -> Result<B::Header, kulupu_pow_consensus::Error<B>>
 where
 	B: BlockT<Hash = H256>,
 	C: HeaderBackend<B>,
 {
-	const PERIOD: u64 = 4096; // ~2.8 days
-	const OFFSET: u64 = 128; // 2 hours
-
-	let parent_header = client
+	client
 		.header(*parent)
 		.map_err(|e| {
 			kulupu_pow_consensus::Error::Environment(format!("Client execution error: {:?}", e))
 		})?
 		.ok_or(kulupu_pow_consensus::Error::Environment(
 			"Parent header not found".to_string(),
-		))?;
+		))
+}
+
+pub fn key_hash<B, C>(
+	client: &C,
+	parent: &BlockId<B>,
+) -> Result<H256, kulupu_pow_consensus::Error<B>>
+where
+	B: BlockT<Hash = H256>,
+	C: HeaderBackend<B>,
+{
+	let parent_header = parent_header(client, parent)?;
+	let parent_number = UniqueSaturatedInto::<u64>::unique_saturated_into(*parent_header.number());
+
+	ancestor_hash(client, parent_header, key_number(parent_number))
+}
+
+/// Number of the block whose hash is the key hash of work built on a parent
+/// numbered `parent_number`.
+pub fn key_number(parent_number: u64) -> u64 {
+	let key_number = parent_number.saturating_sub(parent_number % KEY_PERIOD);
+	if parent_number.saturating_sub(key_number) < KEY_OFFSET {
+		key_number.saturating_sub(KEY_PERIOD)
+	} else {
+		key_number
+	}
+}
+
+/// Key hash that work built on descendants of `parent` switches to at the next
+/// rotation. It is only known once the key block exists, which is within
+/// `KEY_OFFSET` blocks of the rotation.
+pub fn next_key_hash<B, C>(
+	client: &C,
+	parent: &BlockId<B>,
+) -> Result<Option<H256>, kulupu_pow_consensus::Error<B>>
+where
+	B: BlockT<Hash = H256>,
+	C: HeaderBackend<B>,
+{
+	let parent_header = parent_header(client, parent)?;
 	let parent_number = UniqueSaturatedInto::<u64>::unique_saturated_into(*parent_header.number());
 
-	let mut key_number = parent_number.saturating_sub(parent_number % PERIOD);
-	if parent_number.saturating_sub(key_number) < OFFSET {
-		key_number = key_number.saturating_sub(PERIOD);
+	let key_number = parent_number.saturating_sub(parent_number % KEY_PERIOD);
+	if key_number == 0 || parent_number.saturating_sub(key_number) >= KEY_OFFSET {
+		return Ok(None);
+	}
+
+	ancestor_hash(client, parent_header, key_number).map(Some)
+}
+
+/// Generate the RandomX caches of the next key hash in the background, once it
+/// is known. See `compute::spawn_warm_up`.
+pub fn warm_up_next_key<B, C>(
+	client: &C,
+	parent: &BlockId<B>,
+	mode: ComputeMode,
+) -> Result<(), kulupu_pow_consensus::Error<B>>
+where
+	B: BlockT<Hash = H256>,
+	C: HeaderBackend<B>,
+{
+	if let Some(key_hash) = next_key_hash(client, parent)? {
+		compute::spawn_warm_up(key_hash, mode);
 	}
 
-	let mut current = parent_header;
-	while UniqueSaturatedInto::<u64>::unique_saturated_into(*current.number()) != key_number {
+	Ok(())
+}
+
+fn ancestor_hash<B, C>(
+	client: &C,
+	header: B::Header,
+	number: u64,
+) -> Result<H256, kulupu_pow_consensus::Error<B>>
+where
+	B: BlockT<Hash = H256>,
+	C: HeaderBackend<B>,
+{
+	let mut current = header;
+	while UniqueSaturatedInto::<u64>::unique_saturated_into(*current.number()) != number {
 		current = client
 			.header(BlockId::Hash(*current.parent_hash()))
 			.map_err(|e| {
@@ -102,13 +177,206 @@ pub enum RandomXAlgorithmVersion {
 	V2,
 }
 
+impl RandomXAlgorithmVersion {
+	/// Algorithm version of a runtime algorithm identifier, if the node supports it.
+	pub fn from_identifier(identifier: [u8; 8]) -> Option<Self> {
+		match identifier {
+			kulupu_primitives::ALGORITHM_IDENTIFIER_V1 => Some(RandomXAlgorithmVersion::V1),
+			kulupu_primitives::ALGORITHM_IDENTIFIER_V2 => Some(RandomXAlgorithmVersion::V2),
+			_ => None,
+		}
+	}
+
+	/// Runtime algorithm identifier of the algorithm version.
+	pub fn identifier(&self) -> [u8; 8] {
+		match self {
+			RandomXAlgorithmVersion::V1 => kulupu_primitives::ALGORITHM_IDENTIFIER_V1,
+			RandomXAlgorithmVersion::V2 => kulupu_primitives::ALGORITHM_IDENTIFIER_V2,
+		}
+	}
+}
+
+/// Algorithm version required by the runtime at the given block.
+///
+/// Fails with `Error::UnsupportedAlgorithm` if the runtime requires an algorithm
+/// this node does not know about, for example after an algorithm-bumping
+/// runtime upgrade.
+pub fn algorithm_version<B, C>(
+	client: &C,
+	at: &BlockId<B>,
+) -> Result<RandomXAlgorithmVersion, Error<B>>
+where
+	B: BlockT<Hash = H256>,
+	C: ProvideRuntimeApi<B>,
+	C::Api: AlgorithmApi<B>,
+{
+	let identifier = client.runtime_api().identifier(at).map_err(|e| {
+		kulupu_pow_consensus::Error::Environment(format!(
+			"Fetching identifier from runtime failed: {:?}",
+			e
+		))
+	})?;
+
+	RandomXAlgorithmVersion::from_identifier(identifier)
+		.ok_or(Error::UnsupportedAlgorithm(identifier))
+}
+
+/// Work of a seal, recomputed by `seal_work`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SealWork {
+	/// The seal does not decode, or was not mined for the given pre-hash and
+	/// difficulty.
+	Invalid,
+	/// The V2 seal is not signed by the author of the pre-runtime digest.
+	BadSignature,
+	/// Work hash of the seal, not yet checked against any difficulty.
+	Valid(H256),
+}
+
+/// Work hash of a seal for a block built on `parent`, with the V2 signature
+/// checked against the author of `pre_digest`. Checking the work against the
+/// difficulty is left to the caller.
+pub fn seal_work<B, C>(
+	client: &C,
+	parent: &BlockId<B>,
+	pre_hash: &H256,
+	pre_digest: Option<&[u8]>,
+	seal: &RawSeal,
+	difficulty: Difficulty,
+) -> Result<SealWork, Error<B>>
+where
+	B: BlockT<Hash = H256>,
+	C: HeaderBackend<B> + ProvideRuntimeApi<B>,
+	C::Api: AlgorithmApi<B>,
+{
+	let version = algorithm_version(client, parent)?;
+	let key_hash = key_hash(client, parent)?;
+
+	let seal = match decode_seal::<app::Signature>(version.identifier(), &seal[..]) {
+		Ok(seal) => seal,
+		Err(_) => return Ok(SealWork::Invalid),
+	};
+
+	let work = match seal {
+		Seal::V1(seal) => {
+			let compute = ComputeV1 {
+				key_hash,
+				difficulty,
+				pre_hash: *pre_hash,
+				nonce: seal.nonce,
+			};
+
+			let (computed_seal, computed_work) = compute.seal_and_work(ComputeMode::Sync)?;
+			if computed_seal != seal {
+				return Ok(SealWork::Invalid);
+			}
+
+			computed_work
+		}
+		Seal::V2(seal) => {
+			let compute = ComputeV2 {
+				key_hash,
+				difficulty,
+				pre_hash: *pre_hash,
+				nonce: seal.nonce,
+			};
+
+			let author =
+				match pre_digest.map(|pre_digest| app::Public::decode(&mut &pre_digest[..])) {
+					Some(Ok(author)) => author,
+					_ => return Ok(SealWork::BadSignature),
+				};
+			if !compute.verify(&seal.signature, &author) {
+				return Ok(SealWork::BadSignature);
+			}
+
+			let (computed_seal, computed_work) =
+				compute.seal_and_work(seal.signature.clone(), ComputeMode::Sync)?;
+			if computed_seal != seal {
+				return Ok(SealWork::Invalid);
+			}
+
+			computed_work
+		}
+	};
+
+	Ok(SealWork::Valid(work))
+}
+
+/// Difficulty claimed by a seal, if its work meets it, checked without the
+/// runtime of the parent block. The seal is read as whichever algorithm
+/// version it decodes as exactly, and mined on `key_hash`. Used to verify
+/// header chains whose state is not available, such as of snapshots.
+pub fn detached_seal_difficulty(
+	key_hash: H256,
+	pre_hash: &H256,
+	pre_digest: Option<&[u8]>,
+	seal: &[u8],
+) -> Result<Option<Difficulty>, compute::Error> {
+	let (difficulty, work) = if let Ok(seal) = compute::SealV2::decode_all(seal) {
+		let compute = ComputeV2 {
+			key_hash,
+			difficulty: seal.difficulty,
+			pre_hash: *pre_hash,
+			nonce: seal.nonce,
+		};
+
+		let author = match pre_digest.map(|pre_digest| app::Public::decode(&mut &pre_digest[..])) {
+			Some(Ok(author)) => author,
+			_ => return Ok(None),
+		};
+		if !compute.verify(&seal.signature, &author) {
+			return Ok(None);
+		}
+
+		let (computed_seal, computed_work) =
+			compute.seal_and_work(seal.signature.clone(), ComputeMode::Sync)?;
+		if computed_seal != seal {
+			return Ok(None);
+		}
+
+		(seal.difficulty, computed_work)
+	} else if let Ok(seal) = compute::SealV1::decode_all(seal) {
+		let compute = ComputeV1 {
+			key_hash,
+			difficulty: seal.difficulty,
+			pre_hash: *pre_hash,
+			nonce: seal.nonce,
+		};
+
+		let (computed_seal, computed_work) = compute.seal_and_work(ComputeMode::Sync)?;
+		if computed_seal != seal {
+			return Ok(None);
+		}
+
+		(seal.difficulty, computed_work)
+	} else {
+		return Ok(None);
+	};
+
+	Ok(Some(difficulty).filter(|difficulty| is_valid_hash(&work, *difficulty)))
+}
+
 pub struct RandomXAlgorithm<C> {
 	client: Arc<C>,
+	assumed_valid: Option<u64>,
 }
 
 impl<C> RandomXAlgorithm<C> {
 	pub fn new(client: Arc<C>) -> Self {
-		Self { client }
+		Self {
+			client,
+			assumed_valid: None,
+		}
+	}
+
+	/// Skip computing the seals of blocks at or below `number`, only checking
+	/// that they decode. This is only sound together with a block import that
+	/// refuses chains not containing the trusted block at `number`, such as
+	/// [`checkpoint::CheckpointBlockImport::with_trusted`].
+	pub fn with_assumed_valid(mut self, number: u64) -> Self {
+		self.assumed_valid = Some(number);
+		self
 	}
 }
 
@@ -116,6 +384,7 @@ impl<C> Clone for RandomXAlgorithm<C> {
 	fn clone(&self) -> Self {
 		Self {
 			client: self.client.clone(),
+			assumed_valid: self.assumed_valid,
 		}
 	}
 }
@@ -170,25 +439,29 @@ where
 			))
 		})?;
 
-		let version = match version_raw {
-			kulupu_primitives::ALGORITHM_IDENTIFIER_V1 => RandomXAlgorithmVersion::V1,
-			kulupu_primitives::ALGORITHM_IDENTIFIER_V2 => RandomXAlgorithmVersion::V2,
-			_ => {
+		let key_hash = key_hash(self.client.as_ref(), parent)?;
+
+		let seal = match decode_seal::<app::Signature>(version_raw, &seal[..]) {
+			Ok(seal) => seal,
+			Err(SealError::UnknownAlgorithm(_)) => {
 				return Err(kulupu_pow_consensus::Error::<B>::Other(
 					"Unknown algorithm identifier".to_string(),
 				))
 			}
+			Err(SealError::Undecodable) => return Ok(false),
 		};
 
-		let key_hash = key_hash(self.client.as_ref(), parent)?;
-
-		match version {
-			RandomXAlgorithmVersion::V1 => {
-				let seal = match SealV1::decode(&mut &seal[..]) {
-					Ok(seal) => seal,
-					Err(_) => return Ok(false),
-				};
+		if let Some(assumed_valid) = self.assumed_valid {
+			let parent_header = parent_header(self.client.as_ref(), parent)?;
+			let parent_number =
+				UniqueSaturatedInto::<u64>::unique_saturated_into(*parent_header.number());
+			if parent_number < assumed_valid {
+				return Ok(true);
+			}
+		}
 
+		match seal {
+			Seal::V1(seal) => {
 				let compute = ComputeV1 {
 					key_hash,
 					difficulty,
@@ -210,12 +483,7 @@ where
 
 				Ok(true)
 			}
-			RandomXAlgorithmVersion::V2 => {
-				let seal = match SealV2::decode(&mut &seal[..]) {
-					Ok(seal) => seal,
-					Err(_) => return Ok(false),
-				};
-
+			Seal::V2(seal) => {
 				let compute = ComputeV2 {
 					key_hash,
 					difficulty,
@@ -228,6 +496,8 @@ where
 					None => return Ok(false),
 				};
 
+				// The author may be followed by a payout account, which the
+				// signature covers through the pre-hash.
 				let author = match app::Public::decode(&mut &pre_digest[..]) {
 					Ok(author) => author,
 					Err(_) => return Ok(false),
@@ -261,6 +531,8 @@ where
 {
 	Consensus(kulupu_pow_consensus::Error<B>),
 	Compute(compute::Error),
+	/// The runtime requires an algorithm identifier unknown to this node.
+	UnsupportedAlgorithm([u8; 8]),
 }
 
 impl<B> From<kulupu_pow_consensus::Error<B>> for Error<B>
@@ -281,10 +553,30 @@ where
 	}
 }
 
+/// Number of found seals kept in mining statistics.
+const MAX_RECENT_SEALS: usize = 10;
+
+/// A seal found by a mining thread.
+#[derive(Clone, Debug)]
+pub struct FoundSeal {
+	/// Pre-hash of the block the seal is for.
+	pub pre_hash: H256,
+	/// Difficulty the seal meets.
+	pub difficulty: Difficulty,
+	/// When the seal was found.
+	pub time: SystemTime,
+}
+
+/// Mining statistics and nonce allocation shared by all mining threads.
 pub struct Stats {
 	last_clear: Instant,
 	last_display: Instant,
 	round: u32,
+	thread_rounds: BTreeMap<usize, u32>,
+	hashrate: u32,
+	recent_seals: VecDeque<FoundSeal>,
+	nonce_base: U256,
+	next_nonce: u64,
 }
 
 impl Stats {
@@ -293,18 +585,120 @@ impl Stats {
 			last_clear: Instant::now(),
 			last_display: Instant::now(),
 			round: 0,
+			thread_rounds: BTreeMap::new(),
+			hashrate: 0,
+			recent_seals: VecDeque::new(),
+			nonce_base: U256::from(&H256::random_using(&mut thread_rng())[..]),
+			next_nonce: 0,
+		}
+	}
+
+	/// Claim `count` consecutive nonces, which no other thread sharing these
+	/// stats will be handed out. Returns the first of them.
+	///
+	/// Nonces start from a random base, so that nodes mining with the same key
+	/// do not duplicate work either.
+	pub fn claim_nonces(&mut self, count: u32) -> U256 {
+		let start = self
+			.nonce_base
+			.overflowing_add(U256::from(self.next_nonce))
+			.0;
+		self.next_nonce = self.next_nonce.wrapping_add(count as u64);
+		start
+	}
+
+	/// Local hashrate in hashes per second, as last displayed.
+	pub fn hashrate(&self) -> u32 {
+		self.hashrate
+	}
+
+	/// Most recently found seals, latest first.
+	pub fn recent_seals(&self) -> impl Iterator<Item = &FoundSeal> {
+		self.recent_seals.iter()
+	}
+
+	fn record_seal(&mut self, seal: FoundSeal) {
+		self.recent_seals.push_front(seal);
+		self.recent_seals.truncate(MAX_RECENT_SEALS);
+	}
+}
+
+/// Keys signing V2 seals of an author.
+pub enum AuthorPair {
+	/// The key of the author.
+	Single(app::Pair),
+	/// Keys whose joint public key is the author, all signing together.
+	Joint(Vec<app::Pair>),
+}
+
+impl AuthorPair {
+	/// Fetch the keys of `author` from the keystore. With `joint` keys, all of
+	/// them are fetched instead, and `author` must be their joint public key.
+	pub fn fetch(
+		keystore: &LocalKeystore,
+		author: &app::Public,
+		joint: &[app::Public],
+	) -> Result<Self, String> {
+		let fetch = |public: &app::Public| -> Result<app::Pair, String> {
+			keystore
+				.key_pair::<app::Pair>(public)
+				.map_err(|e| format!("Fetching mining key from keystore failed: {:?}", e))?
+				.ok_or_else(|| "Mining key not found in keystore".to_string())
+		};
+
+		if joint.is_empty() {
+			return Ok(Self::Single(fetch(author)?));
+		}
+		if joint::joint_public(joint).as_ref() != Some(author) {
+			return Err("Author is not the joint public key of the joint mining keys".to_string());
+		}
+		Ok(Self::Joint(
+			joint.iter().map(fetch).collect::<Result<_, _>>()?,
+		))
+	}
+
+	/// Sign V2 work with the keys.
+	pub fn sign(&self, compute: &ComputeV2) -> app::Signature {
+		match self {
+			Self::Single(pair) => compute.sign(pair),
+			Self::Joint(pairs) => compute.sign_joint(pairs),
 		}
 	}
 }
 
+/// Check that the keystore holds the keys of `author`, and that seals signed
+/// with them verify against `author`.
+pub fn check_author_key(
+	keystore: &LocalKeystore,
+	author: &app::Public,
+	joint: &[app::Public],
+) -> Result<(), String> {
+	let pair = AuthorPair::fetch(keystore, author, joint)?;
+
+	let compute = ComputeV2 {
+		key_hash: H256::random_using(&mut thread_rng()),
+		pre_hash: H256::random_using(&mut thread_rng()),
+		difficulty: Difficulty::one(),
+		nonce: H256::random_using(&mut thread_rng()),
+	};
+	let signature = pair.sign(&compute);
+	if !compute.verify(&signature, author) {
+		return Err("Mining key signature does not verify against the author".to_string());
+	}
+
+	Ok(())
+}
+
 pub fn mine<B, C>(
 	client: &C,
 	keystore: &LocalKeystore,
+	joint: &[app::Public],
 	parent: &BlockId<B>,
 	pre_hash: &H256,
 	pre_digest: Option<&[u8]>,
 	difficulty: Difficulty,
 	round: u32,
+	thread: usize,
 	stats: &Arc<Mutex<Stats>>,
 ) -> Result<Option<RawSeal>, Error<B>>
 where
@@ -312,27 +706,15 @@ where
 	C: HeaderBackend<B> + AuxStore + ProvideRuntimeApi<B>,
 	C::Api: DifficultyApi<B, Difficulty> + AlgorithmApi<B>,
 {
-	let version_raw = client.runtime_api().identifier(parent).map_err(|e| {
-		kulupu_pow_consensus::Error::Environment(format!(
-			"Fetching identifier from runtime failed: {:?}",
-			e
-		))
-	})?;
-
-	let version = match version_raw {
-		kulupu_primitives::ALGORITHM_IDENTIFIER_V1 => Ok(RandomXAlgorithmVersion::V1),
-		kulupu_primitives::ALGORITHM_IDENTIFIER_V2 => Ok(RandomXAlgorithmVersion::V2),
-		_ => Err(kulupu_pow_consensus::Error::<B>::Other(
-			"Unknown algorithm identifier".to_string(),
-		)),
-	}?;
-
-	let mut rng = SmallRng::from_rng(&mut thread_rng()).map_err(|e| {
-		kulupu_pow_consensus::Error::Environment(format!(
-			"Initialize RNG failed for mining: {:?}",
-			e
-		))
-	})?;
+	let version = algorithm_version(client, parent)?;
+
+	let mut next_nonce = stats.lock().claim_nonces(round);
+	let mut nonce = move || {
+		let mut nonce = H256::zero();
+		next_nonce.to_big_endian(&mut nonce[..]);
+		next_nonce = next_nonce.overflowing_add(U256::one()).0;
+		nonce
+	};
 	let key_hash = key_hash(client, parent)?;
 
 	let pre_digest = pre_digest.ok_or(kulupu_pow_consensus::Error::<B>::Other(
@@ -345,23 +727,15 @@ where
 		)
 	})?;
 
-	let pair = keystore
-		.key_pair::<app::Pair>(&author)
-		.map_err(|_| {
-			kulupu_pow_consensus::Error::<B>::Other(
-				"Unable to mine: fetch pair from author failed".to_string(),
-			)
-		})?
-		.ok_or(kulupu_pow_consensus::Error::<B>::Other(
-			"Unable to mine: key not found in keystore".to_string(),
-		))?;
+	let pair = AuthorPair::fetch(keystore, &author, joint)
+		.map_err(|e| kulupu_pow_consensus::Error::<B>::Other(format!("Unable to mine: {}", e)))?;
 
 	let maybe_seal = match version {
 		RandomXAlgorithmVersion::V1 => compute::loop_raw(
 			&key_hash,
 			ComputeMode::Mining,
 			|| {
-				let nonce = H256::random_using(&mut rng);
+				let nonce = nonce();
 
 				let compute = ComputeV1 {
 					key_hash,
@@ -386,7 +760,7 @@ where
 			&key_hash,
 			ComputeMode::Mining,
 			|| {
-				let nonce = H256::random_using(&mut rng);
+				let nonce = nonce();
 
 				let compute = ComputeV2 {
 					key_hash,
@@ -395,7 +769,7 @@ where
 					nonce,
 				};
 
-				let signature = compute.sign(&pair);
+				let signature = pair.sign(&compute);
 
 				(
 					compute.input(signature.clone()).encode(),
@@ -414,6 +788,14 @@ where
 		),
 	};
 
+	if let Ok(Some(_)) = &maybe_seal {
+		stats.lock().record_seal(FoundSeal {
+			pre_hash: *pre_hash,
+			difficulty,
+			time: SystemTime::now(),
+		});
+	}
+
 	let now = Instant::now();
 
 	let maybe_display = {
@@ -427,6 +809,7 @@ where
 			let mut ret = None;
 
 			stats.round += round;
+			*stats.thread_rounds.entry(thread).or_default() += round;
 			let duration = since_last_clear;
 
 			let clear = duration >= Duration::new(600, 0);
@@ -435,12 +818,14 @@ where
 
 			if display {
 				stats.last_display = now;
-				ret = Some((duration, stats.round));
+				stats.hashrate = stats.round / duration.as_secs() as u32;
+				ret = Some((duration, stats.round, stats.thread_rounds.clone()));
 			}
 
 			if clear {
 				stats.last_clear = now;
 				stats.round = 0;
+				stats.thread_rounds.clear();
 			}
 
 			ret
@@ -454,8 +839,18 @@ where
 		}
 	};
 
-	if let Some((duration, round)) = maybe_display {
+	if let Some((duration, round, thread_rounds)) = maybe_display {
 		let hashrate = round / duration.as_secs() as u32;
+
+		for (thread, round) in thread_rounds {
+			debug!(
+				target: "kulupu-pow",
+				"Thread {} hashrate: {} H/s",
+				thread,
+				round / duration.as_secs() as u32,
+			);
+		}
+
 		let network_hashrate = difficulty / U256::from(60);
 
 		if hashrate == 0 {
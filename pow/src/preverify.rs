@@ -0,0 +1,214 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Kulupu.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Kulupu is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Kulupu is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Kulupu. If not, see <http://www.gnu.org/licenses/>.
+
+//! Parallel seal verification for initial sync.
+//!
+//! Blocks are imported one after another, and the seal of each is verified
+//! with a RandomX hash on the import thread. [`PreverifyingQueue`] wraps an
+//! import queue, and hands the seals of each batch of incoming blocks to a
+//! pool of worker threads first, which compute their hashes with
+//! [`compute::precompute`]. Import stays sequential and verifies seals as
+//! before, taking the hashes computed by the workers where available.
+
+use crate::{
+	compute::{self, Calculation, SealV1, SealV2},
+	key_hash, key_number,
+};
+use codec::DecodeAll;
+use log::*;
+use parking_lot::Mutex;
+use sc_client_api::blockchain::HeaderBackend;
+use sc_consensus::import_queue::{ImportQueue, IncomingBlock, Link, Origin};
+use sp_consensus::BlockOrigin;
+use sp_consensus_pow::POW_ENGINE_ID;
+use sp_core::H256;
+use sp_runtime::{
+	generic::{BlockId, DigestItem},
+	traits::{Block as BlockT, Header as HeaderT, NumberFor, UniqueSaturatedInto},
+	Justifications,
+};
+use std::{
+	marker::PhantomData,
+	sync::{mpsc, Arc},
+	task::Context,
+	thread,
+};
+
+/// Seal whose hash is computed ahead of verification.
+struct Job {
+	key_hash: H256,
+	pre_hash: H256,
+	seal: Vec<u8>,
+}
+
+impl Job {
+	fn run(self) {
+		let calculation = |difficulty, nonce| Calculation {
+			difficulty,
+			pre_hash: self.pre_hash,
+			nonce,
+		};
+
+		// The algorithm version is only known once the parent is imported, so
+		// the seal is read as whichever version it decodes as exactly.
+		let result = if let Ok(seal) = SealV2::decode_all(&self.seal[..]) {
+			compute::precompute(
+				&self.key_hash,
+				&(calculation(seal.difficulty, seal.nonce), seal.signature),
+			)
+		} else if let Ok(seal) = SealV1::decode_all(&self.seal[..]) {
+			compute::precompute(&self.key_hash, &calculation(seal.difficulty, seal.nonce))
+		} else {
+			return;
+		};
+
+		if let Err(err) = result {
+			debug!(
+				target: "kulupu-pow",
+				"Computing seal hash of {} ahead of import failed: {}",
+				self.pre_hash,
+				err.description(),
+			);
+		}
+	}
+}
+
+/// Import queue computing the seal hashes of incoming blocks on worker threads
+/// ahead of their import by `inner`.
+pub struct PreverifyingQueue<B, C, Q> {
+	inner: Q,
+	client: Arc<C>,
+	/// Jobs for the workers, `None` without workers.
+	jobs: Option<Mutex<mpsc::Sender<Job>>>,
+	_marker: PhantomData<B>,
+}
+
+impl<B, C, Q> PreverifyingQueue<B, C, Q> {
+	/// Wrap `inner`, computing seal hashes on `workers` threads. Blocks are
+	/// passed through as they are without workers.
+	pub fn new(inner: Q, client: Arc<C>, workers: usize) -> Self {
+		let jobs = if workers > 0 {
+			let (jobs, receiver) = mpsc::channel::<Job>();
+			let receiver = Arc::new(Mutex::new(receiver));
+			for _ in 0..workers {
+				let receiver = receiver.clone();
+				thread::spawn(move || loop {
+					let job = receiver.lock().recv();
+					match job {
+						Ok(job) => job.run(),
+						Err(_) => break,
+					}
+				});
+			}
+
+			Some(Mutex::new(jobs))
+		} else {
+			None
+		};
+
+		Self {
+			inner,
+			client,
+			jobs,
+			_marker: PhantomData,
+		}
+	}
+}
+
+impl<B, C, Q> PreverifyingQueue<B, C, Q>
+where
+	B: BlockT<Hash = H256>,
+	C: HeaderBackend<B>,
+{
+	/// Hand the seals of `blocks` to the workers. Only the chain of blocks on
+	/// the key hash of the first one is covered, as later key hashes may be
+	/// hashes of blocks not imported yet.
+	fn precompute(&self, jobs: &mpsc::Sender<Job>, blocks: &[IncomingBlock<B>]) {
+		let first = match blocks.first().and_then(|block| block.header.as_ref()) {
+			Some(first) => first,
+			None => return,
+		};
+		let number = UniqueSaturatedInto::<u64>::unique_saturated_into(*first.number());
+		let first_key_number = match number.checked_sub(1) {
+			Some(parent_number) => key_number(parent_number),
+			None => return,
+		};
+		// The parent of the first block is not imported yet either.
+		let key_hash = match key_hash(self.client.as_ref(), &BlockId::Hash(*first.parent_hash())) {
+			Ok(key_hash) => key_hash,
+			Err(_) => return,
+		};
+
+		let mut parent_hash = *first.parent_hash();
+		for block in blocks {
+			let mut header = match &block.header {
+				Some(header) if *header.parent_hash() == parent_hash => header.clone(),
+				_ => break,
+			};
+			let number = UniqueSaturatedInto::<u64>::unique_saturated_into(*header.number());
+			match number.checked_sub(1) {
+				Some(parent_number) if key_number(parent_number) == first_key_number => (),
+				_ => break,
+			}
+			parent_hash = header.hash();
+
+			let seal = match header.digest_mut().pop() {
+				Some(DigestItem::Seal(id, seal)) if id == POW_ENGINE_ID => seal,
+				_ => break,
+			};
+			let job = Job {
+				key_hash,
+				pre_hash: header.hash(),
+				seal,
+			};
+			if jobs.send(job).is_err() {
+				break;
+			}
+		}
+	}
+}
+
+impl<B, C, Q> ImportQueue<B> for PreverifyingQueue<B, C, Q>
+where
+	B: BlockT<Hash = H256>,
+	C: HeaderBackend<B> + Send + Sync,
+	Q: ImportQueue<B>,
+{
+	fn import_blocks(&mut self, origin: BlockOrigin, blocks: Vec<IncomingBlock<B>>) {
+		if let Some(jobs) = &self.jobs {
+			self.precompute(&jobs.lock(), &blocks);
+		}
+
+		self.inner.import_blocks(origin, blocks);
+	}
+
+	fn import_justifications(
+		&mut self,
+		who: Origin,
+		hash: B::Hash,
+		number: NumberFor<B>,
+		justifications: Justifications,
+	) {
+		self.inner
+			.import_justifications(who, hash, number, justifications);
+	}
+
+	fn poll_actions(&mut self, cx: &mut Context, link: &mut dyn Link<B>) {
+		self.inner.poll_actions(cx, link);
+	}
+}
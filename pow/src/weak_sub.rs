@@ -19,6 +19,10 @@
 
 use kulupu_pow_consensus::{PowAlgorithm, PowAux};
 use log::*;
+use parking_lot::Mutex;
+use prometheus_endpoint::{
+	register, Gauge, Histogram, HistogramOpts, PrometheusError, Registry, U64,
+};
 use sc_client_api::{AuxStore, BlockOf};
 use sc_consensus::{
 	BlockCheckParams, BlockImport, BlockImportParams, ForkChoiceStrategy, ImportResult,
@@ -28,7 +32,16 @@ use sp_blockchain::{well_known_cache_keys::Id as CacheKeyId, HeaderMetadata};
 use sp_consensus::{Error as ConsensusError, SelectChain};
 use sp_core::U256;
 use sp_runtime::traits::{Block as BlockT, Header as HeaderT};
-use std::{collections::HashMap, fmt::Debug, marker::PhantomData, sync::Arc};
+use std::{
+	collections::{HashMap, VecDeque},
+	fmt::Debug,
+	marker::PhantomData,
+	sync::Arc,
+	time::Instant,
+};
+
+/// Number of recent imports the rolling maximum of retracted length is computed over.
+const ROLLING_MAX_WINDOW: usize = 1024;
 
 /// Parameters passed to decision function of whether to block the reorg.
 pub struct WeakSubjectiveParams {
@@ -93,6 +106,80 @@ impl WeakSubjectiveAlgorithm for ExponentialWeakSubjectiveAlgorithm {
 	}
 }
 
+/// Whether a reorg retracting `retracted_len` blocks goes beyond the absolute
+/// maximum reorg depth `limit`, if any.
+pub fn exceeds_max_reorg_depth(limit: Option<usize>, retracted_len: usize) -> bool {
+	limit.map_or(false, |limit| retracted_len > limit)
+}
+
+/// Prometheus metrics of observed reorg depths.
+#[derive(Clone)]
+pub struct WeakSubjectiveMetrics {
+	retracted_len: Histogram,
+	max_retracted_len: Gauge<U64>,
+	recent_retracted_lens: Arc<Mutex<VecDeque<usize>>>,
+}
+
+impl WeakSubjectiveMetrics {
+	/// Register the metrics in the given registry.
+	pub fn register(registry: &Registry) -> Result<Self, PrometheusError> {
+		Ok(Self {
+			retracted_len: register(
+				Histogram::with_opts(
+					HistogramOpts::new(
+						"kulupu_weak_subjectivity_retracted_len",
+						"Retracted length of the route from best block to the parent of imported blocks",
+					)
+					.buckets(vec![
+						0.0, 1.0, 2.0, 3.0, 5.0, 10.0, 20.0, 30.0, 50.0, 100.0, 200.0, 500.0, 1000.0,
+					]),
+				)?,
+				registry,
+			)?,
+			max_retracted_len: register(
+				Gauge::new(
+					"kulupu_weak_subjectivity_max_retracted_len",
+					"Maximum retracted length observed over the recent imported blocks",
+				)?,
+				registry,
+			)?,
+			recent_retracted_lens: Arc::new(Mutex::new(VecDeque::with_capacity(
+				ROLLING_MAX_WINDOW,
+			))),
+		})
+	}
+
+	/// Record an observed retracted length.
+	pub fn observe(&self, retracted_len: usize) {
+		self.retracted_len.observe(retracted_len as f64);
+
+		let mut recent = self.recent_retracted_lens.lock();
+		if recent.len() >= ROLLING_MAX_WINDOW {
+			recent.pop_front();
+		}
+		recent.push_back(retracted_len);
+
+		let max = recent.iter().copied().max().unwrap_or(0);
+		self.max_retracted_len.set(max as u64);
+	}
+}
+
+/// Time of the last reorg blocked by weak subjectivity, shared by the clones of
+/// a block import.
+#[derive(Clone, Default)]
+pub struct BlockedReorgs(Arc<Mutex<Option<Instant>>>);
+
+impl BlockedReorgs {
+	/// When the last reorg was blocked, if any was.
+	pub fn last(&self) -> Option<Instant> {
+		*self.0.lock()
+	}
+
+	fn record(&self) {
+		*self.0.lock() = Some(Instant::now());
+	}
+}
+
 /// Block import for weak subjectivity. It must be combined with a PoW block import.
 pub struct WeakSubjectiveBlockImport<B: BlockT, I, C, S, Pow, Reorg> {
 	inner: I,
@@ -101,6 +188,9 @@ pub struct WeakSubjectiveBlockImport<B: BlockT, I, C, S, Pow, Reorg> {
 	pow_algorithm: Pow,
 	reorg_algorithm: Reorg,
 	enabled: bool,
+	max_reorg_depth: Option<usize>,
+	metrics: Option<WeakSubjectiveMetrics>,
+	blocked_reorgs: BlockedReorgs,
 	_marker: PhantomData<B>,
 }
 
@@ -115,6 +205,9 @@ impl<B: BlockT, I: Clone, C, S: Clone, Pow: Clone, Reorg: Clone> Clone
 			pow_algorithm: self.pow_algorithm.clone(),
 			reorg_algorithm: self.reorg_algorithm.clone(),
 			enabled: self.enabled.clone(),
+			max_reorg_depth: self.max_reorg_depth,
+			metrics: self.metrics.clone(),
+			blocked_reorgs: self.blocked_reorgs.clone(),
 			_marker: PhantomData,
 		}
 	}
@@ -139,7 +232,21 @@ where
 		reorg_algorithm: Reorg,
 		select_chain: S,
 		enabled: bool,
+		registry: Option<&Registry>,
 	) -> Self {
+		let metrics = match registry.map(WeakSubjectiveMetrics::register) {
+			Some(Ok(metrics)) => Some(metrics),
+			Some(Err(e)) => {
+				warn!(
+					target: "kulupu-pow",
+					"Failed to register weak subjectivity metrics: {:?}",
+					e,
+				);
+				None
+			}
+			None => None,
+		};
+
 		Self {
 			inner,
 			client,
@@ -147,9 +254,25 @@ where
 			reorg_algorithm,
 			select_chain,
 			enabled,
+			max_reorg_depth: None,
+			metrics,
+			blocked_reorgs: BlockedReorgs::default(),
 			_marker: PhantomData,
 		}
 	}
+
+	/// Reject blocks whose parent is more than `depth` blocks away from the
+	/// best chain, however much work their fork has. This applies even if
+	/// weak subjectivity is disabled.
+	pub fn with_max_reorg_depth(mut self, depth: usize) -> Self {
+		self.max_reorg_depth = Some(depth);
+		self
+	}
+
+	/// Reorgs blocked by this block import and its clones.
+	pub fn blocked_reorgs(&self) -> BlockedReorgs {
+		self.blocked_reorgs.clone()
+	}
 }
 
 #[async_trait::async_trait]
@@ -179,7 +302,8 @@ where
 		mut block: BlockImportParams<B, Self::Transaction>,
 		new_cache: HashMap<CacheKeyId, Vec<u8>>,
 	) -> Result<ImportResult, Self::Error> {
-		if self.enabled && block.fork_choice != Some(ForkChoiceStrategy::Custom(false)) {
+		let enabled = self.enabled || self.max_reorg_depth.is_some();
+		if enabled && block.fork_choice != Some(ForkChoiceStrategy::Custom(false)) {
 			let best_header = self
 				.select_chain
 				.best_chain()
@@ -193,40 +317,59 @@ where
 					.map_err(|e| format!("Find route from best failed: {:?}", e))?;
 
 			let retracted_len = route_from_best.retracted().len();
+			if let Some(metrics) = &self.metrics {
+				metrics.observe(retracted_len);
+			}
+
+			if exceeds_max_reorg_depth(self.max_reorg_depth, retracted_len) {
+				warn!(
+					target: "kulupu-pow",
+					"Rejected a block beyond the maximum reorg depth. Retracted len: {}",
+					retracted_len,
+				);
+				self.blocked_reorgs.record();
+				return Err(ConsensusError::ClientImport(format!(
+					"Reorg retracting {} blocks is beyond the maximum reorg depth",
+					retracted_len,
+				)));
+			}
+
+			if self.enabled {
+				let best_difficulty_aux =
+					PowAux::<U256>::read::<_, B>(self.client.as_ref(), &best_hash)?;
+				let parent_difficulty_aux =
+					PowAux::<U256>::read::<_, B>(self.client.as_ref(), &parent_hash)?;
+				let common_difficulty_aux = PowAux::<U256>::read::<_, B>(
+					self.client.as_ref(),
+					&route_from_best.common_block().hash,
+				)?;
+
+				let best_total_difficulty = best_difficulty_aux.total_difficulty;
+				let common_total_difficulty = common_difficulty_aux.total_difficulty;
+				let new_total_difficulty = parent_difficulty_aux.total_difficulty
+					+ self.pow_algorithm.difficulty(parent_hash)?;
 
-			let best_difficulty_aux =
-				PowAux::<U256>::read::<_, B>(self.client.as_ref(), &best_hash)?;
-			let parent_difficulty_aux =
-				PowAux::<U256>::read::<_, B>(self.client.as_ref(), &parent_hash)?;
-			let common_difficulty_aux = PowAux::<U256>::read::<_, B>(
-				self.client.as_ref(),
-				&route_from_best.common_block().hash,
-			)?;
-
-			let best_total_difficulty = best_difficulty_aux.total_difficulty;
-			let common_total_difficulty = common_difficulty_aux.total_difficulty;
-			let new_total_difficulty = parent_difficulty_aux.total_difficulty
-				+ self.pow_algorithm.difficulty(parent_hash)?;
-
-			let params = WeakSubjectiveParams {
-				best_total_difficulty,
-				common_total_difficulty,
-				new_total_difficulty,
-				retracted_len,
-			};
-
-			match self.reorg_algorithm.weak_subjective_decide(params) {
-				WeakSubjectiveDecision::BlockReorg => {
-					warn!(
-						target: "kulupu-pow",
-						"Weak subjectivity blocked a deep chain reorg. Retracted len: {}, current head total difficulty: {}, reorg total difficulty: {}",
-						retracted_len,
-						best_total_difficulty,
-						new_total_difficulty,
-					);
-					block.fork_choice = Some(ForkChoiceStrategy::Custom(false));
+				let params = WeakSubjectiveParams {
+					best_total_difficulty,
+					common_total_difficulty,
+					new_total_difficulty,
+					retracted_len,
+				};
+
+				match self.reorg_algorithm.weak_subjective_decide(params) {
+					WeakSubjectiveDecision::BlockReorg => {
+						warn!(
+							target: "kulupu-pow",
+							"Weak subjectivity blocked a deep chain reorg. Retracted len: {}, current head total difficulty: {}, reorg total difficulty: {}",
+							retracted_len,
+							best_total_difficulty,
+							new_total_difficulty,
+						);
+						block.fork_choice = Some(ForkChoiceStrategy::Custom(false));
+						self.blocked_reorgs.record();
+					}
+					WeakSubjectiveDecision::Continue => (),
 				}
-				WeakSubjectiveDecision::Continue => (),
 			}
 		}
 
@@ -271,4 +414,12 @@ mod tests {
 		check(U256::from(7000), U256::from(8000), 31, Continue);
 		check(U256::from(7000), U256::from(8000), 40, BlockReorg);
 	}
+
+	#[test]
+	fn max_reorg_depth_rejects_only_deeper_reorgs() {
+		assert!(!exceeds_max_reorg_depth(None, 10_000));
+		assert!(!exceeds_max_reorg_depth(Some(100), 0));
+		assert!(!exceeds_max_reorg_depth(Some(100), 100));
+		assert!(exceeds_max_reorg_depth(Some(100), 101));
+	}
 }
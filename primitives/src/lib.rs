@@ -20,7 +20,9 @@
 
 #![cfg_attr(not(feature = "std"), no_std)]
 
+use codec::{Codec, Decode, Encode};
 use sp_api::decl_runtime_apis;
+use sp_std::vec::Vec;
 
 pub type Difficulty = sp_core::U256;
 
@@ -50,6 +52,9 @@ pub const DIFFICULTY_DAMP_FACTOR: u128 = 3;
 pub const MIN_DIFFICULTY: u128 = DIFFICULTY_DAMP_FACTOR;
 /// Maximum difficulty.
 pub const MAX_DIFFICULTY: u128 = u128::max_value();
+/// Limit of block solve times in the LWMA difficulty adjustment, in target
+/// block times, to bound the effect of bad timestamps.
+pub const LWMA_SOLVE_TIME_LIMIT: u128 = 6;
 
 /// Value of 1 KLP.
 pub const DOLLARS: u128 = 1_000_000_000_000;
@@ -72,8 +77,304 @@ pub const DAYS: u32 = 24 * HOURS;
 pub const ALGORITHM_IDENTIFIER_V1: [u8; 8] = *b"randomx1";
 pub const ALGORITHM_IDENTIFIER_V2: [u8; 8] = *b"randomx2";
 
+/// Seal of the V1 algorithm.
+#[derive(Encode, Decode, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct SealV1 {
+	/// Difficulty the seal was mined at.
+	pub difficulty: Difficulty,
+	/// Nonce of the seal.
+	pub nonce: sp_core::H256,
+}
+
+/// Seal of the V2 algorithm, additionally signed by the block author.
+#[derive(Encode, Decode, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct SealV2<Signature> {
+	/// Difficulty the seal was mined at.
+	pub difficulty: Difficulty,
+	/// Nonce of the seal.
+	pub nonce: sp_core::H256,
+	/// Signature of the author over the pre-hash, difficulty and nonce.
+	pub signature: Signature,
+}
+
+/// Seal of any algorithm version.
+#[derive(Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum Seal<Signature> {
+	/// Seal of the V1 algorithm.
+	V1(SealV1),
+	/// Seal of the V2 algorithm.
+	V2(SealV2<Signature>),
+}
+
+/// Error decoding a seal.
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum SealError {
+	/// The algorithm identifier is not known.
+	UnknownAlgorithm([u8; 8]),
+	/// The seal does not decode as a seal of its algorithm.
+	Undecodable,
+}
+
+/// Decode a seal of the algorithm with the given identifier. New algorithm
+/// versions only need to be added here.
+pub fn decode_seal<Signature: Decode>(
+	identifier: [u8; 8],
+	mut seal: &[u8],
+) -> Result<Seal<Signature>, SealError> {
+	match identifier {
+		ALGORITHM_IDENTIFIER_V1 => SealV1::decode(&mut seal).map(Seal::V1),
+		ALGORITHM_IDENTIFIER_V2 => SealV2::decode(&mut seal).map(Seal::V2),
+		_ => return Err(SealError::UnknownAlgorithm(identifier)),
+	}
+	.map_err(|_| SealError::Undecodable)
+}
+
+/// Reward and mints in effect from a block on.
+#[derive(Encode, Decode, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct EmissionPoint<BlockNumber, AccountId, Balance> {
+	/// Block the reward and mints take effect at.
+	pub start: BlockNumber,
+	/// Reward of each block.
+	pub reward: Balance,
+	/// Mints of each block.
+	pub mints: Vec<(AccountId, Balance)>,
+}
+
+/// Reward and mint storage of the rewards module.
+#[derive(Encode, Decode, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct RewardsInfo<BlockNumber, AccountId, Balance> {
+	/// Current reward of each block.
+	pub reward: Balance,
+	/// Planned reward changes, by the block they take effect at.
+	pub reward_changes: Vec<(BlockNumber, Balance)>,
+	/// Current mints of each block.
+	pub mints: Vec<(AccountId, Balance)>,
+	/// Planned mint changes, by the block they take effect at.
+	pub mint_changes: Vec<(BlockNumber, Vec<(AccountId, Balance)>)>,
+}
+
+/// Reward of a block, as paid to its author.
+#[derive(Encode, Decode, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct BlockReward<AccountId, Balance> {
+	/// Author of the block, or its payout account.
+	pub author: AccountId,
+	/// Block reward, including any parts split off to payout destinations.
+	pub reward: Balance,
+	/// Transaction tips of the block.
+	pub tips: Balance,
+}
+
+/// Storage migrations run by the last runtime upgrade.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct MigrationReport {
+	/// Spec version of the runtime upgrade that ran the migrations.
+	pub spec_version: u32,
+	/// Names of the migrations, in the order they ran, with their weights.
+	pub migrations: Vec<(Vec<u8>, u64)>,
+}
+
+/// A runtime upgrade announced ahead of its activation.
+#[derive(Encode, Decode, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct UpgradeAnnouncement<Hash, BlockNumber> {
+	/// Hash of the runtime code of the upgrade.
+	pub code_hash: Hash,
+	/// Spec version of the upgraded runtime.
+	pub spec_version: u32,
+	/// Block the upgrade is expected to be in effect from.
+	pub activation: BlockNumber,
+}
+
+/// Balance an account can back votes with.
+#[derive(Encode, Decode, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct VotableBalance<Balance> {
+	/// Balance that can back democracy votes and council election votes. Vote
+	/// locks overlap other balance locks, so this is the whole free balance.
+	pub votable: Balance,
+	/// Part of the votable balance locked as mining rewards.
+	pub reward_locked: Balance,
+}
+
+/// Balances of an account locked and transferable at a block.
+#[derive(Encode, Decode, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct LiquidityPoint<BlockNumber, Balance> {
+	/// Block the balances are at.
+	pub at: BlockNumber,
+	/// Balance still locked by reward locks.
+	pub reward_locked: Balance,
+	/// Balance still locked by vesting schedules.
+	pub vesting_locked: Balance,
+	/// Balance locked by lockdrop locks.
+	pub lockdrop_locked: Balance,
+	/// Balance locked by any other lock, such as a governance lock.
+	pub other_locked: Balance,
+	/// Balance not locked against transfers. Locks overlap, so this is the
+	/// free balance less the largest lock.
+	pub transferable: Balance,
+}
+
+/// Status of the treasury.
+#[derive(Encode, Decode, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct TreasuryStatus<Balance, BlockNumber> {
+	/// Spendable balance of the treasury.
+	pub balance: Balance,
+	/// Block of the next spend period, which pays out approved proposals.
+	pub next_spend_period: BlockNumber,
+	/// Number of proposals awaiting approval.
+	pub pending_proposals: u32,
+	/// Number of approved proposals awaiting payout.
+	pub approved_proposals: u32,
+	/// Balance burned at the next spend period, if nothing changes until then.
+	pub projected_burn: Balance,
+}
+
+/// Inherents of a block, decoded for debugging.
+#[derive(Encode, Decode, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct BlockInherents<AccountId> {
+	/// Timestamp set by the block, in milliseconds.
+	pub timestamp: Option<u64>,
+	/// Author of the block, or its payout account, from its pre-runtime digest.
+	pub author: Option<AccountId>,
+	/// Other unsigned extrinsics of the block, as encoded calls.
+	pub other: Vec<Vec<u8>>,
+}
+
 decl_runtime_apis! {
 	pub trait AlgorithmApi {
 		fn identifier() -> [u8; 8];
 	}
+
+	/// Fee context of a block, used for fee estimation.
+	pub trait FeeApi {
+		/// Fee multiplier for the next block, as the inner value of a `FixedU128`.
+		fn next_fee_multiplier() -> u128;
+		/// Fullness of the normal dispatch class of the block, in parts per billion.
+		fn block_fullness() -> u32;
+	}
+
+	/// Difficulty history of recent blocks.
+	pub trait DifficultyHistoryApi {
+		/// Difficulties and timestamps of recent blocks, from earliest to latest,
+		/// ending with the block the API is called at.
+		fn past_difficulties_and_timestamps() -> Vec<(Difficulty, u64)>;
+	}
+
+	/// Emission schedule of block rewards and mints.
+	pub trait RewardScheduleApi<BlockNumber, AccountId, Balance> where
+		BlockNumber: Codec,
+		AccountId: Codec,
+		Balance: Codec,
+	{
+		/// Reward and mints in effect at the current block, followed by those
+		/// taking effect at each planned change.
+		fn reward_schedule() -> Vec<EmissionPoint<BlockNumber, AccountId, Balance>>;
+	}
+
+	/// Reward and mint storage, for wallets and explorers.
+	pub trait RewardsApi<BlockNumber, AccountId, Balance> where
+		BlockNumber: Codec,
+		AccountId: Codec,
+		Balance: Codec,
+	{
+		/// `Reward`, `RewardChanges`, `Mints` and `MintChanges` as stored.
+		fn rewards_info() -> RewardsInfo<BlockNumber, AccountId, Balance>;
+	}
+
+	/// Rewards of past blocks, for explorers.
+	pub trait RewardHistoryApi<AccountId, Balance> where
+		AccountId: Codec,
+		Balance: Codec,
+	{
+		/// Reward of the block the API is called at, read from its events.
+		/// `None` if the block had no author.
+		fn block_reward() -> Option<BlockReward<AccountId, Balance>>;
+	}
+
+	/// Balance claims of past eras.
+	pub trait EraClaimsApi<AccountId, Balance> where
+		AccountId: Codec,
+		Balance: Codec,
+	{
+		/// Balance of `who` at the end of era `era`, if `proof` is a valid
+		/// storage proof of it against the era's final state root.
+		fn era_balance(era: u32, who: AccountId, proof: Vec<Vec<u8>>) -> Option<Balance>;
+	}
+
+	/// Announcements of upcoming runtime upgrades.
+	pub trait UpgradeAnnouncementApi<Hash, BlockNumber> where
+		Hash: Codec,
+		BlockNumber: Codec,
+	{
+		/// The announced runtime upgrade, if any.
+		fn upgrade_announcement() -> Option<UpgradeAnnouncement<Hash, BlockNumber>>;
+	}
+
+	/// Storage migrations of runtime upgrades.
+	pub trait MigrationsApi {
+		/// Migrations run by the last runtime upgrade.
+		fn migration_report() -> MigrationReport;
+	}
+
+	/// Transferability of balances, for exchange deposit policies.
+	pub trait TransferableApi<AccountId, Balance, BlockNumber> where
+		AccountId: Codec,
+		Balance: Codec,
+		BlockNumber: Codec,
+	{
+		/// Whether `who` could transfer `amount` at block `at`, once its reward
+		/// locks and vesting schedules expiring by then are unlocked. Other
+		/// locks, such as lockdrop locks, are assumed to stay.
+		fn is_transferable(who: AccountId, amount: Balance, at: BlockNumber) -> bool;
+	}
+
+	/// Liquidity of balances over time, for wallets.
+	pub trait LiquidityApi<AccountId, Balance, BlockNumber> where
+		AccountId: Codec,
+		Balance: Codec,
+		BlockNumber: Codec,
+	{
+		/// Liquidity of `who` at the current block, and at each later block
+		/// one of its reward locks expires or one of its vesting schedules
+		/// starts or ends releasing. Vesting releases linearly in between.
+		/// Lockdrop and other locks are assumed to stay.
+		fn liquidity_timeline(who: AccountId) -> Vec<LiquidityPoint<BlockNumber, Balance>>;
+	}
+
+	/// Voting power of balances, for wallets.
+	pub trait VotableBalanceApi<AccountId, Balance> where
+		AccountId: Codec,
+		Balance: Codec,
+	{
+		/// Balance `who` can vote with, including locked mining rewards.
+		fn votable_balance(who: AccountId) -> VotableBalance<Balance>;
+	}
+
+	/// Treasury status, for governance dashboards.
+	pub trait TreasuryApi<Balance, BlockNumber> where
+		Balance: Codec,
+		BlockNumber: Codec,
+	{
+		/// Status of the treasury at the block the API is called at.
+		fn treasury_status() -> TreasuryStatus<Balance, BlockNumber>;
+	}
+
+	/// Inherents of blocks, for debugging inherent check failures.
+	pub trait BlockInherentsApi<AccountId> where
+		AccountId: Codec,
+	{
+		/// Inherents of `block`. Called at the parent of `block`.
+		fn block_inherents(block: Block) -> BlockInherents<AccountId>;
+	}
 }
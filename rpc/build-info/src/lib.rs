@@ -0,0 +1,122 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Kulupu.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Kulupu is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Kulupu is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Kulupu. If not, see <http://www.gnu.org/licenses/>.
+
+//! Build info RPC.
+//!
+//! `kulupu_buildInfo` returns how the node was built, together with the hash
+//! of the on-chain runtime code, so that operators can check that all nodes of
+//! a fleet run the same build. Nodes built against different RandomX versions
+//! can disagree on seal validity.
+
+#![warn(missing_docs)]
+
+use jsonrpc_core::{Error, ErrorCode, Result};
+use jsonrpc_derive::rpc;
+use sc_client_api::{Backend, StorageProvider};
+use serde::{Deserialize, Serialize};
+use sp_blockchain::HeaderBackend;
+use sp_core::{hashing::blake2_256, storage::well_known_keys, storage::StorageKey, H256};
+use sp_runtime::{generic::BlockId, traits::Block as BlockT};
+use std::{fmt::Debug, marker::PhantomData, sync::Arc};
+
+/// Error code for failed build info queries.
+const BUILD_INFO_ERROR: i64 = 1;
+
+/// How the node was built.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BuildInfo {
+	/// Node version, including the commit it was built from.
+	pub version: String,
+	/// Cargo profile the node was built with.
+	pub profile: String,
+	/// Enabled cargo features.
+	pub features: Vec<String>,
+	/// Commit of the RandomX library the node was built against.
+	pub randomx_commit: String,
+}
+
+/// Build info of the node and the runtime code at a block.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeBuildInfo {
+	/// How the node was built.
+	#[serde(flatten)]
+	pub build: BuildInfo,
+	/// Blake2-256 hash of the on-chain runtime code.
+	pub runtime_code_hash: H256,
+}
+
+/// Build info RPC methods.
+#[rpc]
+pub trait BuildInfoApi<BlockHash> {
+	/// Build info of the node, with the runtime code hash at block `at`, which
+	/// defaults to the best block.
+	#[rpc(name = "kulupu_buildInfo")]
+	fn build_info(&self, at: Option<BlockHash>) -> Result<NodeBuildInfo>;
+}
+
+fn error<E: Debug>(message: &str, err: E) -> Error {
+	Error {
+		code: ErrorCode::ServerError(BUILD_INFO_ERROR),
+		message: message.into(),
+		data: Some(format!("{:?}", err).into()),
+	}
+}
+
+/// Build info RPC implementation.
+pub struct BuildInfoRpc<C, Block, B> {
+	client: Arc<C>,
+	build: BuildInfo,
+	_marker: PhantomData<(Block, B)>,
+}
+
+impl<C, Block, B> BuildInfoRpc<C, Block, B> {
+	/// Create a new build info RPC.
+	pub fn new(client: Arc<C>, build: BuildInfo) -> Self {
+		Self {
+			client,
+			build,
+			_marker: Default::default(),
+		}
+	}
+}
+
+impl<C, Block, B> BuildInfoApi<Block::Hash> for BuildInfoRpc<C, Block, B>
+where
+	Block: BlockT,
+	B: Backend<Block> + Send + Sync + 'static,
+	C: StorageProvider<Block, B> + HeaderBackend<Block> + Send + Sync + 'static,
+{
+	fn build_info(&self, at: Option<Block::Hash>) -> Result<NodeBuildInfo> {
+		let at = at.unwrap_or_else(|| self.client.info().best_hash);
+		let code = self
+			.client
+			.storage(
+				&BlockId::Hash(at),
+				&StorageKey(well_known_keys::CODE.to_vec()),
+			)
+			.map_err(|e| error("Unable to query runtime code.", e))?
+			.ok_or_else(|| error("Runtime code not found.", at))?;
+
+		Ok(NodeBuildInfo {
+			build: self.build.clone(),
+			runtime_code_hash: H256(blake2_256(&code.0)),
+		})
+	}
+}
@@ -0,0 +1,110 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Kulupu.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Kulupu is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Kulupu is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Kulupu. If not, see <http://www.gnu.org/licenses/>.
+
+//! Checkpoint RPC.
+//!
+//! Lists the checkpoints recorded by a node running with `--checkpoint-depth`,
+//! and lets operators clear them, for example to recover after a checkpoint
+//! was recorded on a chain the network has since abandoned.
+
+#![warn(missing_docs)]
+
+use jsonrpc_core::{Error, ErrorCode, Result};
+use jsonrpc_derive::rpc;
+use sc_client_api::AuxStore;
+use sc_rpc_api::DenyUnsafe;
+use serde::{Deserialize, Serialize};
+use sp_runtime::traits::{Block as BlockT, NumberFor};
+use std::{fmt::Debug, marker::PhantomData, sync::Arc};
+
+/// Error code for failed checkpoint queries.
+const CHECKPOINT_ERROR: i64 = 1;
+
+/// Block recorded as socially final.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Checkpoint<Number, Hash> {
+	/// Block number.
+	pub number: Number,
+	/// Block hash.
+	pub hash: Hash,
+}
+
+/// Checkpoint RPC methods.
+#[rpc]
+pub trait CheckpointApi<Number, Hash> {
+	/// Recorded checkpoints, from earliest to latest.
+	#[rpc(name = "checkpoint_list")]
+	fn checkpoints(&self) -> Result<Vec<Checkpoint<Number, Hash>>>;
+
+	/// Remove all recorded checkpoints. Unsafe.
+	#[rpc(name = "checkpoint_clear")]
+	fn clear_checkpoints(&self) -> Result<()>;
+}
+
+fn error<E: Debug>(message: &str, err: E) -> Error {
+	Error {
+		code: ErrorCode::ServerError(CHECKPOINT_ERROR),
+		message: message.into(),
+		data: Some(format!("{:?}", err).into()),
+	}
+}
+
+/// Checkpoint RPC implementation.
+pub struct Checkpoints<C, B> {
+	client: Arc<C>,
+	deny_unsafe: DenyUnsafe,
+	_marker: PhantomData<B>,
+}
+
+impl<C, B> Checkpoints<C, B> {
+	/// Create a new checkpoint RPC.
+	pub fn new(client: Arc<C>, deny_unsafe: DenyUnsafe) -> Self {
+		Self {
+			client,
+			deny_unsafe,
+			_marker: Default::default(),
+		}
+	}
+}
+
+impl<C, Block> CheckpointApi<NumberFor<Block>, Block::Hash> for Checkpoints<C, Block>
+where
+	Block: BlockT,
+	C: AuxStore + Send + Sync + 'static,
+{
+	fn checkpoints(&self) -> Result<Vec<Checkpoint<NumberFor<Block>, Block::Hash>>> {
+		let checkpoints = kulupu_pow::checkpoint::checkpoints::<Block, _>(self.client.as_ref())
+			.map_err(|e| error("Unable to read checkpoints.", e))?;
+
+		Ok(checkpoints
+			.into_iter()
+			.map(|checkpoint| Checkpoint {
+				number: checkpoint.number,
+				hash: checkpoint.hash,
+			})
+			.collect())
+	}
+
+	fn clear_checkpoints(&self) -> Result<()> {
+		self.deny_unsafe.check_if_safe()?;
+
+		kulupu_pow::checkpoint::clear_checkpoints(self.client.as_ref())
+			.map_err(|e| error("Unable to clear checkpoints.", e))
+	}
+}
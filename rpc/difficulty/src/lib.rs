@@ -0,0 +1,296 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Kulupu.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Kulupu is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Kulupu is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Kulupu. If not, see <http://www.gnu.org/licenses/>.
+
+//! Difficulty history RPC.
+//!
+//! `kulupu_difficultyHistory` returns the difficulties and timestamps of a
+//! block range, read from the `difficulty` pallet's history, together with the
+//! network hashrate over the range. `kulupu_totalDifficulty` returns the total
+//! difficulty of the chain up to a block, and `kulupu_powAux` the difficulty
+//! of the block as well, as recorded by the PoW import for fork choice.
+
+#![warn(missing_docs)]
+
+use jsonrpc_core::{Error, ErrorCode, Result};
+use jsonrpc_derive::rpc;
+use kulupu_pow_consensus::PowAux;
+use kulupu_primitives::DifficultyHistoryApi;
+use sc_client_api::AuxStore;
+use serde::{Deserialize, Serialize};
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use sp_core::U256;
+use sp_runtime::{
+	generic::BlockId,
+	traits::{Block as BlockT, NumberFor, UniqueSaturatedInto},
+};
+use std::{fmt::Debug, marker::PhantomData, sync::Arc};
+
+/// Maximum number of blocks that can be queried at once.
+pub const MAX_RANGE: u64 = 4096;
+
+/// Error code for failed difficulty history queries.
+const DIFFICULTY_ERROR: i64 = 1;
+
+/// Difficulty and timestamp of a block.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DifficultyEntry {
+	/// Block number.
+	pub number: u64,
+	/// Difficulty the block was mined at.
+	pub difficulty: U256,
+	/// Block timestamp in milliseconds.
+	pub timestamp: u64,
+}
+
+/// Difficulty history of a block range.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DifficultyHistory {
+	/// Entries of the range, from earliest to latest.
+	pub entries: Vec<DifficultyEntry>,
+	/// Network hashrate over the range, in hashes per second, if the range
+	/// covers more than one block.
+	pub hashrate: Option<U256>,
+}
+
+/// Total difficulty of the chain up to a block.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TotalDifficulty<Hash> {
+	/// Block hash.
+	pub hash: Hash,
+	/// Sum of the difficulties of the block and all of its ancestors.
+	pub total_difficulty: U256,
+}
+
+/// Difficulty and total difficulty of a block, as recorded for fork choice.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockPowAux<Hash> {
+	/// Block hash.
+	pub hash: Hash,
+	/// Difficulty the block was mined at.
+	pub difficulty: U256,
+	/// Sum of the difficulties of the block and all of its ancestors.
+	pub total_difficulty: U256,
+}
+
+/// Difficulty RPC methods.
+#[rpc]
+pub trait DifficultyApi<BlockNumber, Hash> {
+	/// Difficulty history of blocks `from` to `to`, inclusive. `to` defaults
+	/// to the best block.
+	#[rpc(name = "kulupu_difficultyHistory")]
+	fn difficulty_history(
+		&self,
+		from: BlockNumber,
+		to: Option<BlockNumber>,
+	) -> Result<DifficultyHistory>;
+
+	/// Total difficulty of the chain up to block `hash`, defaulting to the best
+	/// block.
+	#[rpc(name = "kulupu_totalDifficulty")]
+	fn total_difficulty(&self, hash: Option<Hash>) -> Result<TotalDifficulty<Hash>>;
+
+	/// Difficulty and total difficulty of block `hash`, defaulting to the best
+	/// block.
+	#[rpc(name = "kulupu_powAux")]
+	fn pow_aux(&self, hash: Option<Hash>) -> Result<BlockPowAux<Hash>>;
+}
+
+/// Network hashrate over consecutive entries, in hashes per second.
+///
+/// The expected number of hashes to mine a block is its difficulty, so the
+/// hashrate is the total difficulty of all but the first entry, divided by the
+/// time elapsed since the first entry.
+pub fn hashrate(entries: &[DifficultyEntry]) -> Option<U256> {
+	let first = entries.first()?;
+	let last = entries.last()?;
+	let elapsed = last.timestamp.checked_sub(first.timestamp)?;
+	if elapsed == 0 {
+		return None;
+	}
+
+	let work = entries[1..].iter().fold(U256::zero(), |acc, entry| {
+		acc.saturating_add(entry.difficulty)
+	});
+	Some(work.saturating_mul(U256::from(1000)) / U256::from(elapsed))
+}
+
+fn error<E: Debug>(message: &str, err: E) -> Error {
+	Error {
+		code: ErrorCode::ServerError(DIFFICULTY_ERROR),
+		message: message.into(),
+		data: Some(format!("{:?}", err).into()),
+	}
+}
+
+/// Difficulty RPC implementation.
+pub struct Difficulties<C, B> {
+	client: Arc<C>,
+	_marker: PhantomData<B>,
+}
+
+impl<C, B> Difficulties<C, B> {
+	/// Create a new difficulty RPC.
+	pub fn new(client: Arc<C>) -> Self {
+		Self {
+			client,
+			_marker: Default::default(),
+		}
+	}
+}
+
+impl<C, Block> Difficulties<C, Block>
+where
+	Block: BlockT,
+	C: HeaderBackend<Block> + AuxStore,
+{
+	/// PoW aux data of block `hash`, defaulting to the best block.
+	fn read_pow_aux(&self, hash: Option<Block::Hash>) -> Result<(Block::Hash, PowAux<U256>)> {
+		let hash = hash.unwrap_or_else(|| self.client.info().best_hash);
+		// Missing aux data reads as zero, as for genesis, so check that the
+		// block exists first.
+		if self
+			.client
+			.header(BlockId::Hash(hash))
+			.map_err(|e| error("Unable to query header.", e))?
+			.is_none()
+		{
+			return Err(error("Block not found.", hash));
+		}
+
+		let aux = PowAux::<U256>::read::<_, Block>(&*self.client, &hash)
+			.map_err(|e| error("Unable to query PoW aux data.", e))?;
+		Ok((hash, aux))
+	}
+}
+
+impl<C, Block> DifficultyApi<NumberFor<Block>, Block::Hash> for Difficulties<C, Block>
+where
+	Block: BlockT,
+	C: ProvideRuntimeApi<Block> + HeaderBackend<Block> + AuxStore + Send + Sync + 'static,
+	C::Api: DifficultyHistoryApi<Block>,
+{
+	fn difficulty_history(
+		&self,
+		from: NumberFor<Block>,
+		to: Option<NumberFor<Block>>,
+	) -> Result<DifficultyHistory> {
+		let from: u64 = from.unique_saturated_into();
+		let to: u64 = to
+			.unwrap_or_else(|| self.client.info().best_number)
+			.unique_saturated_into();
+		if to < from || to - from >= MAX_RANGE {
+			return Err(Error::invalid_params(format!(
+				"Block range must be non-empty and at most {} blocks",
+				MAX_RANGE
+			)));
+		}
+
+		// Each block keeps the history of its recent ancestors, so walk back
+		// from `to` one history window at a time.
+		let mut entries = Vec::new();
+		let mut number = to;
+		loop {
+			let hash = self
+				.client
+				.hash(number.unique_saturated_into())
+				.map_err(|e| error("Unable to query block hash.", e))?
+				.ok_or_else(|| error("Block not found.", number))?;
+			let history = self
+				.client
+				.runtime_api()
+				.past_difficulties_and_timestamps(&BlockId::Hash(hash))
+				.map_err(|e| error("Unable to query difficulty history.", e))?;
+
+			for (offset, (difficulty, timestamp)) in history.iter().rev().enumerate() {
+				match number.checked_sub(offset as u64) {
+					Some(n) if n >= from => entries.push(DifficultyEntry {
+						number: n,
+						difficulty: *difficulty,
+						timestamp: *timestamp,
+					}),
+					_ => break,
+				}
+			}
+
+			let len = history.len() as u64;
+			if len == 0 || number < from + len {
+				break;
+			}
+			number -= len;
+		}
+		entries.reverse();
+
+		Ok(DifficultyHistory {
+			hashrate: hashrate(&entries),
+			entries,
+		})
+	}
+
+	fn total_difficulty(&self, hash: Option<Block::Hash>) -> Result<TotalDifficulty<Block::Hash>> {
+		let (hash, aux) = self.read_pow_aux(hash)?;
+
+		Ok(TotalDifficulty {
+			hash,
+			total_difficulty: aux.total_difficulty,
+		})
+	}
+
+	fn pow_aux(&self, hash: Option<Block::Hash>) -> Result<BlockPowAux<Block::Hash>> {
+		let (hash, aux) = self.read_pow_aux(hash)?;
+
+		Ok(BlockPowAux {
+			hash,
+			difficulty: aux.difficulty,
+			total_difficulty: aux.total_difficulty,
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn entry(number: u64, difficulty: u64, timestamp: u64) -> DifficultyEntry {
+		DifficultyEntry {
+			number,
+			difficulty: U256::from(difficulty),
+			timestamp,
+		}
+	}
+
+	#[test]
+	fn hashrate_needs_elapsed_time() {
+		assert_eq!(hashrate(&[]), None);
+		assert_eq!(hashrate(&[entry(1, 100, 60_000)]), None);
+	}
+
+	#[test]
+	fn hashrate_excludes_first_entry() {
+		let entries = [
+			entry(1, 1_000, 0),
+			entry(2, 6_000, 60_000),
+			entry(3, 6_000, 120_000),
+		];
+		assert_eq!(hashrate(&entries), Some(U256::from(100)));
+	}
+}
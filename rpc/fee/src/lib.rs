@@ -0,0 +1,238 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Kulupu.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Kulupu is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Kulupu is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Kulupu. If not, see <http://www.gnu.org/licenses/>.
+
+//! Fee estimation RPC.
+//!
+//! `fee_estimateTip` combines the `query_info` of a transaction with the fee
+//! multiplier trajectory and fullness of recent blocks, and suggests a tip
+//! likely to get the transaction included within a given number of blocks.
+
+#![warn(missing_docs)]
+
+use codec::{Codec, Decode};
+use jsonrpc_core::{Error, ErrorCode, Result};
+use jsonrpc_derive::rpc;
+use kulupu_primitives::FeeApi;
+use pallet_transaction_payment_rpc_runtime_api::TransactionPaymentApi;
+use serde::{Deserialize, Serialize};
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use sp_core::Bytes;
+use sp_rpc::number::NumberOrHex;
+use sp_runtime::{
+	generic::BlockId,
+	traits::{Block as BlockT, Header as HeaderT, Zero},
+	PerThing, Perbill,
+};
+use std::{fmt::Debug, marker::PhantomData, sync::Arc};
+
+/// Number of recent blocks considered for the estimation.
+pub const HISTORY_LEN: usize = 10;
+
+/// Error code for failed runtime calls.
+const RUNTIME_ERROR: i64 = 1;
+
+/// Fee estimate of a transaction.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TipEstimate {
+	/// Partial fee of the transaction at the queried block, excluding tip.
+	pub partial_fee: NumberOrHex,
+	/// Suggested tip.
+	pub suggested_tip: NumberOrHex,
+	/// Next fee multipliers of recent blocks, oldest first, as inner values of
+	/// `FixedU128`.
+	pub multipliers: Vec<NumberOrHex>,
+	/// Normal dispatch class fullness of recent blocks, oldest first, in parts
+	/// per billion.
+	pub fullness: Vec<u32>,
+}
+
+/// Fee estimation RPC methods.
+#[rpc]
+pub trait FeeEstimateApi<BlockHash> {
+	/// Estimate the fee of an encoded extrinsic, and suggest a tip to get it
+	/// included within `target_blocks` blocks (defaults to 1).
+	#[rpc(name = "fee_estimateTip")]
+	fn estimate_tip(
+		&self,
+		encoded_xt: Bytes,
+		target_blocks: Option<u32>,
+		at: Option<BlockHash>,
+	) -> Result<TipEstimate>;
+}
+
+/// Suggest a tip for a transaction with `partial_fee`, given the next fee
+/// multipliers and fullness (in parts per billion) of recent blocks, oldest
+/// first.
+///
+/// The partial fee is scaled by the average fullness of recent blocks, so no
+/// tip is suggested while blocks have room to spare. It is then adjusted by the
+/// per-block multiplier trend over `target_blocks`, and spread across them, as a
+/// transaction that can wait longer needs a smaller tip.
+pub fn suggest_tip(
+	partial_fee: u128,
+	multipliers: &[u128],
+	fullness: &[u32],
+	target_blocks: u32,
+) -> u128 {
+	if fullness.is_empty() {
+		return 0;
+	}
+
+	let congestion = fullness.iter().map(|f| *f as f64).sum::<f64>()
+		/ fullness.len() as f64
+		/ Perbill::ACCURACY as f64;
+	let trend = match (multipliers.first(), multipliers.last()) {
+		(Some(first), Some(last)) if *first > 0 && multipliers.len() > 1 => {
+			(*last as f64 / *first as f64).powf(1.0 / (multipliers.len() - 1) as f64)
+		}
+		_ => 1.0,
+	};
+	let target_blocks = target_blocks.max(1);
+
+	(partial_fee as f64 * congestion * trend.powi(target_blocks as i32) / target_blocks as f64)
+		as u128
+}
+
+fn runtime_error<E: Debug>(message: &str, err: E) -> Error {
+	Error {
+		code: ErrorCode::ServerError(RUNTIME_ERROR),
+		message: message.into(),
+		data: Some(format!("{:?}", err).into()),
+	}
+}
+
+/// Fee estimation RPC implementation.
+pub struct FeeEstimate<C, B, Balance> {
+	client: Arc<C>,
+	_marker: PhantomData<(B, Balance)>,
+}
+
+impl<C, B, Balance> FeeEstimate<C, B, Balance> {
+	/// Create a new fee estimation RPC.
+	pub fn new(client: Arc<C>) -> Self {
+		Self {
+			client,
+			_marker: Default::default(),
+		}
+	}
+}
+
+impl<C, Block, Balance> FeeEstimateApi<<Block as BlockT>::Hash> for FeeEstimate<C, Block, Balance>
+where
+	Block: BlockT,
+	C: ProvideRuntimeApi<Block> + HeaderBackend<Block> + Send + Sync + 'static,
+	C::Api: TransactionPaymentApi<Block, Balance> + FeeApi<Block>,
+	Balance: Codec + Copy + Into<u128> + Send + Sync + 'static,
+{
+	fn estimate_tip(
+		&self,
+		encoded_xt: Bytes,
+		target_blocks: Option<u32>,
+		at: Option<<Block as BlockT>::Hash>,
+	) -> Result<TipEstimate> {
+		let mut hash = at.unwrap_or_else(|| self.client.info().best_hash);
+
+		let encoded_len = encoded_xt.len() as u32;
+		let uxt: Block::Extrinsic = Decode::decode(&mut &*encoded_xt).map_err(|e| Error {
+			code: ErrorCode::InvalidParams,
+			message: "Unable to decode extrinsic.".into(),
+			data: Some(format!("{:?}", e).into()),
+		})?;
+		let partial_fee: u128 = self
+			.client
+			.runtime_api()
+			.query_info(&BlockId::Hash(hash), uxt, encoded_len)
+			.map_err(|e| runtime_error("Unable to query dispatch info.", e))?
+			.partial_fee
+			.into();
+
+		let mut multipliers = Vec::with_capacity(HISTORY_LEN);
+		let mut fullness = Vec::with_capacity(HISTORY_LEN);
+		for _ in 0..HISTORY_LEN {
+			let at = BlockId::Hash(hash);
+			multipliers.push(
+				self.client
+					.runtime_api()
+					.next_fee_multiplier(&at)
+					.map_err(|e| runtime_error("Unable to query fee multiplier.", e))?,
+			);
+			fullness.push(
+				self.client
+					.runtime_api()
+					.block_fullness(&at)
+					.map_err(|e| runtime_error("Unable to query block fullness.", e))?,
+			);
+
+			match self
+				.client
+				.header(at)
+				.map_err(|e| runtime_error("Unable to query header.", e))?
+			{
+				Some(header) if !header.number().is_zero() => hash = *header.parent_hash(),
+				_ => break,
+			}
+		}
+		multipliers.reverse();
+		fullness.reverse();
+
+		let suggested_tip = suggest_tip(
+			partial_fee,
+			&multipliers,
+			&fullness,
+			target_blocks.unwrap_or(1),
+		);
+
+		Ok(TipEstimate {
+			partial_fee: partial_fee.into(),
+			suggested_tip: suggested_tip.into(),
+			multipliers: multipliers.into_iter().map(Into::into).collect(),
+			fullness,
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const FULL: u32 = Perbill::ACCURACY;
+
+	#[test]
+	fn no_tip_for_empty_blocks() {
+		assert_eq!(suggest_tip(1000, &[100, 200], &[0, 0], 1), 0);
+		assert_eq!(suggest_tip(1000, &[], &[], 1), 0);
+	}
+
+	#[test]
+	fn tip_spreads_across_target_blocks() {
+		assert_eq!(suggest_tip(1000, &[100, 100], &[FULL, FULL], 1), 1000);
+		assert_eq!(suggest_tip(1000, &[100, 100], &[FULL, FULL], 2), 500);
+		assert_eq!(
+			suggest_tip(1000, &[100, 100], &[FULL / 2, FULL / 2], 0),
+			500
+		);
+	}
+
+	#[test]
+	fn tip_follows_multiplier_trend() {
+		assert_eq!(suggest_tip(1000, &[100, 200], &[FULL, FULL], 1), 2000);
+		assert_eq!(suggest_tip(1000, &[200, 100], &[FULL, FULL], 1), 500);
+	}
+}
@@ -0,0 +1,154 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Kulupu.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Kulupu is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Kulupu is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Kulupu. If not, see <http://www.gnu.org/licenses/>.
+
+//! Block inherents RPC.
+//!
+//! `kulupu_blockInherents` decodes the inherents of a block: its timestamp,
+//! its author from the pre-runtime digest, and any other unsigned extrinsics
+//! as encoded calls. This helps debugging inherent check failures reported by
+//! syncing nodes. The block is decoded by the runtime of its parent, which
+//! needs the parent state.
+
+#![warn(missing_docs)]
+
+use codec::Codec;
+use jsonrpc_core::{Error, ErrorCode, Result};
+use jsonrpc_derive::rpc;
+use kulupu_primitives::BlockInherentsApi;
+use sc_client_api::BlockBackend;
+use serde::{Deserialize, Serialize};
+use sp_api::{ApiExt, ProvideRuntimeApi};
+use sp_blockchain::HeaderBackend;
+use sp_core::Bytes;
+use sp_runtime::{
+	generic::BlockId,
+	traits::{Block as BlockT, Header as HeaderT, Zero},
+};
+use std::{fmt::Debug, marker::PhantomData, sync::Arc};
+
+/// Error code for failed block inherents queries.
+const INHERENTS_ERROR: i64 = 1;
+
+/// Inherents of a block.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Inherents<AccountId> {
+	/// Timestamp set by the block, in milliseconds.
+	pub timestamp: Option<u64>,
+	/// Author of the block, or its payout account.
+	pub author: Option<AccountId>,
+	/// Other unsigned extrinsics of the block, as encoded calls.
+	pub other: Vec<Bytes>,
+}
+
+impl<AccountId> From<kulupu_primitives::BlockInherents<AccountId>> for Inherents<AccountId> {
+	fn from(inherents: kulupu_primitives::BlockInherents<AccountId>) -> Self {
+		Self {
+			timestamp: inherents.timestamp,
+			author: inherents.author,
+			other: inherents.other.into_iter().map(Into::into).collect(),
+		}
+	}
+}
+
+/// Block inherents RPC methods.
+#[rpc]
+pub trait InherentsApi<BlockHash, AccountId> {
+	/// Inherents of block `at`, which defaults to the best block.
+	#[rpc(name = "kulupu_blockInherents")]
+	fn block_inherents(&self, at: Option<BlockHash>) -> Result<Inherents<AccountId>>;
+}
+
+fn error<E: Debug>(message: &str, err: E) -> Error {
+	Error {
+		code: ErrorCode::ServerError(INHERENTS_ERROR),
+		message: message.into(),
+		data: Some(format!("{:?}", err).into()),
+	}
+}
+
+/// Block inherents RPC implementation.
+pub struct BlockInherents<C, B> {
+	client: Arc<C>,
+	_marker: PhantomData<B>,
+}
+
+impl<C, B> BlockInherents<C, B> {
+	/// Create a new block inherents RPC.
+	pub fn new(client: Arc<C>) -> Self {
+		Self {
+			client,
+			_marker: Default::default(),
+		}
+	}
+}
+
+impl<C, Block, AccountId> InherentsApi<Block::Hash, AccountId> for BlockInherents<C, Block>
+where
+	Block: BlockT,
+	C: ProvideRuntimeApi<Block> + HeaderBackend<Block> + BlockBackend<Block>,
+	C: Send + Sync + 'static,
+	C::Api: BlockInherentsApi<Block, AccountId>,
+	AccountId: Codec + Serialize + Send + Sync + 'static,
+{
+	fn block_inherents(&self, at: Option<Block::Hash>) -> Result<Inherents<AccountId>> {
+		let at = at.unwrap_or_else(|| self.client.info().best_hash);
+		let block = self
+			.client
+			.block(&BlockId::Hash(at))
+			.map_err(|e| error("Unable to query block.", e))?
+			.ok_or_else(|| error("Block not found.", at))?
+			.block;
+		if block.header().number().is_zero() {
+			return Err(Error::invalid_params("Genesis block has no inherents"));
+		}
+		let parent = BlockId::Hash(*block.header().parent_hash());
+
+		let api = self.client.runtime_api();
+		let has_api = api
+			.has_api::<dyn BlockInherentsApi<Block, AccountId>>(&parent)
+			.map_err(|e| error("Unable to query runtime version.", e))?;
+		if !has_api {
+			return Err(error(
+				"Runtime of the parent block does not decode inherents.",
+				parent,
+			));
+		}
+
+		api.block_inherents(&parent, block)
+			.map(Into::into)
+			.map_err(|e| error("Unable to decode block inherents.", e))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn other_inherents_are_bytes() {
+		let inherents: Inherents<u64> = kulupu_primitives::BlockInherents {
+			timestamp: Some(1_000),
+			author: Some(1),
+			other: vec![vec![1, 2]],
+		}
+		.into();
+
+		assert_eq!(inherents.other, vec![Bytes(vec![1, 2])]);
+	}
+}
@@ -0,0 +1,136 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Kulupu.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Kulupu is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Kulupu is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Kulupu. If not, see <http://www.gnu.org/licenses/>.
+
+//! Account liquidity timeline RPC.
+//!
+//! `kulupu_liquidityTimeline` combines the reward locks, vesting schedules
+//! and lockdrop locks of an account into the balances locked and
+//! transferable now and at each later block a lock releases, as reported by
+//! the runtime.
+
+#![warn(missing_docs)]
+
+use codec::Codec;
+use jsonrpc_core::{Error, ErrorCode, Result};
+use jsonrpc_derive::rpc;
+use kulupu_primitives::LiquidityApi as LiquidityRuntimeApi;
+use serde::{Deserialize, Serialize};
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use sp_rpc::number::NumberOrHex;
+use sp_runtime::{
+	generic::BlockId,
+	traits::{Block as BlockT, NumberFor, UniqueSaturatedInto},
+};
+use std::{fmt::Debug, marker::PhantomData, sync::Arc};
+
+/// Error code for failed liquidity queries.
+const LIQUIDITY_ERROR: i64 = 1;
+
+/// Balances of an account locked and transferable at a block.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LiquidityPoint {
+	/// Block the balances are at.
+	pub at: u64,
+	/// Balance still locked by reward locks.
+	pub reward_locked: NumberOrHex,
+	/// Balance still locked by vesting schedules.
+	pub vesting_locked: NumberOrHex,
+	/// Balance locked by lockdrop locks.
+	pub lockdrop_locked: NumberOrHex,
+	/// Balance locked by any other lock, such as a governance lock.
+	pub other_locked: NumberOrHex,
+	/// Balance spendable at the block. Locks overlap, so this is the free
+	/// balance less the largest lock.
+	pub transferable: NumberOrHex,
+}
+
+/// Liquidity RPC methods.
+#[rpc]
+pub trait LiquidityApi<BlockHash, AccountId> {
+	/// Liquidity of `who` at block `at`, defaulting to the best block, and at
+	/// each later block one of its reward locks expires or one of its vesting
+	/// schedules starts or ends releasing. Vesting releases linearly between
+	/// the points, and lockdrop and other locks are assumed to stay.
+	#[rpc(name = "kulupu_liquidityTimeline")]
+	fn liquidity_timeline(
+		&self,
+		who: AccountId,
+		at: Option<BlockHash>,
+	) -> Result<Vec<LiquidityPoint>>;
+}
+
+fn error<E: Debug>(message: &str, err: E) -> Error {
+	Error {
+		code: ErrorCode::ServerError(LIQUIDITY_ERROR),
+		message: message.into(),
+		data: Some(format!("{:?}", err).into()),
+	}
+}
+
+/// Liquidity RPC implementation.
+pub struct Liquidity<C, B, Balance> {
+	client: Arc<C>,
+	_marker: PhantomData<(B, Balance)>,
+}
+
+impl<C, B, Balance> Liquidity<C, B, Balance> {
+	/// Create a new liquidity RPC.
+	pub fn new(client: Arc<C>) -> Self {
+		Self {
+			client,
+			_marker: Default::default(),
+		}
+	}
+}
+
+impl<C, Block, AccountId, Balance> LiquidityApi<<Block as BlockT>::Hash, AccountId>
+	for Liquidity<C, Block, Balance>
+where
+	Block: BlockT,
+	C: ProvideRuntimeApi<Block> + HeaderBackend<Block> + Send + Sync + 'static,
+	C::Api: LiquidityRuntimeApi<Block, AccountId, Balance, NumberFor<Block>>,
+	AccountId: Codec + Send + Sync + 'static,
+	Balance: Codec + Into<NumberOrHex> + Send + Sync + 'static,
+{
+	fn liquidity_timeline(
+		&self,
+		who: AccountId,
+		at: Option<<Block as BlockT>::Hash>,
+	) -> Result<Vec<LiquidityPoint>> {
+		let at = BlockId::Hash(at.unwrap_or_else(|| self.client.info().best_hash));
+		let timeline = self
+			.client
+			.runtime_api()
+			.liquidity_timeline(&at, who)
+			.map_err(|e| error("Unable to query liquidity timeline.", e))?;
+
+		Ok(timeline
+			.into_iter()
+			.map(|point| LiquidityPoint {
+				at: point.at.unique_saturated_into(),
+				reward_locked: point.reward_locked.into(),
+				vesting_locked: point.vesting_locked.into(),
+				lockdrop_locked: point.lockdrop_locked.into(),
+				other_locked: point.other_locked.into(),
+				transferable: point.transferable.into(),
+			})
+			.collect())
+	}
+}
@@ -0,0 +1,341 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Kulupu.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Kulupu is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Kulupu is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Kulupu. If not, see <http://www.gnu.org/licenses/>.
+
+//! Block author statistics RPC.
+//!
+//! `kulupu_minerStats` decodes the author account from the PoW pre-runtime
+//! digest of each block in a range, and returns how many blocks each author
+//! mined. Blocks without a decodable author are counted separately.
+//!
+//! `kulupu_minerNames` returns the names miners declared over a range, which
+//! follow the author and payout accounts in the pre-runtime digest. Names are
+//! self-declared and unverified, only labelling the author accounts.
+
+#![warn(missing_docs)]
+
+use codec::Decode;
+use jsonrpc_core::{Error, ErrorCode, Result};
+use jsonrpc_derive::rpc;
+use serde::{Deserialize, Serialize};
+use sp_blockchain::HeaderBackend;
+use sp_consensus_pow::POW_ENGINE_ID;
+use sp_runtime::{
+	generic::BlockId,
+	traits::{Block as BlockT, Header as HeaderT, NumberFor, UniqueSaturatedInto},
+};
+use std::{collections::BTreeMap, fmt::Debug, marker::PhantomData, sync::Arc};
+
+/// Maximum number of blocks that can be queried at once, a week of blocks.
+pub const MAX_RANGE: u64 = 10_080;
+
+/// Maximum length in bytes of a miner name.
+pub const MAX_MINER_NAME_LEN: usize = 32;
+
+/// Error code for failed miner statistics queries.
+const MINER_STATS_ERROR: i64 = 1;
+
+/// Blocks mined by an author.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MinerEntry<AccountId> {
+	/// Author account.
+	pub author: AccountId,
+	/// Number of blocks mined.
+	pub blocks: u64,
+	/// Percentage of the blocks of the range mined.
+	pub percentage: f64,
+}
+
+/// Author statistics of a block range.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MinerStats<AccountId> {
+	/// First block of the range.
+	pub from: u64,
+	/// Last block of the range.
+	pub to: u64,
+	/// Authors of the range, by most blocks mined first.
+	pub miners: Vec<MinerEntry<AccountId>>,
+	/// Blocks without a decodable author digest.
+	pub unknown: u64,
+}
+
+/// Name declared by an author.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MinerName<AccountId> {
+	/// Author account.
+	pub author: AccountId,
+	/// Name declared in the last block of the range mined by the author.
+	pub name: String,
+	/// Number of that block.
+	pub block: u64,
+}
+
+/// Miner statistics RPC methods.
+#[rpc]
+pub trait MinerStatsApi<BlockNumber, AccountId> {
+	/// Block counts per author of blocks `from` to `to`, inclusive. `to`
+	/// defaults to the best block.
+	#[rpc(name = "kulupu_minerStats")]
+	fn miner_stats(
+		&self,
+		from: BlockNumber,
+		to: Option<BlockNumber>,
+	) -> Result<MinerStats<AccountId>>;
+
+	/// Names declared by the authors of blocks `from` to `to`, inclusive. `to`
+	/// defaults to the best block.
+	#[rpc(name = "kulupu_minerNames")]
+	fn miner_names(
+		&self,
+		from: BlockNumber,
+		to: Option<BlockNumber>,
+	) -> Result<Vec<MinerName<AccountId>>>;
+}
+
+/// Author account of a header, decoded from the start of its PoW pre-runtime
+/// digest.
+pub fn author<H: HeaderT, AccountId: Decode>(header: &H) -> Option<AccountId> {
+	let mut pre_runtime = header
+		.digest()
+		.logs()
+		.iter()
+		.filter_map(|item| item.as_pre_runtime())
+		.find(|(id, _)| *id == POW_ENGINE_ID)
+		.map(|(_, data)| data)?;
+
+	AccountId::decode(&mut pre_runtime).ok()
+}
+
+/// Author account and name of a header, decoded from its PoW pre-runtime
+/// digest, where the name follows the author and payout accounts.
+pub fn miner_name<H: HeaderT, AccountId: Decode>(header: &H) -> Option<(AccountId, String)> {
+	let mut pre_runtime = header
+		.digest()
+		.logs()
+		.iter()
+		.filter_map(|item| item.as_pre_runtime())
+		.find(|(id, _)| *id == POW_ENGINE_ID)
+		.map(|(_, data)| data)?;
+
+	let author = AccountId::decode(&mut pre_runtime).ok()?;
+	let _payout = AccountId::decode(&mut pre_runtime).ok()?;
+	let name = Vec::<u8>::decode(&mut pre_runtime).ok()?;
+	if name.len() > MAX_MINER_NAME_LEN {
+		return None;
+	}
+
+	Some((author, String::from_utf8(name).ok()?))
+}
+
+/// Last name declared by each author, given names by ascending block number.
+pub fn latest_names<AccountId: Ord>(
+	names: impl IntoIterator<Item = (u64, AccountId, String)>,
+) -> Vec<MinerName<AccountId>> {
+	names
+		.into_iter()
+		.map(|(block, author, name)| (author, (name, block)))
+		.collect::<BTreeMap<_, _>>()
+		.into_iter()
+		.map(|(author, (name, block))| MinerName {
+			author,
+			name,
+			block,
+		})
+		.collect()
+}
+
+/// Count the blocks of each author, with the number of blocks of unknown
+/// authors.
+pub fn tally<AccountId: Ord>(
+	authors: impl IntoIterator<Item = Option<AccountId>>,
+) -> (Vec<MinerEntry<AccountId>>, u64) {
+	let mut counts = BTreeMap::new();
+	let mut total = 0u64;
+	let mut unknown = 0u64;
+	for author in authors {
+		total += 1;
+		match author {
+			Some(author) => *counts.entry(author).or_insert(0u64) += 1,
+			None => unknown += 1,
+		}
+	}
+
+	let mut miners = counts
+		.into_iter()
+		.map(|(author, blocks)| MinerEntry {
+			author,
+			blocks,
+			percentage: blocks as f64 * 100.0 / total as f64,
+		})
+		.collect::<Vec<_>>();
+	miners.sort_by(|a, b| b.blocks.cmp(&a.blocks));
+
+	(miners, unknown)
+}
+
+fn error<E: Debug>(message: &str, err: E) -> Error {
+	Error {
+		code: ErrorCode::ServerError(MINER_STATS_ERROR),
+		message: message.into(),
+		data: Some(format!("{:?}", err).into()),
+	}
+}
+
+/// Miner statistics RPC implementation.
+pub struct Miners<C, B> {
+	client: Arc<C>,
+	_marker: PhantomData<B>,
+}
+
+impl<C, B> Miners<C, B> {
+	/// Create a new miner statistics RPC.
+	pub fn new(client: Arc<C>) -> Self {
+		Self {
+			client,
+			_marker: Default::default(),
+		}
+	}
+}
+
+impl<C, Block> Miners<C, Block>
+where
+	Block: BlockT,
+	C: HeaderBackend<Block>,
+{
+	/// Headers of blocks `from` to `to`, inclusive, with the range as numbers.
+	fn headers(
+		&self,
+		from: NumberFor<Block>,
+		to: Option<NumberFor<Block>>,
+	) -> Result<(u64, u64, Vec<Block::Header>)> {
+		let from: u64 = from.unique_saturated_into();
+		let to: u64 = to
+			.unwrap_or_else(|| self.client.info().best_number)
+			.unique_saturated_into();
+		if to < from || to - from >= MAX_RANGE {
+			return Err(Error::invalid_params(format!(
+				"Block range must be non-empty and at most {} blocks",
+				MAX_RANGE
+			)));
+		}
+
+		let mut headers = Vec::new();
+		for number in from..=to {
+			let header = self
+				.client
+				.header(BlockId::Number(number.unique_saturated_into()))
+				.map_err(|e| error("Unable to query block header.", e))?
+				.ok_or_else(|| error("Block not found.", number))?;
+			headers.push(header);
+		}
+
+		Ok((from, to, headers))
+	}
+}
+
+impl<C, Block, AccountId> MinerStatsApi<NumberFor<Block>, AccountId> for Miners<C, Block>
+where
+	Block: BlockT,
+	C: HeaderBackend<Block> + Send + Sync + 'static,
+	AccountId: Decode + Ord + Serialize + Send + Sync + 'static,
+{
+	fn miner_stats(
+		&self,
+		from: NumberFor<Block>,
+		to: Option<NumberFor<Block>>,
+	) -> Result<MinerStats<AccountId>> {
+		let (from, to, headers) = self.headers(from, to)?;
+		let (miners, unknown) = tally(headers.iter().map(author));
+
+		Ok(MinerStats {
+			from,
+			to,
+			miners,
+			unknown,
+		})
+	}
+
+	fn miner_names(
+		&self,
+		from: NumberFor<Block>,
+		to: Option<NumberFor<Block>>,
+	) -> Result<Vec<MinerName<AccountId>>> {
+		let (from, _, headers) = self.headers(from, to)?;
+
+		Ok(latest_names(headers.iter().enumerate().filter_map(
+			|(index, header)| {
+				let (author, name) = miner_name(header)?;
+				Some((from + index as u64, author, name))
+			},
+		)))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn tally_counts_blocks_per_author() {
+		let (miners, unknown) = tally(vec![Some(1), Some(2), None, Some(2)]);
+
+		assert_eq!(unknown, 1);
+		assert_eq!(
+			miners,
+			vec![
+				MinerEntry {
+					author: 2,
+					blocks: 2,
+					percentage: 50.0,
+				},
+				MinerEntry {
+					author: 1,
+					blocks: 1,
+					percentage: 25.0,
+				},
+			],
+		);
+	}
+
+	#[test]
+	fn latest_names_keep_last_name_per_author() {
+		let names = latest_names(vec![
+			(1, 1, "old".to_string()),
+			(2, 2, "pool".to_string()),
+			(3, 1, "new".to_string()),
+		]);
+
+		assert_eq!(
+			names,
+			vec![
+				MinerName {
+					author: 1,
+					name: "new".to_string(),
+					block: 3,
+				},
+				MinerName {
+					author: 2,
+					name: "pool".to_string(),
+					block: 2,
+				},
+			],
+		);
+	}
+}
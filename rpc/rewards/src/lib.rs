@@ -0,0 +1,249 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Kulupu.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Kulupu is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Kulupu is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Kulupu. If not, see <http://www.gnu.org/licenses/>.
+
+//! Reward history RPC.
+//!
+//! `kulupu_rewardHistory` returns the author, reward and tips of each block in
+//! a range, page by page, so explorers can backfill reward history without
+//! executing blocks themselves. Rewards are read from the events of each
+//! block, which needs the state of the range, as kept by archive nodes. Blocks
+//! from before the runtime provided reward events are skipped.
+//!
+//! `kulupu_rewardSchedule` returns the current reward and mints along with
+//! their planned changes, as stored by the rewards module.
+
+#![warn(missing_docs)]
+
+use codec::Codec;
+use jsonrpc_core::{Error, ErrorCode, Result};
+use jsonrpc_derive::rpc;
+use kulupu_primitives::{RewardHistoryApi, RewardsApi as RewardsRuntimeApi};
+use serde::{Deserialize, Serialize};
+use sp_api::{ApiExt, ProvideRuntimeApi};
+use sp_blockchain::HeaderBackend;
+use sp_rpc::number::NumberOrHex;
+use sp_runtime::{
+	generic::BlockId,
+	traits::{Block as BlockT, NumberFor, UniqueSaturatedInto},
+};
+use std::{fmt::Debug, marker::PhantomData, sync::Arc};
+
+/// Maximum number of blocks in a page.
+pub const MAX_PAGE_SIZE: u32 = 1000;
+
+/// Error code for failed reward history queries.
+const REWARDS_ERROR: i64 = 1;
+
+/// Reward of a block.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RewardEntry<AccountId> {
+	/// Block number.
+	pub number: u64,
+	/// Author of the block, or its payout account.
+	pub author: AccountId,
+	/// Block reward.
+	pub reward: NumberOrHex,
+	/// Transaction tips of the block.
+	pub tips: NumberOrHex,
+}
+
+/// Page of reward history.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RewardPage<AccountId> {
+	/// Rewards of the blocks of the page that had an author, from earliest to
+	/// latest.
+	pub entries: Vec<RewardEntry<AccountId>>,
+	/// First block of the next page, if the range continues.
+	pub next: Option<u64>,
+}
+
+/// Current reward and mints, with their planned changes.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RewardSchedule<AccountId> {
+	/// Current reward of each block.
+	pub reward: NumberOrHex,
+	/// Planned reward changes, by the block they take effect at.
+	pub reward_changes: Vec<(u64, NumberOrHex)>,
+	/// Current mints of each block.
+	pub mints: Vec<(AccountId, NumberOrHex)>,
+	/// Planned mint changes, by the block they take effect at.
+	pub mint_changes: Vec<(u64, Vec<(AccountId, NumberOrHex)>)>,
+}
+
+/// Reward RPC methods.
+#[rpc]
+pub trait RewardsApi<BlockHash, BlockNumber, AccountId> {
+	/// Reward history of blocks `from` to `to`, inclusive, covering at most
+	/// `page_size` blocks (defaults to and is capped at [`MAX_PAGE_SIZE`]).
+	/// `to` defaults to the best block. Continue from `next` of the returned
+	/// page for the rest of the range.
+	#[rpc(name = "kulupu_rewardHistory")]
+	fn reward_history(
+		&self,
+		from: BlockNumber,
+		to: Option<BlockNumber>,
+		page_size: Option<u32>,
+	) -> Result<RewardPage<AccountId>>;
+
+	/// Reward and mints at block `at`, defaulting to the best block, with
+	/// their planned changes.
+	#[rpc(name = "kulupu_rewardSchedule")]
+	fn reward_schedule(&self, at: Option<BlockHash>) -> Result<RewardSchedule<AccountId>>;
+}
+
+/// Last block of the page of `from` to `to` covering at most `page_size`
+/// blocks, and the first block of the next page, if any.
+pub fn page_end(from: u64, to: u64, page_size: u32) -> (u64, Option<u64>) {
+	let page_size = u64::from(page_size.max(1).min(MAX_PAGE_SIZE));
+	let end = from.saturating_add(page_size - 1).min(to);
+
+	(end, if end < to { Some(end + 1) } else { None })
+}
+
+fn error<E: Debug>(message: &str, err: E) -> Error {
+	Error {
+		code: ErrorCode::ServerError(REWARDS_ERROR),
+		message: message.into(),
+		data: Some(format!("{:?}", err).into()),
+	}
+}
+
+/// Reward RPC implementation.
+pub struct Rewards<C, B, Balance> {
+	client: Arc<C>,
+	_marker: PhantomData<(B, Balance)>,
+}
+
+impl<C, B, Balance> Rewards<C, B, Balance> {
+	/// Create a new reward RPC.
+	pub fn new(client: Arc<C>) -> Self {
+		Self {
+			client,
+			_marker: Default::default(),
+		}
+	}
+}
+
+impl<C, Block, AccountId, Balance> RewardsApi<Block::Hash, NumberFor<Block>, AccountId>
+	for Rewards<C, Block, Balance>
+where
+	Block: BlockT,
+	C: ProvideRuntimeApi<Block> + HeaderBackend<Block> + Send + Sync + 'static,
+	C::Api: RewardHistoryApi<Block, AccountId, Balance>,
+	C::Api: RewardsRuntimeApi<Block, NumberFor<Block>, AccountId, Balance>,
+	AccountId: Codec + Serialize + Send + Sync + 'static,
+	Balance: Codec + Into<NumberOrHex> + Send + Sync + 'static,
+{
+	fn reward_history(
+		&self,
+		from: NumberFor<Block>,
+		to: Option<NumberFor<Block>>,
+		page_size: Option<u32>,
+	) -> Result<RewardPage<AccountId>> {
+		let from: u64 = from.unique_saturated_into();
+		let to: u64 = to
+			.unwrap_or_else(|| self.client.info().best_number)
+			.unique_saturated_into();
+		if to < from {
+			return Err(Error::invalid_params("Block range must be non-empty"));
+		}
+		let (end, next) = page_end(from, to, page_size.unwrap_or(MAX_PAGE_SIZE));
+
+		let api = self.client.runtime_api();
+		let mut entries = Vec::new();
+		for number in from..=end {
+			let hash = self
+				.client
+				.hash(number.unique_saturated_into())
+				.map_err(|e| error("Unable to query block hash.", e))?
+				.ok_or_else(|| error("Block not found.", number))?;
+			let at = BlockId::Hash(hash);
+
+			let has_api = api
+				.has_api::<dyn RewardHistoryApi<Block, AccountId, Balance>>(&at)
+				.map_err(|e| error("Unable to query runtime version.", e))?;
+			if !has_api {
+				continue;
+			}
+
+			let reward = api
+				.block_reward(&at)
+				.map_err(|e| error("Unable to query block reward.", e))?;
+			if let Some(reward) = reward {
+				entries.push(RewardEntry {
+					number,
+					author: reward.author,
+					reward: reward.reward.into(),
+					tips: reward.tips.into(),
+				});
+			}
+		}
+
+		Ok(RewardPage { entries, next })
+	}
+
+	fn reward_schedule(&self, at: Option<Block::Hash>) -> Result<RewardSchedule<AccountId>> {
+		let at = BlockId::Hash(at.unwrap_or_else(|| self.client.info().best_hash));
+		let info = self
+			.client
+			.runtime_api()
+			.rewards_info(&at)
+			.map_err(|e| error("Unable to query reward schedule.", e))?;
+		let mints = |mints: Vec<(AccountId, Balance)>| {
+			mints
+				.into_iter()
+				.map(|(who, amount)| (who, amount.into()))
+				.collect::<Vec<_>>()
+		};
+
+		Ok(RewardSchedule {
+			reward: info.reward.into(),
+			reward_changes: info
+				.reward_changes
+				.into_iter()
+				.map(|(start, reward)| (start.unique_saturated_into(), reward.into()))
+				.collect(),
+			mints: mints(info.mints),
+			mint_changes: info
+				.mint_changes
+				.into_iter()
+				.map(|(start, changes)| (start.unique_saturated_into(), mints(changes)))
+				.collect(),
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn pages_cover_range() {
+		assert_eq!(page_end(10, 20, 5), (14, Some(15)));
+		assert_eq!(page_end(15, 20, 5), (19, Some(20)));
+		assert_eq!(page_end(20, 20, 5), (20, None));
+		assert_eq!(page_end(0, 5_000, 0), (0, Some(1)));
+		assert_eq!(
+			page_end(0, 5_000, u32::MAX),
+			(u64::from(MAX_PAGE_SIZE) - 1, Some(u64::from(MAX_PAGE_SIZE)))
+		);
+	}
+}
@@ -0,0 +1,116 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Kulupu.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Kulupu is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Kulupu is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Kulupu. If not, see <http://www.gnu.org/licenses/>.
+
+//! Treasury status RPC.
+//!
+//! `kulupu_treasuryStatus` returns the treasury balance, the next spend
+//! period, the number of pending and approved proposals and the balance
+//! projected to be burned, as reported by the runtime.
+
+#![warn(missing_docs)]
+
+use codec::Codec;
+use jsonrpc_core::{Error, ErrorCode, Result};
+use jsonrpc_derive::rpc;
+use kulupu_primitives::TreasuryApi;
+use serde::{Deserialize, Serialize};
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use sp_rpc::number::NumberOrHex;
+use sp_runtime::{
+	generic::BlockId,
+	traits::{Block as BlockT, NumberFor, UniqueSaturatedInto},
+};
+use std::{fmt::Debug, marker::PhantomData, sync::Arc};
+
+/// Error code for failed treasury status queries.
+const TREASURY_ERROR: i64 = 1;
+
+/// Status of the treasury.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TreasuryStatus {
+	/// Spendable balance of the treasury.
+	pub balance: NumberOrHex,
+	/// Block of the next spend period, which pays out approved proposals.
+	pub next_spend_period: u64,
+	/// Number of proposals awaiting approval.
+	pub pending_proposals: u32,
+	/// Number of approved proposals awaiting payout.
+	pub approved_proposals: u32,
+	/// Balance burned at the next spend period, if nothing changes until then.
+	/// Bounties funded in the same spend period are not accounted for.
+	pub projected_burn: NumberOrHex,
+}
+
+/// Treasury RPC methods.
+#[rpc]
+pub trait TreasuryStatusApi<BlockHash> {
+	/// Status of the treasury at block `at`, defaulting to the best block.
+	#[rpc(name = "kulupu_treasuryStatus")]
+	fn treasury_status(&self, at: Option<BlockHash>) -> Result<TreasuryStatus>;
+}
+
+fn error<E: Debug>(message: &str, err: E) -> Error {
+	Error {
+		code: ErrorCode::ServerError(TREASURY_ERROR),
+		message: message.into(),
+		data: Some(format!("{:?}", err).into()),
+	}
+}
+
+/// Treasury RPC implementation.
+pub struct Treasury<C, B, Balance> {
+	client: Arc<C>,
+	_marker: PhantomData<(B, Balance)>,
+}
+
+impl<C, B, Balance> Treasury<C, B, Balance> {
+	/// Create a new treasury RPC.
+	pub fn new(client: Arc<C>) -> Self {
+		Self {
+			client,
+			_marker: Default::default(),
+		}
+	}
+}
+
+impl<C, Block, Balance> TreasuryStatusApi<<Block as BlockT>::Hash> for Treasury<C, Block, Balance>
+where
+	Block: BlockT,
+	C: ProvideRuntimeApi<Block> + HeaderBackend<Block> + Send + Sync + 'static,
+	C::Api: TreasuryApi<Block, Balance, NumberFor<Block>>,
+	Balance: Codec + Into<NumberOrHex> + Send + Sync + 'static,
+{
+	fn treasury_status(&self, at: Option<<Block as BlockT>::Hash>) -> Result<TreasuryStatus> {
+		let at = BlockId::Hash(at.unwrap_or_else(|| self.client.info().best_hash));
+		let status = self
+			.client
+			.runtime_api()
+			.treasury_status(&at)
+			.map_err(|e| error("Unable to query treasury status.", e))?;
+
+		Ok(TreasuryStatus {
+			balance: status.balance.into(),
+			next_spend_period: status.next_spend_period.unique_saturated_into(),
+			pending_proposals: status.pending_proposals,
+			approved_proposals: status.approved_proposals,
+			projected_burn: status.projected_burn.into(),
+		})
+	}
+}
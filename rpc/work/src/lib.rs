@@ -0,0 +1,1140 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Kulupu.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Kulupu is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Kulupu is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Kulupu. If not, see <http://www.gnu.org/licenses/>.
+
+//! Work RPC for external miners.
+//!
+//! `work_getCompute` returns the current mining work and `work_submitSeal`
+//! submits a seal for it. `work_subscribeCompute` notifies subscribers whenever
+//! the work changes. Work may be handed out at a share difficulty below the
+//! network difficulty, in which case seals meeting only the share difficulty
+//! are counted in `work_getStats` but not imported. Pools may submit seals at
+//! their own share difficulty, and follow accepted seals with
+//! `work_subscribeShares`. Failures are reported with
+//! the error codes of [`WorkError`]. The same methods are served by the push server in
+//! [`server`], which notifies all connected miners of work changes.
+//!
+//! Miners may name themselves with the optional `worker` parameter of
+//! `work_getCompute` and `work_submitSeal`. The time between a named worker
+//! first fetching work and submitting a seal is tracked, and its percentiles
+//! are reported per worker in `work_getStats`, so slow links causing stale
+//! shares can be spotted.
+//!
+//! When the node is configured to sign work, each [`Compute`] carries a
+//! signature of its [`Compute::signing_payload`] by the mining key, so miners
+//! on untrusted networks can detect substituted work.
+//!
+//! V2 seals may also be signed away from the node, for example when the
+//! author key is kept on an air-gapped signer. `work_getUnsignedSeal` returns
+//! the signing message of a nonce for the current work, and
+//! `work_submitSignedSeal` submits the seal with the signature produced for
+//! it.
+
+#![warn(missing_docs)]
+
+pub mod server;
+
+use codec::Encode;
+use futures::{channel::mpsc, FutureExt, SinkExt, StreamExt};
+use jsonrpc_core::{Error, ErrorCode, Result};
+use jsonrpc_derive::rpc;
+use jsonrpc_pubsub::{manager::SubscriptionManager, typed::Subscriber, SubscriptionId};
+use kulupu_pow::{
+	app,
+	compute::{ComputeV2, SealV2},
+	RandomXAlgorithmVersion, SealWork,
+};
+use kulupu_pow_consensus::{MiningHandle, MiningMetadata, PowAlgorithm};
+use kulupu_primitives::AlgorithmApi;
+use log::*;
+use parking_lot::Mutex;
+use sc_rpc::SubscriptionTaskExecutor;
+use serde::{Deserialize, Serialize};
+use sp_api::{ProvideRuntimeApi, TransactionFor};
+use sp_blockchain::HeaderBackend;
+use sp_core::{Bytes, Pair, H256, U256};
+use sp_runtime::{
+	generic::BlockId,
+	traits::{Block as BlockT, UniqueSaturatedInto},
+};
+use std::{
+	collections::{BTreeMap, HashMap, VecDeque},
+	convert::TryFrom,
+	fmt::Debug,
+	sync::Arc,
+	time::{Duration, Instant},
+};
+
+/// Interval at which subscriptions check for new work.
+const SUBSCRIPTION_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Number of recent round-trip latencies kept per worker.
+const LATENCY_SAMPLES: usize = 100;
+
+/// Maximum number of workers latencies are tracked for.
+const MAX_TRACKED_WORKERS: usize = 1024;
+
+/// Maximum length of a tracked worker name.
+const MAX_WORKER_NAME_LEN: usize = 64;
+
+/// Base of the work RPC error codes.
+const BASE_ERROR: i64 = 1000;
+
+/// Work RPC error. Each variant has its own JSON-RPC error code, so miner
+/// software can tell them apart.
+#[derive(Debug)]
+pub enum WorkError {
+	/// The node has no mining work, for example while it is still syncing.
+	NoMetadata,
+	/// The seal was mined for work that has since been replaced.
+	StaleWork,
+	/// The seal meets the network difficulty, but importing the block failed.
+	ImportFailed,
+	/// Any other failure, such as a failed runtime call.
+	Internal(String),
+	/// The seal does not decode, or was not mined for the current work.
+	InvalidSeal,
+	/// The seal is not signed by the author of the work.
+	BadSignature,
+	/// The seal meets neither the share nor the network difficulty.
+	LowDifficulty,
+	/// Seals of the current work are not signed.
+	UnsignedWork,
+}
+
+impl WorkError {
+	/// JSON-RPC error code of the error.
+	pub fn code(&self) -> i64 {
+		BASE_ERROR
+			+ match self {
+				WorkError::NoMetadata => 1,
+				WorkError::StaleWork => 2,
+				WorkError::ImportFailed => 3,
+				WorkError::Internal(_) => 4,
+				WorkError::InvalidSeal => 5,
+				WorkError::BadSignature => 6,
+				WorkError::LowDifficulty => 7,
+				WorkError::UnsignedWork => 8,
+			}
+	}
+
+	fn internal<E: Debug>(err: E) -> Self {
+		WorkError::Internal(format!("{:?}", err))
+	}
+}
+
+impl From<WorkError> for Error {
+	fn from(err: WorkError) -> Self {
+		let code = ErrorCode::ServerError(err.code());
+		let (message, data) = match err {
+			WorkError::NoMetadata => ("No mining work available", None),
+			WorkError::StaleWork => ("Seal is for stale work", None),
+			WorkError::ImportFailed => ("Importing the mined block failed", None),
+			WorkError::Internal(data) => ("Internal error", Some(data.into())),
+			WorkError::InvalidSeal => ("Seal is invalid for the current work", None),
+			WorkError::BadSignature => ("Seal is not signed by the work author", None),
+			WorkError::LowDifficulty => ("Seal does not meet the share difficulty", None),
+			WorkError::UnsignedWork => ("Seals of the current work are not signed", None),
+		};
+
+		Error {
+			code,
+			message: message.into(),
+			data,
+		}
+	}
+}
+
+/// Mining work handed out to external miners.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Compute {
+	/// Best hash the work is built on.
+	pub best_hash: H256,
+	/// Number of the best block the work is built on.
+	pub best_number: u64,
+	/// Algorithm version the seal must be mined with.
+	pub algorithm: AlgorithmVersion,
+	/// SCALE encoded fields of the seal to submit, in order.
+	pub seal_encoding: String,
+	/// RandomX key hash to use for the work.
+	pub key_hash: H256,
+	/// Pre-hash of the block to be sealed.
+	pub pre_hash: H256,
+	/// Pre-runtime digest, encoded author of the block.
+	pub pre_runtime: Option<Bytes>,
+	/// Network difficulty, which the work is computed with.
+	pub difficulty: U256,
+	/// Difficulty a seal needs to meet to be accepted as a share.
+	pub share_difficulty: U256,
+	/// Signature of the signing payload by the mining key, if the node signs
+	/// work.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub signature: Option<Bytes>,
+}
+
+impl Compute {
+	/// Payload of the work signature: the SCALE encoded `b"kulupu-work"`
+	/// followed by the best hash, key hash, pre-hash, pre-runtime digest,
+	/// difficulty and share difficulty.
+	pub fn signing_payload(&self) -> Vec<u8> {
+		(
+			b"kulupu-work",
+			self.best_hash,
+			self.key_hash,
+			self.pre_hash,
+			self.pre_runtime.as_ref().map(|p| &p.0),
+			self.difficulty,
+			self.share_difficulty,
+		)
+			.encode()
+	}
+
+	/// Whether the work is signed by `author`.
+	pub fn verify_signature(&self, author: &app::Public) -> bool {
+		let signature = match &self.signature {
+			Some(signature) => signature,
+			None => return false,
+		};
+		let signature = match app::Signature::try_from(&signature.0[..]) {
+			Ok(signature) => signature,
+			Err(()) => return false,
+		};
+
+		app::Pair::verify(&signature, &self.signing_payload(), author)
+	}
+}
+
+/// RandomX algorithm version of work.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AlgorithmVersion {
+	/// Seals carry the difficulty and nonce.
+	V1,
+	/// Seals are additionally signed by the author of the pre-runtime digest.
+	V2,
+}
+
+impl AlgorithmVersion {
+	/// SCALE encoded fields of seals of this version, in order.
+	pub fn seal_encoding(&self) -> &'static str {
+		match self {
+			AlgorithmVersion::V1 => "difficulty:U256,nonce:H256",
+			AlgorithmVersion::V2 => "difficulty:U256,nonce:H256,signature:sr25519::Signature",
+		}
+	}
+}
+
+/// V2 seal of the current work awaiting its signature by the author.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UnsignedSeal {
+	/// Pre-hash of the work the seal is for.
+	pub pre_hash: H256,
+	/// Difficulty of the seal.
+	pub difficulty: U256,
+	/// Nonce of the seal.
+	pub nonce: H256,
+	/// Message the author signs with its sr25519 mining key.
+	pub signing_message: H256,
+}
+
+impl UnsignedSeal {
+	/// Unsigned seal of `compute`.
+	pub fn new(compute: &ComputeV2) -> Self {
+		Self {
+			pre_hash: compute.pre_hash,
+			difficulty: compute.difficulty,
+			nonce: compute.nonce,
+			signing_message: compute.signing_message().into(),
+		}
+	}
+
+	/// SCALE encoded seal with `signature`.
+	pub fn seal(&self, signature: app::Signature) -> Bytes {
+		SealV2 {
+			difficulty: self.difficulty,
+			nonce: self.nonce,
+			signature,
+		}
+		.encode()
+		.into()
+	}
+}
+
+/// Parent block info of work.
+#[derive(Clone, Copy)]
+struct ParentInfo {
+	number: u64,
+	key_hash: H256,
+	algorithm: AlgorithmVersion,
+}
+
+/// Outcome of submitting a seal.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SubmitResult {
+	/// The seal meets the share difficulty only, and was counted.
+	Share,
+	/// The seal meets the network difficulty, and the block was imported.
+	Block,
+}
+
+/// Seal accepted as a share or block, as sent to share subscribers.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Share {
+	/// Best hash of the work the seal was mined for.
+	pub best_hash: H256,
+	/// Pre-hash of the work the seal was mined for.
+	pub pre_hash: H256,
+	/// Work hash of the seal.
+	pub work: H256,
+	/// Difficulty the seal was accepted at as a share.
+	pub share_difficulty: U256,
+	/// Whether the seal is a share or a block.
+	pub result: SubmitResult,
+}
+
+/// Counts of seals submitted since the node started.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkStats {
+	/// Seals meeting neither difficulty.
+	pub rejected: u64,
+	/// Seals that are invalid or wrongly signed.
+	pub invalid: u64,
+	/// Seals meeting the share difficulty only.
+	pub shares: u64,
+	/// Seals meeting the network difficulty that were imported.
+	pub blocks: u64,
+	/// Seals meeting the network difficulty whose import failed.
+	pub import_failed: u64,
+	/// Seals for work that had been replaced.
+	pub stale: u64,
+	/// Round-trip latencies of named workers.
+	#[serde(default)]
+	pub workers: BTreeMap<String, WorkerLatency>,
+}
+
+impl WorkStats {
+	fn record(&mut self, result: &std::result::Result<SubmitResult, WorkError>) {
+		let counter = match result {
+			Ok(SubmitResult::Share) => &mut self.shares,
+			Ok(SubmitResult::Block) => &mut self.blocks,
+			Err(WorkError::ImportFailed) => &mut self.import_failed,
+			Err(WorkError::StaleWork) => &mut self.stale,
+			Err(WorkError::LowDifficulty) => &mut self.rejected,
+			Err(WorkError::InvalidSeal) | Err(WorkError::BadSignature) => &mut self.invalid,
+			Err(_) => return,
+		};
+		*counter = counter.saturating_add(1);
+	}
+}
+
+/// Percentiles of the time between a worker first fetching work and
+/// submitting a seal for it, in milliseconds, over its recent submissions.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkerLatency {
+	/// Number of submissions the percentiles are taken over.
+	pub samples: u32,
+	/// Median latency.
+	pub p50: u64,
+	/// 90th percentile latency.
+	pub p90: u64,
+	/// 99th percentile latency.
+	pub p99: u64,
+}
+
+impl WorkerLatency {
+	/// Latency percentiles of `samples`, in milliseconds.
+	pub fn from_samples<'a>(samples: impl IntoIterator<Item = &'a u64>) -> Self {
+		let mut sorted = samples.into_iter().copied().collect::<Vec<_>>();
+		sorted.sort_unstable();
+
+		// Nearest-rank percentile.
+		let percentile = |p: usize| match sorted.len() {
+			0 => 0,
+			len => sorted[((len * p + 99) / 100).max(1) - 1],
+		};
+
+		Self {
+			samples: sorted.len() as u32,
+			p50: percentile(50),
+			p90: percentile(90),
+			p99: percentile(99),
+		}
+	}
+}
+
+/// Work issued to a worker, and its recent round-trip latencies.
+#[derive(Default)]
+struct WorkerTiming {
+	issued: Option<(H256, Instant)>,
+	latencies: VecDeque<u64>,
+}
+
+/// Round-trip latencies of named workers.
+#[derive(Default)]
+struct WorkerTimings(HashMap<String, WorkerTiming>);
+
+impl WorkerTimings {
+	fn worker(&mut self, worker: String) -> Option<&mut WorkerTiming> {
+		if worker.len() > MAX_WORKER_NAME_LEN
+			|| (self.0.len() >= MAX_TRACKED_WORKERS && !self.0.contains_key(&worker))
+		{
+			return None;
+		}
+
+		Some(self.0.entry(worker).or_default())
+	}
+
+	/// Remember when `worker` first fetched the work of `pre_hash`.
+	fn issue(&mut self, worker: String, pre_hash: H256) {
+		if let Some(timing) = self.worker(worker) {
+			match timing.issued {
+				Some((issued, _)) if issued == pre_hash => (),
+				_ => timing.issued = Some((pre_hash, Instant::now())),
+			}
+		}
+	}
+
+	/// Record the latency of a submission by `worker`, measured from when it
+	/// fetched its current work.
+	fn submit(&mut self, worker: String) {
+		if let Some(timing) = self.worker(worker) {
+			if let Some((_, issued)) = timing.issued {
+				if timing.latencies.len() >= LATENCY_SAMPLES {
+					timing.latencies.pop_front();
+				}
+				let latency = u64::try_from(issued.elapsed().as_millis()).unwrap_or(u64::MAX);
+				timing.latencies.push_back(latency);
+			}
+		}
+	}
+
+	fn latencies(&self) -> BTreeMap<String, WorkerLatency> {
+		self.0
+			.iter()
+			.filter(|(_, timing)| !timing.latencies.is_empty())
+			.map(|(worker, timing)| {
+				(
+					worker.clone(),
+					WorkerLatency::from_samples(&timing.latencies),
+				)
+			})
+			.collect()
+	}
+}
+
+/// Share difficulty for a network difficulty and divisor, never below one.
+pub fn share_difficulty(difficulty: U256, divisor: u64) -> U256 {
+	(difficulty / U256::from(divisor.max(1))).max(U256::one())
+}
+
+/// Share difficulty of a seal submission, defaulting to the one derived from
+/// `divisor`. A requested share difficulty is capped to the network difficulty.
+pub fn requested_share_difficulty(difficulty: U256, requested: Option<U256>, divisor: u64) -> U256 {
+	match requested {
+		Some(requested) => requested.min(difficulty).max(U256::one()),
+		None => share_difficulty(difficulty, divisor),
+	}
+}
+
+/// Source of accepted shares, for share subscriptions.
+pub trait WorkShares {
+	/// Stream of seals accepted from now on.
+	fn shares(&self) -> mpsc::UnboundedReceiver<Share>;
+}
+
+/// Work RPC methods.
+#[rpc]
+pub trait WorkApi {
+	/// Get the current mining work, if any. Work fetched by a named `worker`
+	/// starts its latency measurement.
+	#[rpc(name = "work_getCompute")]
+	fn get_compute(&self, worker: Option<String>) -> Result<Option<Compute>>;
+
+	/// Submit a seal for the current mining work. Only seals meeting the
+	/// network difficulty are imported. Seals are accepted as shares at
+	/// `share_difficulty` if given, or at the node's share difficulty otherwise.
+	/// Submissions by a named `worker` are counted in its latencies.
+	#[rpc(name = "work_submitSeal")]
+	fn submit_seal(
+		&self,
+		seal: Bytes,
+		share_difficulty: Option<U256>,
+		worker: Option<String>,
+	) -> Result<SubmitResult>;
+
+	/// Get counts of submitted seals, and latencies of named workers.
+	#[rpc(name = "work_getStats")]
+	fn get_stats(&self) -> Result<WorkStats>;
+
+	/// Get the V2 seal of the current mining work with `nonce`, for its
+	/// signing message to be signed by the author away from the node.
+	#[rpc(name = "work_getUnsignedSeal")]
+	fn get_unsigned_seal(&self, nonce: H256) -> Result<UnsignedSeal>;
+
+	/// Submit an unsigned seal with the `signature` of its signing message,
+	/// as `work_submitSeal` does.
+	#[rpc(name = "work_submitSignedSeal")]
+	fn submit_signed_seal(
+		&self,
+		seal: UnsignedSeal,
+		signature: Bytes,
+		share_difficulty: Option<U256>,
+		worker: Option<String>,
+	) -> Result<SubmitResult>;
+}
+
+/// Work subscription RPC methods.
+#[rpc]
+pub trait WorkPubSubApi {
+	/// RPC metadata.
+	type Metadata;
+
+	/// Subscribe to mining work. The current work is sent right away, and new
+	/// work whenever it changes, including key hash rotations.
+	#[pubsub(
+		subscription = "work_compute",
+		subscribe,
+		name = "work_subscribeCompute"
+	)]
+	fn subscribe_compute(&self, metadata: Self::Metadata, subscriber: Subscriber<Compute>);
+
+	/// Unsubscribe from mining work.
+	#[pubsub(
+		subscription = "work_compute",
+		unsubscribe,
+		name = "work_unsubscribeCompute"
+	)]
+	fn unsubscribe_compute(
+		&self,
+		metadata: Option<Self::Metadata>,
+		id: SubscriptionId,
+	) -> Result<bool>;
+
+	/// Subscribe to seals accepted as shares or blocks.
+	#[pubsub(subscription = "work_share", subscribe, name = "work_subscribeShares")]
+	fn subscribe_shares(&self, metadata: Self::Metadata, subscriber: Subscriber<Share>);
+
+	/// Unsubscribe from accepted seals.
+	#[pubsub(
+		subscription = "work_share",
+		unsubscribe,
+		name = "work_unsubscribeShares"
+	)]
+	fn unsubscribe_shares(
+		&self,
+		metadata: Option<Self::Metadata>,
+		id: SubscriptionId,
+	) -> Result<bool>;
+}
+
+/// Current and replaced mining work, to tell stale seals from invalid ones.
+#[derive(Default)]
+struct RecentWork {
+	current: Option<MiningMetadata<H256, U256>>,
+	replaced: Option<MiningMetadata<H256, U256>>,
+}
+
+/// Work RPC implementation backed by the node's mining worker.
+pub struct Work<B, Algorithm, C, L, Proof>
+where
+	B: BlockT,
+	Algorithm: PowAlgorithm<B>,
+	C: ProvideRuntimeApi<B>,
+	L: sc_consensus::JustificationSyncLink<B>,
+{
+	worker: MiningHandle<B, Algorithm, C, L, Proof>,
+	client: Arc<C>,
+	parent: Arc<Mutex<Option<(H256, ParentInfo)>>>,
+	recent: Arc<Mutex<RecentWork>>,
+	share_divisor: u64,
+	stats: Arc<Mutex<WorkStats>>,
+	timings: Arc<Mutex<WorkerTimings>>,
+	share_sinks: Arc<Mutex<Vec<mpsc::UnboundedSender<Share>>>>,
+	signer: Option<Arc<WorkSigner>>,
+}
+
+/// Signs work with the mining key. The last signature is kept, so the same
+/// work is not signed again with a different signature, and miners can keep
+/// comparing work for changes.
+struct WorkSigner {
+	pair: app::Pair,
+	last: Mutex<Option<(Vec<u8>, Bytes)>>,
+}
+
+impl WorkSigner {
+	fn sign(&self, payload: Vec<u8>) -> Bytes {
+		let mut last = self.last.lock();
+		match &*last {
+			Some((last_payload, signature)) if *last_payload == payload => signature.clone(),
+			_ => {
+				let signature: Bytes = self.pair.sign(&payload).as_ref().to_vec().into();
+				*last = Some((payload, signature.clone()));
+				signature
+			}
+		}
+	}
+}
+
+impl<B, Algorithm, C, L, Proof> Clone for Work<B, Algorithm, C, L, Proof>
+where
+	B: BlockT,
+	Algorithm: PowAlgorithm<B>,
+	C: ProvideRuntimeApi<B>,
+	L: sc_consensus::JustificationSyncLink<B>,
+{
+	fn clone(&self) -> Self {
+		Self {
+			worker: self.worker.clone(),
+			client: self.client.clone(),
+			parent: self.parent.clone(),
+			recent: self.recent.clone(),
+			share_divisor: self.share_divisor,
+			stats: self.stats.clone(),
+			timings: self.timings.clone(),
+			share_sinks: self.share_sinks.clone(),
+			signer: self.signer.clone(),
+		}
+	}
+}
+
+impl<B, Algorithm, C, L, Proof> Work<B, Algorithm, C, L, Proof>
+where
+	B: BlockT<Hash = H256>,
+	Algorithm: PowAlgorithm<B, Difficulty = U256>,
+	C: ProvideRuntimeApi<B> + HeaderBackend<B>,
+	L: sc_consensus::JustificationSyncLink<B>,
+	TransactionFor<C, B>: Send + 'static,
+{
+	/// Create a new work RPC from the mining worker. Shares are accepted at the
+	/// network difficulty divided by `share_divisor`.
+	pub fn new(
+		worker: MiningHandle<B, Algorithm, C, L, Proof>,
+		client: Arc<C>,
+		share_divisor: u64,
+	) -> Self {
+		Self {
+			worker,
+			client,
+			parent: Arc::new(Mutex::new(None)),
+			recent: Arc::new(Mutex::new(RecentWork::default())),
+			share_divisor,
+			stats: Arc::new(Mutex::new(WorkStats::default())),
+			timings: Arc::new(Mutex::new(WorkerTimings::default())),
+			share_sinks: Arc::new(Mutex::new(Vec::new())),
+			signer: None,
+		}
+	}
+
+	/// Sign work handed out with the mining key `signer`.
+	pub fn with_signer(mut self, signer: app::Pair) -> Self {
+		self.signer = Some(Arc::new(WorkSigner {
+			pair: signer,
+			last: Mutex::new(None),
+		}));
+		self
+	}
+
+	/// Remember `metadata` as the current work, and return the work it
+	/// replaced, if any.
+	fn replaced_work(
+		&self,
+		metadata: &MiningMetadata<H256, U256>,
+	) -> Option<MiningMetadata<H256, U256>> {
+		let mut recent = self.recent.lock();
+		if recent.current.as_ref() != Some(metadata) {
+			recent.replaced = recent.current.replace(metadata.clone());
+		}
+		recent.replaced.clone()
+	}
+}
+
+impl<B, Algorithm, C, L, Proof> Work<B, Algorithm, C, L, Proof>
+where
+	B: BlockT<Hash = H256>,
+	Algorithm: PowAlgorithm<B, Difficulty = U256>,
+	C: ProvideRuntimeApi<B> + HeaderBackend<B>,
+	C::Api: AlgorithmApi<B>,
+	L: sc_consensus::JustificationSyncLink<B>,
+	TransactionFor<C, B>: Send + 'static,
+{
+	/// Parent block info of work built on `best_hash`. The last result is
+	/// cached, as it only changes when the best block does.
+	fn parent_info(&self, best_hash: H256) -> std::result::Result<ParentInfo, WorkError> {
+		let mut cached = self.parent.lock();
+		match *cached {
+			Some((hash, info)) if hash == best_hash => Ok(info),
+			_ => {
+				let at = BlockId::Hash(best_hash);
+				let number = self
+					.client
+					.number(best_hash)
+					.map_err(WorkError::internal)?
+					.ok_or_else(|| WorkError::Internal("Best block not found".to_string()))?;
+				let algorithm = match kulupu_pow::algorithm_version(&*self.client, &at)
+					.map_err(WorkError::internal)?
+				{
+					RandomXAlgorithmVersion::V1 => AlgorithmVersion::V1,
+					RandomXAlgorithmVersion::V2 => AlgorithmVersion::V2,
+				};
+				let info = ParentInfo {
+					number: number.unique_saturated_into(),
+					key_hash: kulupu_pow::key_hash(&*self.client, &at)
+						.map_err(WorkError::internal)?,
+					algorithm,
+				};
+				*cached = Some((best_hash, info));
+				Ok(info)
+			}
+		}
+	}
+
+	fn compute(
+		&self,
+		metadata: MiningMetadata<H256, U256>,
+	) -> std::result::Result<Compute, WorkError> {
+		self.replaced_work(&metadata);
+		let parent = self.parent_info(metadata.best_hash)?;
+		let mut compute = Compute {
+			key_hash: parent.key_hash,
+			best_hash: metadata.best_hash,
+			best_number: parent.number,
+			algorithm: parent.algorithm,
+			seal_encoding: parent.algorithm.seal_encoding().to_string(),
+			pre_hash: metadata.pre_hash,
+			pre_runtime: metadata.pre_runtime.map(Into::into),
+			difficulty: metadata.difficulty,
+			share_difficulty: share_difficulty(metadata.difficulty, self.share_divisor),
+			signature: None,
+		};
+		if let Some(signer) = &self.signer {
+			compute.signature = Some(signer.sign(compute.signing_payload()));
+		}
+
+		Ok(compute)
+	}
+
+	/// Whether `seal` is a block or a share of `metadata` at `share_difficulty`.
+	fn check_work(
+		&self,
+		metadata: &MiningMetadata<H256, U256>,
+		seal: &Bytes,
+		share_difficulty: U256,
+	) -> std::result::Result<Share, WorkError> {
+		let work = kulupu_pow::seal_work(
+			&*self.client,
+			&BlockId::Hash(metadata.best_hash),
+			&metadata.pre_hash,
+			metadata.pre_runtime.as_deref(),
+			&seal.0,
+			metadata.difficulty,
+		)
+		.map_err(WorkError::internal)?;
+
+		let work = match work {
+			SealWork::Invalid => return Err(WorkError::InvalidSeal),
+			SealWork::BadSignature => return Err(WorkError::BadSignature),
+			SealWork::Valid(work) => work,
+		};
+		let result = if kulupu_pow::is_valid_hash(&work, metadata.difficulty) {
+			SubmitResult::Block
+		} else if kulupu_pow::is_valid_hash(&work, share_difficulty) {
+			SubmitResult::Share
+		} else {
+			return Err(WorkError::LowDifficulty);
+		};
+
+		Ok(Share {
+			best_hash: metadata.best_hash,
+			pre_hash: metadata.pre_hash,
+			work,
+			share_difficulty,
+			result,
+		})
+	}
+
+	fn check_seal(
+		&self,
+		seal: &Bytes,
+		share_difficulty: Option<U256>,
+	) -> std::result::Result<Share, WorkError> {
+		let metadata = self.worker.metadata().ok_or(WorkError::NoMetadata)?;
+		let replaced = self.replaced_work(&metadata);
+		let share_difficulty =
+			requested_share_difficulty(metadata.difficulty, share_difficulty, self.share_divisor);
+
+		match self.check_work(&metadata, seal, share_difficulty) {
+			Ok(share) if share.result == SubmitResult::Block => {
+				if futures::executor::block_on(self.worker.submit(seal.to_vec())) {
+					Ok(share)
+				} else {
+					Err(WorkError::ImportFailed)
+				}
+			}
+			// A seal that is invalid for the current work may be valid for the
+			// work it replaced.
+			Err(WorkError::InvalidSeal) => match replaced {
+				Some(replaced) if self.check_work(&replaced, seal, share_difficulty).is_ok() => {
+					Err(WorkError::StaleWork)
+				}
+				_ => Err(WorkError::InvalidSeal),
+			},
+			result => result,
+		}
+	}
+}
+
+impl<B, Algorithm, C, L, Proof> WorkShares for Work<B, Algorithm, C, L, Proof>
+where
+	B: BlockT,
+	Algorithm: PowAlgorithm<B>,
+	C: ProvideRuntimeApi<B>,
+	L: sc_consensus::JustificationSyncLink<B>,
+{
+	fn shares(&self) -> mpsc::UnboundedReceiver<Share> {
+		let (sink, stream) = mpsc::unbounded();
+		self.share_sinks.lock().push(sink);
+		stream
+	}
+}
+
+impl<B, Algorithm, C, L, Proof> WorkApi for Work<B, Algorithm, C, L, Proof>
+where
+	B: BlockT<Hash = H256>,
+	Algorithm: PowAlgorithm<B, Difficulty = U256> + Send + Sync + 'static,
+	C: ProvideRuntimeApi<B> + HeaderBackend<B> + Send + Sync + 'static,
+	C::Api: AlgorithmApi<B>,
+	L: sc_consensus::JustificationSyncLink<B> + 'static,
+	Proof: Send + 'static,
+	TransactionFor<C, B>: Send + 'static,
+{
+	fn get_compute(&self, worker: Option<String>) -> Result<Option<Compute>> {
+		let compute = self
+			.worker
+			.metadata()
+			.map(|metadata| self.compute(metadata))
+			.transpose()?;
+		if let (Some(worker), Some(compute)) = (worker, &compute) {
+			self.timings.lock().issue(worker, compute.pre_hash);
+		}
+		Ok(compute)
+	}
+
+	fn submit_seal(
+		&self,
+		seal: Bytes,
+		share_difficulty: Option<U256>,
+		worker: Option<String>,
+	) -> Result<SubmitResult> {
+		if let Some(worker) = worker {
+			self.timings.lock().submit(worker);
+		}
+		let result = self.check_seal(&seal, share_difficulty);
+		debug!(target: "kulupu-work", "Submitted seal: {:?}", result);
+		if let Ok(share) = &result {
+			self.share_sinks
+				.lock()
+				.retain(|sink| sink.unbounded_send(share.clone()).is_ok());
+		}
+		let result = result.map(|share| share.result);
+		self.stats.lock().record(&result);
+		result.map_err(Into::into)
+	}
+
+	fn get_stats(&self) -> Result<WorkStats> {
+		let mut stats = self.stats.lock().clone();
+		stats.workers = self.timings.lock().latencies();
+		Ok(stats)
+	}
+
+	fn get_unsigned_seal(&self, nonce: H256) -> Result<UnsignedSeal> {
+		let metadata = self.worker.metadata().ok_or(WorkError::NoMetadata)?;
+		let parent = self.parent_info(metadata.best_hash)?;
+		if parent.algorithm != AlgorithmVersion::V2 {
+			return Err(WorkError::UnsignedWork.into());
+		}
+
+		Ok(UnsignedSeal::new(&ComputeV2 {
+			key_hash: parent.key_hash,
+			pre_hash: metadata.pre_hash,
+			difficulty: metadata.difficulty,
+			nonce,
+		}))
+	}
+
+	fn submit_signed_seal(
+		&self,
+		seal: UnsignedSeal,
+		signature: Bytes,
+		share_difficulty: Option<U256>,
+		worker: Option<String>,
+	) -> Result<SubmitResult> {
+		let signature = match app::Signature::try_from(&signature.0[..]) {
+			Ok(signature) => signature,
+			Err(()) => {
+				let result = Err(WorkError::BadSignature);
+				self.stats.lock().record(&result);
+				return result.map_err(Into::into);
+			}
+		};
+
+		self.submit_seal(seal.seal(signature), share_difficulty, worker)
+	}
+}
+
+/// Work subscription RPC implementation.
+pub struct WorkPubSub<W> {
+	work: W,
+	manager: SubscriptionManager,
+}
+
+impl<W> WorkPubSub<W> {
+	/// Create a new work subscription RPC.
+	pub fn new(work: W, executor: SubscriptionTaskExecutor) -> Self {
+		Self {
+			work,
+			manager: SubscriptionManager::new(Arc::new(executor)),
+		}
+	}
+}
+
+impl<W: WorkApi + WorkShares + Clone> WorkPubSubApi for WorkPubSub<W> {
+	type Metadata = sc_rpc::Metadata;
+
+	fn subscribe_compute(&self, _metadata: Self::Metadata, subscriber: Subscriber<Compute>) {
+		let computes =
+			futures::stream::unfold((self.work.clone(), None), |(work, last)| async move {
+				loop {
+					match work.get_compute(None) {
+						Ok(Some(compute)) if last.as_ref() != Some(&compute) => {
+							return Some((compute.clone(), (work, Some(compute))))
+						}
+						Ok(_) => (),
+						Err(err) => warn!(target: "kulupu-work", "Fetching work failed: {:?}", err),
+					}
+					futures_timer::Delay::new(SUBSCRIPTION_POLL_INTERVAL).await;
+				}
+			});
+
+		self.manager.add(subscriber, |sink| {
+			computes
+				.map(|compute| Ok(Ok(compute)))
+				.forward(sink.sink_map_err(
+					|e| warn!(target: "kulupu-work", "Error sending notifications: {:?}", e),
+				))
+				.map(|_| ())
+		});
+	}
+
+	fn unsubscribe_compute(
+		&self,
+		_metadata: Option<Self::Metadata>,
+		id: SubscriptionId,
+	) -> Result<bool> {
+		Ok(self.manager.cancel(id))
+	}
+
+	fn subscribe_shares(&self, _metadata: Self::Metadata, subscriber: Subscriber<Share>) {
+		let shares = self.work.shares();
+
+		self.manager.add(subscriber, |sink| {
+			shares
+				.map(|share| Ok(Ok(share)))
+				.forward(sink.sink_map_err(
+					|e| warn!(target: "kulupu-work", "Error sending notifications: {:?}", e),
+				))
+				.map(|_| ())
+		});
+	}
+
+	fn unsubscribe_shares(
+		&self,
+		_metadata: Option<Self::Metadata>,
+		id: SubscriptionId,
+	) -> Result<bool> {
+		Ok(self.manager.cancel(id))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use codec::Decode;
+
+	#[test]
+	fn signed_work_verifies() {
+		let pair = app::Pair::from_seed(&[1; 32]);
+		let mut compute = Compute {
+			best_hash: H256::repeat_byte(1),
+			best_number: 1,
+			algorithm: AlgorithmVersion::V2,
+			seal_encoding: AlgorithmVersion::V2.seal_encoding().to_string(),
+			key_hash: H256::repeat_byte(2),
+			pre_hash: H256::repeat_byte(3),
+			pre_runtime: Some(pair.public().encode().into()),
+			difficulty: U256::from(1_000),
+			share_difficulty: U256::from(100),
+			signature: None,
+		};
+		assert!(!compute.verify_signature(&pair.public()));
+
+		let signature = pair.sign(&compute.signing_payload());
+		compute.signature = Some(signature.as_ref().to_vec().into());
+		assert!(compute.verify_signature(&pair.public()));
+
+		let other = app::Pair::from_seed(&[2; 32]);
+		assert!(!compute.verify_signature(&other.public()));
+
+		compute.pre_runtime = Some(other.public().encode().into());
+		assert!(!compute.verify_signature(&pair.public()));
+	}
+
+	#[test]
+	fn signed_unsigned_seal_verifies() {
+		let pair = app::Pair::from_seed(&[1; 32]);
+		let compute = ComputeV2 {
+			key_hash: H256::repeat_byte(1),
+			pre_hash: H256::repeat_byte(2),
+			difficulty: U256::from(1_000),
+			nonce: H256::repeat_byte(3),
+		};
+		let unsigned = UnsignedSeal::new(&compute);
+
+		let signature = pair.sign(&unsigned.signing_message[..]);
+		assert!(compute.verify(&signature, &pair.public()));
+		assert_eq!(
+			SealV2::decode(&mut &unsigned.seal(signature.clone()).0[..]).unwrap(),
+			compute.seal(signature)
+		);
+	}
+
+	#[test]
+	fn share_difficulty_divides_network_difficulty() {
+		assert_eq!(share_difficulty(U256::from(1_000), 1), U256::from(1_000));
+		assert_eq!(share_difficulty(U256::from(1_000), 8), U256::from(125));
+		assert_eq!(share_difficulty(U256::from(1_000), 0), U256::from(1_000));
+		assert_eq!(share_difficulty(U256::from(3), 10), U256::one());
+	}
+
+	#[test]
+	fn requested_share_difficulty_is_capped() {
+		let difficulty = U256::from(1_000);
+		assert_eq!(
+			requested_share_difficulty(difficulty, None, 8),
+			U256::from(125)
+		);
+		assert_eq!(
+			requested_share_difficulty(difficulty, Some(U256::from(10)), 8),
+			U256::from(10)
+		);
+		assert_eq!(
+			requested_share_difficulty(difficulty, Some(U256::from(5_000)), 8),
+			difficulty
+		);
+		assert_eq!(
+			requested_share_difficulty(difficulty, Some(U256::zero()), 8),
+			U256::one()
+		);
+	}
+
+	#[test]
+	fn errors_have_distinct_codes() {
+		let error: Error = WorkError::StaleWork.into();
+		assert_eq!(error.code, ErrorCode::ServerError(1002));
+		assert_eq!(error.data, None);
+
+		let error: Error = WorkError::Internal("failed".into()).into();
+		assert_eq!(error.code, ErrorCode::ServerError(1004));
+		assert_eq!(error.data, Some("failed".into()));
+
+		let error: Error = WorkError::LowDifficulty.into();
+		assert_eq!(error.code, ErrorCode::ServerError(1007));
+	}
+
+	#[test]
+	fn stats_record_results() {
+		let mut stats = WorkStats::default();
+		stats.record(&Ok(SubmitResult::Share));
+		stats.record(&Ok(SubmitResult::Share));
+		stats.record(&Ok(SubmitResult::Block));
+		stats.record(&Err(WorkError::LowDifficulty));
+		stats.record(&Err(WorkError::StaleWork));
+		stats.record(&Err(WorkError::BadSignature));
+		stats.record(&Err(WorkError::NoMetadata));
+		assert_eq!(
+			stats,
+			WorkStats {
+				rejected: 1,
+				invalid: 1,
+				shares: 2,
+				blocks: 1,
+				import_failed: 0,
+				stale: 1,
+				workers: BTreeMap::new(),
+			}
+		);
+	}
+
+	#[test]
+	fn worker_latency_percentiles() {
+		assert_eq!(WorkerLatency::from_samples(&[]), WorkerLatency::default());
+
+		let samples = (1..=100).rev().collect::<Vec<u64>>();
+		assert_eq!(
+			WorkerLatency::from_samples(&samples),
+			WorkerLatency {
+				samples: 100,
+				p50: 50,
+				p90: 90,
+				p99: 99,
+			}
+		);
+
+		assert_eq!(
+			WorkerLatency::from_samples(&[7]),
+			WorkerLatency {
+				samples: 1,
+				p50: 7,
+				p90: 7,
+				p99: 7,
+			}
+		);
+	}
+
+	#[test]
+	fn worker_timings_are_bounded() {
+		let mut timings = WorkerTimings::default();
+		timings.submit("unknown".into());
+		assert!(timings.latencies().is_empty());
+
+		timings.issue("rig".into(), H256::repeat_byte(1));
+		for _ in 0..LATENCY_SAMPLES + 10 {
+			timings.submit("rig".into());
+		}
+		assert_eq!(timings.latencies()["rig"].samples as usize, LATENCY_SAMPLES);
+
+		timings.issue("x".repeat(MAX_WORKER_NAME_LEN + 1), H256::repeat_byte(1));
+		assert!(timings
+			.worker("x".repeat(MAX_WORKER_NAME_LEN + 1))
+			.is_none());
+	}
+}
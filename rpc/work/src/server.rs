@@ -0,0 +1,206 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Kulupu.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Kulupu is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Kulupu is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Kulupu. If not, see <http://www.gnu.org/licenses/>.
+
+//! Push-based work server for external miners.
+//!
+//! Miners connect over TCP and exchange line-delimited JSON-RPC messages. Each
+//! line sent by a miner is handled as a `work_*` request, and the response is
+//! written back as a single line. Whenever the mining work changes, the server
+//! pushes a `work_notify` notification with the new [`Compute`] to all
+//! connected miners, so they do not have to poll `work_getCompute`.
+
+use crate::{Compute, WorkApi};
+use jsonrpc_core::IoHandler;
+use log::*;
+use parking_lot::Mutex;
+use std::{
+	io::{self, BufRead, BufReader, Read, Write},
+	net::{SocketAddr, TcpListener, TcpStream},
+	sync::{
+		atomic::{AtomicUsize, Ordering},
+		Arc,
+	},
+	thread,
+	time::Duration,
+};
+
+/// Interval at which the server checks for new work.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// Time after which writes to a miner that stopped reading fail.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+/// Maximum size of a request line, in bytes.
+const MAX_REQUEST_SIZE: u64 = 64 * 1024;
+/// Maximum number of connected miners.
+const MAX_CONNECTIONS: usize = 256;
+
+type Connection = Arc<Mutex<TcpStream>>;
+type Connections = Arc<Mutex<Vec<Connection>>>;
+
+/// Start the work server on `addr`, serving `work`.
+pub fn start<W: WorkApi + Clone>(addr: SocketAddr, work: W) -> io::Result<()> {
+	let listener = TcpListener::bind(addr)?;
+	let connections = Connections::default();
+	let active = Arc::new(AtomicUsize::new(0));
+
+	let mut io = IoHandler::default();
+	io.extend_with(work.clone().to_delegate());
+	let io = Arc::new(io);
+
+	{
+		let work = work.clone();
+		let connections = connections.clone();
+		thread::spawn(move || notify_loop(work, connections));
+	}
+
+	thread::spawn(move || {
+		for stream in listener.incoming() {
+			match stream {
+				Ok(stream) => {
+					if active.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+						active.fetch_sub(1, Ordering::SeqCst);
+						warn!(target: "kulupu-work", "Refusing miner: too many connections");
+						continue;
+					}
+
+					let work = work.clone();
+					let io = io.clone();
+					let connections = connections.clone();
+					let active = active.clone();
+					thread::spawn(move || {
+						if let Err(err) = handle_connection(stream, work, io, connections) {
+							debug!(target: "kulupu-work", "Miner connection closed: {:?}", err);
+						}
+						active.fetch_sub(1, Ordering::SeqCst);
+					});
+				}
+				Err(err) => warn!(target: "kulupu-work", "Accepting miner failed: {:?}", err),
+			}
+		}
+	});
+
+	info!(target: "kulupu-work", "Work server listening on {}", addr);
+	Ok(())
+}
+
+fn notification(compute: &Compute) -> String {
+	serde_json::json!({
+		"jsonrpc": "2.0",
+		"method": "work_notify",
+		"params": [compute],
+	})
+	.to_string()
+}
+
+fn write_line(connection: &Connection, line: &str) -> io::Result<()> {
+	let mut stream = connection.lock();
+	stream.write_all(line.as_bytes())?;
+	stream.write_all(b"\n")?;
+	stream.flush()
+}
+
+fn notify_loop<W: WorkApi>(work: W, connections: Connections) {
+	let mut last = None;
+
+	loop {
+		thread::sleep(POLL_INTERVAL);
+
+		let compute = match work.get_compute(None) {
+			Ok(compute) => compute,
+			Err(err) => {
+				warn!(target: "kulupu-work", "Fetching work failed: {:?}", err);
+				continue;
+			}
+		};
+
+		if compute == last {
+			continue;
+		}
+
+		if let Some(compute) = &compute {
+			let notification = notification(compute);
+			// Miners are written to outside of the lock, so that slow ones do not
+			// hold up connecting and disconnecting miners.
+			let failed = connections
+				.lock()
+				.clone()
+				.into_iter()
+				.filter(|connection| write_line(connection, &notification).is_err())
+				.collect::<Vec<_>>();
+			if !failed.is_empty() {
+				connections
+					.lock()
+					.retain(|connection| !failed.iter().any(|f| Arc::ptr_eq(f, connection)));
+			}
+		}
+		last = compute;
+	}
+}
+
+fn handle_connection<W: WorkApi>(
+	stream: TcpStream,
+	work: W,
+	io: Arc<IoHandler>,
+	connections: Connections,
+) -> io::Result<()> {
+	stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+	let connection = Arc::new(Mutex::new(stream.try_clone()?));
+
+	if let Ok(Some(compute)) = work.get_compute(None) {
+		write_line(&connection, &notification(&compute))?;
+	}
+	connections.lock().push(connection.clone());
+
+	let mut reader = BufReader::new(stream);
+	let mut line = String::new();
+	let result = loop {
+		match read_request(&mut reader, &mut line) {
+			Ok(true) => (),
+			Ok(false) => break Ok(()),
+			Err(err) => break Err(err),
+		}
+		if line.trim().is_empty() {
+			continue;
+		}
+
+		if let Some(response) = io.handle_request_sync(&line) {
+			if let Err(err) = write_line(&connection, &response) {
+				break Err(err);
+			}
+		}
+	};
+
+	connections
+		.lock()
+		.retain(|other| !Arc::ptr_eq(other, &connection));
+	result
+}
+
+/// Read a request line of at most `MAX_REQUEST_SIZE` bytes into `line`.
+/// Returns `false` once the miner closed the connection.
+fn read_request<R: BufRead>(reader: &mut R, line: &mut String) -> io::Result<bool> {
+	line.clear();
+	let read = reader.take(MAX_REQUEST_SIZE).read_line(line)?;
+	if read as u64 == MAX_REQUEST_SIZE && !line.ends_with('\n') {
+		return Err(io::Error::new(
+			io::ErrorKind::InvalidData,
+			"Request exceeds the maximum size",
+		));
+	}
+
+	Ok(read > 0)
+}
@@ -0,0 +1,65 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Kulupu.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Kulupu is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Kulupu is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Kulupu. If not, see <http://www.gnu.org/licenses/>.
+
+//! Chain extension exposing mining data to contracts.
+//!
+//! Contracts such as hashrate oracles and mining derivatives can read the
+//! current difficulty, block reward and block author. Each function takes no
+//! input and writes its SCALE encoded result to the output buffer:
+//!
+//! - `1`: current difficulty, as `U256`.
+//! - `2`: current block reward, as `Balance`.
+//! - `3`: current block author or its payout account, as `Option<AccountId>`.
+
+use crate::{Rewards, RocksDbWeight, Runtime};
+use codec::Encode;
+use contracts::chain_extension::{
+	ChainExtension, Environment, Ext, InitState, RetVal, SysConfig, UncheckedFrom,
+};
+use sp_runtime::DispatchError;
+
+/// Function id of the current difficulty.
+pub const DIFFICULTY: u32 = 1;
+/// Function id of the current block reward.
+pub const BLOCK_REWARD: u32 = 2;
+/// Function id of the current block author.
+pub const AUTHOR: u32 = 3;
+
+/// Kulupu chain extension for contracts.
+pub struct KulupuExtension;
+
+impl ChainExtension<Runtime> for KulupuExtension {
+	fn call<E>(func_id: u32, env: Environment<E, InitState>) -> Result<RetVal, DispatchError>
+	where
+		E: Ext<T = Runtime>,
+		<E::T as SysConfig>::AccountId: UncheckedFrom<<E::T as SysConfig>::Hash> + AsRef<[u8]>,
+	{
+		let output = match func_id {
+			DIFFICULTY => difficulty::Module::<Runtime>::difficulty().encode(),
+			BLOCK_REWARD => Rewards::reward().encode(),
+			AUTHOR => Rewards::author().encode(),
+			_ => return Err(DispatchError::Other("Unknown chain extension function")),
+		};
+
+		let mut env = env.buf_in_buf_out();
+		env.charge_weight(RocksDbWeight::get().reads(1))?;
+		env.write(&output, false, None)?;
+
+		Ok(RetVal::Converging(0))
+	}
+}
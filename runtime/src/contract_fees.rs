@@ -0,0 +1,120 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Kulupu.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Kulupu is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Kulupu is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Kulupu. If not, see <http://www.gnu.org/licenses/>.
+
+//! Transaction fees of contract calls.
+//!
+//! Base fees are burned, which leaves miners nothing for including gas-heavy
+//! contract calls in full blocks. Governance can route a share of the fees of
+//! contract calls to the block author instead, paid along with the tips.
+
+use crate::{
+	AccountId, Balance, Balances, Call, DealWithFees, NegativeImbalance, Rewards, Runtime,
+};
+use frame_support::traits::{Get, Imbalance, OnUnbalanced};
+use sp_runtime::{
+	traits::{DispatchInfoOf, PostDispatchInfoOf},
+	transaction_validity::TransactionValidityError,
+	Perbill,
+};
+use sp_std::cmp::min;
+use transaction_payment::{CurrencyAdapter, OnChargeTransaction};
+
+/// Share of contract call fees paid to the block author, set by governance in
+/// percent with the `runtime::contracts::author_fee_percent` variable. All of
+/// them are burned by default.
+pub enum ContractFeeAuthorShare {}
+impl Get<Perbill> for ContractFeeAuthorShare {
+	fn get() -> Perbill {
+		let var =
+			variables::U32s::get(b"runtime::contracts::author_fee_percent".to_vec()).unwrap_or(0);
+		Perbill::from_percent(min(var, 100))
+	}
+}
+
+/// Burns the fees of contract calls except for `ContractFeeAuthorShare`,
+/// which is paid to the block author with the tips.
+pub struct DealWithContractFees;
+impl OnUnbalanced<NegativeImbalance> for DealWithContractFees {
+	fn on_unbalanceds<B>(mut fees_then_tips: impl Iterator<Item = NegativeImbalance>) {
+		if let Some(fees) = fees_then_tips.next() {
+			let to_author = ContractFeeAuthorShare::get() * fees.peek();
+			let (to_author, burned) = fees.split(to_author);
+			drop(burned);
+
+			let to_author = match fees_then_tips.next() {
+				Some(tips) => to_author.merge(tips),
+				None => to_author,
+			};
+			Rewards::on_unbalanced(to_author);
+		}
+	}
+}
+
+type ChargeFees = CurrencyAdapter<Balances, DealWithFees>;
+type ChargeContractFees = CurrencyAdapter<Balances, DealWithContractFees>;
+
+/// Charges transaction fees from the balance of the sender, dealing with the
+/// fees of contract calls with `DealWithContractFees` and the others with
+/// `DealWithFees`.
+pub struct ChargeTransactionFees;
+impl OnChargeTransaction<Runtime> for ChargeTransactionFees {
+	type Balance = Balance;
+	/// Withdrawn fee, and whether it is for a contract call.
+	type LiquidityInfo = (Option<NegativeImbalance>, bool);
+
+	fn withdraw_fee(
+		who: &AccountId,
+		call: &Call,
+		info: &DispatchInfoOf<Call>,
+		fee: Balance,
+		tip: Balance,
+	) -> Result<Self::LiquidityInfo, TransactionValidityError> {
+		let withdrawn =
+			<ChargeFees as OnChargeTransaction<Runtime>>::withdraw_fee(who, call, info, fee, tip)?;
+		Ok((withdrawn, matches!(call, Call::Contracts(..))))
+	}
+
+	fn correct_and_deposit_fee(
+		who: &AccountId,
+		info: &DispatchInfoOf<Call>,
+		post_info: &PostDispatchInfoOf<Call>,
+		corrected_fee: Balance,
+		tip: Balance,
+		(withdrawn, contract): Self::LiquidityInfo,
+	) -> Result<(), TransactionValidityError> {
+		if contract {
+			<ChargeContractFees as OnChargeTransaction<Runtime>>::correct_and_deposit_fee(
+				who,
+				info,
+				post_info,
+				corrected_fee,
+				tip,
+				withdrawn,
+			)
+		} else {
+			<ChargeFees as OnChargeTransaction<Runtime>>::correct_and_deposit_fee(
+				who,
+				info,
+				post_info,
+				corrected_fee,
+				tip,
+				withdrawn,
+			)
+		}
+	}
+}
@@ -22,7 +22,12 @@
 // `construct_runtime!` does a lot of recursion and requires us to increase the limit to 256.
 #![recursion_limit = "256"]
 
+mod chain_extension;
+mod contract_fees;
 mod fee;
+mod miner_priority;
+mod transferable;
+mod treasury_status;
 mod weights;
 
 extern crate system as frame_system;
@@ -35,7 +40,7 @@ use crate::fee::WeightToFee;
 use codec::{Decode, Encode, MaxEncodedLen};
 use contracts::weights::WeightInfo;
 use frame_support::{
-	traits::{Everything, Nothing},
+	traits::{Contains, Everything, Nothing, WithdrawReasons},
 	PalletId,
 };
 use kulupu_primitives::{deposit, BLOCK_TIME, CENTS, DAYS, DOLLARS, HOURS, MICROCENTS, MILLICENTS};
@@ -46,11 +51,12 @@ use sp_core::{
 	OpaqueMetadata,
 };
 use sp_runtime::traits::{
-	BlakeTwo256, Block as BlockT, Convert, ConvertInto, IdentifyAccount, Verify,
+	AccountIdConversion, BlakeTwo256, Block as BlockT, Convert, ConvertInto, IdentifyAccount,
+	Verify,
 };
 use sp_runtime::{
 	create_runtime_str, generic,
-	transaction_validity::{TransactionSource, TransactionValidity},
+	transaction_validity::{TransactionPriority, TransactionSource, TransactionValidity},
 	ApplyExtrinsicResult, FixedPointNumber, MultiSignature, Percent, Perquintill, RuntimeDebug,
 };
 use sp_std::{
@@ -129,10 +135,10 @@ pub const VERSION: RuntimeVersion = RuntimeVersion {
 	spec_name: create_runtime_str!("kulupu"),
 	impl_name: create_runtime_str!("kulupu"),
 	authoring_version: 5,
-	spec_version: 28,
+	spec_version: 29,
 	impl_version: 0,
 	apis: RUNTIME_API_VERSIONS,
-	transaction_version: 10,
+	transaction_version: 11,
 };
 
 /// The version infromation used to identify this runtime when compiled natively.
@@ -311,7 +317,7 @@ impl OnUnbalanced<NegativeImbalance> for DealWithFees {
 			drop(fees);
 			if let Some(tips) = fees_then_tips.next() {
 				// Pay tips to miners.
-				Author::on_unbalanced(tips);
+				Rewards::on_unbalanced(tips);
 			}
 		}
 	}
@@ -333,7 +339,7 @@ parameter_types! {
 }
 
 impl transaction_payment::Config for Runtime {
-	type OnChargeTransaction = transaction_payment::CurrencyAdapter<Balances, DealWithFees>;
+	type OnChargeTransaction = contract_fees::ChargeTransactionFees;
 	type TransactionByteFee = TransactionByteFee;
 	type OperationalFeeMultiplier = OperationalFeeMultiplier;
 	type WeightToFee = WeightToFee;
@@ -351,6 +357,104 @@ impl difficulty::Config for Runtime {
 
 impl eras::Config for Runtime {}
 
+pub struct EraBalances;
+
+impl era_claims::EraBalances<AccountId, Balance> for EraBalances {
+	fn storage_key(era: u32, who: &AccountId) -> Option<Vec<u8>> {
+		match era {
+			// Era 0 kept balances in `Balances FreeBalance`, hashed with `blake2_256`.
+			0 => Some(
+				[
+					&sp_io::hashing::twox_128(b"Balances")[..],
+					&sp_io::hashing::twox_128(b"FreeBalance")[..],
+					&sp_io::hashing::blake2_256(who.as_ref())[..],
+				]
+				.concat(),
+			),
+			_ => None,
+		}
+	}
+
+	fn decode_balance(_era: u32, value: &[u8]) -> Option<Balance> {
+		Balance::decode(&mut &value[..]).ok()
+	}
+}
+
+impl era_claims::Config for Runtime {
+	type Event = Event;
+	type Currency = Balances;
+	type EraBalances = EraBalances;
+	/// Era 0 balances were already carried over at genesis.
+	type OnClaim = ();
+}
+
+impl upgrade_announcement::Config for Runtime {
+	type Event = Event;
+	/// A straight majority of the council can announce upgrades.
+	type AnnounceOrigin = system::EnsureOneOf<
+		AccountId,
+		collective::EnsureProportionMoreThan<_1, _2, AccountId, CouncilCollective>,
+		system::EnsureRoot<AccountId>,
+	>;
+}
+
+/// Calls setting PoW parameters, the only ones the PoW governance track can
+/// dispatch.
+pub struct PowParameterCalls;
+impl Contains<Call> for PowParameterCalls {
+	fn contains(call: &Call) -> bool {
+		matches!(
+			call,
+			Call::Rewards(rewards::Call::set_lock_params { .. })
+				| Call::Difficulty(difficulty::Call::set_algorithm { .. })
+				| Call::Difficulty(difficulty::Call::set_median_time_past_blocks { .. })
+		)
+	}
+}
+
+impl pow_governance::Config for Runtime {
+	type Event = Event;
+	type Call = Call;
+	type AllowedCalls = PowParameterCalls;
+	/// A straight majority of the council proposes PoW parameter changes.
+	type ProposeOrigin = system::EnsureOneOf<
+		AccountId,
+		collective::EnsureProportionMoreThan<_1, _2, AccountId, CouncilCollective>,
+		system::EnsureRoot<AccountId>,
+	>;
+	/// A straight majority of the technical committee approves them.
+	type ApproveOrigin = system::EnsureOneOf<
+		AccountId,
+		collective::EnsureProportionMoreThan<_1, _2, AccountId, TechnicalCollective>,
+		system::EnsureRoot<AccountId>,
+	>;
+	/// Either body can cancel them.
+	type CancelOrigin = system::EnsureOneOf<
+		AccountId,
+		collective::EnsureProportionMoreThan<_1, _2, AccountId, CouncilCollective>,
+		collective::EnsureProportionMoreThan<_1, _2, AccountId, TechnicalCollective>,
+	>;
+}
+
+parameter_types! {
+	pub const UtilizationHistoryLength: u32 = DAYS;
+}
+
+impl metrics::Config for Runtime {
+	type HistoryLength = UtilizationHistoryLength;
+}
+
+/// Part of each block reward that is liquid immediately, set by governance in
+/// cents. The rest is locked.
+pub enum LiquidReward {}
+impl frame_support::traits::Get<Balance> for LiquidReward {
+	fn get() -> Balance {
+		let var =
+			variables::U32s::get(b"runtime::rewards::liquid_reward_cents".to_vec()).unwrap_or(100);
+		Balance::from(var) * CENTS
+	}
+}
+
 pub struct GenerateRewardLocks;
 
 impl rewards::GenerateRewardLocks<Runtime> for GenerateRewardLocks {
@@ -360,7 +464,7 @@ impl rewards::GenerateRewardLocks<Runtime> for GenerateRewardLocks {
 		lock_parameters: Option<rewards::LockParameters>,
 	) -> BTreeMap<BlockNumber, Balance> {
 		let mut locks = BTreeMap::new();
-		let locked_reward = total_reward.saturating_sub(1 * DOLLARS);
+		let locked_reward = total_reward.saturating_sub(LiquidReward::get());
 
 		if locked_reward > 0 {
 			let total_lock_period: BlockNumber;
@@ -398,6 +502,18 @@ parameter_types! {
 	pub DonationDestination: AccountId = Treasury::account_id();
 	pub const LockBounds: rewards::LockBounds = rewards::LockBounds {period_max: 500, period_min: 20,
 																	divide_max: 50, divide_min: 2};
+	/// Locked rewards can still be used to pay transaction fees without tips.
+	/// Tipped fees are withdrawn for all reasons, so the lock applies to them.
+	pub RewardLockExemptReasons: WithdrawReasons = WithdrawReasons::TRANSACTION_PAYMENT;
+	/// Daily miners keep at most this many reward locks, in buckets of a few days.
+	pub const MaxRewardLocks: u32 = 100;
+	pub const MaxPayoutDestinations: u32 = 8;
+	/// Bounds of the reward schedule, well above any schedule set so far.
+	pub const MaxMints: u32 = 64;
+	pub const MaxRewardChanges: u32 = 1024;
+	pub const MaxMintChanges: u32 = 1024;
+	/// Expired reward locks of this many accounts are unlocked each block.
+	pub const MaxAutoUnlocks: u32 = 16;
 }
 
 impl rewards::Config for Runtime {
@@ -407,17 +523,44 @@ impl rewards::Config for Runtime {
 	type GenerateRewardLocks = GenerateRewardLocks;
 	type WeightInfo = crate::weights::rewards::WeightInfo<Self>;
 	type LockParametersBounds = LockBounds;
+	type LockExemptReasons = RewardLockExemptReasons;
+	type SplitReward = Pool;
+	type MaxRewardLocks = MaxRewardLocks;
+	type MaxPayoutDestinations = MaxPayoutDestinations;
+	type MaxMints = MaxMints;
+	type MaxRewardChanges = MaxRewardChanges;
+	type MaxMintChanges = MaxMintChanges;
+	type MaxAutoUnlocks = MaxAutoUnlocks;
+	type RecentAuthorBlocks = RecentAuthorBlocks;
 }
 
-pub struct Author;
-impl OnUnbalanced<NegativeImbalance> for Author {
-	fn on_nonzero_unbalanced(amount: NegativeImbalance) {
-		if let Some(author) = Rewards::author() {
-			Balances::resolve_creating(&author, amount);
-		} else {
-			drop(amount);
-		}
-	}
+parameter_types! {
+	/// Blocks since an account last authored for its calls to be prioritized.
+	pub const RecentAuthorBlocks: BlockNumber = DAYS;
+	/// Priority added to reward unlocks and referendum votes of recent authors, as
+	/// much as a tip of one cent adds to a transaction filling a block, so that
+	/// tipped transactions can still outrank them.
+	pub const MinerCallPriority: TransactionPriority = CENTS as TransactionPriority;
+}
+
+parameter_types! {
+	pub const PoolDeposit: Balance = 100 * DOLLARS;
+	pub const MaxPoolMembers: u32 = 100;
+	/// Weight of paying a block reward to a pool member with a full payout split.
+	pub PoolMemberPayoutWeight: Weight = {
+		use rewards::WeightInfo;
+		let payout = |p| <Runtime as rewards::Config>::WeightInfo::on_finalize(p);
+		payout(MaxPayoutDestinations::get() + 1).saturating_sub(payout(0))
+	};
+}
+
+impl pool::Config for Runtime {
+	type Event = Event;
+	type Currency = Balances;
+	type PoolDeposit = PoolDeposit;
+	type MaxMembers = MaxPoolMembers;
+	type MemberPayoutWeight = PoolMemberPayoutWeight;
+	type WeightInfo = crate::weights::pool::WeightInfo<Self>;
 }
 
 parameter_types! {
@@ -653,6 +796,27 @@ parameter_types! {
 	pub const BountyValueMinimum: Balance = 10 * DOLLARS;
 
 	pub const MaxApprovals: u32 = 100;
+
+	pub const CommunityFundPalletId: PalletId = PalletId(*b"py/cmfnd");
+	pub CommunityFund: AccountId = CommunityFundPalletId::get().into_account();
+}
+
+/// Destination of the part of the treasury burned each spend period. It is
+/// destroyed, unless governance sets the
+/// `runtime::treasury::burn_to_community_fund` variable to a non-zero value,
+/// which routes it to the community fund account instead.
+pub struct TreasuryBurnDestination;
+impl OnUnbalanced<NegativeImbalance> for TreasuryBurnDestination {
+	fn on_nonzero_unbalanced(amount: NegativeImbalance) {
+		let to_community_fund =
+			variables::U32s::get(b"runtime::treasury::burn_to_community_fund".to_vec())
+				.unwrap_or(0);
+		if to_community_fund != 0 {
+			Balances::resolve_creating(&CommunityFund::get(), amount);
+		} else {
+			drop(amount);
+		}
+	}
 }
 
 impl treasury::Config for Runtime {
@@ -674,7 +838,7 @@ impl treasury::Config for Runtime {
 	type SpendPeriod = SpendPeriod;
 	type SpendFunds = Bounties;
 	type Burn = Burn;
-	type BurnDestination = ();
+	type BurnDestination = TreasuryBurnDestination;
 	type PalletId = TreasuryPalletId;
 	type MaxApprovals = MaxApprovals;
 	type WeightInfo = ();
@@ -758,6 +922,7 @@ pub enum ProxyType {
 	NonTransfer,
 	Governance,
 	IdentityJudgement,
+	Mining,
 }
 impl Default for ProxyType {
 	fn default() -> Self {
@@ -780,6 +945,7 @@ impl InstanceFilter<Call> for ProxyType {
 				Call::Democracy(..) |
 				Call::Council(..) |
 				Call::TechnicalCommittee(..) |
+				Call::PowGovernance(..) |
 				Call::ElectionsPhragmen(..) |
 				Call::TechnicalMembership(..) |
 				Call::Treasury(..) |
@@ -798,12 +964,24 @@ impl InstanceFilter<Call> for ProxyType {
 					| Call::Council(..) | Call::TechnicalCommittee(..)
 					| Call::ElectionsPhragmen(..)
 					| Call::Treasury(..) | Call::Utility(..)
+					| Call::PowGovernance(..)
 			),
 			ProxyType::IdentityJudgement => matches!(
 				c,
 				Call::Identity(identity::Call::provide_judgement { .. })
 					| Call::Utility(utility::Call::batch { .. })
 			),
+			// Maintenance of mining rewards and pools, for hot keys of miners
+			// keeping the reward account cold. Payout splits, pool members and
+			// balance transfers are left to the reward account, as they could
+			// redirect its funds or all future rewards.
+			ProxyType::Mining => matches!(
+				c,
+				Call::Rewards(rewards::Call::unlock { .. })
+					| Call::Rewards(rewards::Call::set_standing_donation { .. })
+					| Call::Pool(pool::Call::set_fee { .. })
+					| Call::Utility(utility::Call::batch { .. })
+			),
 		}
 	}
 	fn is_superset(&self, o: &Self) -> bool {
@@ -811,6 +989,7 @@ impl InstanceFilter<Call> for ProxyType {
 			(x, y) if x == y => true,
 			(ProxyType::Any, _) => true,
 			(_, ProxyType::Any) => false,
+			(ProxyType::NonTransfer, ProxyType::Mining) => false,
 			(ProxyType::NonTransfer, _) => true,
 			_ => false,
 		}
@@ -845,6 +1024,26 @@ impl vesting::Config for Runtime {
 	const MAX_VESTING_SCHEDULES: u32 = 28;
 }
 
+parameter_types! {
+	// One storage item; key size is 32; value is size 4+16+1+2 bytes = 23 bytes.
+	pub const ConfigDepositBase: Balance = deposit(1, 55);
+	// Additional storage item size of 32 bytes.
+	pub const FriendDepositFactor: Balance = deposit(0, 32);
+	pub const MaxFriends: u16 = 9;
+	// One storage item; key size is 64; value is size 4+16+1 bytes = 21 bytes.
+	pub const RecoveryDeposit: Balance = deposit(1, 85);
+}
+
+impl recovery::Config for Runtime {
+	type Event = Event;
+	type Call = Call;
+	type Currency = Balances;
+	type ConfigDepositBase = ConfigDepositBase;
+	type FriendDepositFactor = FriendDepositFactor;
+	type MaxFriends = MaxFriends;
+	type RecoveryDeposit = RecoveryDeposit;
+}
+
 impl variables::Config for Runtime {
 	type Event = Event;
 }
@@ -897,12 +1096,26 @@ impl contracts::Config for Runtime {
 	type CallStack = [contracts::Frame<Self>; 31];
 	type WeightPrice = transaction_payment::Pallet<Self>;
 	type WeightInfo = contracts::weights::SubstrateWeight<Self>;
-	type ChainExtension = ();
+	type ChainExtension = chain_extension::KulupuExtension;
 	type DeletionQueueDepth = DeletionQueueDepth;
 	type DeletionWeightLimit = DeletionWeightLimit;
 	type Schedule = Schedule;
 }
 
+parameter_types! {
+	pub SponsoredContractsFunding: AccountId = TreasuryPalletId::get().into_account();
+}
+
+impl sponsored_contracts::Config for Runtime {
+	type Event = Event;
+	type ApproveOrigin = system::EnsureOneOf<
+		AccountId,
+		collective::EnsureProportionMoreThan<_1, _2, AccountId, CouncilCollective>,
+		system::EnsureRoot<AccountId>,
+	>;
+	type FundingAccount = SponsoredContractsFunding;
+}
+
 parameter_types! {
 	pub ProofLimit: u32 = 1024;
 }
@@ -952,6 +1165,13 @@ construct_runtime!(
 		Lockdrop: lockdrop::{Pallet, Call, Storage, Event<T>} = 24,
 		Contracts: contracts::{Pallet, Call, Storage, Event<T>} = 25,
 		AtomicSwap: atomic_swap::{Pallet, Call, Storage, Event<T>} = 26,
+		Pool: pool::{Pallet, Call, Storage, Event<T>} = 27,
+		Metrics: metrics::{Pallet, Storage} = 28,
+		SponsoredContracts: sponsored_contracts::{Pallet, Call, Storage, Event<T>} = 29,
+		EraClaims: era_claims::{Pallet, Call, Storage, Event<T>} = 30,
+		UpgradeAnnouncement: upgrade_announcement::{Pallet, Call, Storage, Event<T>} = 31,
+		PowGovernance: pow_governance::{Pallet, Call, Storage, Event<T>} = 32,
+		Recovery: recovery::{Pallet, Call, Storage, Event<T>} = 33,
 	}
 );
 
@@ -980,6 +1200,39 @@ impl frame_support::traits::OnRuntimeUpgrade for PhragmenElectionV4RuntimeUpgrad
 	}
 }
 
+/// Migration `M`, recorded by name with its weight once run. Migrations that
+/// weigh nothing had nothing to migrate, and are not recorded.
+pub struct RecordedMigration<Name, M>(sp_std::marker::PhantomData<(Name, M)>);
+
+impl<Name, M> frame_support::traits::OnRuntimeUpgrade for RecordedMigration<Name, M>
+where
+	Name: frame_support::traits::Get<&'static str>,
+	M: frame_support::traits::OnRuntimeUpgrade,
+{
+	fn on_runtime_upgrade() -> frame_support::weights::Weight {
+		let weight = M::on_runtime_upgrade();
+		if weight == 0 {
+			return 0;
+		}
+
+		log::info!(
+			target: "runtime::migrations",
+			"Ran migration {} with weight {}",
+			Name::get(),
+			weight,
+		);
+		UpgradeAnnouncement::record_migration(Name::get().as_bytes(), weight);
+
+		weight.saturating_add(RocksDbWeight::get().reads_writes(2, 2))
+	}
+}
+
+parameter_types! {
+	pub const PalletVersionMigration: &'static str = "pallet_version_to_storage_version";
+	pub const PhragmenElectionV4Migration: &'static str = "elections_phragmen_v4";
+	pub const RewardsMigration: &'static str = "rewards";
+}
+
 /// The address format for describing accounts.
 pub type Address = sp_runtime::MultiAddress<AccountId, AccountIndex>;
 /// Block header type as expected by this runtime.
@@ -999,6 +1252,7 @@ pub type SignedExtra = (
 	system::CheckNonce<Runtime>,
 	system::CheckWeight<Runtime>,
 	transaction_payment::ChargeTransactionPayment<Runtime>,
+	miner_priority::PrioritizeMinerCalls,
 );
 /// Unchecked extrinsic type as expected by this runtime.
 pub type UncheckedExtrinsic = generic::UncheckedExtrinsic<Address, Call, Signature, SignedExtra>;
@@ -1014,8 +1268,9 @@ pub type Executive = frame_executive::Executive<
 	Runtime,
 	AllPallets,
 	(
-		MigratePalletVersionToStorageVersion,
-		PhragmenElectionV4RuntimeUpgrade,
+		RecordedMigration<PalletVersionMigration, MigratePalletVersionToStorageVersion>,
+		RecordedMigration<PhragmenElectionV4Migration, PhragmenElectionV4RuntimeUpgrade>,
+		RecordedMigration<RewardsMigration, rewards::MigrateStorage<Runtime>>,
 	),
 >;
 
@@ -1128,6 +1383,166 @@ impl_runtime_apis! {
 		}
 	}
 
+	impl kulupu_primitives::RewardsApi<Block, BlockNumber, AccountId, Balance> for Runtime {
+		fn rewards_info() -> kulupu_primitives::RewardsInfo<BlockNumber, AccountId, Balance> {
+			kulupu_primitives::RewardsInfo {
+				reward: Rewards::reward(),
+				reward_changes: Rewards::reward_changes().into_iter().collect(),
+				mints: Rewards::mints().into_iter().collect(),
+				mint_changes: Rewards::mint_changes()
+					.into_iter()
+					.map(|(start, mints)| (start, mints.into_iter().collect()))
+					.collect(),
+			}
+		}
+	}
+
+	impl kulupu_primitives::RewardScheduleApi<Block, BlockNumber, AccountId, Balance> for Runtime {
+		fn reward_schedule() -> Vec<kulupu_primitives::EmissionPoint<BlockNumber, AccountId, Balance>> {
+			rewards::Module::<Runtime>::emission_schedule()
+				.into_iter()
+				.map(|(start, reward, mints)| kulupu_primitives::EmissionPoint {
+					start,
+					reward,
+					mints: mints.into_iter().collect(),
+				})
+				.collect()
+		}
+	}
+
+	impl kulupu_primitives::RewardHistoryApi<Block, AccountId, Balance> for Runtime {
+		fn block_reward() -> Option<kulupu_primitives::BlockReward<AccountId, Balance>> {
+			let mut block_reward = None;
+			let mut tips = 0;
+			for record in System::events() {
+				match record.event {
+					Event::Rewards(rewards::RawEvent::Rewarded(author, reward)) => {
+						block_reward = Some((author, reward));
+					}
+					Event::Rewards(rewards::RawEvent::TipsPaid(_, paid)) => tips = paid,
+					_ => (),
+				}
+			}
+
+			block_reward.map(|(author, reward)| kulupu_primitives::BlockReward {
+				author,
+				reward,
+				tips,
+			})
+		}
+	}
+
+	impl kulupu_primitives::EraClaimsApi<Block, AccountId, Balance> for Runtime {
+		fn era_balance(era: u32, who: AccountId, proof: Vec<Vec<u8>>) -> Option<Balance> {
+			era_claims::Module::<Runtime>::verify_balance(era, &who, proof).ok()
+		}
+	}
+
+	impl kulupu_primitives::MigrationsApi<Block> for Runtime {
+		fn migration_report() -> kulupu_primitives::MigrationReport {
+			kulupu_primitives::MigrationReport {
+				spec_version: UpgradeAnnouncement::migrations_spec_version(),
+				migrations: UpgradeAnnouncement::migrations(),
+			}
+		}
+	}
+
+	impl kulupu_primitives::UpgradeAnnouncementApi<Block, Hash, BlockNumber> for Runtime {
+		fn upgrade_announcement() -> Option<kulupu_primitives::UpgradeAnnouncement<Hash, BlockNumber>> {
+			UpgradeAnnouncement::upcoming().map(|announcement| {
+				kulupu_primitives::UpgradeAnnouncement {
+					code_hash: announcement.code_hash,
+					spec_version: announcement.spec_version,
+					activation: announcement.activation,
+				}
+			})
+		}
+	}
+
+	impl kulupu_primitives::BlockInherentsApi<Block, AccountId> for Runtime {
+		fn block_inherents(block: Block) -> kulupu_primitives::BlockInherents<AccountId> {
+			let mut inherents = kulupu_primitives::BlockInherents {
+				timestamp: None,
+				author: Rewards::author_from_digest(&block.header.digest),
+				other: Vec::new(),
+			};
+			for extrinsic in block.extrinsics {
+				if extrinsic.signature.is_some() {
+					continue;
+				}
+
+				match extrinsic.function {
+					Call::Timestamp(timestamp::Call::set { now }) => {
+						inherents.timestamp = Some(now);
+					}
+					call => inherents.other.push(call.encode()),
+				}
+			}
+
+			inherents
+		}
+	}
+
+	impl kulupu_primitives::TransferableApi<Block, AccountId, Balance, BlockNumber> for Runtime {
+		fn is_transferable(who: AccountId, amount: Balance, at: BlockNumber) -> bool {
+			amount <= transferable::transferable_balance(&who, at)
+		}
+	}
+
+	impl kulupu_primitives::LiquidityApi<Block, AccountId, Balance, BlockNumber> for Runtime {
+		fn liquidity_timeline(
+			who: AccountId,
+		) -> Vec<kulupu_primitives::LiquidityPoint<BlockNumber, Balance>> {
+			transferable::liquidity_timeline(&who)
+		}
+	}
+
+	impl kulupu_primitives::VotableBalanceApi<Block, AccountId, Balance> for Runtime {
+		fn votable_balance(who: AccountId) -> kulupu_primitives::VotableBalance<Balance> {
+			let reward_locked = Balances::locks(&who)
+				.iter()
+				.find(|lock| lock.id == rewards::REWARDS_ID)
+				.map(|lock| lock.amount)
+				.unwrap_or(0);
+
+			kulupu_primitives::VotableBalance {
+				votable: Balances::free_balance(&who),
+				reward_locked,
+			}
+		}
+	}
+
+	impl kulupu_primitives::TreasuryApi<Block, Balance, BlockNumber> for Runtime {
+		fn treasury_status() -> kulupu_primitives::TreasuryStatus<Balance, BlockNumber> {
+			treasury_status::treasury_status()
+		}
+	}
+
+	impl kulupu_primitives::DifficultyHistoryApi<Block> for Runtime {
+		fn past_difficulties_and_timestamps() -> Vec<(kulupu_primitives::Difficulty, u64)> {
+			difficulty::Module::<Runtime>::past_difficulties_and_timestamps()
+				.iter()
+				.filter_map(|entry| entry.map(|entry| (entry.difficulty, entry.timestamp)))
+				.collect()
+		}
+	}
+
+	impl kulupu_primitives::FeeApi<Block> for Runtime {
+		fn next_fee_multiplier() -> u128 {
+			TransactionPayment::next_fee_multiplier().into_inner()
+		}
+
+		fn block_fullness() -> u32 {
+			let weights = BlockWeights::get();
+			let max_normal = weights
+				.get(DispatchClass::Normal)
+				.max_total
+				.unwrap_or(weights.max_block);
+			let used = *System::block_weight().get(DispatchClass::Normal);
+			Perbill::from_rational(used, max_normal).deconstruct()
+		}
+	}
+
 	impl pallet_contracts_rpc_runtime_api::ContractsApi<Block, AccountId, Balance, BlockNumber, Hash> for Runtime {
 		fn call(
 			origin: AccountId,
@@ -1204,6 +1619,7 @@ impl_runtime_apis! {
 			add_benchmark!(params, batches, vesting, Vesting);
 
 			add_benchmark!(params, batches, rewards, Rewards);
+			add_benchmark!(params, batches, pool, Pool);
 			add_benchmark!(params, batches, lockdrop, Lockdrop);
 
 			if batches.is_empty() { return Err("Benchmark not found for this pallet.".into()) }
@@ -1211,3 +1627,383 @@ impl_runtime_apis! {
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use frame_support::{assert_ok, traits::OnInitialize};
+
+	fn new_test_ext() -> sp_io::TestExternalities {
+		system::GenesisConfig::default()
+			.build_storage::<Runtime>()
+			.unwrap()
+			.into()
+	}
+
+	fn initialize_block(author: &AccountId) {
+		let pre_digest = DigestItem::PreRuntime(sp_consensus_pow::POW_ENGINE_ID, author.encode());
+		System::initialize(
+			&1,
+			&Default::default(),
+			&generic::Digest {
+				logs: vec![pre_digest],
+			},
+			system::InitKind::Full,
+		);
+		Rewards::on_initialize(1);
+	}
+
+	#[test]
+	fn liquid_reward_is_set_by_governance() {
+		new_test_ext().execute_with(|| {
+			let locked = |reward| {
+				<GenerateRewardLocks as rewards::GenerateRewardLocks<Runtime>>::generate_reward_locks(
+					1, reward, None,
+				)
+				.values()
+				.sum::<Balance>()
+			};
+			assert_eq!(LiquidReward::get(), DOLLARS);
+			assert_eq!(locked(10 * DOLLARS), 9 * DOLLARS);
+
+			variables::U32s::insert(b"runtime::rewards::liquid_reward_cents".to_vec(), 500);
+			assert_eq!(LiquidReward::get(), 5 * DOLLARS);
+			assert_eq!(locked(10 * DOLLARS), 5 * DOLLARS);
+		});
+	}
+
+	#[test]
+	fn fees_are_burned_and_tips_paid_to_author() {
+		new_test_ext().execute_with(|| {
+			let author = AccountId::from([1; 32]);
+			initialize_block(&author);
+
+			let issuance = Balances::total_issuance();
+			DealWithFees::on_unbalanceds(
+				vec![Balances::issue(10 * DOLLARS), Balances::issue(DOLLARS)].into_iter(),
+			);
+			assert_eq!(Balances::free_balance(&author), DOLLARS);
+			assert_eq!(Balances::total_issuance(), issuance + DOLLARS);
+		});
+	}
+
+	#[test]
+	fn contract_fees_are_shared_with_author_when_set() {
+		use contract_fees::DealWithContractFees;
+
+		new_test_ext().execute_with(|| {
+			let author = AccountId::from([1; 32]);
+			initialize_block(&author);
+
+			let issuance = Balances::total_issuance();
+			DealWithContractFees::on_unbalanceds(
+				vec![Balances::issue(10 * DOLLARS), Balances::issue(DOLLARS)].into_iter(),
+			);
+			assert_eq!(Balances::free_balance(&author), DOLLARS);
+
+			variables::U32s::insert(b"runtime::contracts::author_fee_percent".to_vec(), 30);
+			DealWithContractFees::on_unbalanceds(
+				vec![Balances::issue(10 * DOLLARS), Balances::issue(DOLLARS)].into_iter(),
+			);
+			assert_eq!(Balances::free_balance(&author), 5 * DOLLARS);
+			assert_eq!(Balances::total_issuance(), issuance + 5 * DOLLARS);
+		});
+	}
+
+	#[test]
+	fn tips_are_split_between_author_and_fund() {
+		new_test_ext().execute_with(|| {
+			let author = AccountId::from([1; 32]);
+			let fund = AccountId::from([2; 32]);
+			initialize_block(&author);
+
+			assert_ok!(Rewards::set_tip_split(
+				Origin::root(),
+				vec![(fund.clone(), Perbill::from_percent(20))]
+			));
+			DealWithFees::on_unbalanceds(
+				vec![Balances::issue(10 * DOLLARS), Balances::issue(10 * DOLLARS)].into_iter(),
+			);
+			assert_eq!(Balances::free_balance(&author), 8 * DOLLARS);
+			assert_eq!(Balances::free_balance(&fund), 2 * DOLLARS);
+		});
+	}
+
+	#[test]
+	fn treasury_burn_goes_to_community_fund_when_set() {
+		new_test_ext().execute_with(|| {
+			let issuance = Balances::total_issuance();
+
+			TreasuryBurnDestination::on_unbalanced(Balances::issue(DOLLARS));
+			assert_eq!(Balances::total_issuance(), issuance);
+			assert_eq!(Balances::free_balance(&CommunityFund::get()), 0);
+
+			variables::U32s::insert(b"runtime::treasury::burn_to_community_fund".to_vec(), 1);
+			TreasuryBurnDestination::on_unbalanced(Balances::issue(DOLLARS));
+			assert_eq!(Balances::total_issuance(), issuance + DOLLARS);
+			assert_eq!(Balances::free_balance(&CommunityFund::get()), DOLLARS);
+		});
+	}
+
+	#[test]
+	fn reward_locks_release_for_transfers() {
+		use frame_support::traits::LockableCurrency;
+
+		new_test_ext().execute_with(|| {
+			let miner = AccountId::from([1; 32]);
+			let transferable = |at| transferable::transferable_balance(&miner, at);
+			Balances::make_free_balance_be(&miner, 100 * DOLLARS);
+			rewards::RewardLocks::<Runtime>::insert(
+				&miner,
+				vec![(10, 30 * DOLLARS), (20, 20 * DOLLARS)]
+					.into_iter()
+					.collect::<BTreeMap<_, _>>(),
+			);
+			Balances::set_lock(
+				rewards::REWARDS_ID,
+				&miner,
+				50 * DOLLARS,
+				WithdrawReasons::except(WithdrawReasons::TRANSACTION_PAYMENT),
+			);
+			Balances::set_lock(
+				*b"feesonly",
+				&miner,
+				90 * DOLLARS,
+				WithdrawReasons::TRANSACTION_PAYMENT,
+			);
+
+			assert_eq!(transferable(5), 50 * DOLLARS);
+			assert_eq!(transferable(10), 80 * DOLLARS);
+			assert_eq!(transferable(20), 100 * DOLLARS);
+
+			Balances::set_lock(*b"lockdrop", &miner, 60 * DOLLARS, WithdrawReasons::all());
+			assert_eq!(transferable(5), 40 * DOLLARS);
+			assert_eq!(transferable(20), 40 * DOLLARS);
+		});
+	}
+
+	#[test]
+	fn liquidity_timeline_lists_lock_releases() {
+		use frame_support::traits::LockableCurrency;
+
+		new_test_ext().execute_with(|| {
+			let miner = AccountId::from([1; 32]);
+			Balances::make_free_balance_be(&miner, 100 * DOLLARS);
+			rewards::RewardLocks::<Runtime>::insert(
+				&miner,
+				vec![(10, 30 * DOLLARS), (20, 20 * DOLLARS)]
+					.into_iter()
+					.collect::<BTreeMap<_, _>>(),
+			);
+			Balances::set_lock(
+				rewards::REWARDS_ID,
+				&miner,
+				50 * DOLLARS,
+				WithdrawReasons::except(WithdrawReasons::TRANSACTION_PAYMENT),
+			);
+			Balances::set_lock(*b"lockdrop", &miner, 40 * DOLLARS, WithdrawReasons::all());
+
+			let timeline = transferable::liquidity_timeline(&miner);
+			assert_eq!(
+				timeline
+					.iter()
+					.map(|point| (point.at, point.reward_locked, point.transferable))
+					.collect::<Vec<_>>(),
+				vec![
+					(0, 50 * DOLLARS, 50 * DOLLARS),
+					(10, 20 * DOLLARS, 60 * DOLLARS),
+					(20, 0, 60 * DOLLARS),
+				],
+			);
+			assert!(timeline
+				.iter()
+				.all(|point| point.lockdrop_locked == 40 * DOLLARS));
+		});
+	}
+
+	#[test]
+	fn locked_rewards_back_democracy_votes() {
+		use democracy::{AccountVote, Conviction, Vote, VoteThreshold};
+		use frame_support::traits::LockableCurrency;
+
+		new_test_ext().execute_with(|| {
+			let miner = AccountId::from([1; 32]);
+			Balances::make_free_balance_be(&miner, 100 * DOLLARS);
+			Balances::set_lock(
+				rewards::REWARDS_ID,
+				&miner,
+				100 * DOLLARS,
+				WithdrawReasons::except(WithdrawReasons::TRANSACTION_PAYMENT),
+			);
+
+			let index = Democracy::internal_start_referendum(
+				Default::default(),
+				VoteThreshold::SuperMajorityApprove,
+				0,
+			);
+			assert_ok!(Democracy::vote(
+				Origin::signed(miner.clone()),
+				index,
+				AccountVote::Standard {
+					vote: Vote {
+						aye: true,
+						conviction: Conviction::Locked1x,
+					},
+					balance: 100 * DOLLARS,
+				},
+			));
+			assert!(Balances::locks(&miner)
+				.iter()
+				.any(|lock| lock.id == *b"democrac" && lock.amount == 100 * DOLLARS));
+		});
+	}
+
+	#[test]
+	fn treasury_burn_is_projected_after_approvals() {
+		let burn = Permill::from_percent(1);
+
+		assert_eq!(treasury_status::projected_burn(1_000, vec![], burn), 10);
+		assert_eq!(
+			treasury_status::projected_burn(1_000, vec![300, 600], burn),
+			1
+		);
+		assert_eq!(
+			treasury_status::projected_burn(1_000, vec![300, 800], burn),
+			0
+		);
+	}
+
+	#[test]
+	fn recovery_config_reserves_deposits() {
+		new_test_ext().execute_with(|| {
+			let owner = AccountId::from([1; 32]);
+			let friends = vec![AccountId::from([2; 32]), AccountId::from([3; 32])];
+			Balances::make_free_balance_be(&owner, 100 * DOLLARS);
+
+			assert_ok!(Recovery::create_recovery(
+				Origin::signed(owner.clone()),
+				friends,
+				2,
+				DAYS,
+			));
+			assert_eq!(
+				Balances::reserved_balance(&owner),
+				ConfigDepositBase::get() + 2 * FriendDepositFactor::get(),
+			);
+		});
+	}
+
+	#[test]
+	fn mining_proxy_filters_calls() {
+		let transfer = Call::Balances(balances::Call::transfer {
+			dest: Address::Id(AccountId::default()),
+			value: DOLLARS,
+		});
+		let batch = |calls| Call::Utility(utility::Call::batch { calls });
+
+		assert!(
+			ProxyType::Mining.filter(&Call::Rewards(rewards::Call::unlock {
+				target: AccountId::default(),
+			}))
+		);
+		assert!(ProxyType::Mining.filter(&batch(vec![])));
+		assert!(!ProxyType::Mining.filter(&transfer));
+		assert!(
+			!ProxyType::Mining.filter(&Call::Pool(pool::Call::set_members {
+				members: vec![(AccountId::default(), 1)],
+			}))
+		);
+		assert!(
+			!ProxyType::Mining.filter(&Call::Rewards(rewards::Call::set_payout_split {
+				split: vec![]
+			}))
+		);
+		assert!(!ProxyType::NonTransfer.is_superset(&ProxyType::Mining));
+		assert!(ProxyType::Any.is_superset(&ProxyType::Mining));
+	}
+
+	#[test]
+	fn difficulty_epochs_are_summarized() {
+		use frame_support::traits::OnTimestampSet;
+		use sp_core::U256;
+
+		new_test_ext().execute_with(|| {
+			let mine = |number: BlockNumber, difficulty: u64| {
+				System::set_block_number(number);
+				difficulty::CurrentDifficulty::put(U256::from(difficulty));
+				<Difficulty as OnTimestampSet<_>>::on_timestamp_set(u64::from(number) * BLOCK_TIME);
+			};
+			mine(998, 100);
+			mine(999, 300);
+			assert_eq!(Difficulty::epoch_summary(0), None);
+
+			mine(1000, 50);
+			let summary = Difficulty::epoch_summary(0).unwrap();
+			assert_eq!(summary.blocks, 2);
+			assert_eq!(summary.min, U256::from(100));
+			assert_eq!(summary.max, U256::from(300));
+			assert_eq!(summary.average(), U256::from(200));
+
+			let (index, current) = Difficulty::current_epoch().unwrap();
+			assert_eq!((index, current.blocks), (1, 1));
+			assert_eq!(current.min, U256::from(50));
+		});
+	}
+
+	#[test]
+	fn pow_governance_track_only_dispatches_pow_parameter_calls() {
+		use frame_support::weights::GetDispatchInfo;
+		use sp_runtime::traits::Hash as _;
+
+		new_test_ext().execute_with(|| {
+			System::set_block_number(1);
+			let transfer = Call::Balances(balances::Call::transfer {
+				dest: Address::Id(AccountId::default()),
+				value: DOLLARS,
+			});
+			assert!(!PowParameterCalls::contains(&transfer));
+			assert!(PowGovernance::propose(Origin::root(), Box::new(transfer)).is_err());
+
+			let call = Call::Difficulty(difficulty::Call::set_algorithm {
+				algorithm: difficulty::DifficultyAlgorithm::Lwma,
+			});
+			let hash = BlakeTwo256::hash_of(&call);
+			let weight = call.get_dispatch_info().weight;
+			assert!(PowParameterCalls::contains(&call));
+			assert_ok!(PowGovernance::propose(Origin::root(), Box::new(call)));
+			assert_ok!(PowGovernance::approve(Origin::root(), hash, weight));
+			assert_eq!(
+				Difficulty::algorithm(),
+				difficulty::DifficultyAlgorithm::Lwma
+			);
+		});
+	}
+
+	#[test]
+	fn runtime_upgrade_migrations_are_recorded() {
+		use frame_support::traits::OnRuntimeUpgrade;
+
+		struct Migration;
+		impl OnRuntimeUpgrade for Migration {
+			fn on_runtime_upgrade() -> Weight {
+				1_000
+			}
+		}
+
+		new_test_ext().execute_with(|| {
+			// Rewards storage is already at the latest version.
+			RecordedMigration::<RewardsMigration, rewards::MigrateStorage<Runtime>>::on_runtime_upgrade();
+			assert!(UpgradeAnnouncement::migrations().is_empty());
+
+			RecordedMigration::<RewardsMigration, Migration>::on_runtime_upgrade();
+			assert_eq!(
+				UpgradeAnnouncement::migrations_spec_version(),
+				VERSION.spec_version
+			);
+			assert_eq!(
+				UpgradeAnnouncement::migrations(),
+				vec![(b"rewards".to_vec(), 1_000)]
+			);
+		});
+	}
+}
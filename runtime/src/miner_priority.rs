@@ -0,0 +1,89 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Kulupu.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Kulupu is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Kulupu is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Kulupu. If not, see <http://www.gnu.org/licenses/>.
+
+//! Transaction priority for governance and unlock calls of miners.
+//!
+//! Miners' rewards are mostly locked, which keeps them from taking part in
+//! governance as easily as other holders. Accounts that authored a block
+//! recently get their reward unlocks and referendum votes prioritized in the
+//! transaction pool.
+
+use crate::{AccountId, Call, MinerCallPriority, Runtime};
+use codec::{Decode, Encode};
+use scale_info::TypeInfo;
+use sp_runtime::{
+	traits::{DispatchInfoOf, SignedExtension},
+	transaction_validity::{TransactionValidity, TransactionValidityError, ValidTransaction},
+};
+
+/// Raise the priority of `rewards::unlock` and `democracy::vote` calls signed
+/// by accounts that authored one of the last `RecentAuthorBlocks` blocks.
+#[derive(Encode, Decode, Clone, Eq, PartialEq, Default, TypeInfo)]
+pub struct PrioritizeMinerCalls;
+
+impl sp_std::fmt::Debug for PrioritizeMinerCalls {
+	fn fmt(&self, f: &mut sp_std::fmt::Formatter) -> sp_std::fmt::Result {
+		write!(f, "PrioritizeMinerCalls")
+	}
+}
+
+impl SignedExtension for PrioritizeMinerCalls {
+	const IDENTIFIER: &'static str = "PrioritizeMinerCalls";
+	type AccountId = AccountId;
+	type Call = Call;
+	type AdditionalSigned = ();
+	type Pre = ();
+
+	fn additional_signed(&self) -> Result<(), TransactionValidityError> {
+		Ok(())
+	}
+
+	fn validate(
+		&self,
+		who: &AccountId,
+		call: &Call,
+		_info: &DispatchInfoOf<Call>,
+		_len: usize,
+	) -> TransactionValidity {
+		let prioritized = matches!(
+			call,
+			Call::Rewards(rewards::Call::unlock { .. })
+				| Call::Democracy(democracy::Call::vote { .. })
+		);
+
+		if prioritized && rewards::Module::<Runtime>::is_recent_author(who) {
+			Ok(ValidTransaction {
+				priority: MinerCallPriority::get(),
+				..Default::default()
+			})
+		} else {
+			Ok(ValidTransaction::default())
+		}
+	}
+
+	fn pre_dispatch(
+		self,
+		_who: &AccountId,
+		_call: &Call,
+		_info: &DispatchInfoOf<Call>,
+		_len: usize,
+	) -> Result<(), TransactionValidityError> {
+		// Priority only matters in the transaction pool.
+		Ok(())
+	}
+}
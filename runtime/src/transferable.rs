@@ -0,0 +1,113 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Kulupu.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Kulupu is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Kulupu is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Kulupu. If not, see <http://www.gnu.org/licenses/>.
+
+//! Balance an account could transfer at a future block.
+//!
+//! Exchanges crediting deposits from miners need to know when the deposited
+//! funds can be moved on. Reward locks and vesting schedules release over
+//! time, so their balance locks are replaced by what they will still lock at
+//! the given block, assuming the account unlocks them by then. Any other lock,
+//! such as a lockdrop or governance lock, is assumed to stay as it is.
+
+use crate::{AccountId, Balance, Balances, BlockNumber, Rewards, Runtime};
+use core::convert::TryFrom;
+use frame_support::traits::LockIdentifier;
+use kulupu_primitives::LiquidityPoint;
+use sp_runtime::traits::{ConvertInto, Saturating};
+use sp_std::prelude::*;
+
+/// Balance lock identifier of `pallet_vesting`.
+const VESTING_ID: LockIdentifier = *b"vesting ";
+/// Balance lock identifier of `pallet_lockdrop`.
+const LOCKDROP_ID: LockIdentifier = *b"lockdrop";
+
+/// Balance of `who` not locked against transfers at block `at`, or at the
+/// current block if `at` has passed.
+pub fn transferable_balance(who: &AccountId, at: BlockNumber) -> Balance {
+	liquidity_at(who, at).transferable
+}
+
+/// Balances of `who` locked by each kind of lock and transferable at block
+/// `at`, or at the current block if `at` has passed.
+pub fn liquidity_at(who: &AccountId, at: BlockNumber) -> LiquidityPoint<BlockNumber, Balance> {
+	let at = at.max(system::Pallet::<Runtime>::block_number());
+	let mut point = LiquidityPoint {
+		at,
+		reward_locked: 0,
+		vesting_locked: 0,
+		lockdrop_locked: 0,
+		other_locked: 0,
+		transferable: 0,
+	};
+
+	for lock in Balances::locks(who)
+		.iter()
+		.filter(|lock| lock.reasons != balances::Reasons::Fee)
+	{
+		let (locked, amount) = match lock.id {
+			rewards::REWARDS_ID => (&mut point.reward_locked, Rewards::locked_at(who, at)),
+			VESTING_ID => (
+				&mut point.vesting_locked,
+				vesting::Vesting::<Runtime>::get(who)
+					.map(|schedules| {
+						schedules
+							.iter()
+							.map(|schedule| schedule.locked_at::<ConvertInto>(at))
+							.fold(0, Balance::saturating_add)
+					})
+					.unwrap_or(0),
+			),
+			LOCKDROP_ID => (&mut point.lockdrop_locked, lock.amount),
+			_ => (&mut point.other_locked, lock.amount),
+		};
+		*locked = (*locked).max(amount);
+	}
+
+	let locked = point
+		.reward_locked
+		.max(point.vesting_locked)
+		.max(point.lockdrop_locked)
+		.max(point.other_locked);
+	point.transferable = Balances::free_balance(who).saturating_sub(locked);
+	point
+}
+
+/// Liquidity of `who` at the current block, and at each later block one of
+/// its reward locks expires or one of its vesting schedules starts or ends
+/// releasing.
+pub fn liquidity_timeline(who: &AccountId) -> Vec<LiquidityPoint<BlockNumber, Balance>> {
+	let now = system::Pallet::<Runtime>::block_number();
+
+	let mut blocks = Rewards::reward_locks(who)
+		.keys()
+		.copied()
+		.collect::<Vec<_>>();
+	if let Some(schedules) = vesting::Vesting::<Runtime>::get(who) {
+		for schedule in schedules.iter() {
+			let end = schedule.ending_block_as_balance::<ConvertInto>();
+			blocks.push(schedule.starting_block());
+			blocks.push(BlockNumber::try_from(end).unwrap_or(BlockNumber::MAX));
+		}
+	}
+	blocks.retain(|block| *block > now);
+	blocks.push(now);
+	blocks.sort_unstable();
+	blocks.dedup();
+
+	blocks.into_iter().map(|at| liquidity_at(who, at)).collect()
+}
@@ -0,0 +1,75 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Kulupu.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Kulupu is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Kulupu is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Kulupu. If not, see <http://www.gnu.org/licenses/>.
+
+//! Treasury status, for governance dashboards.
+//!
+//! The next spend period pays out approved proposals in order, as far as the
+//! treasury balance covers them, and burns part of what remains if all of them
+//! were paid. The projected burn follows the same steps, but does not account
+//! for bounties funded in the same spend period.
+
+use crate::{Balance, BlockNumber, Runtime, SpendPeriod, Treasury};
+use frame_support::traits::Get;
+use kulupu_primitives::TreasuryStatus;
+use sp_runtime::{traits::Saturating, Permill};
+
+/// Balance burned of `budget` after paying out `approved` proposals in order,
+/// as the treasury does at the end of a spend period.
+pub fn projected_burn(
+	budget: Balance,
+	approved: impl IntoIterator<Item = Balance>,
+	burn: Permill,
+) -> Balance {
+	let mut remaining = budget;
+	for value in approved {
+		if value > remaining {
+			// Proposals left unpaid carry the whole balance over.
+			return 0;
+		}
+		remaining = remaining.saturating_sub(value);
+	}
+
+	(burn * remaining).min(remaining)
+}
+
+/// Status of the treasury at the current block.
+pub fn treasury_status() -> TreasuryStatus<Balance, BlockNumber> {
+	let now = system::Pallet::<Runtime>::block_number();
+	let period = SpendPeriod::get();
+	let next_spend_period = (now / period).saturating_add(1).saturating_mul(period);
+
+	let approvals = Treasury::approvals();
+	let proposals = treasury::Proposals::<Runtime>::iter().count() as u32;
+	let approved = approvals
+		.iter()
+		.filter_map(|index| Treasury::proposals(index))
+		.map(|proposal| proposal.value);
+
+	let balance = Treasury::pot();
+	TreasuryStatus {
+		balance,
+		next_spend_period,
+		pending_proposals: proposals.saturating_sub(approvals.len() as u32),
+		approved_proposals: approvals.len() as u32,
+		projected_burn: projected_burn(
+			balance,
+			approved,
+			<Runtime as treasury::Config>::Burn::get(),
+		),
+	}
+}
@@ -20,4 +20,5 @@
 //! Weights for pallets used in this runtime.
 
 pub mod lockdrop;
+pub mod pool;
 pub mod rewards;
@@ -53,23 +53,74 @@ pub struct WeightInfo<T>(PhantomData<T>);
 impl<T: frame_system::Config> rewards::WeightInfo for WeightInfo<T> {
 	fn on_initialize() -> Weight {
 		(14_700_000 as Weight)
-			.saturating_add(T::DbWeight::get().reads(2 as Weight))
-			.saturating_add(T::DbWeight::get().writes(2 as Weight))
+			.saturating_add(T::DbWeight::get().reads(4 as Weight))
+			.saturating_add(T::DbWeight::get().writes(6 as Weight))
 	}
-	fn on_finalize() -> Weight {
-		(121_300_000 as Weight)
+	fn on_finalize(p: u32) -> Weight {
+		(76_100_000 as Weight)
+			.saturating_add((45_200_000 as Weight).saturating_mul(p as Weight))
 			.saturating_add(T::DbWeight::get().reads(5 as Weight))
-			.saturating_add(T::DbWeight::get().writes(3 as Weight))
+			.saturating_add(T::DbWeight::get().reads((3 as Weight).saturating_mul(p as Weight)))
+			.saturating_add(T::DbWeight::get().writes(2 as Weight))
+			.saturating_add(T::DbWeight::get().writes((4 as Weight).saturating_mul(p as Weight)))
 	}
 	fn unlock() -> Weight {
 		(45_200_000 as Weight)
+			.saturating_add(T::DbWeight::get().reads(3 as Weight))
+			.saturating_add(T::DbWeight::get().writes(5 as Weight))
+	}
+	fn auto_unlock(b: u32, n: u32) -> Weight {
+		(2_000_000 as Weight)
+			.saturating_add((3_000_000 as Weight).saturating_mul(b as Weight))
+			.saturating_add((48_000_000 as Weight).saturating_mul(n as Weight))
+			.saturating_add(T::DbWeight::get().reads(1 as Weight))
+			.saturating_add(T::DbWeight::get().reads((1 as Weight).saturating_mul(b as Weight)))
+			.saturating_add(T::DbWeight::get().reads((3 as Weight).saturating_mul(n as Weight)))
+			.saturating_add(T::DbWeight::get().writes(1 as Weight))
+			.saturating_add(T::DbWeight::get().writes((5 as Weight).saturating_mul(n as Weight)))
+	}
+	fn set_schedule(m: u32, r: u32, c: u32) -> Weight {
+		(32_500_000 as Weight)
+			.saturating_add((400_000 as Weight).saturating_mul(m as Weight))
+			.saturating_add((300_000 as Weight).saturating_mul(r as Weight))
+			.saturating_add((500_000 as Weight).saturating_mul(c as Weight))
+			.saturating_add(T::DbWeight::get().writes(4 as Weight))
+	}
+	fn add_reward_change() -> Weight {
+		(14_000_000 as Weight)
+			.saturating_add(T::DbWeight::get().reads(1 as Weight))
+			.saturating_add(T::DbWeight::get().writes(1 as Weight))
+	}
+	fn remove_reward_change() -> Weight {
+		(12_000_000 as Weight)
 			.saturating_add(T::DbWeight::get().reads(1 as Weight))
 			.saturating_add(T::DbWeight::get().writes(1 as Weight))
 	}
-	fn set_schedule() -> Weight {
-		(32_500_000 as Weight).saturating_add(T::DbWeight::get().writes(4 as Weight))
+	fn add_mint_change(m: u32) -> Weight {
+		(16_000_000 as Weight)
+			.saturating_add((400_000 as Weight).saturating_mul(m as Weight))
+			.saturating_add(T::DbWeight::get().reads(1 as Weight))
+			.saturating_add(T::DbWeight::get().writes(1 as Weight))
+	}
+	fn remove_mint_change() -> Weight {
+		(13_000_000 as Weight)
+			.saturating_add(T::DbWeight::get().reads(1 as Weight))
+			.saturating_add(T::DbWeight::get().writes(1 as Weight))
 	}
 	fn set_lock_params() -> Weight {
 		(0 as Weight).saturating_add(T::DbWeight::get().writes(1 as Weight))
 	}
+	fn set_payout_split(d: u32) -> Weight {
+		(18_000_000 as Weight)
+			.saturating_add((250_000 as Weight).saturating_mul(d as Weight))
+			.saturating_add(T::DbWeight::get().writes(1 as Weight))
+	}
+	fn set_tip_split(d: u32) -> Weight {
+		(17_000_000 as Weight)
+			.saturating_add((250_000 as Weight).saturating_mul(d as Weight))
+			.saturating_add(T::DbWeight::get().writes(1 as Weight))
+	}
+	fn set_standing_donation() -> Weight {
+		(16_000_000 as Weight).saturating_add(T::DbWeight::get().writes(1 as Weight))
+	}
 }
@@ -0,0 +1,107 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Kulupu.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Kulupu is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Kulupu is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Kulupu. If not, see <http://www.gnu.org/licenses/>.
+
+//! Metrics of block authors seen at import.
+//!
+//! Blocks without a decodable author pre-runtime digest pay no mining reward,
+//! which the runtime skips silently. Such blocks point at buggy or malicious
+//! miner software, so they are logged and counted in the
+//! `kulupu_import_author_digest_errors_total` metric.
+
+use codec::Decode;
+use futures::StreamExt;
+use kulupu_runtime::{opaque::Block, AccountId};
+use log::*;
+use sc_client_api::BlockchainEvents;
+use sp_consensus_pow::POW_ENGINE_ID;
+use sp_runtime::traits::Header as HeaderT;
+use std::sync::Arc;
+use substrate_prometheus_endpoint::{register, CounterVec, Opts, PrometheusError, Registry, U64};
+
+/// Problem with the author pre-runtime digest of a block.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AuthorDigestError {
+	/// The block has no PoW pre-runtime digest.
+	Missing,
+	/// The PoW pre-runtime digest does not start with an account.
+	Undecodable,
+}
+
+impl AuthorDigestError {
+	fn label(&self) -> &'static str {
+		match self {
+			AuthorDigestError::Missing => "missing",
+			AuthorDigestError::Undecodable => "undecodable",
+		}
+	}
+}
+
+/// Check the author pre-runtime digest of `header` the way the rewards pallet
+/// decodes it.
+pub fn check_author<H: HeaderT>(header: &H) -> Result<AccountId, AuthorDigestError> {
+	let mut pre_runtime = header
+		.digest()
+		.logs()
+		.iter()
+		.filter_map(|item| item.as_pre_runtime())
+		.find(|(id, _)| *id == POW_ENGINE_ID)
+		.map(|(_, data)| data)
+		.ok_or(AuthorDigestError::Missing)?;
+
+	AccountId::decode(&mut pre_runtime).map_err(|_| AuthorDigestError::Undecodable)
+}
+
+/// Count imported blocks with a missing or undecodable author digest.
+pub async fn monitor<C>(client: Arc<C>, registry: Registry)
+where
+	C: BlockchainEvents<Block>,
+{
+	let counter = match register_counter(&registry) {
+		Ok(counter) => counter,
+		Err(err) => {
+			warn!("Registering author digest metric failed: {:?}", err);
+			return;
+		}
+	};
+
+	let mut notifications = client.import_notification_stream();
+	while let Some(notification) = notifications.next().await {
+		if let Err(err) = check_author(&notification.header) {
+			warn!(
+				"Imported block #{} ({}) has a {} author digest, and pays no reward.",
+				notification.header.number(),
+				notification.hash,
+				err.label(),
+			);
+			counter.with_label_values(&[err.label()]).inc();
+		}
+	}
+}
+
+fn register_counter(registry: &Registry) -> Result<CounterVec<U64>, PrometheusError> {
+	register(
+		CounterVec::new(
+			Opts::new(
+				"kulupu_import_author_digest_errors_total",
+				"Number of imported blocks with a missing or undecodable author digest",
+			),
+			&["reason"],
+		)?,
+		registry,
+	)
+}
@@ -0,0 +1,111 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Kulupu.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Kulupu is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Kulupu is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Kulupu. If not, see <http://www.gnu.org/licenses/>.
+
+//! Metrics of the best block, for alerting on stalled chains.
+//!
+//! The difficulty of the best block, the network hashrate estimated from it
+//! and the time since the best block last changed are exposed as the
+//! `kulupu_difficulty`, `kulupu_network_hashrate` and
+//! `kulupu_best_block_age_seconds` metrics.
+
+use kulupu_primitives::{Difficulty, BLOCK_TIME_SEC};
+use kulupu_runtime::opaque::Block;
+use log::*;
+use sc_client_api::blockchain::HeaderBackend;
+use sp_api::ProvideRuntimeApi;
+use sp_consensus_pow::DifficultyApi;
+use sp_runtime::generic::BlockId;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use substrate_prometheus_endpoint::{register, Gauge, PrometheusError, Registry, F64, U64};
+
+/// Interval at which the metrics are updated.
+const UPDATE_INTERVAL: Duration = Duration::from_secs(5);
+
+struct Metrics {
+	difficulty: Gauge<F64>,
+	hashrate: Gauge<F64>,
+	best_block_age: Gauge<U64>,
+}
+
+impl Metrics {
+	fn register(registry: &Registry) -> Result<Self, PrometheusError> {
+		Ok(Self {
+			difficulty: register(
+				Gauge::new("kulupu_difficulty", "Difficulty of the best block")?,
+				registry,
+			)?,
+			hashrate: register(
+				Gauge::new(
+					"kulupu_network_hashrate",
+					"Network hashrate in hashes per second, estimated from the difficulty",
+				)?,
+				registry,
+			)?,
+			best_block_age: register(
+				Gauge::new(
+					"kulupu_best_block_age_seconds",
+					"Seconds since the best block last changed",
+				)?,
+				registry,
+			)?,
+		})
+	}
+}
+
+/// Update the best block metrics periodically.
+pub async fn monitor<C>(client: Arc<C>, registry: Registry)
+where
+	C: ProvideRuntimeApi<Block> + HeaderBackend<Block>,
+	C::Api: DifficultyApi<Block, Difficulty>,
+{
+	let metrics = match Metrics::register(&registry) {
+		Ok(metrics) => metrics,
+		Err(err) => {
+			warn!("Registering chain metrics failed: {:?}", err);
+			return;
+		}
+	};
+
+	let mut best_hash = None;
+	let mut best_changed = Instant::now();
+
+	loop {
+		let info = client.info();
+		if best_hash != Some(info.best_hash) {
+			best_hash = Some(info.best_hash);
+			best_changed = Instant::now();
+
+			match client
+				.runtime_api()
+				.difficulty(&BlockId::Hash(info.best_hash))
+			{
+				Ok(difficulty) => {
+					// Difficulty is the expected number of hashes per block.
+					let difficulty = difficulty.low_u128() as f64;
+					metrics.difficulty.set(difficulty);
+					metrics.hashrate.set(difficulty / BLOCK_TIME_SEC as f64);
+				}
+				Err(err) => debug!("Fetching difficulty for chain metrics failed: {:?}", err),
+			}
+		}
+		metrics.best_block_age.set(best_changed.elapsed().as_secs());
+
+		futures_timer::Delay::new(UPDATE_INTERVAL).await;
+	}
+}
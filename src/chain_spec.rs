@@ -23,7 +23,7 @@ use kulupu_runtime::{
 };
 use sc_service::ChainType;
 use serde_json::json;
-use sp_core::{crypto::UncheckedFrom, sr25519, Pair, Public, U256};
+use sp_core::{crypto::UncheckedFrom, sr25519, Pair, Public, H256, U256};
 use sp_runtime::traits::{IdentifyAccount, Verify};
 
 type AccountPublic = <Signature as Verify>::Signer;
@@ -141,7 +141,10 @@ fn testnet_genesis(
 			],
 		},
 		indices: IndicesConfig { indices: vec![] },
-		difficulty: DifficultyConfig { initial_difficulty },
+		difficulty: DifficultyConfig {
+			initial_difficulty,
+			past_difficulties_and_timestamps: Vec::new(),
+		},
 		rewards: RewardsConfig {
 			reward: 60 * DOLLARS,
 			mints: Default::default(),
@@ -153,12 +156,52 @@ fn testnet_genesis(
 /// Swamp bottom genesis config generation.
 #[allow(unused)]
 pub fn mainnet_genesis() -> GenesisConfig {
-	let era_state = crate::eras::era0_state();
+	era_genesis(
+		include_bytes!("../res/eras/1/3-swamp-bottom/kulupu_runtime.compact.wasm").to_vec(),
+		crate::eras::era0_state(),
+		Vec::new(),
+	)
+}
+
+/// Chain spec of the era starting from `era_state`, with the builtin runtime.
+pub fn next_era_config(
+	name: &str,
+	id: &str,
+	era_state: crate::eras::State,
+	past_eras: Vec<pallet_eras::Era<H256>>,
+) -> Result<ChainSpec, String> {
+	let wasm_binary = WASM_BINARY.ok_or("Wasm binary not available".to_string())?;
+	let mainnet = mainnet_config();
+
+	Ok(ChainSpec::from_genesis(
+		name,
+		id,
+		ChainType::Live,
+		move || era_genesis(wasm_binary.to_vec(), era_state.clone(), past_eras.clone()),
+		vec![],
+		None,
+		mainnet.protocol_id(),
+		Some(mainnet.properties()),
+		None,
+	))
+}
+
+/// Genesis config of an era starting from the final state of the previous
+/// era, `era_state`, which follows `past_eras`.
+fn era_genesis(
+	code: Vec<u8>,
+	era_state: crate::eras::State,
+	mut past_eras: Vec<pallet_eras::Era<H256>>,
+) -> GenesisConfig {
+	past_eras.push(pallet_eras::Era {
+		genesis_block_hash: era_state.previous_era.genesis_block_hash,
+		final_block_hash: era_state.previous_era.final_block_hash,
+		final_state_root: era_state.previous_era.final_state_root,
+	});
 
 	GenesisConfig {
 		system: SystemConfig {
-			code: include_bytes!("../res/eras/1/3-swamp-bottom/kulupu_runtime.compact.wasm")
-				.to_vec(),
+			code,
 			changes_trie_config: Default::default(),
 		},
 		balances: BalancesConfig {
@@ -182,14 +225,9 @@ pub fn mainnet_genesis() -> GenesisConfig {
 		},
 		difficulty: DifficultyConfig {
 			initial_difficulty: era_state.difficulty,
+			past_difficulties_and_timestamps: era_state.past_difficulties,
 		},
-		eras: ErasConfig {
-			past_eras: vec![pallet_eras::Era {
-				genesis_block_hash: era_state.previous_era.genesis_block_hash,
-				final_block_hash: era_state.previous_era.final_block_hash,
-				final_state_root: era_state.previous_era.final_state_root,
-			}],
-		},
+		eras: ErasConfig { past_eras },
 		rewards: RewardsConfig {
 			reward: 60 * DOLLARS,
 			mints: Default::default(),
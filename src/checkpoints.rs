@@ -0,0 +1,66 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Kulupu.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Kulupu is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Kulupu is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Kulupu. If not, see <http://www.gnu.org/licenses/>.
+
+//! Recording of checkpoints.
+//!
+//! Every `CHECKPOINT_INTERVAL` blocks, the block the configured depth below
+//! the new best block is recorded as a checkpoint, which block import then
+//! refuses to reorg away from.
+
+use futures::StreamExt;
+use kulupu_pow::checkpoint::{add_checkpoint, Checkpoint};
+use kulupu_runtime::opaque::Block;
+use log::*;
+use sc_client_api::{blockchain::HeaderBackend, AuxStore, BlockchainEvents};
+use sp_runtime::traits::Header as HeaderT;
+use std::sync::Arc;
+
+/// Number of blocks between checkpoints.
+const CHECKPOINT_INTERVAL: u32 = 10;
+
+/// Record checkpoints `depth` blocks below each new best block.
+pub async fn monitor<C>(client: Arc<C>, depth: u32)
+where
+	C: BlockchainEvents<Block> + HeaderBackend<Block> + AuxStore,
+{
+	let mut imports = client.import_notification_stream();
+
+	while let Some(notification) = imports.next().await {
+		if !notification.is_new_best {
+			continue;
+		}
+
+		let number = match notification.header.number().checked_sub(depth) {
+			Some(number) if number > 0 && number % CHECKPOINT_INTERVAL == 0 => number,
+			_ => continue,
+		};
+		let hash = match client.hash(number) {
+			Ok(Some(hash)) => hash,
+			Ok(None) => continue,
+			Err(err) => {
+				warn!("Fetching hash of block #{} failed: {:?}", number, err);
+				continue;
+			}
+		};
+
+		match add_checkpoint::<Block, _>(client.as_ref(), Checkpoint { number, hash }) {
+			Ok(()) => debug!("Recorded checkpoint #{} ({})", number, hash),
+			Err(err) => warn!("Recording checkpoint #{} failed: {:?}", number, err),
+		}
+	}
+}
@@ -16,10 +16,23 @@
 // You should have received a copy of the GNU General Public License
 // along with Kulupu. If not, see <http://www.gnu.org/licenses/>.
 
+use kulupu_pow::checkpoint::Checkpoint;
+use kulupu_runtime::BlockNumber;
 use sc_cli::RunCmd;
+use sp_core::{crypto::Ss58AddressFormat, H256};
+use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::str::FromStr;
 use structopt::StructOpt;
 
+/// SS58 address prefix of Kulupu accounts.
+pub const DEFAULT_SS58_PREFIX: u16 = 16;
+
+/// Address format for an optional SS58 prefix override, defaulting to Kulupu's.
+pub fn ss58_format(prefix: Option<u16>) -> Ss58AddressFormat {
+	Ss58AddressFormat::from(prefix.unwrap_or(DEFAULT_SS58_PREFIX))
+}
+
 #[derive(Debug, StructOpt)]
 pub enum Subcommand {
 	/// Build a chain specification.
@@ -37,6 +50,22 @@ pub enum Subcommand {
 	/// Import blocks.
 	ImportBlocks(sc_cli::ImportBlocksCmd),
 
+	/// Export the state at a block as the start of the next era.
+	#[structopt(name = "export-era")]
+	ExportEra(ExportEraCommand),
+
+	/// Export the state at a block, with the header chain leading to it.
+	#[structopt(name = "export-snapshot")]
+	ExportSnapshot(ExportSnapshotCommand),
+
+	/// Bootstrap an empty database from a snapshot.
+	#[structopt(name = "import-snapshot")]
+	ImportSnapshot(ImportSnapshotCommand),
+
+	/// Project total issuance per year under the current reward schedule.
+	#[structopt(name = "emission")]
+	Emission(EmissionCommand),
+
 	/// Remove the whole chain.
 	PurgeChain(sc_cli::PurgeChainCmd),
 
@@ -75,6 +104,79 @@ impl FromStr for RandomxFlag {
 	}
 }
 
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum RandomxMode {
+	Auto,
+	Full,
+	Light,
+	#[cfg(feature = "light-deterministic")]
+	LightDeterministic,
+}
+
+impl FromStr for RandomxMode {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"auto" => Ok(Self::Auto),
+			"full" => Ok(Self::Full),
+			"light" => Ok(Self::Light),
+			#[cfg(feature = "light-deterministic")]
+			"light-deterministic" => Ok(Self::LightDeterministic),
+			_ => Err("Unknown mode".to_string()),
+		}
+	}
+}
+
+/// What an imported snapshot is trusted for, beyond what can be checked
+/// against the genesis block and the state root.
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum SnapshotTrust {
+	/// Verify the seal of every header and recompute difficulties.
+	Seals,
+	/// Trust the difficulties recorded in the snapshot.
+	Difficulties,
+}
+
+impl FromStr for SnapshotTrust {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"seals" => Ok(Self::Seals),
+			"difficulties" => Ok(Self::Difficulties),
+			_ => Err("Unknown trust model".to_string()),
+		}
+	}
+}
+
+/// Preset of options for a common way of running the node.
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum Profile {
+	/// Mine on all cores with full RandomX caches.
+	Miner,
+	/// Keep the state of all blocks.
+	Archive,
+	/// Serve safe RPC methods publicly, with the state of all blocks.
+	Rpc,
+	/// Run as a light client, verifying seals with light RandomX caches.
+	LightVerify,
+}
+
+impl FromStr for Profile {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"miner" => Ok(Self::Miner),
+			"archive" => Ok(Self::Archive),
+			"rpc" => Ok(Self::Rpc),
+			"light-verify" => Ok(Self::LightVerify),
+			_ => Err("Unknown profile".to_string()),
+		}
+	}
+}
+
 #[derive(Debug, StructOpt)]
 pub struct Cli {
 	#[structopt(subcommand)]
@@ -83,8 +185,25 @@ pub struct Cli {
 	#[structopt(flatten)]
 	pub run: RunCmd,
 
+	/// Preset of options to run the node with: `miner`, `archive`, `rpc` or
+	/// `light-verify`. Options given explicitly take precedence.
+	#[structopt(long)]
+	pub profile: Option<Profile>,
 	#[structopt(long)]
 	pub author: Option<String>,
+	/// Mining keys signing seals jointly, instead of a single author key. The
+	/// author is their joint public key, and all of them must be in the
+	/// keystore.
+	#[structopt(long, use_delimiter = true)]
+	pub joint_author_keys: Vec<String>,
+	/// Account to pay mining rewards to, instead of the author key signing the
+	/// seals.
+	#[structopt(long)]
+	pub payout: Option<String>,
+	/// Name of the miner, declared in the pre-runtime digest of mined blocks
+	/// so that explorers can label them.
+	#[structopt(long)]
+	pub miner_name: Option<String>,
 	#[structopt(long)]
 	pub threads: Option<usize>,
 	#[structopt(long)]
@@ -95,8 +214,97 @@ pub struct Cli {
 	pub disable_weak_subjectivity: bool,
 	#[structopt(long)]
 	pub check_inherents_after: Option<u32>,
+	/// Reorgs retracting more blocks than this need more total difficulty than
+	/// the best chain to be imported.
+	#[structopt(long)]
+	pub max_reorg_depth: Option<usize>,
+	/// Factor the total difficulty a reorg needs grows by for each retracted
+	/// block beyond the maximum reorg depth. At least 1.
+	#[structopt(long)]
+	pub reorg_penalty_factor: Option<f64>,
+	/// Reject blocks of forks retracting more blocks than this, whatever their
+	/// total difficulty. Disabled by default.
+	#[structopt(long)]
+	pub absolute_max_reorg_depth: Option<usize>,
+	/// Record blocks this many blocks below the best block as checkpoints, and
+	/// refuse to import chains conflicting with them. Disabled by default.
+	#[structopt(long)]
+	pub checkpoint_depth: Option<u32>,
+	/// Trusted block given as `<number>:<hash>`. Seals of blocks at or below it
+	/// are not computed, and chains not containing it are refused. Overrides the
+	/// `powCheckpoint` property of the chain spec.
+	#[structopt(long)]
+	pub pow_checkpoint: Option<Checkpoint<BlockNumber, H256>>,
 	#[structopt(long)]
 	pub randomx_flags: Vec<RandomxFlag>,
+	/// RandomX caches to use: `full`, `light`, or `auto` to use full caches for
+	/// mining only. Verification only reuses full caches generated for mining,
+	/// so nodes not mining use light caches either way. Nodes built with the
+	/// `light-deterministic` feature also accept `light-deterministic`, which
+	/// mines development chains on light caches with a fixed key, so that local
+	/// networks behave the same on any machine.
+	#[structopt(long)]
+	pub randomx_mode: Option<RandomxMode>,
+	/// Memory in MiB RandomX may use. Caps the number of mining threads with
+	/// full VMs, or falls back to light caches if the full caches do not fit.
+	#[structopt(long)]
+	pub randomx_memory_limit: Option<u64>,
+	/// Number of RandomX VMs shared by mining and verification threads, rather
+	/// than one per thread. Threads wait for a free VM beyond this.
+	#[structopt(long)]
+	pub randomx_vm_count: Option<usize>,
+	/// Number of threads computing the seal hashes of incoming blocks ahead of
+	/// their import, to speed up initial sync. Disabled by default.
+	#[structopt(long)]
+	pub fast_sync_verify_workers: Option<usize>,
+	/// Address to serve push-based mining work to external miners on.
+	#[structopt(long)]
+	pub work_server: Option<SocketAddr>,
+	/// Pause mining when the best block has not changed for this many target
+	/// block times, while peers have seen a higher block.
+	#[structopt(long)]
+	pub stale_tip_blocks: Option<u32>,
+	/// HTTP endpoint to post JSON to when mining is paused or resumed on a
+	/// stale best block.
+	#[structopt(long)]
+	pub stale_tip_webhook: Option<String>,
+	/// Pause mining for this many target block times after a reorg blocked by
+	/// weak subjectivity, or one retracting at least `--reorg-backoff-depth`
+	/// blocks. Disabled by default.
+	#[structopt(long)]
+	pub reorg_backoff_blocks: Option<u32>,
+	/// Number of retracted blocks a reorg needs to pause mining.
+	#[structopt(long)]
+	pub reorg_backoff_depth: Option<u32>,
+	/// HTTP RPC endpoint of a primary node. When set, this node only mines
+	/// while the primary produces no new work.
+	#[structopt(long)]
+	pub standby_primary: Option<String>,
+	/// Seconds without new work from the primary before this node mines.
+	#[structopt(long)]
+	pub standby_timeout: Option<u64>,
+	/// Divisor of the network difficulty to accept work RPC shares at. Only
+	/// seals meeting the network difficulty are imported.
+	#[structopt(long)]
+	pub work_share_divisor: Option<u64>,
+	/// Sign work handed out over the work RPC with the mining key, so external
+	/// miners can detect work substituted on the way.
+	#[structopt(long)]
+	pub sign_work: bool,
+	/// NTP servers (`host:port`) whose time new blocks are timestamped with,
+	/// instead of system time.
+	#[structopt(long, use_delimiter = true)]
+	pub ntp_server: Vec<String>,
+	/// Maximum offset to system time in seconds that NTP time is trusted up to.
+	/// Larger offsets are clamped to it.
+	#[structopt(long)]
+	pub ntp_max_offset: Option<u64>,
+	/// Address to serve a local mining dashboard page on.
+	#[structopt(long)]
+	pub dashboard: Option<SocketAddr>,
+	/// SS58 prefix to parse and render addresses with, for custom testnets.
+	#[structopt(long)]
+	pub ss58_prefix: Option<u16>,
 }
 
 #[derive(Debug, StructOpt)]
@@ -105,10 +313,173 @@ pub struct ExportBuiltinWasmCommand {
 	pub folder: String,
 }
 
+#[derive(Debug, StructOpt)]
+pub struct ExportEraCommand {
+	/// Final block of the era. Defaults to the best block.
+	#[structopt(long)]
+	pub block: Option<u32>,
+
+	/// File to write the era state JSON to.
+	#[structopt(long, parse(from_os_str))]
+	pub output: PathBuf,
+
+	/// File to write a chain spec of the next era to, with the builtin runtime.
+	#[structopt(long, parse(from_os_str))]
+	pub chain_spec: Option<PathBuf>,
+
+	/// Name of the next era chain spec.
+	#[structopt(long, default_value = "Kulupu")]
+	pub name: String,
+
+	/// Id of the next era chain spec.
+	#[structopt(long, default_value = "kulupu")]
+	pub id: String,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub shared_params: sc_cli::SharedParams,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub pruning_params: sc_cli::PruningParams,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub database_params: sc_cli::DatabaseParams,
+}
+
+impl sc_cli::CliConfiguration for ExportEraCommand {
+	fn shared_params(&self) -> &sc_cli::SharedParams {
+		&self.shared_params
+	}
+	fn pruning_params(&self) -> Option<&sc_cli::PruningParams> {
+		Some(&self.pruning_params)
+	}
+	fn database_params(&self) -> Option<&sc_cli::DatabaseParams> {
+		Some(&self.database_params)
+	}
+}
+
+#[derive(Debug, StructOpt)]
+pub struct EmissionCommand {
+	/// Block to take the reward schedule and total issuance at. Defaults to the
+	/// best block.
+	#[structopt(long)]
+	pub block: Option<u32>,
+
+	/// Number of years to project.
+	#[structopt(long, default_value = "10")]
+	pub years: u32,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub shared_params: sc_cli::SharedParams,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub pruning_params: sc_cli::PruningParams,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub database_params: sc_cli::DatabaseParams,
+}
+
+impl sc_cli::CliConfiguration for EmissionCommand {
+	fn shared_params(&self) -> &sc_cli::SharedParams {
+		&self.shared_params
+	}
+	fn pruning_params(&self) -> Option<&sc_cli::PruningParams> {
+		Some(&self.pruning_params)
+	}
+	fn database_params(&self) -> Option<&sc_cli::DatabaseParams> {
+		Some(&self.database_params)
+	}
+}
+
+#[derive(Debug, StructOpt)]
+pub struct ExportSnapshotCommand {
+	/// Block to take the snapshot at. Defaults to the best block.
+	#[structopt(long)]
+	pub block: Option<u32>,
+
+	/// File to write the snapshot to.
+	#[structopt(long, parse(from_os_str))]
+	pub output: PathBuf,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub shared_params: sc_cli::SharedParams,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub pruning_params: sc_cli::PruningParams,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub database_params: sc_cli::DatabaseParams,
+}
+
+impl sc_cli::CliConfiguration for ExportSnapshotCommand {
+	fn shared_params(&self) -> &sc_cli::SharedParams {
+		&self.shared_params
+	}
+	fn pruning_params(&self) -> Option<&sc_cli::PruningParams> {
+		Some(&self.pruning_params)
+	}
+	fn database_params(&self) -> Option<&sc_cli::DatabaseParams> {
+		Some(&self.database_params)
+	}
+}
+
+#[derive(Debug, StructOpt)]
+pub struct ImportSnapshotCommand {
+	/// File to read the snapshot from.
+	#[structopt(long, parse(from_os_str))]
+	pub input: PathBuf,
+
+	/// Trust model of the snapshot: `seals` verifies the seal of every header,
+	/// `difficulties` trusts the difficulties it records. Defaults to `seals`.
+	#[structopt(long)]
+	pub trust: Option<SnapshotTrust>,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub shared_params: sc_cli::SharedParams,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub pruning_params: sc_cli::PruningParams,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub database_params: sc_cli::DatabaseParams,
+}
+
+impl sc_cli::CliConfiguration for ImportSnapshotCommand {
+	fn shared_params(&self) -> &sc_cli::SharedParams {
+		&self.shared_params
+	}
+	fn pruning_params(&self) -> Option<&sc_cli::PruningParams> {
+		Some(&self.pruning_params)
+	}
+	fn database_params(&self) -> Option<&sc_cli::DatabaseParams> {
+		Some(&self.database_params)
+	}
+}
+
 #[derive(Debug, StructOpt)]
 pub struct ImportMiningKeyCommand {
-	#[structopt()]
-	pub suri: String,
+	#[structopt(required_unless = "file")]
+	pub suri: Option<String>,
+
+	/// File of SURIs to import, one per line. Empty lines and lines starting
+	/// with `#` are skipped.
+	#[structopt(long, parse(from_os_str), conflicts_with = "suri")]
+	pub file: Option<PathBuf>,
+
+	/// SS58 prefix to render the address with, for custom testnets.
+	#[structopt(long)]
+	pub ss58_prefix: Option<u16>,
 
 	#[allow(missing_docs)]
 	#[structopt(flatten)]
@@ -130,6 +501,10 @@ impl sc_cli::CliConfiguration for ImportMiningKeyCommand {
 
 #[derive(Debug, StructOpt)]
 pub struct GenerateMiningKeyCommand {
+	/// SS58 prefix to render the address with, for custom testnets.
+	#[structopt(long)]
+	pub ss58_prefix: Option<u16>,
+
 	#[allow(missing_docs)]
 	#[structopt(flatten)]
 	pub shared_params: sc_cli::SharedParams,
@@ -17,21 +17,37 @@
 // along with Kulupu. If not, see <http://www.gnu.org/licenses/>.
 
 use crate::chain_spec;
-use crate::cli::{Cli, RandomxFlag, Subcommand};
+use crate::cli::{ss58_format, Cli, Profile, RandomxFlag, RandomxMode, SnapshotTrust, Subcommand};
 use crate::service;
+use kulupu_primitives::DOLLARS;
+use kulupu_rpc_miner_stats::MAX_MINER_NAME_LEN;
 use log::{info, warn};
-use sc_cli::{ChainSpec, Role, RuntimeVersion, SubstrateCli};
+use sc_cli::{ChainSpec, Role, RpcMethods, RuntimeVersion, SubstrateCli};
 use sc_keystore::LocalKeystore;
 use sc_service::{config::KeystoreConfig, PartialComponents};
+use sp_blockchain::HeaderBackend;
 use sp_core::{
-	crypto::{Pair, Ss58AddressFormat, Ss58Codec},
+	crypto::{Pair, Ss58Codec},
 	hexdisplay::HexDisplay,
 };
 use sp_keystore::SyncCryptoStore;
-use std::{fs::File, io::Write, path::PathBuf};
+use std::{fs::File, io::Write, path::PathBuf, time::Duration};
 
 const DEFAULT_CHECK_INHERENTS_AFTER: u32 = 152650;
 const DEFAULT_ROUND: u32 = 1000;
+const DEFAULT_WORK_SHARE_DIVISOR: u64 = 1;
+const DEFAULT_STALE_TIP_BLOCKS: u32 = 30;
+const DEFAULT_STANDBY_TIMEOUT: u64 = 60;
+const DEFAULT_REORG_BACKOFF_DEPTH: u32 = 10;
+const DEFAULT_NTP_MAX_OFFSET: u64 = 30;
+const DEFAULT_MAX_REORG_DEPTH: usize = 30;
+const DEFAULT_REORG_PENALTY_FACTOR: f64 = 1.1;
+/// Default of `--in-peers` and `--out-peers` in `sc_cli`.
+const DEFAULT_PEERS: u32 = 25;
+/// Peers of nodes run with the `rpc` profile, to follow the best block closely.
+const RPC_PROFILE_PEERS: u32 = 50;
+/// Peers of nodes run with the `light-verify` profile.
+const LIGHT_VERIFY_PROFILE_PEERS: u32 = 10;
 
 /// URL for the telemetry server. Disabled by default.
 pub const POLKADOT_TELEMETRY_URL: &str = "wss://telemetry.polkadot.io/submit/";
@@ -78,9 +94,65 @@ impl SubstrateCli for Cli {
 	}
 }
 
+/// Set the options of the profile of `cli` that were not given explicitly.
+/// Peer limits have defaults, so they are only set while at their defaults.
+fn apply_profile(cli: &mut Cli) {
+	let profile = match cli.profile {
+		Some(profile) => profile,
+		None => return,
+	};
+
+	let run = &mut cli.run;
+	let set_peers = |peers: &mut u32, profile_peers| {
+		if *peers == DEFAULT_PEERS {
+			*peers = profile_peers;
+		}
+	};
+	match profile {
+		Profile::Miner => {
+			run.validator = true;
+			cli.threads.get_or_insert_with(num_cpus::get);
+			cli.randomx_mode.get_or_insert(RandomxMode::Full);
+		}
+		Profile::Archive => {
+			run.import_params
+				.pruning_params
+				.pruning
+				.get_or_insert_with(|| "archive".into());
+			cli.randomx_mode.get_or_insert(RandomxMode::Light);
+			cli.fast_sync_verify_workers
+				.get_or_insert_with(num_cpus::get);
+		}
+		Profile::Rpc => {
+			run.import_params
+				.pruning_params
+				.pruning
+				.get_or_insert_with(|| "archive".into());
+			run.rpc_external = true;
+			run.ws_external = true;
+			if run.rpc_methods == RpcMethods::Auto {
+				run.rpc_methods = RpcMethods::Safe;
+			}
+			set_peers(&mut run.network_params.in_peers, RPC_PROFILE_PEERS);
+			set_peers(&mut run.network_params.out_peers, RPC_PROFILE_PEERS);
+			cli.randomx_mode.get_or_insert(RandomxMode::Light);
+		}
+		Profile::LightVerify => {
+			run.light = true;
+			set_peers(&mut run.network_params.in_peers, LIGHT_VERIFY_PROFILE_PEERS);
+			set_peers(
+				&mut run.network_params.out_peers,
+				LIGHT_VERIFY_PROFILE_PEERS,
+			);
+			cli.randomx_mode.get_or_insert(RandomxMode::Light);
+		}
+	}
+}
+
 /// Parse and run command line arguments
 pub fn run() -> sc_cli::Result<()> {
 	let mut cli = Cli::from_args();
+	apply_profile(&mut cli);
 	if cli.enable_polkadot_telemetry {
 		cli.run
 			.telemetry_endpoints
@@ -95,8 +167,78 @@ pub fn run() -> sc_cli::Result<()> {
 	if cli.randomx_flags.contains(&RandomxFlag::Secure) {
 		randomx_config.secure = true;
 	}
+	randomx_config.cache_mode = match cli.randomx_mode.unwrap_or(RandomxMode::Auto) {
+		RandomxMode::Auto => kulupu_pow::compute::CacheMode::Auto,
+		RandomxMode::Full => kulupu_pow::compute::CacheMode::Full,
+		RandomxMode::Light => kulupu_pow::compute::CacheMode::Light,
+		#[cfg(feature = "light-deterministic")]
+		RandomxMode::LightDeterministic => kulupu_pow::compute::CacheMode::LightDeterministic,
+	};
+	if let Some(limit) = cli.randomx_memory_limit {
+		let max_full_vms = kulupu_pow::compute::max_full_vms(limit.saturating_mul(1024 * 1024));
+		if max_full_vms == 0 {
+			if randomx_config.cache_mode == kulupu_pow::compute::CacheMode::Full {
+				return Err(format!(
+					"RandomX memory limit of {} MiB does not fit the full caches of full mode.",
+					limit,
+				)
+				.into());
+			}
+
+			if randomx_config.cache_mode == kulupu_pow::compute::CacheMode::Auto {
+				warn!(
+					"RandomX memory limit of {} MiB does not fit the full caches, using light caches only.",
+					limit,
+				);
+				randomx_config.cache_mode = kulupu_pow::compute::CacheMode::Light;
+			}
+		}
+		randomx_config.max_full_vms = Some(max_full_vms);
+	}
+	if let Some(vm_count) = cli.randomx_vm_count {
+		if vm_count == 0 {
+			return Err("RandomX VM count must be at least 1.".into());
+		}
+
+		// Pooled full VMs are not counted against the memory limit otherwise.
+		let vm_count = match randomx_config.max_full_vms {
+			Some(max_full_vms) if max_full_vms > 0 => vm_count.min(max_full_vms),
+			_ => vm_count,
+		};
+		randomx_config.vm_count = Some(vm_count);
+	}
 	let _ = kulupu_pow::compute::set_global_config(randomx_config);
 
+	let reorg_penalty_factor = cli
+		.reorg_penalty_factor
+		.unwrap_or(DEFAULT_REORG_PENALTY_FACTOR);
+	if reorg_penalty_factor.is_nan() || reorg_penalty_factor < 1.0 {
+		return Err("Reorg penalty factor must be at least 1.".into());
+	}
+	let weak_subjective_algorithm = kulupu_pow::weak_sub::ExponentialWeakSubjectiveAlgorithm(
+		cli.max_reorg_depth.unwrap_or(DEFAULT_MAX_REORG_DEPTH),
+		reorg_penalty_factor,
+	);
+	if cli.absolute_max_reorg_depth == Some(0) {
+		return Err("Absolute max reorg depth must be at least 1.".into());
+	}
+	if cli.checkpoint_depth == Some(0) {
+		return Err("Checkpoint depth must be at least 1.".into());
+	}
+	if cli.reorg_backoff_depth == Some(0) {
+		return Err("Reorg backoff depth must be at least 1.".into());
+	}
+	if !cli.joint_author_keys.is_empty() && cli.joint_author_keys.len() != 2 {
+		return Err("Joint author keys must be exactly 2.".into());
+	}
+	if cli
+		.miner_name
+		.as_ref()
+		.map_or(false, |name| name.len() > MAX_MINER_NAME_LEN)
+	{
+		return Err(format!("Miner name must be at most {} bytes.", MAX_MINER_NAME_LEN).into());
+	}
+
 	match &cli.subcommand {
 		Some(Subcommand::BuildSpec(cmd)) => {
 			let runner = cli.create_runner(cmd)?;
@@ -115,6 +257,10 @@ pub fn run() -> sc_cli::Result<()> {
 					cli.check_inherents_after
 						.unwrap_or(DEFAULT_CHECK_INHERENTS_AFTER),
 					!cli.disable_weak_subjectivity,
+					weak_subjective_algorithm.clone(),
+					cli.absolute_max_reorg_depth,
+					cli.checkpoint_depth.is_some(),
+					cli.pow_checkpoint,
 				)?;
 				Ok((cmd.run(client, import_queue), task_manager))
 			})
@@ -131,6 +277,10 @@ pub fn run() -> sc_cli::Result<()> {
 					cli.check_inherents_after
 						.unwrap_or(DEFAULT_CHECK_INHERENTS_AFTER),
 					!cli.disable_weak_subjectivity,
+					weak_subjective_algorithm.clone(),
+					cli.absolute_max_reorg_depth,
+					cli.checkpoint_depth.is_some(),
+					cli.pow_checkpoint,
 				)?;
 				Ok((cmd.run(client, config.database), task_manager))
 			})
@@ -147,6 +297,10 @@ pub fn run() -> sc_cli::Result<()> {
 					cli.check_inherents_after
 						.unwrap_or(DEFAULT_CHECK_INHERENTS_AFTER),
 					!cli.disable_weak_subjectivity,
+					weak_subjective_algorithm.clone(),
+					cli.absolute_max_reorg_depth,
+					cli.checkpoint_depth.is_some(),
+					cli.pow_checkpoint,
 				)?;
 				Ok((cmd.run(client, config.chain_spec), task_manager))
 			})
@@ -164,10 +318,154 @@ pub fn run() -> sc_cli::Result<()> {
 					cli.check_inherents_after
 						.unwrap_or(DEFAULT_CHECK_INHERENTS_AFTER),
 					!cli.disable_weak_subjectivity,
+					weak_subjective_algorithm.clone(),
+					cli.absolute_max_reorg_depth,
+					cli.checkpoint_depth.is_some(),
+					cli.pow_checkpoint,
 				)?;
 				Ok((cmd.run(client, import_queue), task_manager))
 			})
 		}
+		Some(Subcommand::ExportEra(cmd)) => {
+			let runner = cli.create_runner(cmd)?;
+			runner.sync_run(|config| {
+				let PartialComponents { client, .. } = crate::service::new_partial(
+					&config,
+					cli.check_inherents_after
+						.unwrap_or(DEFAULT_CHECK_INHERENTS_AFTER),
+					!cli.disable_weak_subjectivity,
+					weak_subjective_algorithm.clone(),
+					cli.absolute_max_reorg_depth,
+					cli.checkpoint_depth.is_some(),
+					cli.pow_checkpoint,
+				)?;
+				let number = cmd.block.unwrap_or_else(|| client.info().best_number);
+
+				let state = crate::eras::export_state(&*client, number)?;
+				info!(
+					"Exporting state of block #{} ({} balances, {} indices) to {}",
+					number,
+					state.balances.len(),
+					state.indices.len(),
+					cmd.output.display(),
+				);
+				serde_json::to_writer_pretty(File::create(&cmd.output)?, &state)
+					.map_err(|e| format!("Writing era state failed: {:?}", e))?;
+
+				if let Some(path) = &cmd.chain_spec {
+					let past_eras = crate::eras::past_eras(&*client, number)?;
+					let spec =
+						crate::chain_spec::next_era_config(&cmd.name, &cmd.id, state, past_eras)?;
+
+					info!("Exporting next era chain spec to {}", path.display());
+					let json = spec.as_json(false)?;
+					File::create(path)?.write_all(json.as_bytes())?;
+				}
+
+				Ok(())
+			})
+		}
+		Some(Subcommand::Emission(cmd)) => {
+			let runner = cli.create_runner(cmd)?;
+			runner.sync_run(|config| {
+				let PartialComponents { client, .. } = crate::service::new_partial(
+					&config,
+					cli.check_inherents_after
+						.unwrap_or(DEFAULT_CHECK_INHERENTS_AFTER),
+					!cli.disable_weak_subjectivity,
+					weak_subjective_algorithm.clone(),
+					cli.absolute_max_reorg_depth,
+					cli.checkpoint_depth.is_some(),
+					cli.pow_checkpoint,
+				)?;
+				let number = cmd.block.unwrap_or_else(|| client.info().best_number);
+
+				let issuance = crate::emission::total_issuance(&*client, number)?;
+				let schedule = crate::emission::emission_schedule(&*client, number)?;
+				info!(
+					"Projecting issuance from block #{} over {} years",
+					number, cmd.years
+				);
+
+				println!("Year	End block	Minted (KLP)	Total issuance (KLP)	Inflation");
+				let mut previous = issuance;
+				for (index, year) in
+					crate::emission::project(&schedule, number, issuance, cmd.years)
+						.into_iter()
+						.enumerate()
+				{
+					let inflation = if previous == 0 {
+						0.0
+					} else {
+						year.minted as f64 * 100.0 / previous as f64
+					};
+					println!(
+						"{}	{}	{}	{}	{:.2}%",
+						index + 1,
+						year.end,
+						year.minted / DOLLARS,
+						year.total_issuance / DOLLARS,
+						inflation,
+					);
+					previous = year.total_issuance;
+				}
+
+				Ok(())
+			})
+		}
+		Some(Subcommand::ExportSnapshot(cmd)) => {
+			let runner = cli.create_runner(cmd)?;
+			runner.sync_run(|config| {
+				let PartialComponents { client, .. } = crate::service::new_partial(
+					&config,
+					cli.check_inherents_after
+						.unwrap_or(DEFAULT_CHECK_INHERENTS_AFTER),
+					!cli.disable_weak_subjectivity,
+					weak_subjective_algorithm.clone(),
+					cli.absolute_max_reorg_depth,
+					cli.checkpoint_depth.is_some(),
+					cli.pow_checkpoint,
+				)?;
+				let number = cmd.block.unwrap_or_else(|| client.info().best_number);
+
+				let snapshot = crate::snapshot::export_snapshot(&*client, number)?;
+				info!(
+					"Exporting snapshot of block #{} ({} state entries) to {}",
+					number,
+					snapshot.state.len(),
+					cmd.output.display(),
+				);
+				crate::snapshot::write_snapshot(&cmd.output, &snapshot)?;
+
+				Ok(())
+			})
+		}
+		Some(Subcommand::ImportSnapshot(cmd)) => {
+			let runner = cli.create_runner(cmd)?;
+			runner.sync_run(|config| {
+				let PartialComponents { client, .. } = crate::service::new_partial(
+					&config,
+					cli.check_inherents_after
+						.unwrap_or(DEFAULT_CHECK_INHERENTS_AFTER),
+					!cli.disable_weak_subjectivity,
+					weak_subjective_algorithm.clone(),
+					cli.absolute_max_reorg_depth,
+					cli.checkpoint_depth.is_some(),
+					cli.pow_checkpoint,
+				)?;
+
+				info!("Reading snapshot from {}", cmd.input.display());
+				let snapshot = crate::snapshot::read_snapshot(&cmd.input)?;
+				crate::snapshot::import_snapshot(
+					&*client,
+					&*client,
+					snapshot,
+					cmd.trust.unwrap_or(SnapshotTrust::Seals),
+				)?;
+
+				Ok(())
+			})
+		}
 		Some(Subcommand::PurgeChain(cmd)) => {
 			let runner = cli.create_runner(cmd)?;
 			runner.sync_run(|config| cmd.run(config.database))
@@ -185,6 +483,10 @@ pub fn run() -> sc_cli::Result<()> {
 					cli.check_inherents_after
 						.unwrap_or(DEFAULT_CHECK_INHERENTS_AFTER),
 					!cli.disable_weak_subjectivity,
+					weak_subjective_algorithm.clone(),
+					cli.absolute_max_reorg_depth,
+					cli.checkpoint_depth.is_some(),
+					cli.pow_checkpoint,
 				)?;
 				Ok((cmd.run(client, backend), task_manager))
 			})
@@ -228,21 +530,70 @@ pub fn run() -> sc_cli::Result<()> {
 					KeystoreConfig::InMemory => LocalKeystore::in_memory(),
 				};
 
-				let pair = kulupu_pow::app::Pair::from_string(&cmd.suri, None)
-					.map_err(|e| format!("Invalid seed: {:?}", e))?;
+				let import = |suri: &str| -> Result<kulupu_pow::app::Public, String> {
+					let pair = kulupu_pow::app::Pair::from_string(suri, None)
+						.map_err(|e| format!("Invalid seed: {:?}", e))?;
 
-				SyncCryptoStore::insert_unknown(
-					&keystore,
-					kulupu_pow::app::ID,
-					&cmd.suri,
-					pair.public().as_ref(),
-				)
-				.map_err(|e| format!("Registering mining key failed: {:?}", e))?;
+					SyncCryptoStore::insert_unknown(
+						&keystore,
+						kulupu_pow::app::ID,
+						suri,
+						pair.public().as_ref(),
+					)
+					.map_err(|e| format!("Registering mining key failed: {:?}", e))?;
+
+					Ok(pair.public())
+				};
+
+				let path = match (&cmd.suri, &cmd.file) {
+					(Some(suri), _) => {
+						let public = import(suri)?;
+						info!(
+							"Registered one mining key (public key 0x{}, address {}).",
+							HexDisplay::from(&public.as_ref()),
+							public.to_ss58check_with_version(ss58_format(cmd.ss58_prefix)),
+						);
+						return Ok(());
+					}
+					(None, Some(path)) => path,
+					(None, None) => return Err("No SURI or file given".into()),
+				};
+
+				let contents = std::fs::read_to_string(path)?;
+				let (mut imported, mut failed) = (0, 0);
+				for (index, line) in contents.lines().enumerate() {
+					let suri = line.trim();
+					if suri.is_empty() || suri.starts_with('#') {
+						continue;
+					}
+
+					// Errors leave out the line, which holds a secret.
+					match import(suri) {
+						Ok(public) => {
+							imported += 1;
+							info!(
+								"Registered mining key of line {} (public key 0x{}, address {}).",
+								index + 1,
+								HexDisplay::from(&public.as_ref()),
+								public.to_ss58check_with_version(ss58_format(cmd.ss58_prefix)),
+							);
+						}
+						Err(err) => {
+							failed += 1;
+							warn!("Importing mining key of line {} failed: {}", index + 1, err);
+						}
+					}
+				}
 
 				info!(
-					"Registered one mining key (public key 0x{}).",
-					HexDisplay::from(&pair.public().as_ref())
+					"Registered {} mining keys from {}, {} failed.",
+					imported,
+					path.display(),
+					failed,
 				);
+				if failed > 0 {
+					return Err(format!("{} mining keys failed to import", failed).into());
+				}
 
 				Ok(())
 			})
@@ -275,7 +626,7 @@ pub fn run() -> sc_cli::Result<()> {
 					HexDisplay::from(&pair.public().as_ref()),
 					phrase,
 					pair.public()
-						.to_ss58check_with_version(Ss58AddressFormat::KulupuAccount),
+						.to_ss58check_with_version(ss58_format(cmd.ss58_prefix)),
 				);
 
 				Ok(())
@@ -296,23 +647,65 @@ pub fn run() -> sc_cli::Result<()> {
 		}
 		None => {
 			let runner = cli.create_runner(&cli.run)?;
+			if let Some(prefix) = cli.ss58_prefix {
+				sp_core::crypto::set_default_ss58_version(prefix.into());
+			}
 			runner
 				.run_node_until_exit(|config| async move {
+					#[cfg(feature = "light-deterministic")]
+					if kulupu_pow::compute::global_config().cache_mode
+						== kulupu_pow::compute::CacheMode::LightDeterministic
+						&& config.chain_spec.chain_type() == sc_service::ChainType::Live
+					{
+						return Err(sc_service::Error::Other(
+							"Light deterministic RandomX mode is only available on development chains."
+								.into(),
+						));
+					}
+
 					match config.role {
 						Role::Light => service::new_light(
 							config,
 							cli.check_inherents_after
 								.unwrap_or(DEFAULT_CHECK_INHERENTS_AFTER),
 							!cli.disable_weak_subjectivity,
+							weak_subjective_algorithm.clone(),
+							cli.absolute_max_reorg_depth,
 						),
 						_ => service::new_full(
 							config,
 							cli.author.as_ref().map(|s| s.as_str()),
+							&cli.joint_author_keys,
+							cli.payout.as_ref().map(|s| s.as_str()),
+							cli.miner_name.as_ref().map(|s| s.as_str()),
 							cli.threads.unwrap_or(1),
 							cli.round.unwrap_or(DEFAULT_ROUND),
 							cli.check_inherents_after
 								.unwrap_or(DEFAULT_CHECK_INHERENTS_AFTER),
 							!cli.disable_weak_subjectivity,
+							weak_subjective_algorithm.clone(),
+							cli.absolute_max_reorg_depth,
+							cli.checkpoint_depth,
+							cli.pow_checkpoint,
+							cli.fast_sync_verify_workers.unwrap_or(0),
+							cli.work_server,
+							cli.work_share_divisor.unwrap_or(DEFAULT_WORK_SHARE_DIVISOR),
+							cli.sign_work,
+							cli.dashboard,
+							cli.stale_tip_blocks.unwrap_or(DEFAULT_STALE_TIP_BLOCKS),
+							cli.stale_tip_webhook.clone(),
+							cli.reorg_backoff_blocks,
+							cli.reorg_backoff_depth
+								.unwrap_or(DEFAULT_REORG_BACKOFF_DEPTH),
+							cli.ntp_server.clone(),
+							Duration::from_secs(
+								cli.ntp_max_offset.unwrap_or(DEFAULT_NTP_MAX_OFFSET),
+							),
+							cli.standby_primary.clone(),
+							Duration::from_secs(
+								cli.standby_timeout.unwrap_or(DEFAULT_STANDBY_TIMEOUT),
+							),
+							ss58_format(cli.ss58_prefix),
 						),
 					}
 				})
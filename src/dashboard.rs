@@ -0,0 +1,247 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Kulupu.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Kulupu is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Kulupu is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Kulupu. If not, see <http://www.gnu.org/licenses/>.
+
+//! Local mining dashboard.
+//!
+//! A plain HTML page for miners without a monitoring setup, showing the local
+//! and network hashrate, the seals found recently, the reward locks of the
+//! payout account and the sync status of the node. The page reloads itself
+//! every `REFRESH_SECS` seconds.
+
+use codec::{Decode, Encode};
+use hyper::{
+	service::{make_service_fn, service_fn},
+	Body, Request, Response, Server, StatusCode,
+};
+use kulupu_pow::Stats;
+use kulupu_primitives::{Difficulty, BLOCK_TIME_SEC, DOLLARS};
+use kulupu_runtime::{opaque::Block, AccountId, Balance, BlockNumber};
+use log::*;
+use parking_lot::Mutex;
+use sc_client_api::{Backend, StorageProvider};
+use sc_network::NetworkService;
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use sp_consensus_pow::DifficultyApi;
+use sp_core::{
+	crypto::Ss58Codec,
+	hashing::{twox_128, twox_64},
+	storage::StorageKey,
+};
+use sp_runtime::{generic::BlockId, traits::Block as BlockT};
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+/// Seconds between page reloads.
+const REFRESH_SECS: u32 = 10;
+
+struct Dashboard<C, B> {
+	client: Arc<C>,
+	network: Arc<NetworkService<Block, <Block as BlockT>::Hash>>,
+	stats: Arc<Mutex<Stats>>,
+	account: AccountId,
+	_marker: PhantomData<B>,
+}
+
+/// Bind the dashboard to `addr`, returning the future serving it.
+pub fn start<C, B>(
+	addr: SocketAddr,
+	client: Arc<C>,
+	network: Arc<NetworkService<Block, <Block as BlockT>::Hash>>,
+	stats: Arc<Mutex<Stats>>,
+	account: AccountId,
+) -> Result<impl Future<Output = ()>, hyper::Error>
+where
+	C: ProvideRuntimeApi<Block>
+		+ HeaderBackend<Block>
+		+ StorageProvider<Block, B>
+		+ Send
+		+ Sync
+		+ 'static,
+	C::Api: DifficultyApi<Block, Difficulty>,
+	B: Backend<Block> + 'static,
+{
+	let dashboard = Arc::new(Dashboard {
+		client,
+		network,
+		stats,
+		account,
+		_marker: PhantomData,
+	});
+	let server = Server::try_bind(&addr)?.serve(make_service_fn(move |_| {
+		let dashboard = dashboard.clone();
+		async move {
+			Ok::<_, Infallible>(service_fn(move |request| {
+				let dashboard = dashboard.clone();
+				async move { Ok::<_, Infallible>(dashboard.respond(request).await) }
+			}))
+		}
+	}));
+
+	info!("Mining dashboard available at http://{}", addr);
+	Ok(async move {
+		if let Err(err) = server.await {
+			warn!("Mining dashboard failed: {:?}", err);
+		}
+	})
+}
+
+impl<C, B> Dashboard<C, B>
+where
+	C: ProvideRuntimeApi<Block> + HeaderBackend<Block> + StorageProvider<Block, B>,
+	C::Api: DifficultyApi<Block, Difficulty>,
+	B: Backend<Block>,
+{
+	async fn respond(&self, request: Request<Body>) -> Response<Body> {
+		if request.uri().path() != "/" {
+			return Response::builder()
+				.status(StatusCode::NOT_FOUND)
+				.body(Body::from("Not found"))
+				.expect("Response is valid");
+		}
+
+		let peers = self.network.status().await.ok().map(|status| {
+			(
+				status.num_connected_peers,
+				status.best_seen_block.unwrap_or_default(),
+			)
+		});
+
+		Response::builder()
+			.header("content-type", "text/html; charset=utf-8")
+			.body(Body::from(self.render(peers)))
+			.expect("Response is valid")
+	}
+
+	fn render(&self, peers: Option<(usize, BlockNumber)>) -> String {
+		let info = self.client.info();
+		let at = BlockId::Hash(info.best_hash);
+		let difficulty = self.client.runtime_api().difficulty(&at).ok();
+		let (hashrate, seals) = {
+			let stats = self.stats.lock();
+			(
+				stats.hashrate(),
+				stats.recent_seals().cloned().collect::<Vec<_>>(),
+			)
+		};
+		let locks = self.reward_locks(&at);
+
+		let mut page = format!(
+			"<!DOCTYPE html><html><head><meta charset=\"utf-8\">\
+			<meta http-equiv=\"refresh\" content=\"{}\">\
+			<title>Kulupu miner</title></head><body><h1>Kulupu miner</h1>",
+			REFRESH_SECS,
+		);
+
+		page.push_str("<h2>Sync</h2><table>");
+		page.push_str(&row("Syncing", self.network.is_major_syncing()));
+		page.push_str(&row(
+			"Best block",
+			format!("#{} ({})", info.best_number, info.best_hash),
+		));
+		page.push_str(&row(
+			"Finalized block",
+			format!("#{}", info.finalized_number),
+		));
+		if let Some((connected, best_seen)) = peers {
+			page.push_str(&row("Peers", connected));
+			page.push_str(&row("Best seen block", format!("#{}", best_seen)));
+		}
+		page.push_str("</table>");
+
+		page.push_str("<h2>Mining</h2><table>");
+		page.push_str(&row("Local hashrate", format!("{} H/s", hashrate)));
+		if let Some(difficulty) = difficulty {
+			page.push_str(&row("Difficulty", difficulty));
+			page.push_str(&row(
+				"Network hashrate",
+				format!("{} H/s", difficulty / Difficulty::from(BLOCK_TIME_SEC)),
+			));
+		}
+		page.push_str("</table>");
+
+		page.push_str("<h2>Seals found</h2><table><tr><th>Pre-hash</th><th>Difficulty</th><th>Found</th></tr>");
+		let now = SystemTime::now();
+		for seal in seals {
+			let ago = now
+				.duration_since(seal.time)
+				.map(|ago| format!("{}s ago", ago.as_secs()))
+				.unwrap_or_default();
+			page.push_str(&format!(
+				"<tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+				seal.pre_hash, seal.difficulty, ago,
+			));
+		}
+		page.push_str("</table>");
+
+		page.push_str(&format!(
+			"<h2>Reward locks of {}</h2><table><tr><th>Unlocks at</th><th>Balance</th></tr>",
+			self.account.to_ss58check(),
+		));
+		let mut total = 0;
+		for (number, balance) in locks {
+			total += balance;
+			page.push_str(&row(format!("#{}", number), format_balance(balance)));
+		}
+		page.push_str(&row("Total", format_balance(total)));
+		page.push_str("</table></body></html>");
+
+		page
+	}
+
+	/// Reward locks of the payout account, read from `Rewards RewardLocks`.
+	fn reward_locks(&self, at: &BlockId<Block>) -> BTreeMap<BlockNumber, Balance> {
+		let account = self.account.encode();
+		let key = [
+			&twox_128(b"Rewards")[..],
+			&twox_128(b"RewardLocks")[..],
+			&twox_64(&account)[..],
+			&account[..],
+		]
+		.concat();
+
+		match self.client.storage(at, &StorageKey(key)) {
+			Ok(Some(data)) => BTreeMap::decode(&mut &data.0[..]).unwrap_or_else(|err| {
+				debug!("Decoding reward locks failed: {:?}", err);
+				BTreeMap::new()
+			}),
+			Ok(None) => BTreeMap::new(),
+			Err(err) => {
+				debug!("Fetching reward locks failed: {:?}", err);
+				BTreeMap::new()
+			}
+		}
+	}
+}
+
+fn row(name: impl std::fmt::Display, value: impl std::fmt::Display) -> String {
+	format!("<tr><td>{}</td><td>{}</td></tr>", name, value)
+}
+
+fn format_balance(balance: Balance) -> String {
+	format!(
+		"{}.{:04} KLP",
+		balance / DOLLARS,
+		balance % DOLLARS / (DOLLARS / 10_000)
+	)
+}
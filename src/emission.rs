@@ -0,0 +1,132 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Kulupu.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Kulupu is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Kulupu is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Kulupu. If not, see <http://www.gnu.org/licenses/>.
+
+//! Projection of total issuance under the reward schedule of a block.
+//!
+//! Rewards and mints follow the schedule, including the changes planned in
+//! it. Fees burned and treasury burns are not projected, so the projection is
+//! an upper bound of issuance.
+
+use codec::Decode;
+use kulupu_primitives::{RewardScheduleApi, DAYS};
+use kulupu_runtime::{opaque::Block, AccountId, Balance, BlockNumber};
+use sc_client_api::{Backend, StorageProvider};
+use sp_api::ProvideRuntimeApi;
+use sp_core::{hashing::twox_128, storage::StorageKey};
+use sp_runtime::generic::BlockId;
+
+/// Number of blocks of a year.
+pub const YEAR: BlockNumber = 365 * DAYS;
+
+/// Issuance at the end of a projected year.
+pub struct YearIssuance {
+	/// Last block of the year.
+	pub end: BlockNumber,
+	/// Balance minted during the year.
+	pub minted: Balance,
+	/// Total issuance at the end of the year.
+	pub total_issuance: Balance,
+}
+
+/// Total issuance at block `number`.
+pub fn total_issuance<C, B>(client: &C, number: BlockNumber) -> Result<Balance, String>
+where
+	C: StorageProvider<Block, B>,
+	B: Backend<Block>,
+{
+	let key = [twox_128(b"Balances"), twox_128(b"TotalIssuance")].concat();
+	let value = client
+		.storage(&BlockId::Number(number), &StorageKey(key))
+		.map_err(|e| format!("Unable to query total issuance: {:?}", e))?
+		.ok_or_else(|| "Total issuance not found".to_string())?;
+
+	Balance::decode(&mut &value.0[..]).map_err(|e| format!("Invalid total issuance: {:?}", e))
+}
+
+/// Emission of each block from a block on, by the block it starts at, under
+/// the schedule at block `number`.
+pub fn emission_schedule<C>(
+	client: &C,
+	number: BlockNumber,
+) -> Result<Vec<(BlockNumber, Balance)>, String>
+where
+	C: ProvideRuntimeApi<Block>,
+	C::Api: RewardScheduleApi<Block, BlockNumber, AccountId, Balance>,
+{
+	let schedule = client
+		.runtime_api()
+		.reward_schedule(&BlockId::Number(number))
+		.map_err(|e| format!("Unable to query reward schedule: {:?}", e))?;
+
+	Ok(schedule
+		.into_iter()
+		.map(|point| {
+			let minted = point
+				.mints
+				.iter()
+				.fold(point.reward, |total, (_, mint)| total.saturating_add(*mint));
+			(point.start, minted)
+		})
+		.collect())
+}
+
+/// Project issuance over `years` years after block `number`, starting from
+/// `issuance` and following `schedule`.
+pub fn project(
+	schedule: &[(BlockNumber, Balance)],
+	number: BlockNumber,
+	issuance: Balance,
+	years: u32,
+) -> Vec<YearIssuance> {
+	// Emission of blocks `from` to `to`, inclusive.
+	let emitted = |from: BlockNumber, to: BlockNumber| {
+		schedule
+			.iter()
+			.enumerate()
+			.fold(0 as Balance, |total, (index, (start, per_block))| {
+				let end = schedule
+					.get(index + 1)
+					.map_or(BlockNumber::MAX, |(next, _)| next.saturating_sub(1));
+				let (from, to) = (from.max(*start), to.min(end));
+				if from > to {
+					return total;
+				}
+
+				let blocks = Balance::from(to - from) + 1;
+				total.saturating_add(per_block.saturating_mul(blocks))
+			})
+	};
+
+	let mut total_issuance = issuance;
+	(0..years)
+		.map(|year| {
+			let start = number
+				.saturating_add(year.saturating_mul(YEAR))
+				.saturating_add(1);
+			let end = start.saturating_add(YEAR - 1);
+			let minted = emitted(start, end);
+			total_issuance = total_issuance.saturating_add(minted);
+
+			YearIssuance {
+				end,
+				minted,
+				total_issuance,
+			}
+		})
+		.collect()
+}
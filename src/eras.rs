@@ -16,10 +16,23 @@
 // You should have received a copy of the GNU General Public License
 // along with Kulupu. If not, see <http://www.gnu.org/licenses/>.
 
+use codec::Decode;
+use kulupu_primitives::{Difficulty, DifficultyHistoryApi};
+use kulupu_runtime::{opaque::Block, AccountId, AccountIndex};
+use sc_client_api::{Backend, StorageProvider};
 use serde::{Deserialize, Serialize};
-use sp_core::{H256, U256};
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use sp_consensus_pow::DifficultyApi;
+use sp_core::{hashing::twox_128, storage::StorageKey, H256, U256};
+use sp_runtime::{generic::BlockId, traits::Header as HeaderT};
 
-#[derive(Serialize, Deserialize)]
+type AccountInfo = frame_system::AccountInfo<
+	kulupu_runtime::Index,
+	pallet_balances::AccountData<kulupu_runtime::Balance>,
+>;
+
+#[derive(Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct PreviousEra {
 	pub genesis_block_hash: H256,
@@ -27,25 +40,30 @@ pub struct PreviousEra {
 	pub final_state_root: H256,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Balance {
 	pub address: H256,
 	pub balance: U256,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Index {
 	pub address: H256,
 	pub index: u32,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct State {
 	pub previous_era: PreviousEra,
 	pub difficulty: U256,
+	/// Difficulties and timestamps of the final blocks of the era, from
+	/// earliest to latest. Absent from era states exported before it was
+	/// recorded.
+	#[serde(default)]
+	pub past_difficulties: Vec<(U256, u64)>,
 	pub balances: Vec<Balance>,
 	pub indices: Vec<Index>,
 }
@@ -55,3 +73,109 @@ pub fn era0_state() -> State {
 	serde_json::from_slice(include_bytes!("../res/eras/0/final.json"))
 		.expect("Included era state is valid")
 }
+
+/// Storage key prefix of a storage map.
+fn map_prefix(module: &[u8], item: &[u8]) -> Vec<u8> {
+	[twox_128(module), twox_128(item)].concat()
+}
+
+/// Export the state at block `number`, to start the next era from.
+pub fn export_state<C, B>(client: &C, number: u32) -> Result<State, String>
+where
+	C: StorageProvider<Block, B> + HeaderBackend<Block> + ProvideRuntimeApi<Block>,
+	C::Api: DifficultyApi<Block, Difficulty> + DifficultyHistoryApi<Block>,
+	B: Backend<Block>,
+{
+	let hash = client
+		.hash(number)
+		.map_err(|e| format!("Unable to query block hash: {:?}", e))?
+		.ok_or_else(|| format!("Block #{} not found", number))?;
+	let header = client
+		.header(BlockId::Hash(hash))
+		.map_err(|e| format!("Unable to query header: {:?}", e))?
+		.ok_or_else(|| format!("Header of block #{} not found", number))?;
+	let at = BlockId::Hash(hash);
+
+	let difficulty = client
+		.runtime_api()
+		.difficulty(&at)
+		.map_err(|e| format!("Unable to query difficulty: {:?}", e))?;
+	let past_difficulties = client
+		.runtime_api()
+		.past_difficulties_and_timestamps(&at)
+		.map_err(|e| format!("Unable to query difficulty history: {:?}", e))?;
+
+	// `System Account` and `Indices Accounts` are both `blake2_128_concat`
+	// maps, so keys end with a 16 byte hash followed by the encoded key.
+	let accounts_prefix = map_prefix(b"System", b"Account");
+	let mut balances = Vec::new();
+	for (key, value) in client
+		.storage_pairs(&at, &StorageKey(accounts_prefix.clone()))
+		.map_err(|e| format!("Unable to query accounts: {:?}", e))?
+	{
+		let address = AccountId::decode(&mut &key.0[accounts_prefix.len() + 16..])
+			.map_err(|e| format!("Invalid account key: {:?}", e))?;
+		let info = AccountInfo::decode(&mut &value.0[..])
+			.map_err(|e| format!("Invalid account info: {:?}", e))?;
+
+		let balance = info.data.free.saturating_add(info.data.reserved);
+		if balance > 0 {
+			balances.push(Balance {
+				address: H256::from(<[u8; 32]>::from(address)),
+				balance: U256::from(balance),
+			});
+		}
+	}
+
+	let indices_prefix = map_prefix(b"Indices", b"Accounts");
+	let mut indices = Vec::new();
+	for (key, value) in client
+		.storage_pairs(&at, &StorageKey(indices_prefix.clone()))
+		.map_err(|e| format!("Unable to query indices: {:?}", e))?
+	{
+		let index = AccountIndex::decode(&mut &key.0[indices_prefix.len() + 16..])
+			.map_err(|e| format!("Invalid index key: {:?}", e))?;
+		let (address, _, _) =
+			<(AccountId, kulupu_runtime::Balance, bool)>::decode(&mut &value.0[..])
+				.map_err(|e| format!("Invalid index: {:?}", e))?;
+
+		indices.push(Index {
+			address: H256::from(<[u8; 32]>::from(address)),
+			index,
+		});
+	}
+
+	Ok(State {
+		previous_era: PreviousEra {
+			genesis_block_hash: client.info().genesis_hash,
+			final_block_hash: hash,
+			final_state_root: *header.state_root(),
+		},
+		difficulty,
+		past_difficulties,
+		balances,
+		indices,
+	})
+}
+
+/// Eras before the one of `client`, as recorded in the `eras` pallet at
+/// block `number`.
+pub fn past_eras<C, B>(client: &C, number: u32) -> Result<Vec<pallet_eras::Era<H256>>, String>
+where
+	C: StorageProvider<Block, B> + HeaderBackend<Block>,
+	B: Backend<Block>,
+{
+	let hash = client
+		.hash(number)
+		.map_err(|e| format!("Unable to query block hash: {:?}", e))?
+		.ok_or_else(|| format!("Block #{} not found", number))?;
+	let value = client
+		.storage(
+			&BlockId::Hash(hash),
+			&StorageKey(map_prefix(b"Eras", b"PastEras")),
+		)
+		.map_err(|e| format!("Unable to query past eras: {:?}", e))?
+		.ok_or("Past eras not found")?;
+
+	Decode::decode(&mut &value.0[..]).map_err(|e| format!("Invalid past eras: {:?}", e))
+}
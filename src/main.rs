@@ -20,13 +20,25 @@
 
 #![warn(missing_docs)]
 
+mod author_metrics;
+mod chain_metrics;
 mod chain_spec;
+mod checkpoints;
 #[macro_use]
 mod service;
 mod cli;
 mod command;
+mod dashboard;
+mod emission;
 mod eras;
+mod ntp_time;
+mod reannounce;
+mod reorg_backoff;
 mod rpc;
+mod snapshot;
+mod stale_tip;
+mod standby;
+mod upgrade_announcement;
 
 fn main() -> sc_cli::Result<()> {
 	command::run()
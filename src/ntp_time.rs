@@ -0,0 +1,160 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Kulupu.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Kulupu is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Kulupu is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Kulupu. If not, see <http://www.gnu.org/licenses/>.
+
+//! NTP-disciplined timestamps of new blocks.
+//!
+//! Difficulty adjusts by block timestamps, so miners whose clocks drift skew
+//! it. The clock of new blocks can instead follow the offset to system time
+//! measured against NTP servers. Offsets beyond a limit are clamped to it, as
+//! a system clock that far off, or NTP responses that far off, both need
+//! attention rather than trust.
+
+use log::*;
+use std::{
+	net::UdpSocket,
+	sync::{
+		atomic::{AtomicI64, Ordering},
+		Arc,
+	},
+	time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Interval at which NTP servers are queried.
+const SYNC_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+/// Interval at which NTP servers are retried after all of them failed.
+const RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Timeout of a single NTP query.
+const QUERY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Offset to system time from which divergence is logged as a warning.
+const WARN_OFFSET_MS: i64 = 2_000;
+
+/// Seconds from the NTP epoch (1900) to the Unix epoch (1970).
+const NTP_UNIX_OFFSET_SECS: u64 = 2_208_988_800;
+
+/// Clock of new blocks, system time corrected by the last measured offset.
+/// Without measurements, it is system time.
+#[derive(Clone, Default)]
+pub struct NtpClock(Arc<AtomicI64>);
+
+impl NtpClock {
+	/// Current time in milliseconds since the Unix epoch.
+	pub fn now(&self) -> u64 {
+		let offset = self.0.load(Ordering::Relaxed);
+		let system = unix_millis(SystemTime::now());
+
+		if offset >= 0 {
+			system.saturating_add(offset as u64)
+		} else {
+			system.saturating_sub(offset.unsigned_abs())
+		}
+	}
+}
+
+fn unix_millis(time: SystemTime) -> u64 {
+	time.duration_since(UNIX_EPOCH)
+		.map(|d| d.as_millis() as u64)
+		.unwrap_or(0)
+}
+
+/// Milliseconds since the Unix epoch of an NTP timestamp.
+fn ntp_millis(bytes: &[u8]) -> u64 {
+	let secs = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as u64;
+	let fraction = u32::from_be_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]) as u64;
+
+	secs.saturating_sub(NTP_UNIX_OFFSET_SECS) * 1000 + ((fraction * 1000) >> 32)
+}
+
+/// Offset of `server`'s clock to system time in milliseconds, measured with a
+/// single SNTP query.
+fn query(server: &str) -> Result<i64, String> {
+	let socket =
+		UdpSocket::bind("0.0.0.0:0").map_err(|e| format!("Binding socket failed: {}", e))?;
+	socket
+		.set_read_timeout(Some(QUERY_TIMEOUT))
+		.map_err(|e| format!("Setting timeout failed: {}", e))?;
+	socket
+		.connect(server)
+		.map_err(|e| format!("Connecting failed: {}", e))?;
+
+	// Version 4, client mode.
+	let mut request = [0u8; 48];
+	request[0] = 0x23;
+	let sent = unix_millis(SystemTime::now());
+	socket
+		.send(&request)
+		.map_err(|e| format!("Sending request failed: {}", e))?;
+
+	let mut response = [0u8; 48];
+	let len = socket
+		.recv(&mut response)
+		.map_err(|e| format!("Receiving response failed: {}", e))?;
+	let received = unix_millis(SystemTime::now());
+	if len < 48 || response[0] & 0x07 != 4 || response[1] == 0 {
+		return Err("Invalid response".into());
+	}
+
+	let server_received = ntp_millis(&response[32..40]) as i64;
+	let server_sent = ntp_millis(&response[40..48]) as i64;
+
+	Ok(((server_received - sent as i64) + (server_sent - received as i64)) / 2)
+}
+
+/// Keep `clock` disciplined by the median offset of `servers`, clamped to
+/// `max_offset`.
+pub async fn sync(clock: NtpClock, servers: Vec<String>, max_offset: Duration) {
+	let max_offset = max_offset.as_millis() as i64;
+
+	loop {
+		let mut offsets = servers
+			.iter()
+			.filter_map(|server| match query(server) {
+				Ok(offset) => Some(offset),
+				Err(err) => {
+					debug!("Querying NTP server {} failed: {}", server, err);
+					None
+				}
+			})
+			.collect::<Vec<_>>();
+		if offsets.is_empty() {
+			warn!("No NTP server responded, keeping the previous clock offset.");
+			futures_timer::Delay::new(RETRY_INTERVAL).await;
+			continue;
+		}
+
+		offsets.sort_unstable();
+		let offset = offsets[offsets.len() / 2];
+		if offset.abs() > max_offset {
+			warn!(
+				"NTP time is {} ms ahead of system time, beyond the limit of {} ms. Check the system clock.",
+				offset, max_offset,
+			);
+		} else if offset.abs() >= WARN_OFFSET_MS {
+			warn!("NTP time is {} ms ahead of system time.", offset);
+		} else {
+			debug!("NTP time is {} ms ahead of system time.", offset);
+		}
+		clock
+			.0
+			.store(offset.max(-max_offset).min(max_offset), Ordering::Relaxed);
+
+		futures_timer::Delay::new(SYNC_INTERVAL).await;
+	}
+}
@@ -0,0 +1,81 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Kulupu.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Kulupu is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Kulupu is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Kulupu. If not, see <http://www.gnu.org/licenses/>.
+
+//! Re-announcement of blocks mined by this node.
+//!
+//! A miner with few or poorly connected peers risks its blocks being orphaned
+//! before they propagate. While a block mined here is still the best block and
+//! has not been built upon for a while, it is announced again whenever a new
+//! peer connects.
+
+use futures::StreamExt;
+use kulupu_runtime::opaque::Block;
+use log::*;
+use sc_client_api::{blockchain::HeaderBackend, BlockchainEvents};
+use sc_network::{Event, NetworkService};
+use sp_consensus::BlockOrigin;
+use sp_runtime::traits::Block as BlockT;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Time after which a block mined here that has not been built upon is
+/// announced to new peers.
+const REANNOUNCE_AFTER: Duration = Duration::from_secs(30);
+
+/// Announce the last block mined here again to each newly connected peer,
+/// while it is still the best block.
+pub async fn monitor<C>(
+	client: Arc<C>,
+	network: Arc<NetworkService<Block, <Block as BlockT>::Hash>>,
+) where
+	C: BlockchainEvents<Block> + HeaderBackend<Block>,
+{
+	let mut imports = client.import_notification_stream().fuse();
+	let mut events = network.event_stream("kulupu-reannounce").fuse();
+	let mut own = None;
+
+	loop {
+		futures::select! {
+			notification = imports.next() => match notification {
+				Some(notification) if notification.is_new_best => {
+					own = if notification.origin == BlockOrigin::Own {
+						Some((notification.hash, Instant::now()))
+					} else {
+						None
+					};
+				}
+				Some(_) => (),
+				None => break,
+			},
+			event = events.next() => match event {
+				Some(Event::SyncConnected { remote }) => match own {
+					Some((hash, mined))
+						if mined.elapsed() >= REANNOUNCE_AFTER && client.info().best_hash == hash =>
+					{
+						debug!("Announcing own best block {} again for new peer {}", hash, remote);
+						network.announce_block(hash, None);
+					}
+					_ => (),
+				},
+				Some(_) => (),
+				None => break,
+			},
+			complete => break,
+		}
+	}
+}
@@ -0,0 +1,122 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Kulupu.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Kulupu is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Kulupu is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Kulupu. If not, see <http://www.gnu.org/licenses/>.
+
+//! Mining backoff during deep reorgs.
+//!
+//! Work mined while the network is switching between competing forks is
+//! likely wasted, and the blocks it produces add to the confusion. After a
+//! reorg blocked by weak subjectivity, or a new best block retracting too many
+//! blocks, mining is paused until no such reorg has been seen for a while.
+
+use futures::StreamExt;
+use kulupu_pow::weak_sub::BlockedReorgs;
+use kulupu_runtime::opaque::Block;
+use log::*;
+use parking_lot::Mutex;
+use sc_client_api::{blockchain::HeaderBackend, BlockchainEvents};
+use sp_blockchain::HeaderMetadata;
+use sp_consensus::SyncOracle;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Whether mining is backed off after a deep reorg.
+#[derive(Clone)]
+pub struct ReorgBackoff {
+	duration: Duration,
+	last_reorg: Arc<Mutex<Option<Instant>>>,
+	blocked_reorgs: BlockedReorgs,
+}
+
+impl ReorgBackoff {
+	/// Back off for `duration` after deep reorgs, or reorgs blocked by the
+	/// weak subjectivity block import of `blocked_reorgs`.
+	pub fn new(duration: Duration, blocked_reorgs: BlockedReorgs) -> Self {
+		Self {
+			duration,
+			last_reorg: Arc::new(Mutex::new(None)),
+			blocked_reorgs,
+		}
+	}
+
+	/// Whether a deep or blocked reorg happened within the backoff duration.
+	pub fn is_paused(&self) -> bool {
+		let last_reorg = *self.last_reorg.lock();
+		last_reorg
+			.max(self.blocked_reorgs.last())
+			.map_or(false, |last| last.elapsed() < self.duration)
+	}
+}
+
+/// Sync oracle reporting major syncing while mining is backed off, so the
+/// mining worker drops its work and builds no new one.
+#[derive(Clone)]
+pub struct BackoffSyncOracle<SO> {
+	inner: SO,
+	backoff: ReorgBackoff,
+}
+
+impl<SO> BackoffSyncOracle<SO> {
+	/// Wrap the sync oracle `inner`.
+	pub fn new(inner: SO, backoff: ReorgBackoff) -> Self {
+		Self { inner, backoff }
+	}
+}
+
+impl<SO: SyncOracle> SyncOracle for BackoffSyncOracle<SO> {
+	fn is_major_syncing(&mut self) -> bool {
+		self.backoff.is_paused() || self.inner.is_major_syncing()
+	}
+
+	fn is_offline(&mut self) -> bool {
+		self.inner.is_offline()
+	}
+}
+
+/// Watch new best blocks, and back off when one retracts at least `depth`
+/// blocks of the previous best chain.
+pub async fn monitor<C>(client: Arc<C>, backoff: ReorgBackoff, depth: u32)
+where
+	C: BlockchainEvents<Block> + HeaderBackend<Block> + HeaderMetadata<Block>,
+	C::Error: std::fmt::Debug,
+{
+	let mut best_hash = client.info().best_hash;
+	let mut notifications = client.import_notification_stream();
+
+	while let Some(notification) = notifications.next().await {
+		if !notification.is_new_best {
+			continue;
+		}
+
+		match sp_blockchain::tree_route(client.as_ref(), best_hash, notification.hash) {
+			Ok(route) if route.retracted().len() >= depth as usize => {
+				warn!(
+					"New best block #{} ({}) retracted {} blocks. Mining is paused for {} seconds after the last deep reorg.",
+					notification.header.number,
+					notification.hash,
+					route.retracted().len(),
+					backoff.duration.as_secs(),
+				);
+				*backoff.last_reorg.lock() = Some(Instant::now());
+			}
+			Ok(_) => (),
+			Err(err) => debug!("Finding route of new best block failed: {:?}", err),
+		}
+
+		best_hash = notification.hash;
+	}
+}
@@ -28,27 +28,54 @@ use sp_block_builder::BlockBuilder;
 use sp_blockchain::{Error as BlockChainError, HeaderBackend, HeaderMetadata};
 
 /// Full client dependencies.
-pub struct FullDeps<C, P> {
+pub struct FullDeps<C, P, W, I> {
 	/// The client instance to use.
 	pub client: Arc<C>,
 	/// Transaction pool instance.
 	pub pool: Arc<P>,
 	/// Whether to deny unsafe calls
 	pub deny_unsafe: DenyUnsafe,
+	/// Work RPC, available when the node is mining.
+	pub work: Option<W>,
+	/// Build info RPC.
+	pub build_info: I,
+	/// Executor to drive subscriptions.
+	pub subscription_executor: sc_rpc::SubscriptionTaskExecutor,
 }
 
 /// Instantiate all full RPC extensions.
-pub fn create_full<C, P>(deps: FullDeps<C, P>) -> jsonrpc_core::IoHandler<sc_rpc::Metadata>
+pub fn create_full<C, P, W, I>(
+	deps: FullDeps<C, P, W, I>,
+) -> jsonrpc_core::IoHandler<sc_rpc::Metadata>
 where
 	C: ProvideRuntimeApi<Block>,
 	C: HeaderBackend<Block> + HeaderMetadata<Block, Error = BlockChainError> + 'static,
-	C: Send + Sync + 'static,
+	C: sc_client_api::AuxStore + sc_client_api::BlockBackend<Block> + Send + Sync + 'static,
 	C::Api: substrate_frame_rpc_system::AccountNonceApi<Block, AccountId, Index>,
 	C::Api: pallet_contracts_rpc::ContractsRuntimeApi<Block, AccountId, Balance, BlockNumber, Hash>,
 	C::Api: pallet_transaction_payment_rpc::TransactionPaymentRuntimeApi<Block, Balance>,
 	C::Api: BlockBuilder<Block>,
+	C::Api: kulupu_primitives::FeeApi<Block>,
+	C::Api: kulupu_primitives::DifficultyHistoryApi<Block>,
+	C::Api: kulupu_primitives::RewardHistoryApi<Block, AccountId, Balance>,
+	C::Api: kulupu_primitives::RewardsApi<Block, BlockNumber, AccountId, Balance>,
+	C::Api: kulupu_primitives::BlockInherentsApi<Block, AccountId>,
+	C::Api: kulupu_primitives::TreasuryApi<Block, Balance, BlockNumber>,
+	C::Api: kulupu_primitives::LiquidityApi<Block, AccountId, Balance, BlockNumber>,
 	P: TransactionPool + 'static,
+	W: kulupu_rpc_work::WorkApi + kulupu_rpc_work::WorkShares + Clone,
+	I: kulupu_rpc_build_info::BuildInfoApi<Hash>,
 {
+	use kulupu_rpc_build_info::BuildInfoApi;
+	use kulupu_rpc_checkpoint::{CheckpointApi, Checkpoints};
+	use kulupu_rpc_difficulty::{Difficulties, DifficultyApi};
+	use kulupu_rpc_fee::{FeeEstimate, FeeEstimateApi};
+	use kulupu_rpc_inherents::{BlockInherents, InherentsApi};
+	use kulupu_rpc_liquidity::{Liquidity, LiquidityApi};
+	use kulupu_rpc_miner_stats::{MinerStatsApi, Miners};
+	use kulupu_rpc_rewards::{Rewards, RewardsApi};
+	use kulupu_rpc_treasury::{Treasury, TreasuryStatusApi};
+	use kulupu_rpc_work::{WorkApi, WorkPubSub, WorkPubSubApi};
 	use pallet_contracts_rpc::{Contracts, ContractsApi};
 	use pallet_transaction_payment_rpc::{TransactionPayment, TransactionPaymentApi};
 	use substrate_frame_rpc_system::{FullSystem, SystemApi};
@@ -58,6 +85,9 @@ where
 		client,
 		pool,
 		deny_unsafe,
+		work,
+		build_info,
+		subscription_executor,
 	} = deps;
 
 	io.extend_with(SystemApi::to_delegate(FullSystem::new(
@@ -69,6 +99,43 @@ where
 		client.clone(),
 	)));
 	io.extend_with(ContractsApi::to_delegate(Contracts::new(client.clone())));
+	io.extend_with(FeeEstimateApi::to_delegate(FeeEstimate::new(
+		client.clone(),
+	)));
+	io.extend_with(DifficultyApi::to_delegate(Difficulties::new(
+		client.clone(),
+	)));
+	io.extend_with(RewardsApi::<Hash, BlockNumber, AccountId>::to_delegate(
+		Rewards::<_, Block, Balance>::new(client.clone()),
+	));
+	io.extend_with(InherentsApi::<Hash, AccountId>::to_delegate(
+		BlockInherents::<_, Block>::new(client.clone()),
+	));
+	io.extend_with(MinerStatsApi::<BlockNumber, AccountId>::to_delegate(
+		Miners::<_, Block>::new(client.clone()),
+	));
+	io.extend_with(TreasuryStatusApi::to_delegate(
+		Treasury::<_, Block, Balance>::new(client.clone()),
+	));
+	io.extend_with(LiquidityApi::<Hash, AccountId>::to_delegate(Liquidity::<
+		_,
+		Block,
+		Balance,
+	>::new(
+		client.clone(),
+	)));
+	io.extend_with(CheckpointApi::to_delegate(Checkpoints::<_, Block>::new(
+		client.clone(),
+		deny_unsafe,
+	)));
+	io.extend_with(BuildInfoApi::to_delegate(build_info));
+	if let Some(work) = work {
+		io.extend_with(WorkPubSubApi::to_delegate(WorkPubSub::new(
+			work.clone(),
+			subscription_executor,
+		)));
+		io.extend_with(WorkApi::to_delegate(work));
+	}
 	// Extend this RPC with a custom API by using the following syntax.
 	// `YourRpcStruct` should have a reference to a client, which is needed
 	// to call into the runtime.
@@ -18,15 +18,22 @@
 
 //! Service and ServiceFactory implementation. Specialized wrapper over substrate service.
 
+use crate::ntp_time::NtpClock;
+use crate::reorg_backoff::{BackoffSyncOracle, ReorgBackoff};
+use crate::stale_tip::StaleTip;
+use crate::standby::Standby;
+use crate::upgrade_announcement::UpgradeGate;
 use async_trait::async_trait;
 use codec::Encode;
-use kulupu_pow::compute::Error as ComputeError;
+use futures::StreamExt;
+use kulupu_pow::checkpoint::CheckpointFor;
 use kulupu_pow::compute::RandomxError;
+use kulupu_pow::compute::{CacheMode, ComputeMode, Error as ComputeError};
 use kulupu_pow::Error as PowError;
 use kulupu_runtime::{self, opaque::Block, RuntimeApi};
 use log::*;
 use parking_lot::Mutex;
-use sc_client_api::{backend::RemoteBackend, ExecutorProvider};
+use sc_client_api::{backend::RemoteBackend, BlockchainEvents, ExecutorProvider};
 use sc_consensus::DefaultImportQueue;
 use sc_executor::NativeElseWasmExecutor;
 use sc_service::{error::Error as ServiceError, Configuration, TaskManager};
@@ -37,6 +44,7 @@ use sp_core::{
 };
 use sp_keystore::{SyncCryptoStore, SyncCryptoStorePtr};
 use sp_runtime::{generic::BlockId, traits::Block as BlockT};
+use std::net::SocketAddr;
 use std::path::PathBuf;
 use std::str::FromStr;
 use std::sync::Arc;
@@ -62,6 +70,7 @@ pub fn decode_author(
 	author: Option<&str>,
 	keystore: SyncCryptoStorePtr,
 	keystore_path: Option<PathBuf>,
+	ss58_format: Ss58AddressFormat,
 ) -> Result<kulupu_pow::app::Public, String> {
 	if let Some(author) = author {
 		if author.starts_with("0x") {
@@ -72,7 +81,7 @@ pub fn decode_author(
 		} else {
 			let (address, version) = kulupu_pow::app::Public::from_ss58check_with_version(author)
 				.map_err(|_| "Invalid author address".to_string())?;
-			if version != Ss58AddressFormat::KulupuAccount {
+			if version != ss58_format {
 				return Err("Invalid author version".to_string());
 			}
 			Ok(address)
@@ -92,8 +101,7 @@ pub fn decode_author(
 
 		info!(
 			"Generated a mining key with address: {}",
-			pair.public()
-				.to_ss58check_with_version(Ss58AddressFormat::KulupuAccount)
+			pair.public().to_ss58check_with_version(ss58_format)
 		);
 
 		match keystore_path {
@@ -105,12 +113,74 @@ pub fn decode_author(
 	}
 }
 
+/// Decode the account mining rewards are paid out to, if it differs from the
+/// author.
+pub fn decode_payout(
+	payout: &str,
+	ss58_format: Ss58AddressFormat,
+) -> Result<kulupu_runtime::AccountId, String> {
+	if payout.starts_with("0x") {
+		Ok(kulupu_runtime::AccountId::unchecked_from(
+			H256::from_str(&payout[2..]).map_err(|_| "Invalid payout account".to_string())?,
+		))
+	} else {
+		let (address, version) = kulupu_runtime::AccountId::from_ss58check_with_version(payout)
+			.map_err(|_| "Invalid payout address".to_string())?;
+		if version != ss58_format {
+			return Err("Invalid payout version".to_string());
+		}
+		Ok(address)
+	}
+}
+
+/// Decode joint mining keys, returning them with their joint public key as the
+/// author. An explicitly configured author must match it.
+pub fn decode_joint_author(
+	joint_keys: &[String],
+	author: Option<&str>,
+	ss58_format: Ss58AddressFormat,
+) -> Result<(kulupu_pow::app::Public, Vec<kulupu_pow::app::Public>), String> {
+	let decode = |key: &str| -> Result<kulupu_pow::app::Public, String> {
+		if key.starts_with("0x") {
+			Ok(kulupu_pow::app::Public::unchecked_from(
+				H256::from_str(&key[2..]).map_err(|_| "Invalid joint mining key".to_string())?,
+			))
+		} else {
+			let (address, version) = kulupu_pow::app::Public::from_ss58check_with_version(key)
+				.map_err(|_| "Invalid joint mining key address".to_string())?;
+			if version != ss58_format {
+				return Err("Invalid joint mining key version".to_string());
+			}
+			Ok(address)
+		}
+	};
+
+	let joint = joint_keys
+		.iter()
+		.map(|key| decode(key))
+		.collect::<Result<Vec<_>, _>>()?;
+	let joint_author = kulupu_pow::joint::joint_public(&joint)
+		.ok_or_else(|| "Invalid joint mining keys".to_string())?;
+	if let Some(author) = author {
+		if decode(author)? != joint_author {
+			return Err("Author is not the joint public key of the joint mining keys".to_string());
+		}
+	}
+
+	Ok((joint_author, joint))
+}
+
 type FullClient =
 	sc_service::TFullClient<Block, RuntimeApi, NativeElseWasmExecutor<ExecutorDispatch>>;
 type FullBackend = sc_service::TFullBackend<Block>;
 type FullSelectChain = sc_consensus::LongestChain<FullBackend, Block>;
 
-pub struct CreateInherentDataProviders;
+/// Inherent data of new blocks, with the clock their timestamps are taken
+/// from.
+#[derive(Default)]
+pub struct CreateInherentDataProviders {
+	clock: NtpClock,
+}
 
 #[async_trait]
 impl sp_inherents::CreateInherentDataProviders<Block, ()> for CreateInherentDataProviders {
@@ -121,19 +191,25 @@ impl sp_inherents::CreateInherentDataProviders<Block, ()> for CreateInherentData
 		_parent: <Block as BlockT>::Hash,
 		_extra_args: (),
 	) -> Result<Self::InherentDataProviders, Box<dyn std::error::Error + Send + Sync>> {
-		Ok(sp_timestamp::InherentDataProvider::from_system_time())
+		Ok(sp_timestamp::InherentDataProvider::new(
+			self.clock.now().into(),
+		))
 	}
 }
 
 type PowBlockImport = kulupu_pow_consensus::PowBlockImport<
 	Block,
-	kulupu_pow::weak_sub::WeakSubjectiveBlockImport<
+	kulupu_pow::checkpoint::CheckpointBlockImport<
 		Block,
-		Arc<FullClient>,
+		kulupu_pow::weak_sub::WeakSubjectiveBlockImport<
+			Block,
+			Arc<FullClient>,
+			FullClient,
+			FullSelectChain,
+			kulupu_pow::RandomXAlgorithm<FullClient>,
+			kulupu_pow::weak_sub::ExponentialWeakSubjectiveAlgorithm,
+		>,
 		FullClient,
-		FullSelectChain,
-		kulupu_pow::RandomXAlgorithm<FullClient>,
-		kulupu_pow::weak_sub::ExponentialWeakSubjectiveAlgorithm,
 	>,
 	FullClient,
 	FullSelectChain,
@@ -152,6 +228,10 @@ pub fn new_partial(
 	config: &Configuration,
 	check_inherents_after: u32,
 	enable_weak_subjectivity: bool,
+	weak_subjective_algorithm: kulupu_pow::weak_sub::ExponentialWeakSubjectiveAlgorithm,
+	absolute_max_reorg_depth: Option<usize>,
+	enable_checkpoints: bool,
+	pow_checkpoint: Option<CheckpointFor<Block>>,
 ) -> Result<
 	sc_service::PartialComponents<
 		FullClient,
@@ -159,7 +239,11 @@ pub fn new_partial(
 		FullSelectChain,
 		DefaultImportQueue<Block, FullClient>,
 		sc_transaction_pool::FullPool<Block, FullClient>,
-		(PowBlockImport, Option<Telemetry>),
+		(
+			PowBlockImport,
+			Option<Telemetry>,
+			kulupu_pow::weak_sub::BlockedReorgs,
+		),
 	>,
 	ServiceError,
 > {
@@ -202,24 +286,51 @@ pub fn new_partial(
 		client.clone(),
 	);
 
-	let algorithm = kulupu_pow::RandomXAlgorithm::new(client.clone());
+	let pow_checkpoint = match pow_checkpoint {
+		Some(checkpoint) => Some(checkpoint),
+		None => chain_spec_pow_checkpoint(config)?,
+	};
+
+	let mut algorithm = kulupu_pow::RandomXAlgorithm::new(client.clone());
+	if let Some(checkpoint) = pow_checkpoint {
+		info!(
+			"✅ Assuming blocks up to checkpoint #{} ({}) valid",
+			checkpoint.number, checkpoint.hash,
+		);
+		algorithm = algorithm.with_assumed_valid(checkpoint.number.into());
+	}
 
-	let weak_sub_block_import = kulupu_pow::weak_sub::WeakSubjectiveBlockImport::new(
+	let mut weak_sub_block_import = kulupu_pow::weak_sub::WeakSubjectiveBlockImport::new(
 		client.clone(),
 		client.clone(),
 		algorithm.clone(),
-		kulupu_pow::weak_sub::ExponentialWeakSubjectiveAlgorithm(30, 1.1),
+		weak_subjective_algorithm,
 		select_chain.clone(),
 		enable_weak_subjectivity,
+		config.prometheus_registry(),
 	);
+	if let Some(depth) = absolute_max_reorg_depth {
+		weak_sub_block_import = weak_sub_block_import.with_max_reorg_depth(depth);
+	}
 
-	let pow_block_import = kulupu_pow_consensus::PowBlockImport::new(
+	let blocked_reorgs = weak_sub_block_import.blocked_reorgs();
+
+	let mut checkpoint_block_import = kulupu_pow::checkpoint::CheckpointBlockImport::new(
 		weak_sub_block_import,
 		client.clone(),
+		enable_checkpoints,
+	);
+	if let Some(checkpoint) = pow_checkpoint {
+		checkpoint_block_import = checkpoint_block_import.with_trusted(checkpoint);
+	}
+
+	let pow_block_import = kulupu_pow_consensus::PowBlockImport::new(
+		checkpoint_block_import,
+		client.clone(),
 		algorithm.clone(),
 		check_inherents_after,
 		select_chain.clone(),
-		CreateInherentDataProviders,
+		CreateInherentDataProviders::default(),
 		sp_consensus::CanAuthorWithNativeVersion::new(client.executor().clone()),
 	);
 
@@ -239,18 +350,97 @@ pub fn new_partial(
 		keystore_container,
 		select_chain,
 		transaction_pool,
-		other: (pow_block_import, telemetry),
+		other: (pow_block_import, telemetry, blocked_reorgs),
 	})
 }
 
+/// Trusted PoW checkpoint given as `<number>:<hash>` in the `powCheckpoint`
+/// property of the chain spec, if any.
+fn chain_spec_pow_checkpoint(
+	config: &Configuration,
+) -> Result<Option<CheckpointFor<Block>>, ServiceError> {
+	let checkpoint = match config.chain_spec.properties().get("powCheckpoint") {
+		Some(checkpoint) => checkpoint,
+		None => return Ok(None),
+	};
+
+	checkpoint
+		.as_str()
+		.ok_or_else(|| "Chain spec PoW checkpoint must be a string".to_string())?
+		.parse()
+		.map(Some)
+		.map_err(|e| ServiceError::Other(format!("Invalid chain spec PoW checkpoint: {}", e)))
+}
+
+/// How this node was built, for the build info RPC.
+fn build_info() -> kulupu_rpc_build_info::BuildInfo {
+	let mut features = Vec::new();
+	if cfg!(feature = "wasmtime") {
+		features.push("wasmtime".to_string());
+	}
+	if cfg!(feature = "runtime-benchmarks") {
+		features.push("runtime-benchmarks".to_string());
+	}
+
+	kulupu_rpc_build_info::BuildInfo {
+		version: env!("SUBSTRATE_CLI_IMPL_VERSION").into(),
+		profile: env!("KULUPU_BUILD_PROFILE").into(),
+		features,
+		randomx_commit: kulupu_pow::compute::RANDOMX_COMMIT.into(),
+	}
+}
+
+/// Log the reorg policy enforced on block import.
+fn log_reorg_policy(
+	enable_weak_subjectivity: bool,
+	algorithm: &kulupu_pow::weak_sub::ExponentialWeakSubjectiveAlgorithm,
+	absolute_max_reorg_depth: Option<usize>,
+) {
+	if enable_weak_subjectivity {
+		info!(
+			"⚖️  Reorg policy: reorgs deeper than {} blocks need {}x more work per extra block",
+			algorithm.0, algorithm.1,
+		);
+	} else if absolute_max_reorg_depth.is_none() {
+		warn!("Weak subjectivity is disabled, reorgs of any depth are accepted");
+	}
+	if let Some(depth) = absolute_max_reorg_depth {
+		info!(
+			"⚖️  Reorg policy: reorgs deeper than {} blocks are rejected",
+			depth
+		);
+	}
+}
+
 /// Builds a new service for a full client.
 pub fn new_full(
 	config: Configuration,
 	author: Option<&str>,
+	joint_keys: &[String],
+	payout: Option<&str>,
+	miner_name: Option<&str>,
 	threads: usize,
 	round: u32,
 	check_inherents_after: u32,
 	enable_weak_subjectivity: bool,
+	weak_subjective_algorithm: kulupu_pow::weak_sub::ExponentialWeakSubjectiveAlgorithm,
+	absolute_max_reorg_depth: Option<usize>,
+	checkpoint_depth: Option<u32>,
+	pow_checkpoint: Option<CheckpointFor<Block>>,
+	verify_workers: usize,
+	work_server: Option<SocketAddr>,
+	work_share_divisor: u64,
+	sign_work: bool,
+	dashboard: Option<SocketAddr>,
+	stale_tip_blocks: u32,
+	stale_tip_webhook: Option<String>,
+	reorg_backoff_blocks: Option<u32>,
+	reorg_backoff_depth: u32,
+	ntp_servers: Vec<String>,
+	ntp_max_offset: Duration,
+	standby_primary: Option<String>,
+	standby_timeout: Duration,
+	ss58_format: Ss58AddressFormat,
 ) -> Result<TaskManager, ServiceError> {
 	let sc_service::PartialComponents {
 		client,
@@ -260,8 +450,50 @@ pub fn new_full(
 		keystore_container,
 		select_chain,
 		transaction_pool,
-		other: (pow_block_import, mut telemetry),
-	} = new_partial(&config, check_inherents_after, enable_weak_subjectivity)?;
+		other: (pow_block_import, mut telemetry, blocked_reorgs),
+	} = new_partial(
+		&config,
+		check_inherents_after,
+		enable_weak_subjectivity,
+		weak_subjective_algorithm.clone(),
+		absolute_max_reorg_depth,
+		checkpoint_depth.is_some(),
+		pow_checkpoint,
+	)?;
+	log_reorg_policy(
+		enable_weak_subjectivity,
+		&weak_subjective_algorithm,
+		absolute_max_reorg_depth,
+	);
+
+	if verify_workers > 0 {
+		info!(
+			"Computing seal hashes of incoming blocks on {} threads",
+			verify_workers
+		);
+	}
+	let import_queue =
+		kulupu_pow::preverify::PreverifyingQueue::new(import_queue, client.clone(), verify_workers);
+
+	// Nodes not mining never generate full caches, so they are only prepared
+	// for mining.
+	if kulupu_pow::compute::global_config().cache_mode == CacheMode::Full
+		&& config.role.is_authority()
+		&& threads > 0
+	{
+		let key_hash = kulupu_pow::key_hash(
+			client.as_ref(),
+			&BlockId::Hash(client.chain_info().best_hash),
+		)
+		.map_err(|e| ServiceError::Other(format!("Fetching key hash failed: {:?}", e)))?;
+
+		kulupu_pow::compute::prepare_full_cache(&key_hash).map_err(|e| {
+			ServiceError::Other(format!(
+				"RandomX full mode is set, but generating the full cache failed: {}",
+				e.description(),
+			))
+		})?;
+	}
 
 	let (network, system_rpc_tx, network_starter) =
 		sc_service::build_network(sc_service::BuildNetworkParams {
@@ -287,23 +519,187 @@ pub fn new_full(
 	let role = config.role.clone();
 	let prometheus_registry = config.prometheus_registry().cloned();
 
+	let keystore_path = config.keystore.path().map(|p| p.to_owned());
+
+	// Without a backoff duration, mining is never paused.
+	let reorg_backoff = ReorgBackoff::new(
+		Duration::from_millis(kulupu_primitives::BLOCK_TIME) * reorg_backoff_blocks.unwrap_or(0),
+		blocked_reorgs,
+	);
+
+	let worker = if role.is_authority() {
+		let best_hash = client.chain_info().best_hash;
+		if let Err(PowError::UnsupportedAlgorithm(identifier)) =
+			kulupu_pow::algorithm_version(client.as_ref(), &BlockId::Hash(best_hash))
+		{
+			return Err(ServiceError::Other(format!(
+				"Runtime requires mining algorithm {:?}, which this node does not support. Upgrade the node to mine.",
+				String::from_utf8_lossy(&identifier),
+			)));
+		}
+
+		let (author, joint) = if joint_keys.is_empty() {
+			let author = decode_author(
+				author,
+				keystore_container.sync_keystore(),
+				keystore_path,
+				ss58_format,
+			)?;
+			(author, Vec::new())
+		} else {
+			let (author, joint) = decode_joint_author(joint_keys, author, ss58_format)?;
+			info!(
+				"Mining as joint author {} of {} keys",
+				author.to_ss58check_with_version(ss58_format),
+				joint.len(),
+			);
+			(author, joint)
+		};
+		// The payout account, if any, extends the author pre-runtime digest.
+		// Seals are still signed by the author key. The miner name follows the
+		// payout account, which is then the author itself if not set.
+		let payout = match payout {
+			Some(payout) => {
+				let payout = decode_payout(payout, ss58_format)?;
+				info!(
+					"Mining rewards are paid out to: {}",
+					payout.to_ss58check_with_version(ss58_format)
+				);
+				Some(payout)
+			}
+			None => None,
+		};
+		let reward_account = payout.clone().unwrap_or_else(|| {
+			kulupu_runtime::AccountId::from(sp_core::sr25519::Public::from(author))
+		});
+		let pre_runtime = match (payout, miner_name) {
+			(_, Some(name)) => {
+				info!("Declaring miner name in mined blocks: {}", name);
+				(author, reward_account.clone(), name.as_bytes().to_vec()).encode()
+			}
+			(Some(payout), None) => (author, payout).encode(),
+			(None, None) => author.encode(),
+		};
+		// Fail before mining rather than on submitting seals that do not verify.
+		if threads > 0 {
+			if let Some(keystore) = keystore_container.local_keystore() {
+				kulupu_pow::check_author_key(&keystore, &author, &joint).map_err(|e| {
+					ServiceError::Other(format!(
+						"Mining key of author {} is not usable: {}",
+						author.to_ss58check_with_version(ss58_format),
+						e,
+					))
+				})?;
+			}
+		}
+		let work_signer = if sign_work {
+			if !joint.is_empty() {
+				return Err(ServiceError::Other(
+					"Signing work is not supported with joint mining keys".to_string(),
+				));
+			}
+			let pair = keystore_container
+				.local_keystore()
+				.and_then(|keystore| keystore.key_pair::<kulupu_pow::app::Pair>(&author).ok())
+				.flatten()
+				.ok_or_else(|| {
+					ServiceError::Other(format!(
+						"Signing work needs the mining key of author {} in the keystore",
+						author.to_ss58check_with_version(ss58_format),
+					))
+				})?;
+			Some(pair)
+		} else {
+			None
+		};
+		let clock = NtpClock::default();
+		if !ntp_servers.is_empty() {
+			info!(
+				"Timestamping new blocks with NTP time of {}",
+				ntp_servers.join(", ")
+			);
+			task_manager.spawn_handle().spawn_blocking(
+				"ntp-time",
+				crate::ntp_time::sync(clock.clone(), ntp_servers, ntp_max_offset),
+			);
+		}
+		let algorithm = kulupu_pow::RandomXAlgorithm::new(client.clone());
+
+		let proposer = sc_basic_authorship::ProposerFactory::new(
+			task_manager.spawn_handle(),
+			client.clone(),
+			transaction_pool.clone(),
+			prometheus_registry.as_ref(),
+			telemetry.as_ref().map(|x| x.handle()),
+		);
+
+		let (worker, worker_task) = kulupu_pow_consensus::start_mining_worker(
+			Box::new(pow_block_import.clone()),
+			client.clone(),
+			select_chain.clone(),
+			algorithm,
+			proposer,
+			BackoffSyncOracle::new(network.clone(), reorg_backoff.clone()),
+			network.clone(),
+			Some(pre_runtime),
+			CreateInherentDataProviders { clock },
+			Duration::new(10, 0),
+			Duration::new(10, 0),
+			sp_consensus::CanAuthorWithNativeVersion::new(client.executor().clone()),
+		);
+		task_manager
+			.spawn_handle()
+			.spawn_blocking("pow", worker_task);
+
+		Some((worker, reward_account, work_signer, joint))
+	} else {
+		None
+	};
+	let (worker, reward_account, work_signer, joint) = match worker {
+		Some((worker, reward_account, work_signer, joint)) => {
+			(Some(worker), Some(reward_account), work_signer, joint)
+		}
+		None => (None, None, None, Vec::new()),
+	};
+
+	let work = worker.clone().map(|worker| {
+		let work = kulupu_rpc_work::Work::new(worker, client.clone(), work_share_divisor);
+		match work_signer {
+			Some(signer) => work.with_signer(signer),
+			None => work,
+		}
+	});
+
+	if let Some(addr) = work_server {
+		match work.clone() {
+			Some(work) => kulupu_rpc_work::server::start(addr, work).map_err(|e| {
+				ServiceError::Other(format!("Starting work server failed: {:?}", e))
+			})?,
+			None => warn!("Work server is only available when the node is mining."),
+		}
+	}
+
 	let rpc_extensions_builder = {
 		let client = client.clone();
 		let pool = transaction_pool.clone();
 
-		Box::new(move |deny_unsafe, _| {
+		Box::new(move |deny_unsafe, subscription_executor| {
 			let deps = crate::rpc::FullDeps {
 				client: client.clone(),
 				pool: pool.clone(),
 				deny_unsafe,
+				work: work.clone(),
+				build_info: kulupu_rpc_build_info::BuildInfoRpc::<_, Block, FullBackend>::new(
+					client.clone(),
+					build_info(),
+				),
+				subscription_executor,
 			};
 
 			Ok(crate::rpc::create_full(deps))
 		})
 	};
 
-	let keystore_path = config.keystore.path().map(|p| p.to_owned());
-
 	let _rpc_handlers = sc_service::spawn_tasks(sc_service::SpawnTasksParams {
 		network: network.clone(),
 		client: client.clone(),
@@ -319,65 +715,208 @@ pub fn new_full(
 		telemetry: telemetry.as_mut(),
 	})?;
 
-	if role.is_authority() {
-		let author = decode_author(author, keystore_container.sync_keystore(), keystore_path)?;
-		let algorithm = kulupu_pow::RandomXAlgorithm::new(client.clone());
-
-		let proposer = sc_basic_authorship::ProposerFactory::new(
-			task_manager.spawn_handle(),
-			client.clone(),
-			transaction_pool.clone(),
-			prometheus_registry.as_ref(),
-			telemetry.as_ref().map(|x| x.handle()),
+	if let Some(registry) = prometheus_registry.clone() {
+		task_manager.spawn_handle().spawn(
+			"author-metrics",
+			crate::author_metrics::monitor(client.clone(), registry.clone()),
 		);
+		task_manager.spawn_handle().spawn(
+			"chain-metrics",
+			crate::chain_metrics::monitor(client.clone(), registry),
+		);
+	}
 
-		let (worker, worker_task) = kulupu_pow_consensus::start_mining_worker(
-			Box::new(pow_block_import.clone()),
-			client.clone(),
-			select_chain.clone(),
-			algorithm,
-			proposer,
-			network.clone(),
-			network.clone(),
-			Some(author.encode()),
-			CreateInherentDataProviders,
-			Duration::new(10, 0),
-			Duration::new(10, 0),
-			sp_consensus::CanAuthorWithNativeVersion::new(client.executor().clone()),
+	if let Some(depth) = checkpoint_depth {
+		info!(
+			"Recording checkpoints {} blocks below the best block",
+			depth
 		);
-		task_manager
-			.spawn_handle()
-			.spawn_blocking("pow", worker_task);
+		task_manager.spawn_handle().spawn(
+			"checkpoints",
+			crate::checkpoints::monitor(client.clone(), depth),
+		);
+	}
+
+	// Generate the RandomX caches of the next key hash ahead of each rotation.
+	let warm_up_mode = if worker.is_some() && threads > 0 {
+		ComputeMode::Mining
+	} else {
+		ComputeMode::Sync
+	};
+	let warm_up_client = client.clone();
+	task_manager.spawn_handle().spawn(
+		"randomx-warm-up",
+		client
+			.import_notification_stream()
+			.for_each(move |notification| {
+				if notification.is_new_best {
+					if let Err(err) = kulupu_pow::warm_up_next_key(
+						warm_up_client.as_ref(),
+						&BlockId::Hash(notification.hash),
+						warm_up_mode,
+					) {
+						warn!("Warming up RandomX caches failed: {:?}", err);
+					}
+				}
+
+				futures::future::ready(())
+			}),
+	);
+
+	if dashboard.is_some() && worker.is_none() {
+		warn!("Dashboard is only available when the node is mining.");
+	}
+	if sign_work && worker.is_none() {
+		warn!("Work is only signed when the node is mining.");
+	}
+
+	if let Some(worker) = worker {
+		let randomx_config = kulupu_pow::compute::global_config();
+		let threads = match randomx_config.max_full_vms {
+			Some(max_full_vms) if max_full_vms > 0 && threads > max_full_vms => {
+				warn!(
+					"Mining with {} threads instead of {}, to stay within the RandomX memory limit.",
+					max_full_vms, threads,
+				);
+				max_full_vms
+			}
+			_ => threads,
+		};
+		if threads > 0 {
+			// Pooled VMs are shared by the mining and verification threads.
+			let (full_vms, light_vms) = match randomx_config.vm_count {
+				Some(vm_count) => (threads.min(vm_count), vm_count),
+				None => (threads, threads + 1),
+			};
+			let memory_usage = match randomx_config.cache_mode {
+				CacheMode::Light => kulupu_pow::compute::memory_usage(0, light_vms),
+				#[cfg(feature = "light-deterministic")]
+				CacheMode::LightDeterministic => kulupu_pow::compute::memory_usage(0, light_vms),
+				_ if randomx_config.vm_count.is_some() => {
+					kulupu_pow::compute::memory_usage(full_vms, light_vms)
+				}
+				_ => kulupu_pow::compute::memory_usage(full_vms, 1),
+			};
+			info!(
+				"RandomX is estimated to use {} MiB of memory mining with {} threads.",
+				memory_usage / (1024 * 1024),
+				threads,
+			);
+		}
 
 		let stats = Arc::new(Mutex::new(kulupu_pow::Stats::new()));
+		let stale_tip = StaleTip::default();
+
+		if let (Some(addr), Some(account)) = (dashboard, reward_account) {
+			let dashboard = crate::dashboard::start(
+				addr,
+				client.clone(),
+				network.clone(),
+				stats.clone(),
+				account,
+			)
+			.map_err(|e| ServiceError::Other(format!("Starting dashboard failed: {:?}", e)))?;
+			task_manager.spawn_handle().spawn("dashboard", dashboard);
+		}
+
+		task_manager.spawn_handle().spawn(
+			"stale-tip",
+			crate::stale_tip::monitor(
+				client.clone(),
+				network.clone(),
+				stale_tip.clone(),
+				Duration::from_millis(kulupu_primitives::BLOCK_TIME) * stale_tip_blocks,
+				stale_tip_webhook,
+				prometheus_registry.clone(),
+			),
+		);
+
+		task_manager.spawn_handle().spawn(
+			"reannounce",
+			crate::reannounce::monitor(client.clone(), network.clone()),
+		);
+
+		let upgrade_gate = UpgradeGate::default();
+		task_manager.spawn_handle().spawn(
+			"upgrade-announcement",
+			crate::upgrade_announcement::monitor(client.clone(), upgrade_gate.clone()),
+		);
+
+		if let Some(blocks) = reorg_backoff_blocks {
+			info!(
+				"Pausing mining for {} blocks after reorgs retracting {} or more blocks",
+				blocks, reorg_backoff_depth,
+			);
+			task_manager.spawn_handle().spawn(
+				"reorg-backoff",
+				crate::reorg_backoff::monitor(
+					client.clone(),
+					reorg_backoff.clone(),
+					reorg_backoff_depth,
+				),
+			);
+		}
 
-		for _ in 0..threads {
+		let standby = standby_primary.map(|primary| {
+			info!("Mining as a standby for primary {}", primary);
+
+			let standby = Standby::default();
+			task_manager.spawn_handle().spawn(
+				"standby",
+				crate::standby::monitor(primary, standby_timeout, standby.clone()),
+			);
+			standby
+		});
+
+		for thread in 0..threads {
 			if let Some(keystore) = keystore_container.local_keystore() {
 				let worker = worker.clone();
 				let client = client.clone();
 				let stats = stats.clone();
+				let stale_tip = stale_tip.clone();
+				let standby = standby.clone();
+				let upgrade_gate = upgrade_gate.clone();
+				let reorg_backoff = reorg_backoff.clone();
+				let joint = joint.clone();
 
 				thread::spawn(move || loop {
+					let standing_by = standby.as_ref().map_or(false, |s| !s.is_active());
+					if stale_tip.is_stale() || standing_by || upgrade_gate.is_blocked() {
+						thread::sleep(Duration::new(1, 0));
+						continue;
+					}
+
 					let metadata = worker.metadata();
 					if let Some(metadata) = metadata {
 						match kulupu_pow::mine(
 							client.as_ref(),
 							&keystore,
+							&joint,
 							&BlockId::Hash(metadata.best_hash),
 							&metadata.pre_hash,
 							metadata.pre_runtime.as_ref().map(|v| &v[..]),
 							metadata.difficulty,
 							round,
+							thread,
 							&stats,
 						) {
 							Ok(Some(seal)) => {
 								let current_metadata = worker.metadata();
-								if current_metadata == Some(metadata) {
+								if current_metadata == Some(metadata) && !reorg_backoff.is_paused()
+								{
 									let _ = futures::executor::block_on(worker.submit(seal));
 								}
 							}
 							Ok(None) => (),
-							Err(PowError::Compute(ComputeError::CacheNotAvailable)) => {
+							Err(PowError::UnsupportedAlgorithm(identifier)) => {
+								error!(
+									"Runtime requires mining algorithm {:?}, which this node does not support. Mining stopped, upgrade the node to continue.",
+									String::from_utf8_lossy(&identifier),
+								);
+								break;
+							}
+							Err(PowError::Compute(ComputeError::CacheNotAvailable))
+							| Err(PowError::Compute(ComputeError::FullVmLimitReached)) => {
 								thread::sleep(Duration::new(1, 0));
 							}
 							Err(PowError::Compute(ComputeError::Randomx(
@@ -409,7 +948,15 @@ pub fn new_light(
 	config: Configuration,
 	check_inherents_after: u32,
 	enable_weak_subjectivity: bool,
+	weak_subjective_algorithm: kulupu_pow::weak_sub::ExponentialWeakSubjectiveAlgorithm,
+	absolute_max_reorg_depth: Option<usize>,
 ) -> Result<TaskManager, ServiceError> {
+	log_reorg_policy(
+		enable_weak_subjectivity,
+		&weak_subjective_algorithm,
+		absolute_max_reorg_depth,
+	);
+
 	let telemetry = config
 		.telemetry_endpoints
 		.clone()
@@ -451,14 +998,18 @@ pub fn new_light(
 
 	let algorithm = kulupu_pow::RandomXAlgorithm::new(client.clone());
 
-	let weak_sub_block_import = kulupu_pow::weak_sub::WeakSubjectiveBlockImport::new(
+	let mut weak_sub_block_import = kulupu_pow::weak_sub::WeakSubjectiveBlockImport::new(
 		client.clone(),
 		client.clone(),
 		algorithm.clone(),
-		kulupu_pow::weak_sub::ExponentialWeakSubjectiveAlgorithm(30, 1.1),
+		weak_subjective_algorithm,
 		select_chain.clone(),
 		enable_weak_subjectivity,
+		config.prometheus_registry(),
 	);
+	if let Some(depth) = absolute_max_reorg_depth {
+		weak_sub_block_import = weak_sub_block_import.with_max_reorg_depth(depth);
+	}
 
 	let pow_block_import = kulupu_pow_consensus::PowBlockImport::new(
 		weak_sub_block_import,
@@ -466,7 +1017,7 @@ pub fn new_light(
 		algorithm.clone(),
 		check_inherents_after,
 		select_chain.clone(),
-		CreateInherentDataProviders,
+		CreateInherentDataProviders::default(),
 		sp_consensus::NeverCanAuthor,
 	);
 
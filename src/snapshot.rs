@@ -0,0 +1,319 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Kulupu.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Kulupu is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Kulupu is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Kulupu. If not, see <http://www.gnu.org/licenses/>.
+
+//! State snapshots, to bootstrap nodes without executing every block.
+//!
+//! Warp sync is not available for PoW chains. A snapshot holds the state at a
+//! block, along with the header chain up to it and the difficulty of each
+//! header. Importing it into an empty database writes the headers without
+//! state and the state of the snapshot block, after which the node syncs the
+//! following blocks as usual.
+//!
+//! The header chain is always checked to link the genesis block to the
+//! snapshot block, and the client checks the state against the state root of
+//! the snapshot block. Whether the difficulties are trusted as well is chosen
+//! with [`SnapshotTrust`].
+
+use crate::cli::SnapshotTrust;
+use codec::{Decode, Encode, IoReader};
+use kulupu_pow_consensus::{aux_key, PowAux};
+use kulupu_runtime::{opaque::Block, Header};
+use log::*;
+use sc_client_api::{AuxStore, Backend, StorageProvider};
+use sc_consensus::{
+	BlockImport, BlockImportParams, ForkChoiceStrategy, ImportResult, ImportedState, StateAction,
+	StorageChanges,
+};
+use sp_blockchain::HeaderBackend;
+use sp_consensus::BlockOrigin;
+use sp_consensus_pow::POW_ENGINE_ID;
+use sp_core::{
+	storage::{well_known_keys, ChildInfo, StorageKey},
+	H256, U256,
+};
+use sp_runtime::{
+	generic::{BlockId, DigestItem},
+	traits::Header as HeaderT,
+};
+use std::{
+	collections::HashMap,
+	fs::File,
+	io::{BufReader, BufWriter, Read, Write},
+	path::Path,
+};
+
+/// Magic bytes and format version at the start of snapshot files.
+const SNAPSHOT_MAGIC: [u8; 8] = *b"klpsnap1";
+
+/// Number of headers between progress logs.
+const PROGRESS_INTERVAL: usize = 10_000;
+
+/// State at a block, with the header chain leading to it.
+#[derive(Encode, Decode)]
+pub struct Snapshot {
+	/// Genesis hash of the chain.
+	pub genesis_hash: H256,
+	/// Headers from block 1 to the snapshot block.
+	pub headers: Vec<Header>,
+	/// Difficulty and total difficulty of each header.
+	pub difficulties: Vec<PowAux<U256>>,
+	/// Top trie storage at the snapshot block.
+	pub state: Vec<(Vec<u8>, Vec<u8>)>,
+	/// Default child trie storages at the snapshot block, by child storage key.
+	pub child_state: Vec<(Vec<u8>, Vec<(Vec<u8>, Vec<u8>)>)>,
+}
+
+/// Take a snapshot at block `number`.
+pub fn export_snapshot<C, B>(client: &C, number: u32) -> Result<Snapshot, String>
+where
+	C: StorageProvider<Block, B> + HeaderBackend<Block> + AuxStore,
+	B: Backend<Block>,
+{
+	if number == 0 {
+		return Err("Snapshots start after the genesis block".into());
+	}
+
+	let mut headers = Vec::with_capacity(number as usize);
+	let mut difficulties = Vec::with_capacity(number as usize);
+	for n in 1..=number {
+		let header = client
+			.header(BlockId::Number(n))
+			.map_err(|e| format!("Unable to query header: {:?}", e))?
+			.ok_or_else(|| format!("Header of block #{} not found", n))?;
+		let aux = PowAux::<U256>::read::<_, Block>(client, &header.hash())
+			.map_err(|e| format!("Unable to query difficulty of block #{}: {:?}", n, e))?;
+
+		headers.push(header);
+		difficulties.push(aux);
+	}
+
+	let at = BlockId::Number(number);
+	let state = client
+		.storage_pairs(&at, &StorageKey(Vec::new()))
+		.map_err(|e| format!("Unable to query state: {:?}", e))?
+		.into_iter()
+		.map(|(key, value)| (key.0, value.0))
+		.collect::<Vec<_>>();
+
+	let mut child_state = Vec::new();
+	for (key, _) in &state {
+		let storage_key = match key.strip_prefix(well_known_keys::DEFAULT_CHILD_STORAGE_KEY_PREFIX)
+		{
+			Some(storage_key) => storage_key,
+			None => continue,
+		};
+		let child_info = ChildInfo::new_default(storage_key);
+
+		let mut pairs = Vec::new();
+		for child_key in client
+			.child_storage_keys(&at, &child_info, &StorageKey(Vec::new()))
+			.map_err(|e| format!("Unable to query child state: {:?}", e))?
+		{
+			if let Some(value) = client
+				.child_storage(&at, &child_info, &child_key)
+				.map_err(|e| format!("Unable to query child state: {:?}", e))?
+			{
+				pairs.push((child_key.0, value.0));
+			}
+		}
+		child_state.push((storage_key.to_vec(), pairs));
+	}
+
+	Ok(Snapshot {
+		genesis_hash: client.info().genesis_hash,
+		headers,
+		difficulties,
+		state,
+		child_state,
+	})
+}
+
+/// Write `snapshot` to the file at `path`.
+pub fn write_snapshot(path: &Path, snapshot: &Snapshot) -> Result<(), String> {
+	let mut file = BufWriter::new(
+		File::create(path).map_err(|e| format!("Creating snapshot file failed: {:?}", e))?,
+	);
+	file.write_all(&SNAPSHOT_MAGIC)
+		.map_err(|e| format!("Writing snapshot failed: {:?}", e))?;
+	snapshot.encode_to(&mut file);
+	file.flush()
+		.map_err(|e| format!("Writing snapshot failed: {:?}", e))
+}
+
+/// Read a snapshot from the file at `path`.
+pub fn read_snapshot(path: &Path) -> Result<Snapshot, String> {
+	let mut file = BufReader::new(
+		File::open(path).map_err(|e| format!("Opening snapshot file failed: {:?}", e))?,
+	);
+	let mut magic = [0; 8];
+	file.read_exact(&mut magic)
+		.map_err(|e| format!("Reading snapshot failed: {:?}", e))?;
+	if magic != SNAPSHOT_MAGIC {
+		return Err("Not a Kulupu snapshot, or of an unsupported version".into());
+	}
+
+	Snapshot::decode(&mut IoReader(file)).map_err(|e| format!("Invalid snapshot: {:?}", e))
+}
+
+/// Difficulties of the headers of `snapshot`, checked as far as `trust`
+/// requires.
+fn checked_difficulties(
+	snapshot: &Snapshot,
+	trust: SnapshotTrust,
+) -> Result<Vec<PowAux<U256>>, String> {
+	match trust {
+		SnapshotTrust::Difficulties => {
+			let mut total_difficulty = U256::zero();
+			for (header, aux) in snapshot.headers.iter().zip(&snapshot.difficulties) {
+				total_difficulty = total_difficulty.saturating_add(aux.difficulty);
+				if aux.total_difficulty != total_difficulty {
+					return Err(format!(
+						"Total difficulty of block #{} does not add up",
+						header.number
+					));
+				}
+			}
+
+			Ok(snapshot.difficulties.clone())
+		}
+		SnapshotTrust::Seals => {
+			let mut difficulties = Vec::with_capacity(snapshot.headers.len());
+			let mut total_difficulty = U256::zero();
+			for (index, header) in snapshot.headers.iter().enumerate() {
+				let key_number = kulupu_pow::key_number(u64::from(header.number) - 1) as usize;
+				let key_hash = match key_number {
+					0 => snapshot.genesis_hash,
+					n => snapshot.headers[n - 1].hash(),
+				};
+
+				let mut header = header.clone();
+				let seal = match header.digest_mut().pop() {
+					Some(DigestItem::Seal(id, seal)) if id == POW_ENGINE_ID => seal,
+					_ => return Err(format!("Block #{} has no seal", header.number)),
+				};
+				let pre_digest = header.digest().logs().iter().find_map(|log| match log {
+					DigestItem::PreRuntime(id, data) if *id == POW_ENGINE_ID => Some(&data[..]),
+					_ => None,
+				});
+
+				let difficulty = kulupu_pow::detached_seal_difficulty(
+					key_hash,
+					&header.hash(),
+					pre_digest,
+					&seal,
+				)
+				.map_err(|e| format!("Verifying seal failed: {}", e.description()))?
+				.ok_or_else(|| format!("Invalid seal of block #{}", header.number))?;
+
+				total_difficulty = total_difficulty.saturating_add(difficulty);
+				difficulties.push(PowAux {
+					difficulty,
+					total_difficulty,
+				});
+
+				if (index + 1) % PROGRESS_INTERVAL == 0 {
+					info!("Verified seals of {} headers", index + 1);
+				}
+			}
+
+			Ok(difficulties)
+		}
+	}
+}
+
+/// Import `snapshot` into the empty database of `client` through
+/// `block_import`, bypassing consensus checks.
+pub fn import_snapshot<C, I>(
+	client: &C,
+	mut block_import: I,
+	snapshot: Snapshot,
+	trust: SnapshotTrust,
+) -> Result<(), String>
+where
+	C: HeaderBackend<Block>,
+	I: BlockImport<Block>,
+	I::Error: std::fmt::Debug,
+{
+	let info = client.info();
+	if info.best_number != 0 {
+		return Err("Snapshots can only be imported into an empty database".into());
+	}
+	if snapshot.genesis_hash != info.genesis_hash {
+		return Err("Snapshot is of a different chain".into());
+	}
+	if snapshot.headers.is_empty() || snapshot.headers.len() != snapshot.difficulties.len() {
+		return Err("Snapshot headers and difficulties do not match".into());
+	}
+	// Imported state only covers the top trie.
+	if !snapshot.child_state.is_empty() {
+		return Err(
+			"Snapshot contains child tries, which this client cannot import state of".into(),
+		);
+	}
+
+	let mut parent_hash = info.genesis_hash;
+	for (index, header) in snapshot.headers.iter().enumerate() {
+		if header.parent_hash != parent_hash || header.number as usize != index + 1 {
+			return Err(format!(
+				"Header #{} does not extend the chain",
+				header.number
+			));
+		}
+		parent_hash = header.hash();
+	}
+
+	let difficulties = checked_difficulties(&snapshot, trust)?;
+
+	let Snapshot { headers, state, .. } = snapshot;
+	let mut state = Some(state);
+	let target_hash = parent_hash;
+	let count = headers.len();
+	for (index, (header, aux)) in headers.into_iter().zip(difficulties).enumerate() {
+		let hash = header.hash();
+		let number = header.number;
+
+		let mut params = BlockImportParams::new(BlockOrigin::NetworkInitialSync, header);
+		let target_state = if hash == target_hash {
+			state.take()
+		} else {
+			None
+		};
+		params.state_action = match target_state {
+			Some(state) => StateAction::ApplyChanges(StorageChanges::Import(ImportedState {
+				block: hash,
+				state,
+			})),
+			None => StateAction::Skip,
+		};
+		params.fork_choice = Some(ForkChoiceStrategy::Custom(true));
+		params.auxiliary.push((aux_key(&hash), Some(aux.encode())));
+
+		match futures::executor::block_on(block_import.import_block(params, HashMap::new())) {
+			Ok(ImportResult::Imported(_)) | Ok(ImportResult::AlreadyInChain) => (),
+			Ok(result) => return Err(format!("Importing block #{} failed: {:?}", number, result)),
+			Err(err) => return Err(format!("Importing block #{} failed: {:?}", number, err)),
+		}
+
+		if (index + 1) % PROGRESS_INTERVAL == 0 {
+			info!("Imported {} of {} headers", index + 1, count);
+		}
+	}
+
+	info!("Imported snapshot of block #{} ({})", count, target_hash);
+	Ok(())
+}
@@ -0,0 +1,153 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Kulupu.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Kulupu is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Kulupu is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Kulupu. If not, see <http://www.gnu.org/licenses/>.
+
+//! Detection of a stale best block while mining.
+//!
+//! A node stuck on a fork keeps mining on its own stalled tip. When the best
+//! block has not changed for too long while peers advertise a higher chain,
+//! mining is paused until the node catches up.
+
+use kulupu_runtime::opaque::Block;
+use log::*;
+use sc_client_api::blockchain::HeaderBackend;
+use sc_network::NetworkService;
+use sp_runtime::traits::Block as BlockT;
+use std::sync::{
+	atomic::{AtomicBool, Ordering},
+	Arc,
+};
+use std::time::{Duration, Instant};
+use substrate_prometheus_endpoint::{register, Gauge, PrometheusError, Registry, U64};
+
+/// Interval at which the best block is checked.
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Whether mining is paused on a stale best block.
+#[derive(Clone, Default)]
+pub struct StaleTip(Arc<AtomicBool>);
+
+impl StaleTip {
+	/// Whether the best block is currently considered stale.
+	pub fn is_stale(&self) -> bool {
+		self.0.load(Ordering::Relaxed)
+	}
+}
+
+/// Watch the best block, and mark the tip stale when it has not changed for
+/// `max_age` while peers have seen a higher block. Changes are logged, shown
+/// in the `kulupu_mining_stale_tip` metric and posted as JSON to `webhook`.
+pub async fn monitor<C>(
+	client: Arc<C>,
+	network: Arc<NetworkService<Block, <Block as BlockT>::Hash>>,
+	stale_tip: StaleTip,
+	max_age: Duration,
+	webhook: Option<String>,
+	registry: Option<Registry>,
+) where
+	C: HeaderBackend<Block>,
+{
+	let gauge = match registry.as_ref().map(register_gauge).transpose() {
+		Ok(gauge) => gauge,
+		Err(err) => {
+			warn!("Registering stale tip metric failed: {:?}", err);
+			None
+		}
+	};
+
+	let mut best_hash = client.info().best_hash;
+	let mut best_changed = Instant::now();
+
+	loop {
+		futures_timer::Delay::new(CHECK_INTERVAL).await;
+
+		let info = client.info();
+		if info.best_hash != best_hash {
+			best_hash = info.best_hash;
+			best_changed = Instant::now();
+		}
+
+		let best_seen = match network.status().await {
+			Ok(status) => status.best_seen_block,
+			Err(()) => continue,
+		};
+
+		let stale = best_changed.elapsed() >= max_age
+			&& best_seen.map_or(false, |best_seen| best_seen > info.best_number);
+
+		if stale == stale_tip.is_stale() {
+			continue;
+		}
+		stale_tip.0.store(stale, Ordering::Relaxed);
+
+		if let Some(gauge) = &gauge {
+			gauge.set(stale as u64);
+		}
+
+		if stale {
+			error!(
+				"Best block #{} ({}) has not changed for {} minutes, but peers have seen #{}. Mining is paused until the node catches up. Check the node's connectivity and whether it is on a fork.",
+				info.best_number,
+				info.best_hash,
+				best_changed.elapsed().as_secs() / 60,
+				best_seen.unwrap_or_default(),
+			);
+		} else {
+			info!(
+				"Best block #{} is no longer stale, resuming mining.",
+				info.best_number
+			);
+		}
+
+		if let Some(webhook) = &webhook {
+			let body = serde_json::json!({
+				"stale": stale,
+				"bestNumber": info.best_number,
+				"bestHash": info.best_hash,
+				"bestSeen": best_seen,
+			});
+			post_webhook(webhook, body.to_string()).await;
+		}
+	}
+}
+
+fn register_gauge(registry: &Registry) -> Result<Gauge<U64>, PrometheusError> {
+	register(
+		Gauge::new(
+			"kulupu_mining_stale_tip",
+			"Whether mining is paused because the best block is stale",
+		)?,
+		registry,
+	)
+}
+
+async fn post_webhook(webhook: &str, body: String) {
+	let request = match hyper::Request::post(webhook)
+		.header("content-type", "application/json")
+		.body(hyper::Body::from(body))
+	{
+		Ok(request) => request,
+		Err(err) => {
+			warn!("Invalid stale tip webhook {}: {:?}", webhook, err);
+			return;
+		}
+	};
+
+	if let Err(err) = hyper::Client::new().request(request).await {
+		warn!("Posting to stale tip webhook failed: {:?}", err);
+	}
+}
@@ -0,0 +1,111 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Kulupu.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Kulupu is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Kulupu is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Kulupu. If not, see <http://www.gnu.org/licenses/>.
+
+//! Cold-standby mining.
+//!
+//! A standby node watches the work RPC of a primary node, and only mines when
+//! the primary is unreachable or its work has not changed for too long.
+
+use kulupu_rpc_work::Compute;
+use log::*;
+use std::sync::{
+	atomic::{AtomicBool, Ordering},
+	Arc,
+};
+use std::time::{Duration, Instant};
+
+/// Interval at which the primary is polled.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Whether a standby node should be mining.
+#[derive(Clone, Default)]
+pub struct Standby(Arc<AtomicBool>);
+
+impl Standby {
+	/// Whether the primary is down, and this node should mine.
+	pub fn is_active(&self) -> bool {
+		self.0.load(Ordering::Relaxed)
+	}
+}
+
+/// Poll `work_getCompute` of the primary HTTP RPC endpoint, and activate the
+/// standby when no new work has been seen for `timeout`.
+pub async fn monitor(primary: String, timeout: Duration, standby: Standby) {
+	let mut last_compute = None;
+	let mut last_update = Instant::now();
+
+	loop {
+		match fetch_compute(&primary).await {
+			Ok(Some(compute)) if last_compute.as_ref() != Some(&compute) => {
+				last_compute = Some(compute);
+				last_update = Instant::now();
+			}
+			Ok(_) => (),
+			Err(err) => debug!("Fetching work from primary {} failed: {}", primary, err),
+		}
+
+		let active = last_update.elapsed() >= timeout;
+		if active != standby.is_active() {
+			standby.0.store(active, Ordering::Relaxed);
+
+			if active {
+				warn!(
+					"No new work from primary {} for {} seconds, starting to mine.",
+					primary,
+					timeout.as_secs(),
+				);
+			} else {
+				info!(
+					"Primary {} is producing work again, stopping mining.",
+					primary
+				);
+			}
+		}
+
+		futures_timer::Delay::new(POLL_INTERVAL).await;
+	}
+}
+
+async fn fetch_compute(primary: &str) -> Result<Option<Compute>, String> {
+	let body = serde_json::json!({
+		"jsonrpc": "2.0",
+		"id": 1,
+		"method": "work_getCompute",
+		"params": [],
+	});
+	let request = hyper::Request::post(primary)
+		.header("content-type", "application/json")
+		.body(hyper::Body::from(body.to_string()))
+		.map_err(|e| format!("{:?}", e))?;
+
+	let response = hyper::Client::new()
+		.request(request)
+		.await
+		.map_err(|e| format!("{:?}", e))?;
+	let bytes = hyper::body::to_bytes(response.into_body())
+		.await
+		.map_err(|e| format!("{:?}", e))?;
+
+	let mut output: serde_json::Value =
+		serde_json::from_slice(&bytes).map_err(|e| format!("{:?}", e))?;
+	if let Some(error) = output.get("error") {
+		return Err(error.to_string());
+	}
+
+	serde_json::from_value(output["result"].take()).map_err(|e| format!("{:?}", e))
+}
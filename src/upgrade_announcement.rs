@@ -0,0 +1,169 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Kulupu.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Kulupu is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Kulupu is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Kulupu. If not, see <http://www.gnu.org/licenses/>.
+
+//! Watching of announced runtime upgrades.
+//!
+//! Operators are warned as soon as the council announces a runtime upgrade
+//! that the node's native runtime does not support. From the announced
+//! activation block on, such a node stops mining until it is upgraded, rather
+//! than keep authoring blocks with an outdated runtime.
+//!
+//! The storage migrations run by the last runtime upgrade are logged as well,
+//! on startup and after each upgrade.
+
+use futures::StreamExt;
+use kulupu_primitives::{
+	MigrationReport, MigrationsApi, UpgradeAnnouncement, UpgradeAnnouncementApi,
+};
+use kulupu_runtime::{opaque::Block, BlockNumber, Hash};
+use log::*;
+use sc_client_api::BlockchainEvents;
+use sp_api::{Core, ProvideRuntimeApi};
+use sp_runtime::generic::BlockId;
+use sp_runtime::traits::Header as HeaderT;
+use std::sync::{
+	atomic::{AtomicBool, Ordering},
+	Arc,
+};
+
+/// Whether mining is paused on an unsupported runtime upgrade.
+#[derive(Clone, Default)]
+pub struct UpgradeGate(Arc<AtomicBool>);
+
+impl UpgradeGate {
+	/// Whether an announced upgrade this node does not support is active.
+	pub fn is_blocked(&self) -> bool {
+		self.0.load(Ordering::Relaxed)
+	}
+}
+
+/// Watch the upgrade announcement of each new best block, log changes to it,
+/// and block `gate` once an announced upgrade the native runtime does not
+/// support reaches its activation block.
+pub async fn monitor<C>(client: Arc<C>, gate: UpgradeGate)
+where
+	C: BlockchainEvents<Block> + ProvideRuntimeApi<Block>,
+	C::Api: UpgradeAnnouncementApi<Block, Hash, BlockNumber> + MigrationsApi<Block>,
+{
+	let native = kulupu_runtime::VERSION.spec_version;
+	let mut imports = client.import_notification_stream();
+	let mut last = None;
+	let mut last_report = None;
+
+	while let Some(notification) = imports.next().await {
+		if !notification.is_new_best {
+			continue;
+		}
+
+		let at = BlockId::Hash(notification.hash);
+		match client.runtime_api().migration_report(&at) {
+			Ok(report) => {
+				if last_report.as_ref() != Some(&report) {
+					log_migrations(&report);
+				}
+				last_report = Some(report);
+			}
+			Err(err) => debug!("Fetching migration report failed: {:?}", err),
+		}
+
+		let announcement = match client.runtime_api().upgrade_announcement(&at) {
+			Ok(announcement) => announcement,
+			Err(err) => {
+				debug!("Fetching upgrade announcement failed: {:?}", err);
+				continue;
+			}
+		};
+
+		if announcement != last {
+			log_announcement(announcement.as_ref(), native);
+		}
+
+		let blocked = match &announcement {
+			Some(announcement) => {
+				notification.header.number() + 1 >= announcement.activation
+					&& native < announcement.spec_version
+			}
+			// Keep mining stopped if the unsupported upgrade was enacted,
+			// rather than cancelled.
+			None => {
+				gate.is_blocked()
+					&& client
+						.runtime_api()
+						.version(&at)
+						.map_or(true, |version| version.spec_version > native)
+			}
+		};
+
+		if blocked != gate.is_blocked() {
+			gate.0.store(blocked, Ordering::Relaxed);
+
+			if blocked {
+				error!(
+					"Runtime upgrade to spec version {} is active, but this node only supports spec version {}. Mining stopped, upgrade the node to continue.",
+					announcement.as_ref().map_or(0, |a| a.spec_version),
+					native,
+				);
+			} else {
+				info!("Runtime upgrade announcement no longer applies, resuming mining.");
+			}
+		}
+
+		last = announcement;
+	}
+}
+
+fn log_migrations(report: &MigrationReport) {
+	// No upgrade recorded migrations yet.
+	if report.spec_version == 0 {
+		return;
+	}
+
+	info!(
+		"Runtime upgrade to spec version {} ran {} storage migrations.",
+		report.spec_version,
+		report.migrations.len(),
+	);
+	for (name, weight) in &report.migrations {
+		info!(
+			"Migration {} completed with weight {}.",
+			String::from_utf8_lossy(name),
+			weight,
+		);
+	}
+}
+
+fn log_announcement(announcement: Option<&UpgradeAnnouncement<Hash, BlockNumber>>, native: u32) {
+	let announcement = match announcement {
+		Some(announcement) => announcement,
+		None => {
+			info!("Runtime upgrade announcement cleared.");
+			return;
+		}
+	};
+
+	info!(
+		"Runtime upgrade to spec version {} (code hash {}) announced for block #{}.",
+		announcement.spec_version, announcement.code_hash, announcement.activation,
+	);
+	if native < announcement.spec_version {
+		warn!(
+			"This node only supports runtime spec version {}. Upgrade it before block #{}, or it will stop mining.",
+			native, announcement.activation,
+		);
+	}
+}